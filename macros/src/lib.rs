@@ -0,0 +1,150 @@
+//! Procedural macros of the `stivale-boot` crate, re-exported by it behind the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Expr, FnArg, ItemFn, ReturnType, Type};
+
+/// The size of the stack declared for the kernel when no `stack_size` argument is passed.
+const DEFAULT_STACK_SIZE: usize = 0x10000;
+
+/// The arguments of the [main] attribute.
+struct MainArgs {
+    stack_size: Expr,
+    tags: Option<Expr>,
+}
+
+/// Checks that `function` has the `fn(&StivaleStruct) -> !` signature the entry point calls.
+fn check_signature(function: &ItemFn) -> syn::Result<()> {
+    let sig = &function.sig;
+
+    if sig.asyncness.is_some() || sig.constness.is_some() || sig.unsafety.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "the kernel main function must be a plain `fn`",
+        ));
+    }
+
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "the kernel main function must not be generic",
+        ));
+    }
+
+    if sig.inputs.len() != 1 || !matches!(sig.inputs[0], FnArg::Typed(_)) {
+        return Err(syn::Error::new(
+            sig.inputs.span(),
+            "the kernel main function must take the boot information as its only argument",
+        ));
+    }
+
+    match &sig.output {
+        ReturnType::Type(_, ty) if matches!(**ty, Type::Never(_)) => Ok(()),
+        ReturnType::Type(_, ty) => Err(syn::Error::new(
+            ty.span(),
+            "the kernel main function must never return, use `-> !`",
+        )),
+        ReturnType::Default => Err(syn::Error::new(
+            sig.span(),
+            "the kernel main function must never return, add `-> !`",
+        )),
+    }
+}
+
+fn expand_main(args: MainArgs, function: ItemFn) -> syn::Result<TokenStream2> {
+    check_signature(&function)?;
+
+    let name = &function.sig.ident;
+    let stack_size = &args.stack_size;
+
+    // Without header tags, a terminal is requested, which requires the framebuffer header tag.
+    let (tag_statics, tags) = match &args.tags {
+        Some(tags) => (quote!(), quote!(#tags)),
+        None => (
+            quote! {
+                static TERMINAL_TAG: ::stivale_boot::v2::StivaleTerminalHeaderTag =
+                    ::stivale_boot::v2::StivaleTerminalHeaderTag::new();
+
+                static FRAMEBUFFER_TAG: ::stivale_boot::v2::StivaleFramebufferHeaderTag =
+                    ::stivale_boot::v2::StivaleFramebufferHeaderTag::new()
+                        .next(&TERMINAL_TAG as *const _ as *const ());
+            },
+            quote!(&FRAMEBUFFER_TAG as *const _ as *const ()),
+        ),
+    };
+
+    Ok(quote! {
+        #function
+
+        const _: () = {
+            #[cfg(target_arch = "x86")]
+            compile_error!(
+                "#[stivale_boot::v2::main] does not support i686 targets, use \
+                 stivale2_protected_mode_entry! instead"
+            );
+
+            static STACK: ::stivale_boot::v2::KernelStack<{ #stack_size }> =
+                ::stivale_boot::v2::KernelStack::new();
+
+            #tag_statics
+
+            extern "C" fn __stivale2_start(
+                boot_info: &'static ::stivale_boot::v2::StivaleStruct,
+            ) -> ! {
+                // SAFETY: This is the entry point of the kernel, called by the bootloader with
+                // the boot information.
+                unsafe { ::stivale_boot::v2::enter_main(boot_info, #name) }
+            }
+
+            #[used]
+            #[link_section = ".stivale2hdr"]
+            static __STIVALE2_HEADER: ::stivale_boot::v2::StivaleHeader =
+                ::stivale_boot::v2::StivaleHeader::new()
+                    .stack(STACK.top() as *const u8)
+                    .entry_point(__stivale2_start)
+                    .tags(#tags);
+
+            #[panic_handler]
+            fn __stivale2_panic(info: &::core::panic::PanicInfo) -> ! {
+                // SAFETY: The boot information registered by `enter_main` is the one the kernel
+                // was entered with, and the kernel is required to be built with frame pointers.
+                unsafe { ::stivale_boot::v2::panic_print(info) };
+
+                loop {
+                    ::core::hint::spin_loop();
+                }
+            }
+        };
+    })
+}
+
+/// Turns `fn kmain(boot_info: &'static StivaleStruct) -> !` into the entry point of a 64-bit
+/// kernel. See the documentation of the re-export in `stivale_boot::v2` for the details.
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut main_args = MainArgs {
+        stack_size: parse_quote!(#DEFAULT_STACK_SIZE),
+        tags: None,
+    };
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("stack_size") {
+            main_args.stack_size = meta.value()?.parse()?;
+            Ok(())
+        } else if meta.path.is_ident("tags") {
+            main_args.tags = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported argument, expected `stack_size` or `tags`"))
+        }
+    });
+
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    expand_main(main_args, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
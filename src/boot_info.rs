@@ -0,0 +1,453 @@
+//! Protocol-agnostic access to the information passed by the bootloader. Kernel subsystems can
+//! be written against the [BootInfo] trait, which is implemented by the boot structures of the
+//! supported protocols, and tested against mock implementations.
+
+use core::iter::Map;
+use core::slice;
+
+use crate::v1;
+use crate::v2::{self, StivaleMemoryMapEntryType};
+
+/// A region of the physical memory map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryRegion {
+    /// Physical address of base of the memory region.
+    pub base: u64,
+    /// Length of this memory region.
+    pub length: u64,
+    /// The type of this memory region.
+    pub kind: StivaleMemoryMapEntryType,
+}
+
+impl MemoryRegion {
+    /// Returns the end address of this memory region.
+    #[inline]
+    pub fn end_address(&self) -> u64 {
+        self.base + self.length
+    }
+}
+
+/// A framebuffer set up by the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FramebufferInfo {
+    /// The address of the framebuffer.
+    pub address: u64,
+    /// Width of the framebuffer in pixels.
+    pub width: u64,
+    /// Height of the framebuffer in pixels.
+    pub height: u64,
+    /// Pitch of the framebuffer in bytes.
+    pub pitch: u64,
+    /// The framebuffer bits per pixels.
+    pub bpp: u16,
+    /// Memory model of the framebuffer. If set to one, its RGB.
+    pub memory_model: u8,
+    /// Size of the red mask in RGB.
+    pub red_mask_size: u8,
+    /// Shift of the red mask in RGB.
+    pub red_mask_shift: u8,
+    /// Size of the green mask in RGB.
+    pub green_mask_size: u8,
+    /// Shift of the green mask in RGB.
+    pub green_mask_shift: u8,
+    /// Size of the blue mask in RGB.
+    pub blue_mask_size: u8,
+    /// Shift of the blue mask in RGB.
+    pub blue_mask_shift: u8,
+}
+
+impl From<&v2::StivaleFramebufferTag> for FramebufferInfo {
+    fn from(tag: &v2::StivaleFramebufferTag) -> Self {
+        Self {
+            address: tag.framebuffer_addr,
+            width: tag.framebuffer_width as u64,
+            height: tag.framebuffer_height as u64,
+            pitch: tag.framebuffer_pitch as u64,
+            bpp: tag.framebuffer_bpp,
+            memory_model: tag.memory_model,
+            red_mask_size: tag.red_mask_size,
+            red_mask_shift: tag.red_mask_shift,
+            green_mask_size: tag.green_mask_size,
+            green_mask_shift: tag.green_mask_shift,
+            blue_mask_size: tag.blue_mask_size,
+            blue_mask_shift: tag.blue_mask_shift,
+        }
+    }
+}
+
+/// A module loaded by the bootloader alongside the kernel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModuleInfo<'a> {
+    /// The string associated with the module in the bootloader configuration.
+    pub name: &'a str,
+    /// Address where this module has been loaded.
+    pub start: u64,
+    /// End address of this module.
+    pub end: u64,
+}
+
+impl<'a> ModuleInfo<'a> {
+    /// Returns the size of this module.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Information passed by the bootloader, independent of the boot protocol.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::boot_info::BootInfo;
+/// use stivale_boot::v2::StivaleMemoryMapEntryType;
+///
+/// fn usable_memory<B: BootInfo>(boot_info: &B) -> u64 {
+///     boot_info
+///         .memory_map()
+///         .filter(|region| region.kind == StivaleMemoryMapEntryType::Usable)
+///         .map(|region| region.length)
+///         .sum()
+/// }
+///
+/// assert_eq!(usable_memory(&stivale_boot::v2::StivaleStruct::new()), 0);
+/// ```
+pub trait BootInfo {
+    /// Iterator over the memory map.
+    type MemoryMap<'a>: Iterator<Item = MemoryRegion>
+    where
+        Self: 'a;
+
+    /// Iterator over the loaded modules.
+    type Modules<'a>: Iterator<Item = ModuleInfo<'a>>
+    where
+        Self: 'a;
+
+    /// Returns an iterator over the memory map. It is empty if the bootloader did not
+    /// provide one.
+    fn memory_map(&self) -> Self::MemoryMap<'_>;
+
+    /// Returns the framebuffer, if one was set up by the bootloader.
+    fn framebuffer(&self) -> Option<FramebufferInfo>;
+
+    /// Returns an iterator over the modules loaded by the bootloader.
+    fn modules(&self) -> Self::Modules<'_>;
+
+    /// Returns the address of the RSDP ACPI structure, if available.
+    fn rsdp(&self) -> Option<u64>;
+
+    /// Returns the command line passed to the kernel, if any.
+    fn cmdline(&self) -> Option<&str>;
+
+    /// Returns the UNIX epoch at boot, if available.
+    fn epoch(&self) -> Option<u64>;
+
+    /// Returns the 32-bit and 64-bit SMBIOS entry point addresses, zero if unavailable.
+    fn smbios(&self) -> Option<(u64, u64)>;
+}
+
+fn v2_region(entry: &v2::StivaleMemoryMapEntry) -> MemoryRegion {
+    MemoryRegion {
+        base: entry.base,
+        length: entry.length,
+        kind: entry.entry_type(),
+    }
+}
+
+fn v2_module(module: &v2::StivaleModule) -> ModuleInfo<'_> {
+    ModuleInfo {
+        name: module.as_str(),
+        start: module.start,
+        end: module.end,
+    }
+}
+
+impl BootInfo for v2::StivaleStruct {
+    type MemoryMap<'a> = Map<
+        slice::Iter<'a, v2::StivaleMemoryMapEntry>,
+        fn(&v2::StivaleMemoryMapEntry) -> MemoryRegion,
+    >;
+
+    type Modules<'a> =
+        Map<slice::Iter<'a, v2::StivaleModule>, fn(&'a v2::StivaleModule) -> ModuleInfo<'a>>;
+
+    fn memory_map(&self) -> Self::MemoryMap<'_> {
+        let entries = self.memory_map().map(|tag| tag.as_slice()).unwrap_or(&[]);
+        entries.iter().map(v2_region as fn(&_) -> _)
+    }
+
+    fn framebuffer(&self) -> Option<FramebufferInfo> {
+        self.framebuffer().map(FramebufferInfo::from)
+    }
+
+    fn modules(&self) -> Self::Modules<'_> {
+        let modules = self.modules().map(|tag| tag.as_slice()).unwrap_or(&[]);
+        modules.iter().map(v2_module as fn(_) -> _)
+    }
+
+    fn rsdp(&self) -> Option<u64> {
+        self.rsdp().map(|tag| tag.rsdp)
+    }
+
+    fn cmdline(&self) -> Option<&str> {
+        self.command_line().and_then(|tag| tag.as_str().ok())
+    }
+
+    fn epoch(&self) -> Option<u64> {
+        self.epoch().map(|tag| tag.epoch)
+    }
+
+    fn smbios(&self) -> Option<(u64, u64)> {
+        self.smbios()
+            .map(|tag| (tag.smbios_entry_32, tag.smbios_entry_64))
+    }
+}
+
+fn v1_region(entry: &v1::StivaleMemoryMapEntry) -> MemoryRegion {
+    MemoryRegion {
+        base: entry.base,
+        length: entry.length,
+        kind: entry.entry_type(),
+    }
+}
+
+fn v1_module(module: &v1::StivaleModule) -> ModuleInfo<'_> {
+    ModuleInfo {
+        name: module.as_str(),
+        start: module.start,
+        end: module.end,
+    }
+}
+
+impl BootInfo for v1::StivaleStruct {
+    type MemoryMap<'a> =
+        Map<v1::StivaleMemoryMapIter<'a>, fn(&v1::StivaleMemoryMapEntry) -> MemoryRegion>;
+
+    type Modules<'a> = Map<v1::StivaleModuleIter<'a>, fn(&'a v1::StivaleModule) -> ModuleInfo<'a>>;
+
+    fn memory_map(&self) -> Self::MemoryMap<'_> {
+        self.memory_map_iter().map(v1_region as fn(&_) -> _)
+    }
+
+    fn framebuffer(&self) -> Option<FramebufferInfo> {
+        self.as_v2()
+            .framebuffer()
+            .map(|tag| FramebufferInfo::from(&tag))
+    }
+
+    fn modules(&self) -> Self::Modules<'_> {
+        self.modules_iter().map(v1_module as fn(_) -> _)
+    }
+
+    fn rsdp(&self) -> Option<u64> {
+        self.as_v2().rsdp().map(|tag| tag.rsdp)
+    }
+
+    fn cmdline(&self) -> Option<&str> {
+        self.as_v2().command_line()
+    }
+
+    fn epoch(&self) -> Option<u64> {
+        Some(self.unix_epoch)
+    }
+
+    fn smbios(&self) -> Option<(u64, u64)> {
+        self.as_v2()
+            .smbios()
+            .map(|tag| (tag.smbios_entry_32, tag.smbios_entry_64))
+    }
+}
+
+/// The boot protocol the kernel was booted with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    /// The legacy stivale protocol, the entry argument points to a [v1::StivaleStruct].
+    Stivale,
+    /// The stivale2 protocol, the entry argument points to a [v2::StivaleStruct].
+    Stivale2,
+    /// The Limine protocol, the information is found in the responses of the requests.
+    Limine,
+    /// The protocol could not be recognised.
+    Unknown,
+}
+
+/// Returns whether `bytes` is a non-empty, null-terminated and zero padded ASCII string, as
+/// found in the brand and version fields of the stivale2 structure.
+fn is_padded_string(bytes: &[u8]) -> bool {
+    let length = match bytes.iter().position(|c| *c == 0) {
+        Some(length) if length > 0 => length,
+        _ => return false,
+    };
+
+    bytes[..length]
+        .iter()
+        .all(|c| c.is_ascii_graphic() || *c == b' ')
+        && bytes[length..].iter().all(|c| *c == 0)
+}
+
+/// Guesses the boot protocol from the structure pointed to by the entry argument `arg`.
+///
+/// A stivale2 structure is recognised by its brand and version strings, while a stivale
+/// structure is recognised by its memory map fields. Limine does not pass an entry argument,
+/// see [detect_protocol_with_limine] to also detect it.
+///
+/// ## Safety
+/// If `arg` is non-null and 8 byte aligned, it must point to at least 136 readable bytes.
+pub unsafe fn detect_protocol(arg: u64) -> Protocol {
+    if arg == 0 || arg & 0x7 != 0 {
+        return Protocol::Unknown;
+    }
+
+    let bytes = core::slice::from_raw_parts(core::ptr::with_exposed_provenance(arg as usize), 136);
+
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[128..136]);
+    let tags = u64::from_ne_bytes(word);
+
+    if is_padded_string(&bytes[..64]) && is_padded_string(&bytes[64..128]) && tags & 0x7 == 0 {
+        return Protocol::Stivale2;
+    }
+
+    let stivale = &*core::ptr::with_exposed_provenance::<v1::StivaleStruct>(arg as usize);
+
+    // The memory map is always provided, and is made out of a few dozen entries at most.
+    if stivale.memory_map_addr != 0
+        && stivale.memory_map_addr & 0x7 == 0
+        && stivale.memory_map_len > 0
+        && stivale.memory_map_len <= 0x1000
+    {
+        return Protocol::Stivale;
+    }
+
+    Protocol::Unknown
+}
+
+/// Same as [detect_protocol], but reports [Protocol::Limine] if the bootloader acknowledged
+/// `base_revision`. The base revision must be at least one, as a bootloader unaware of Limine
+/// leaves it untouched.
+///
+/// ## Safety
+/// See [detect_protocol].
+pub unsafe fn detect_protocol_with_limine(
+    arg: u64,
+    base_revision: &crate::limine::LimineBaseRevision,
+) -> Protocol {
+    if base_revision.is_supported() {
+        Protocol::Limine
+    } else {
+        detect_protocol(arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::*;
+    use crate::limine::LimineBaseRevision;
+    use crate::v2::{write_boot_struct, BootResponse, StivaleMemoryMapEntry};
+
+    /// A buffer large enough for the structures, aligned like them.
+    #[repr(C, align(8))]
+    struct Buffer([u8; 136]);
+
+    impl Buffer {
+        fn addr(&self) -> u64 {
+            v2::utils::ptr_to_addr(self.0.as_ptr())
+        }
+
+        fn write_u64(&mut self, offset: usize, value: u64) {
+            self.0[offset..offset + 8].copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+
+    #[test]
+    fn stivale2_struct() {
+        let memory_map = [StivaleMemoryMapEntry::new(
+            0x100000,
+            0x100000,
+            StivaleMemoryMapEntryType::Usable,
+        )];
+        let response = BootResponse {
+            bootloader_brand: "Limine",
+            bootloader_version: "2.6.1",
+            memory_map: Some(&memory_map),
+            ..Default::default()
+        };
+
+        let mut buffer = vec![0; 512];
+        let addr = write_boot_struct(&mut buffer, &response).unwrap();
+
+        assert_eq!(unsafe { detect_protocol(addr) }, Protocol::Stivale2);
+    }
+
+    #[test]
+    fn stivale2_struct_without_brand() {
+        let mut buffer = vec![0; 512];
+        let addr = write_boot_struct(&mut buffer, &BootResponse::default()).unwrap();
+
+        assert_eq!(unsafe { detect_protocol(addr) }, Protocol::Unknown);
+    }
+
+    #[test]
+    fn stivale_struct() {
+        // The command line, memory map and framebuffer fields.
+        let mut buffer = Buffer([0; 136]);
+        buffer.write_u64(0, 0x7000);
+        buffer.write_u64(8, 0x8000);
+        buffer.write_u64(16, 12);
+        buffer.write_u64(24, 0xfd00_0000);
+
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Stivale);
+
+        // Too many memory map entries.
+        buffer.write_u64(16, 0x10000);
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Unknown);
+
+        // A misaligned memory map.
+        buffer.write_u64(8, 0x8004);
+        buffer.write_u64(16, 12);
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Unknown);
+    }
+
+    #[test]
+    fn zeroed_or_garbage() {
+        let buffer = Buffer([0; 136]);
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Unknown);
+
+        let buffer = Buffer([0xff; 136]);
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Unknown);
+
+        // Printable bytes everywhere, so the strings are never terminated.
+        let buffer = Buffer([b'A'; 136]);
+        assert_eq!(unsafe { detect_protocol(buffer.addr()) }, Protocol::Unknown);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let buffer = Buffer([0; 136]);
+
+        assert_eq!(unsafe { detect_protocol(0) }, Protocol::Unknown);
+        assert_eq!(
+            unsafe { detect_protocol(buffer.addr() + 4) },
+            Protocol::Unknown
+        );
+    }
+
+    #[test]
+    fn limine() {
+        let buffer = Buffer([0; 136]);
+
+        // The bootloader zeroes the revision once it acknowledged it.
+        let acknowledged = LimineBaseRevision::new(0);
+        let unacknowledged = LimineBaseRevision::new(1);
+
+        assert_eq!(
+            unsafe { detect_protocol_with_limine(buffer.addr(), &acknowledged) },
+            Protocol::Limine
+        );
+        assert_eq!(
+            unsafe { detect_protocol_with_limine(buffer.addr(), &unacknowledged) },
+            Protocol::Unknown
+        );
+    }
+}
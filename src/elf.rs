@@ -0,0 +1,356 @@
+//! Minimal, allocation free ELF reader used to inspect kernel images. Only little endian
+//! images are supported, which covers every architecture targeted by the stivale protocols.
+
+use core::convert::TryFrom;
+
+/// Errors returned while reading an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElfError {
+    /// The image does not start with the ELF magic.
+    InvalidMagic,
+    /// The image is neither a 32-bit nor a 64-bit little endian ELF.
+    Unsupported,
+    /// A header or a section points outside of the image.
+    Truncated,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+/// A section of the ELF image.
+#[derive(Clone, Copy)]
+pub(crate) struct ElfSection {
+    pub name: u32,
+    pub kind: u32,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+}
+
+/// A loadable segment of the ELF image.
+#[derive(Clone, Copy)]
+pub(crate) struct ElfSegment {
+    pub kind: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub file_size: u64,
+}
+
+pub(crate) const PT_LOAD: u32 = 1;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+/// A symbol of the symbol table.
+#[derive(Clone, Copy)]
+struct ElfSymbol {
+    name: u32,
+    kind: u8,
+    section: u16,
+    value: u64,
+    size: u64,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ElfFile<'a> {
+    image: &'a [u8],
+    class: ElfClass,
+}
+
+fn read<const N: usize>(image: &[u8], offset: usize) -> Result<[u8; N], ElfError> {
+    let end = offset.checked_add(N).ok_or(ElfError::Truncated)?;
+    let bytes = image.get(offset..end).ok_or(ElfError::Truncated)?;
+
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    Ok(array)
+}
+
+fn read_u16(image: &[u8], offset: usize) -> Result<u16, ElfError> {
+    read(image, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(image: &[u8], offset: usize) -> Result<u32, ElfError> {
+    read(image, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(image: &[u8], offset: usize) -> Result<u64, ElfError> {
+    read(image, offset).map(u64::from_le_bytes)
+}
+
+/// Returns the offset of the entry at `index` of the table at `table`, whose entries are
+/// `entry_size` bytes apart, checking that the `fields_size` bytes read from it do not overflow.
+fn entry_offset(
+    table: usize,
+    entry_size: usize,
+    index: usize,
+    fields_size: usize,
+) -> Result<usize, ElfError> {
+    let base = index
+        .checked_mul(entry_size)
+        .and_then(|offset| offset.checked_add(table))
+        .ok_or(ElfError::Truncated)?;
+
+    base.checked_add(fields_size).ok_or(ElfError::Truncated)?;
+    Ok(base)
+}
+
+impl<'a> ElfFile<'a> {
+    pub fn parse(image: &'a [u8]) -> Result<Self, ElfError> {
+        if image.get(0..4) != Some(b"\x7fELF".as_ref()) {
+            return Err(ElfError::InvalidMagic);
+        }
+
+        let class = match image.get(4) {
+            Some(1) => ElfClass::Elf32,
+            Some(2) => ElfClass::Elf64,
+            _ => return Err(ElfError::Unsupported),
+        };
+
+        // Only little endian images are supported.
+        if image.get(5) != Some(&1) {
+            return Err(ElfError::Unsupported);
+        }
+
+        Ok(Self { image, class })
+    }
+
+    /// Reads a natural word (4 bytes for ELF32 and 8 bytes for ELF64) at `offset`.
+    fn word(&self, offset: usize) -> Result<u64, ElfError> {
+        match self.class {
+            ElfClass::Elf32 => read_u32(self.image, offset).map(u64::from),
+            ElfClass::Elf64 => read_u64(self.image, offset),
+        }
+    }
+
+    /// Returns the offset, entry size and count of either the section or the program
+    /// header table.
+    fn table(&self, sections: bool) -> Result<(usize, usize, usize), ElfError> {
+        let (offset, entry_size, count) = match (self.class, sections) {
+            (ElfClass::Elf32, false) => (0x1c, 0x2a, 0x2c),
+            (ElfClass::Elf32, true) => (0x20, 0x2e, 0x30),
+            (ElfClass::Elf64, false) => (0x20, 0x36, 0x38),
+            (ElfClass::Elf64, true) => (0x28, 0x3a, 0x3c),
+        };
+
+        Ok((
+            usize::try_from(self.word(offset)?).map_err(|_| ElfError::Truncated)?,
+            read_u16(self.image, entry_size)? as usize,
+            read_u16(self.image, count)? as usize,
+        ))
+    }
+
+    pub fn section_count(&self) -> Result<usize, ElfError> {
+        Ok(self.table(true)?.2)
+    }
+
+    pub fn section(&self, index: usize) -> Result<ElfSection, ElfError> {
+        let (table, entry_size, count) = self.table(true)?;
+
+        if index >= count {
+            return Err(ElfError::Truncated);
+        }
+
+        let fields_size = match self.class {
+            ElfClass::Elf32 => 0x28,
+            ElfClass::Elf64 => 0x40,
+        };
+
+        let base = entry_offset(table, entry_size, index, fields_size)?;
+        let image = self.image;
+
+        Ok(match self.class {
+            ElfClass::Elf32 => ElfSection {
+                name: read_u32(image, base)?,
+                kind: read_u32(image, base + 0x04)?,
+                offset: read_u32(image, base + 0x10)? as u64,
+                size: read_u32(image, base + 0x14)? as u64,
+                link: read_u32(image, base + 0x18)?,
+            },
+            ElfClass::Elf64 => ElfSection {
+                name: read_u32(image, base)?,
+                kind: read_u32(image, base + 0x04)?,
+                offset: read_u64(image, base + 0x18)?,
+                size: read_u64(image, base + 0x20)?,
+                link: read_u32(image, base + 0x28)?,
+            },
+        })
+    }
+
+    /// Returns the contents of `section`.
+    pub fn section_data(&self, section: &ElfSection) -> Result<&'a [u8], ElfError> {
+        let start = section.offset as usize;
+        let end = start
+            .checked_add(section.size as usize)
+            .ok_or(ElfError::Truncated)?;
+
+        self.image.get(start..end).ok_or(ElfError::Truncated)
+    }
+
+    /// Returns the null-terminated string at `offset` in the string table `strtab`.
+    pub fn string(&self, strtab: &ElfSection, offset: u32) -> Result<&'a [u8], ElfError> {
+        let data = self.section_data(strtab)?;
+        let string = data.get(offset as usize..).ok_or(ElfError::Truncated)?;
+        let length = string.iter().position(|c| *c == 0).unwrap_or(string.len());
+
+        Ok(&string[..length])
+    }
+
+    /// Looks up a section by name.
+    pub fn section_by_name(&self, name: &[u8]) -> Result<Option<ElfSection>, ElfError> {
+        let shstrndx = match self.class {
+            ElfClass::Elf32 => read_u16(self.image, 0x32)?,
+            ElfClass::Elf64 => read_u16(self.image, 0x3e)?,
+        };
+
+        let strtab = self.section(shstrndx as usize)?;
+
+        for index in 0..self.section_count()? {
+            let section = self.section(index)?;
+
+            if self.string(&strtab, section.name)? == name {
+                return Ok(Some(section));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn segment_count(&self) -> Result<usize, ElfError> {
+        Ok(self.table(false)?.2)
+    }
+
+    pub fn segment(&self, index: usize) -> Result<ElfSegment, ElfError> {
+        let (table, entry_size, count) = self.table(false)?;
+
+        if index >= count {
+            return Err(ElfError::Truncated);
+        }
+
+        let fields_size = match self.class {
+            ElfClass::Elf32 => 0x20,
+            ElfClass::Elf64 => 0x38,
+        };
+
+        let base = entry_offset(table, entry_size, index, fields_size)?;
+        let image = self.image;
+
+        Ok(match self.class {
+            ElfClass::Elf32 => ElfSegment {
+                kind: read_u32(image, base)?,
+                offset: read_u32(image, base + 0x04)? as u64,
+                vaddr: read_u32(image, base + 0x08)? as u64,
+                file_size: read_u32(image, base + 0x10)? as u64,
+            },
+            ElfClass::Elf64 => ElfSegment {
+                kind: read_u32(image, base)?,
+                offset: read_u64(image, base + 0x08)?,
+                vaddr: read_u64(image, base + 0x10)?,
+                file_size: read_u64(image, base + 0x20)?,
+            },
+        })
+    }
+
+    /// Translates the virtual address `addr` into an offset in the image, using the file
+    /// backed part of the loadable segments.
+    pub fn offset_of(&self, addr: u64) -> Option<usize> {
+        for index in 0..self.segment_count().ok()? {
+            let segment = self.segment(index).ok()?;
+
+            if segment.kind == PT_LOAD
+                && addr >= segment.vaddr
+                && addr - segment.vaddr < segment.file_size
+            {
+                let offset = segment.offset.checked_add(addr - segment.vaddr)?;
+                return usize::try_from(offset).ok();
+            }
+        }
+
+        None
+    }
+
+    /// Reads the symbol at `index` in the symbol table `symtab`.
+    fn symbol(&self, symtab: &ElfSection, index: usize) -> Result<ElfSymbol, ElfError> {
+        let data = self.section_data(symtab)?;
+
+        Ok(match self.class {
+            ElfClass::Elf32 => {
+                let base = entry_offset(0, 0x10, index, 0x10)?;
+                ElfSymbol {
+                    name: read_u32(data, base)?,
+                    kind: read::<1>(data, base + 0x0c)?[0] & 0xf,
+                    section: read_u16(data, base + 0x0e)?,
+                    value: read_u32(data, base + 0x04)? as u64,
+                    size: read_u32(data, base + 0x08)? as u64,
+                }
+            }
+            ElfClass::Elf64 => {
+                let base = entry_offset(0, 0x18, index, 0x18)?;
+                ElfSymbol {
+                    name: read_u32(data, base)?,
+                    kind: read::<1>(data, base + 0x04)?[0] & 0xf,
+                    section: read_u16(data, base + 0x06)?,
+                    value: read_u64(data, base + 0x08)?,
+                    size: read_u64(data, base + 0x10)?,
+                }
+            }
+        })
+    }
+
+    /// Returns the name of the function containing the virtual address `addr` along with the
+    /// offset of `addr` in it. Functions with an unknown size are assumed to extend up to the
+    /// next function.
+    pub fn symbolize(&self, addr: u64) -> Result<Option<(&'a [u8], u64)>, ElfError> {
+        let mut symtab = None;
+
+        for index in 0..self.section_count()? {
+            let section = self.section(index)?;
+
+            if section.kind == SHT_SYMTAB {
+                symtab = Some(section);
+                break;
+            }
+        }
+
+        let symtab = match symtab {
+            Some(symtab) => symtab,
+            None => return Ok(None),
+        };
+
+        let strtab = self.section(symtab.link as usize)?;
+        let entry_size = match self.class {
+            ElfClass::Elf32 => 0x10,
+            ElfClass::Elf64 => 0x18,
+        };
+
+        let mut best: Option<ElfSymbol> = None;
+
+        for index in 0..symtab.size as usize / entry_size {
+            let symbol = self.symbol(&symtab, index)?;
+
+            // Skip undefined symbols, which have no address.
+            if symbol.kind != STT_FUNC || symbol.section == 0 || symbol.value > addr {
+                continue;
+            }
+
+            if symbol.size != 0 && addr - symbol.value >= symbol.size {
+                continue;
+            }
+
+            if best.is_none_or(|best| symbol.value > best.value) {
+                best = Some(symbol);
+            }
+        }
+
+        match best {
+            Some(symbol) => Ok(Some((
+                self.string(&strtab, symbol.name)?,
+                addr - symbol.value,
+            ))),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Readers for the archive formats commonly used for initramfs modules, the `newc` cpio format
+//! and the ustar format. Both readers iterate over the entries of an archive in place, without
+//! allocating.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use stivale_boot::initramfs::CpioReader;
+//! use stivale_boot::v2::StivaleStruct;
+//!
+//! fn find_init(boot_info: &StivaleStruct) -> Option<&'static [u8]> {
+//!     let initrd = boot_info.modules()?.find("initrd")?;
+//!
+//!     CpioReader::new(initrd.as_bytes())
+//!         .filter_map(Result::ok)
+//!         .find(|entry| entry.path == "sbin/init")
+//!         .map(|entry| entry.data)
+//! }
+//! ```
+
+/// Errors returned while reading an archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitramfsError {
+    /// A header or the data of an entry extends past the end of the archive.
+    Truncated,
+    /// A header does not have the expected magic.
+    InvalidMagic,
+    /// A numeric field of a header is malformed.
+    InvalidField,
+    /// A path is not valid UTF-8.
+    InvalidPath,
+}
+
+/// An entry of an archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InitramfsEntry<'a> {
+    /// The path of the entry, relative to the root of the archive. For ustar archives, this is
+    /// the name field of the header, see `prefix`.
+    pub path: &'a str,
+    /// The prefix of the path, which has to be prepended to `path` with a `/` separator if not
+    /// empty. Only used by ustar archives, for paths longer than 100 bytes.
+    pub prefix: &'a str,
+    /// The file mode, containing both the file type and the permission bits.
+    pub mode: u32,
+    /// The contents of the entry.
+    pub data: &'a [u8],
+}
+
+impl<'a> InitramfsEntry<'a> {
+    /// Returns whether the entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.mode & 0o170000 == 0o100000
+    }
+
+    /// Returns whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+}
+
+fn path(bytes: &[u8]) -> Result<&str, InitramfsError> {
+    let length = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..length]).map_err(|_| InitramfsError::InvalidPath)
+}
+
+/// Rounds `value` up to the next multiple of `align`, which must be a power of two.
+fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// The magic of `newc` cpio headers, without and with checksums.
+const CPIO_MAGIC: [&[u8]; 2] = [b"070701", b"070702"];
+/// Size of a `newc` cpio header.
+const CPIO_HEADER_SIZE: usize = 110;
+/// Path of the entry marking the end of a cpio archive.
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+/// Reader over the entries of a `newc` cpio archive, as produced by `cpio -H newc`.
+#[derive(Clone)]
+pub struct CpioReader<'a> {
+    /// The part of the archive that has not been read yet.
+    remaining: &'a [u8],
+}
+
+impl<'a> CpioReader<'a> {
+    /// Creates a new reader over the archive in `archive`.
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self { remaining: archive }
+    }
+
+    /// Parses the 8 digit hexadecimal header field at `index`.
+    fn field(header: &[u8], index: usize) -> Result<usize, InitramfsError> {
+        let start = 6 + index * 8;
+        let digits = core::str::from_utf8(&header[start..start + 8])
+            .map_err(|_| InitramfsError::InvalidField)?;
+
+        usize::from_str_radix(digits, 16).map_err(|_| InitramfsError::InvalidField)
+    }
+
+    fn read_entry(&mut self) -> Result<Option<InitramfsEntry<'a>>, InitramfsError> {
+        let archive = self.remaining;
+        let header = archive
+            .get(..CPIO_HEADER_SIZE)
+            .ok_or(InitramfsError::Truncated)?;
+
+        if !CPIO_MAGIC.contains(&&header[..6]) {
+            return Err(InitramfsError::InvalidMagic);
+        }
+
+        let mode = Self::field(header, 1)? as u32;
+        let file_size = Self::field(header, 6)?;
+        let name_size = Self::field(header, 11)?;
+
+        let name_end = CPIO_HEADER_SIZE
+            .checked_add(name_size)
+            .ok_or(InitramfsError::Truncated)?;
+        let name = archive
+            .get(CPIO_HEADER_SIZE..name_end)
+            .ok_or(InitramfsError::Truncated)?;
+        let path = path(name)?;
+
+        let data_start = align_up(name_end, 4);
+        let data_end = data_start
+            .checked_add(file_size)
+            .ok_or(InitramfsError::Truncated)?;
+        let data = archive
+            .get(data_start..data_end)
+            .ok_or(InitramfsError::Truncated)?;
+
+        if path == CPIO_TRAILER {
+            return Ok(None);
+        }
+
+        self.remaining = archive.get(align_up(data_end, 4)..).unwrap_or(&[]);
+
+        Ok(Some(InitramfsEntry {
+            path,
+            prefix: "",
+            mode,
+            data,
+        }))
+    }
+}
+
+impl<'a> Iterator for CpioReader<'a> {
+    type Item = Result<InitramfsEntry<'a>, InitramfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        let entry = self.read_entry();
+
+        // Stop at the trailer or at the first error.
+        if !matches!(entry, Ok(Some(_))) {
+            self.remaining = &[];
+        }
+
+        entry.transpose()
+    }
+}
+
+/// Size of a ustar block, which is also the size of a header.
+const TAR_BLOCK_SIZE: usize = 512;
+/// The magic of ustar headers, which is also accepted without the trailing null byte as
+/// written by GNU tar.
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Reader over the entries of a ustar archive, as produced by `tar --format=ustar`.
+#[derive(Clone)]
+pub struct TarReader<'a> {
+    /// The part of the archive that has not been read yet.
+    remaining: &'a [u8],
+}
+
+impl<'a> TarReader<'a> {
+    /// Creates a new reader over the archive in `archive`.
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self { remaining: archive }
+    }
+
+    /// Parses the octal header field spanning `range`, which may be padded with spaces and
+    /// null bytes.
+    fn field(header: &[u8], range: core::ops::Range<usize>) -> Result<usize, InitramfsError> {
+        let digits = core::str::from_utf8(&header[range])
+            .map_err(|_| InitramfsError::InvalidField)?
+            .trim_matches(|c| c == ' ' || c == '\0');
+
+        if digits.is_empty() {
+            return Ok(0);
+        }
+
+        usize::from_str_radix(digits, 8).map_err(|_| InitramfsError::InvalidField)
+    }
+
+    fn read_entry(&mut self) -> Result<Option<InitramfsEntry<'a>>, InitramfsError> {
+        let archive = self.remaining;
+        let header = archive
+            .get(..TAR_BLOCK_SIZE)
+            .ok_or(InitramfsError::Truncated)?;
+
+        // The archive ends with zeroed blocks.
+        if header[0] == 0 {
+            return Ok(None);
+        }
+
+        if &header[257..262] != TAR_MAGIC {
+            return Err(InitramfsError::InvalidMagic);
+        }
+
+        let mode = Self::field(header, 100..108)? as u32;
+        let size = Self::field(header, 124..136)?;
+
+        // The mode field only holds the permission bits, the file type is in the type flag.
+        let kind = match header[156] {
+            b'5' => 0o040000,
+            b'2' => 0o120000,
+            b'3' => 0o020000,
+            b'4' => 0o060000,
+            b'6' => 0o010000,
+            _ => 0o100000,
+        };
+
+        let data_end = TAR_BLOCK_SIZE
+            .checked_add(size)
+            .ok_or(InitramfsError::Truncated)?;
+        let data = archive
+            .get(TAR_BLOCK_SIZE..data_end)
+            .ok_or(InitramfsError::Truncated)?;
+
+        self.remaining = archive
+            .get(align_up(data_end, TAR_BLOCK_SIZE)..)
+            .unwrap_or(&[]);
+
+        Ok(Some(InitramfsEntry {
+            path: path(&header[..100])?,
+            prefix: path(&header[345..500])?,
+            mode: (mode & 0o7777) | kind,
+            data,
+        }))
+    }
+}
+
+impl<'a> Iterator for TarReader<'a> {
+    type Item = Result<InitramfsEntry<'a>, InitramfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        let entry = self.read_entry();
+
+        // Stop at the end of the archive or at the first error.
+        if !matches!(entry, Ok(Some(_))) {
+            self.remaining = &[];
+        }
+
+        entry.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Appends a `newc` cpio entry, with the name size and file size fields set to `name_size`
+    /// and `file_size`.
+    fn cpio_entry_with_sizes(
+        archive: &mut Vec<u8>,
+        path: &str,
+        mode: u32,
+        data: &[u8],
+        name_size: usize,
+        file_size: usize,
+    ) {
+        let mut fields = [0; 13];
+        fields[1] = mode as usize;
+        fields[4] = 1;
+        fields[6] = file_size;
+        fields[11] = name_size;
+
+        archive.extend_from_slice(b"070701");
+
+        for field in fields {
+            archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+        }
+
+        archive.extend_from_slice(path.as_bytes());
+        archive.push(0);
+        archive.resize(align_up(archive.len(), 4), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align_up(archive.len(), 4), 0);
+    }
+
+    fn cpio_entry(archive: &mut Vec<u8>, path: &str, mode: u32, data: &[u8]) {
+        cpio_entry_with_sizes(archive, path, mode, data, path.len() + 1, data.len());
+    }
+
+    fn cpio_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+
+        cpio_entry(&mut archive, "sbin", 0o040755, &[]);
+        cpio_entry(&mut archive, "sbin/init", 0o100755, b"\x7fELF init");
+        cpio_entry(&mut archive, CPIO_TRAILER, 0, &[]);
+        archive
+    }
+
+    #[test]
+    fn cpio_entries() {
+        let mut archive = cpio_archive();
+
+        // Anything past the trailer is ignored.
+        archive.extend_from_slice(&[0xff; 16]);
+
+        let entries = CpioReader::new(&archive)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].path, "sbin");
+        assert!(entries[0].is_dir());
+        assert!(entries[0].data.is_empty());
+
+        assert_eq!(entries[1].path, "sbin/init");
+        assert_eq!(entries[1].prefix, "");
+        assert_eq!(entries[1].mode, 0o100755);
+        assert!(entries[1].is_file());
+        assert_eq!(entries[1].data, b"\x7fELF init");
+    }
+
+    #[test]
+    fn cpio_truncated() {
+        let archive = cpio_archive();
+
+        // In the middle of the header of the first entry.
+        let mut reader = CpioReader::new(&archive[..CPIO_HEADER_SIZE - 10]);
+        assert_eq!(reader.next(), Some(Err(InitramfsError::Truncated)));
+        assert_eq!(reader.next(), None);
+
+        // In the middle of the data of the second entry.
+        let mut reader = CpioReader::new(&archive[..CPIO_HEADER_SIZE * 2 + 20]);
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert_eq!(reader.next(), Some(Err(InitramfsError::Truncated)));
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn cpio_oversized_sizes() {
+        let mut archive = Vec::new();
+        cpio_entry_with_sizes(&mut archive, "init", 0o100755, b"init", 5, 0xffff_ffff);
+        assert_eq!(
+            CpioReader::new(&archive).next(),
+            Some(Err(InitramfsError::Truncated))
+        );
+
+        let mut archive = Vec::new();
+        cpio_entry_with_sizes(&mut archive, "init", 0o100755, b"init", 0xffff_ffff, 4);
+        assert_eq!(
+            CpioReader::new(&archive).next(),
+            Some(Err(InitramfsError::Truncated))
+        );
+    }
+
+    #[test]
+    fn cpio_invalid_header() {
+        let mut archive = cpio_archive();
+        archive[..6].copy_from_slice(b"070707");
+        assert_eq!(
+            CpioReader::new(&archive).next(),
+            Some(Err(InitramfsError::InvalidMagic))
+        );
+
+        // The file size field of the first entry.
+        let mut archive = cpio_archive();
+        archive[54..62].copy_from_slice(b"0000000g");
+        assert_eq!(
+            CpioReader::new(&archive).next(),
+            Some(Err(InitramfsError::InvalidField))
+        );
+    }
+
+    /// Returns a ustar header, with the size field set to `size`.
+    fn tar_header(name: &str, prefix: &str, mode: u32, type_flag: u8, size: usize) -> Vec<u8> {
+        let mut header = std::vec![0; TAR_BLOCK_SIZE];
+
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(format!("{:07o}", mode).as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = type_flag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        header
+    }
+
+    fn tar_entry(archive: &mut Vec<u8>, name: &str, prefix: &str, type_flag: u8, data: &[u8]) {
+        archive.extend_from_slice(&tar_header(name, prefix, 0o755, type_flag, data.len()));
+        archive.extend_from_slice(data);
+        archive.resize(align_up(archive.len(), TAR_BLOCK_SIZE), 0);
+    }
+
+    fn tar_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+
+        tar_entry(&mut archive, "sbin/", "", b'5', &[]);
+        tar_entry(&mut archive, "init", "usr/lib/systemd", b'0', &[0xaa; 600]);
+        tar_entry(&mut archive, "sbin/init", "", b'2', &[]);
+        archive.resize(archive.len() + TAR_BLOCK_SIZE * 2, 0);
+        archive
+    }
+
+    #[test]
+    fn tar_entries() {
+        let archive = tar_archive();
+        let entries = TarReader::new(&archive)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].path, "sbin/");
+        assert_eq!(entries[0].mode, 0o040755);
+        assert!(entries[0].is_dir());
+
+        // The data spans two blocks, the second one padded.
+        assert_eq!(entries[1].path, "init");
+        assert_eq!(entries[1].prefix, "usr/lib/systemd");
+        assert_eq!(entries[1].mode, 0o100755);
+        assert!(entries[1].is_file());
+        assert_eq!(entries[1].data, &[0xaa; 600][..]);
+
+        assert_eq!(entries[2].mode, 0o120755);
+        assert!(!entries[2].is_file());
+    }
+
+    #[test]
+    fn tar_type_flags() {
+        let kinds = [
+            (b'0', 0o100000),
+            (0, 0o100000),
+            (b'2', 0o120000),
+            (b'3', 0o020000),
+            (b'4', 0o060000),
+            (b'5', 0o040000),
+            (b'6', 0o010000),
+        ];
+
+        for (type_flag, kind) in kinds {
+            let mut archive = Vec::new();
+            tar_entry(&mut archive, "entry", "", type_flag, &[]);
+
+            let entry = TarReader::new(&archive).next().unwrap().unwrap();
+            assert_eq!(entry.mode, 0o755 | kind);
+        }
+    }
+
+    #[test]
+    fn tar_truncated() {
+        let archive = tar_archive();
+
+        let mut reader = TarReader::new(&archive[..TAR_BLOCK_SIZE - 1]);
+        assert_eq!(reader.next(), Some(Err(InitramfsError::Truncated)));
+        assert_eq!(reader.next(), None);
+
+        // In the middle of the data of the second entry.
+        let mut reader = TarReader::new(&archive[..TAR_BLOCK_SIZE * 3]);
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert_eq!(reader.next(), Some(Err(InitramfsError::Truncated)));
+        assert_eq!(reader.next(), None);
+    }
+
+    // The size does not fit in a 32-bit `usize`, so it is rejected as malformed there.
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn tar_oversized_size() {
+        let archive = tar_header("init", "", 0o755, b'0', 0o77_777_777_777);
+
+        assert_eq!(
+            TarReader::new(&archive).next(),
+            Some(Err(InitramfsError::Truncated))
+        );
+    }
+
+    #[test]
+    fn tar_invalid_header() {
+        let mut archive = tar_archive();
+        archive[257..263].copy_from_slice(b"cpio\0\0");
+        assert_eq!(
+            TarReader::new(&archive).next(),
+            Some(Err(InitramfsError::InvalidMagic))
+        );
+
+        let mut archive = tar_archive();
+        archive[124..135].copy_from_slice(b"0000000009x");
+        assert_eq!(
+            TarReader::new(&archive).next(),
+            Some(Err(InitramfsError::InvalidField))
+        );
+    }
+}
@@ -1,4 +1,22 @@
 #![no_std]
 
+#[cfg(any(feature = "alloc", feature = "mock", feature = "arbitrary"))]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "elf")]
+mod elf;
+
+pub mod boot_info;
+#[cfg(feature = "initramfs")]
+pub mod initramfs;
+pub mod limine;
+pub mod prelude;
+#[cfg(feature = "qemu")]
+pub mod qemu;
 pub mod v1;
 pub mod v2;
@@ -0,0 +1,251 @@
+//! Conversions between the stivale2 and Limine structures, easing the migration of kernels and
+//! letting bootloaders expose both protocols from the same information.
+
+use core::convert::TryFrom;
+use core::num::TryFromIntError;
+
+use super::*;
+use crate::v2::{self, BuildError, StivaleStruct, TagArena};
+
+/// Returns the raw Limine memory map type of `entry_type`.
+fn raw_entry_type(entry_type: StivaleMemoryMapEntryType) -> u64 {
+    match entry_type {
+        StivaleMemoryMapEntryType::Usable => memory_map_type::USABLE,
+        StivaleMemoryMapEntryType::Reserved => memory_map_type::RESERVED,
+        StivaleMemoryMapEntryType::AcpiReclaimable => memory_map_type::ACPI_RECLAIMABLE,
+        StivaleMemoryMapEntryType::AcpiNvs => memory_map_type::ACPI_NVS,
+        StivaleMemoryMapEntryType::BadMemory => memory_map_type::BAD_MEMORY,
+        StivaleMemoryMapEntryType::BootloaderReclaimable => memory_map_type::BOOTLOADER_RECLAIMABLE,
+        StivaleMemoryMapEntryType::Kernel => memory_map_type::KERNEL_AND_MODULES,
+        StivaleMemoryMapEntryType::Framebuffer => memory_map_type::FRAMEBUFFER,
+    }
+}
+
+impl From<&v2::StivaleMemoryMapEntry> for LimineMemmapEntry {
+    fn from(entry: &v2::StivaleMemoryMapEntry) -> Self {
+        Self::new(entry.base, entry.length, raw_entry_type(entry.entry_type()))
+    }
+}
+
+impl From<&LimineMemmapEntry> for v2::StivaleMemoryMapEntry {
+    fn from(entry: &LimineMemmapEntry) -> Self {
+        Self::new(entry.base, entry.length, entry.entry_type())
+    }
+}
+
+impl From<&v2::StivaleFramebufferTag> for LimineFramebuffer {
+    fn from(tag: &v2::StivaleFramebufferTag) -> Self {
+        let mut framebuffer = Self::new(
+            tag.framebuffer_addr,
+            tag.framebuffer_width as u64,
+            tag.framebuffer_height as u64,
+            tag.framebuffer_pitch as u64,
+            tag.framebuffer_bpp,
+        );
+
+        framebuffer.memory_model = tag.memory_model;
+        framebuffer.red_mask_size = tag.red_mask_size;
+        framebuffer.red_mask_shift = tag.red_mask_shift;
+        framebuffer.green_mask_size = tag.green_mask_size;
+        framebuffer.green_mask_shift = tag.green_mask_shift;
+        framebuffer.blue_mask_size = tag.blue_mask_size;
+        framebuffer.blue_mask_shift = tag.blue_mask_shift;
+        framebuffer
+    }
+}
+
+/// Fails if the dimensions of the framebuffer do not fit in the 16-bit fields used by stivale2.
+impl TryFrom<&LimineFramebuffer> for v2::StivaleFramebufferTag {
+    type Error = TryFromIntError;
+
+    fn try_from(framebuffer: &LimineFramebuffer) -> Result<Self, Self::Error> {
+        let mut tag = Self::new(
+            framebuffer.address,
+            u16::try_from(framebuffer.width)?,
+            u16::try_from(framebuffer.height)?,
+            u16::try_from(framebuffer.pitch)?,
+            framebuffer.bpp,
+        );
+
+        tag.memory_model = framebuffer.memory_model;
+        tag.red_mask_size = framebuffer.red_mask_size;
+        tag.red_mask_shift = framebuffer.red_mask_shift;
+        tag.green_mask_size = framebuffer.green_mask_size;
+        tag.green_mask_shift = framebuffer.green_mask_shift;
+        tag.blue_mask_size = framebuffer.blue_mask_size;
+        tag.blue_mask_shift = framebuffer.blue_mask_shift;
+        Ok(tag)
+    }
+}
+
+/// The command line of the file is used as the module string, and truncated to 127 bytes.
+impl From<&LimineFile> for v2::StivaleModule {
+    fn from(file: &LimineFile) -> Self {
+        Self::new(file.cmdline(), file.address, file.address + file.size)
+    }
+}
+
+/// The size of the string field of [v2::StivaleModule].
+const MODULE_STRING_LEN: usize = 128;
+
+/// Limine responses built from a stivale2 structure.
+#[derive(Default)]
+pub struct LimineResponses<'b> {
+    pub memmap: Option<&'b LimineMemmapResponse>,
+    pub framebuffer: Option<&'b LimineFramebufferResponse>,
+    pub hhdm: Option<&'b LimineHhdmResponse>,
+    pub modules: Option<&'b LimineModuleResponse>,
+}
+
+impl<'b> LimineResponses<'b> {
+    /// Builds the responses matching the tags of `stivale` in `arena`. The pointers stored in
+    /// the responses are the addresses the kernel will see the arena at.
+    pub fn from_stivale2(
+        arena: &mut TagArena<'b>,
+        stivale: &StivaleStruct,
+    ) -> Result<Self, BuildError> {
+        let mut responses = Self::default();
+
+        if let Some(tag) = stivale.memory_map() {
+            let array = alloc_array(arena, tag.as_slice(), |_, entry| {
+                Ok(LimineMemmapEntry::from(entry))
+            })?;
+
+            let count = tag.as_slice().len() as u64;
+            responses.memmap = Some(arena.alloc(&LimineMemmapResponse::new(count, array))?);
+        }
+
+        if let Some(tag) = stivale.framebuffer() {
+            let array = alloc_array(arena, core::slice::from_ref(tag), |_, tag| {
+                Ok(LimineFramebuffer::from(tag))
+            })?;
+
+            responses.framebuffer = Some(arena.alloc(&LimineFramebufferResponse::new(1, array))?);
+        }
+
+        if let Some(tag) = stivale.vmap() {
+            responses.hhdm = Some(arena.alloc(&LimineHhdmResponse::new(tag.address))?);
+        }
+
+        if let Some(tag) = stivale.modules() {
+            let array = alloc_array(arena, tag.as_slice(), |arena, module| {
+                // The string may lack its null terminator if it was truncated, so it is copied
+                // with a terminator of its own, which doubles as the empty path.
+                let bytes = module.string.as_bytes();
+                let length = bytes.len();
+
+                let mut terminated = [0; MODULE_STRING_LEN + 1];
+                terminated[..length].copy_from_slice(bytes);
+
+                let string = arena.alloc_bytes(&terminated[..length + 1], 1)?;
+                let cmdline = arena.address_of(&*string);
+
+                Ok(LimineFile::new(
+                    module.start,
+                    module.size(),
+                    (cmdline + length as u64) as *const u8,
+                    cmdline as *const u8,
+                ))
+            })?;
+
+            let count = tag.as_slice().len() as u64;
+            responses.modules = Some(arena.alloc(&LimineModuleResponse::new(count, array))?);
+        }
+
+        Ok(responses)
+    }
+}
+
+/// Copies the conversion of every item of `items` into `arena`, along with an array of pointers
+/// to the copies, and returns the address of the array.
+fn alloc_array<'b, S, T: 'b>(
+    arena: &mut TagArena<'b>,
+    items: &[S],
+    mut convert: impl FnMut(&mut TagArena<'b>, &S) -> Result<T, BuildError>,
+) -> Result<u64, BuildError> {
+    let pointers = arena.alloc_words(items.len())?;
+
+    for (pointer, item) in pointers.iter_mut().zip(items) {
+        let converted = convert(arena, item)?;
+        let copy = arena.alloc(&converted)?;
+
+        *pointer = arena.address_of(&*copy);
+    }
+
+    Ok(arena.address_of(&*pointers))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::*;
+    use crate::v2::{round_trip, BootResponse, StivaleModule};
+
+    /// Returns a module whose string field holds `string` as is.
+    fn module(string: [u8; MODULE_STRING_LEN]) -> StivaleModule {
+        let mut module = StivaleModule::new("", 0x1000, 0x2000);
+
+        // SAFETY: The string field is a transparent wrapper around the array.
+        unsafe {
+            core::ptr::addr_of_mut!(module.string)
+                .cast::<[u8; MODULE_STRING_LEN]>()
+                .write_unaligned(string)
+        };
+
+        module
+    }
+
+    /// Converts the only module of a stivale2 struct holding `module`.
+    fn convert(module: StivaleModule) -> &'static LimineFile {
+        let modules = [module];
+        let response = BootResponse {
+            modules: Some(&modules),
+            ..Default::default()
+        };
+
+        let stivale = round_trip(Box::leak(Box::new([0u8; 1024])), &response).unwrap();
+        let mut arena = TagArena::new(Box::leak(Box::new([0u8; 1024])));
+        let responses = LimineResponses::from_stivale2(&mut arena, stivale).unwrap();
+
+        responses.modules.unwrap().modules()[0]
+    }
+
+    /// Checks that the path of `file` is the empty string at the terminator of its command line
+    /// of `length` bytes.
+    fn assert_terminated(file: &LimineFile, length: usize) {
+        let path = file.path().as_ptr();
+
+        assert_eq!(path, file.cmdline().as_ptr().wrapping_add(length));
+        assert_eq!(unsafe { *path }, 0);
+    }
+
+    #[test]
+    fn module_string() {
+        let mut string = [0; MODULE_STRING_LEN];
+        string[..6].copy_from_slice(b"initrd");
+
+        let file = convert(module(string));
+        assert_eq!(file.cmdline(), "initrd");
+        assert_eq!(file.path(), "");
+        assert_terminated(file, 6);
+    }
+
+    #[test]
+    fn truncated_module_string() {
+        let file = convert(module([b'a'; MODULE_STRING_LEN]));
+
+        assert_eq!(file.cmdline().len(), MODULE_STRING_LEN);
+        assert_terminated(file, MODULE_STRING_LEN);
+    }
+
+    #[test]
+    fn invalid_utf8_module_string() {
+        let mut string = [0; MODULE_STRING_LEN];
+        string[..5].copy_from_slice(b"ab\xffcd");
+
+        let file = convert(module(string));
+        assert_eq!(file.cmdline(), "ab");
+        assert_terminated(file, 5);
+    }
+}
@@ -0,0 +1,136 @@
+//! This module contains the definitions for the Limine boot protocol, the successor of the
+//! stivale2 boot protocol. Instead of a header with a linked list of tags, the kernel places
+//! requests anywhere in its image, which the bootloader finds by their identifiers and answers
+//! by filling in their response pointer.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use stivale_boot::limine::*;
+//!
+//! #[used]
+//! #[link_section = ".requests"]
+//! static BASE_REVISION: LimineBaseRevision = LimineBaseRevision::new(1);
+//!
+//! #[used]
+//! #[link_section = ".requests"]
+//! static MEMMAP_REQUEST: LimineMemmapRequest = LimineMemmapRequest::new();
+//!
+//! fn kmain() {
+//!     assert!(BASE_REVISION.is_supported());
+//!
+//!     let memmap = MEMMAP_REQUEST.get_response().expect("no memory map");
+//!
+//!     for entry in memmap.entries() {
+//!         let _ = (entry.base, entry.length, entry.entry_type());
+//!     }
+//! }
+//! ```
+
+use core::cell::UnsafeCell;
+
+mod compat;
+mod request;
+mod response;
+
+pub use compat::*;
+pub use request::*;
+pub use response::*;
+
+pub use crate::v2::StivaleMemoryMapEntryType;
+
+/// The magic shared by the identifiers of all the requests.
+pub const LIMINE_COMMON_MAGIC: [u64; 2] = [0xc7b1dd30df4c8b88, 0x0a82e883a194f07b];
+
+/// The magic of the base revision tag.
+pub const LIMINE_BASE_REVISION_MAGIC: [u64; 2] = [0xf9562b2d5c95a6c8, 0x6a7b384944536bdc];
+
+/// The identifier of the marker placed before the requests.
+pub const LIMINE_REQUESTS_START_MARKER: [u64; 4] = [
+    0xf6b8f4b39de7d1ae,
+    0xfab91a6940fcb9cf,
+    0x785c6ed015d3e316,
+    0x181e920a7852b9d9,
+];
+
+/// The identifier of the marker placed after the requests.
+pub const LIMINE_REQUESTS_END_MARKER: [u64; 2] = [0xadc0e0531bb10d03, 0x9572709f31764c62];
+
+/// Tag specifying the base revision of the protocol the kernel was written against. The
+/// bootloader sets the revision to zero if it supports the requested base revision.
+#[repr(C)]
+pub struct LimineBaseRevision {
+    id: [u64; 2],
+    revision: UnsafeCell<u64>,
+}
+
+unsafe impl Sync for LimineBaseRevision {}
+
+impl LimineBaseRevision {
+    /// Creates a new base revision tag requesting the base revision `revision`.
+    pub const fn new(revision: u64) -> Self {
+        Self {
+            id: LIMINE_BASE_REVISION_MAGIC,
+            revision: UnsafeCell::new(revision),
+        }
+    }
+
+    /// Returns whether the bootloader supports the requested base revision.
+    pub fn is_supported(&self) -> bool {
+        // SAFETY: The revision is only ever written by the bootloader before the kernel is
+        // entered.
+        unsafe { core::ptr::read_volatile(self.revision.get()) == 0 }
+    }
+}
+
+/// Marker placed before the requests. If the kernel provides the start and end markers, the
+/// bootloader only looks for the requests in between them.
+#[repr(C)]
+pub struct LimineRequestsStartMarker {
+    id: [u64; 4],
+}
+
+impl LimineRequestsStartMarker {
+    pub const fn new() -> Self {
+        Self {
+            id: LIMINE_REQUESTS_START_MARKER,
+        }
+    }
+}
+
+impl Default for LimineRequestsStartMarker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marker placed after the requests.
+#[repr(C)]
+pub struct LimineRequestsEndMarker {
+    id: [u64; 2],
+}
+
+impl LimineRequestsEndMarker {
+    pub const fn new() -> Self {
+        Self {
+            id: LIMINE_REQUESTS_END_MARKER,
+        }
+    }
+}
+
+impl Default for LimineRequestsEndMarker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Raw memory map entry types used by the Limine protocol.
+pub mod memory_map_type {
+    pub const USABLE: u64 = 0;
+    pub const RESERVED: u64 = 1;
+    pub const ACPI_RECLAIMABLE: u64 = 2;
+    pub const ACPI_NVS: u64 = 3;
+    pub const BAD_MEMORY: u64 = 4;
+    pub const BOOTLOADER_RECLAIMABLE: u64 = 5;
+    pub const KERNEL_AND_MODULES: u64 = 6;
+    pub const FRAMEBUFFER: u64 = 7;
+}
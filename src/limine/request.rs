@@ -0,0 +1,111 @@
+use core::cell::UnsafeCell;
+
+use super::response::*;
+use super::LIMINE_COMMON_MAGIC;
+
+/// Pointer to the response of a request, filled in by the bootloader.
+#[repr(transparent)]
+struct ResponsePtr<T>(UnsafeCell<*const T>);
+
+impl<T> ResponsePtr<T> {
+    const fn null() -> Self {
+        Self(UnsafeCell::new(core::ptr::null()))
+    }
+
+    fn get(&self) -> Option<&'static T> {
+        // SAFETY: The pointer is only ever written by the bootloader before the kernel is
+        // entered, and it is either null or points to a valid response.
+        unsafe {
+            let ptr = core::ptr::read_volatile(self.0.get());
+            ptr.as_ref()
+        }
+    }
+}
+
+macro_rules! make_request {
+    ($(#[$meta:meta])* struct $name:ident: $id:expr => $response:ty;) => {
+        make_request!($(#[$meta])* struct $name: $id => $response {};);
+    };
+
+    ($(#[$meta:meta])* struct $name:ident: $id:expr => $response:ty {$($(#[$field_meta:meta])* $field_name:ident : $field_ty:ty = $field_default:expr),*};) => {
+        $(#[$meta])*
+        #[repr(C)]
+        pub struct $name {
+            id: [u64; 4],
+            revision: u64,
+            response: ResponsePtr<$response>,
+            $($field_name: $field_ty),*
+        }
+
+        unsafe impl Sync for $name {}
+
+        impl $name {
+            pub const fn new() -> Self {
+                Self {
+                    id: [LIMINE_COMMON_MAGIC[0], LIMINE_COMMON_MAGIC[1], $id[0], $id[1]],
+                    revision: 0,
+                    response: ResponsePtr::null(),
+                    $($field_name: $field_default),*
+                }
+            }
+
+            /// Sets the revision of the request.
+            pub const fn revision(mut self, revision: u64) -> Self {
+                self.revision = revision;
+                self
+            }
+
+            /// Returns the response of the bootloader, or `None` if the request was not
+            /// answered.
+            pub fn get_response(&self) -> Option<&'static $response> {
+                self.response.get()
+            }
+
+            $($(#[$field_meta])* pub const fn $field_name(mut self, value: $field_ty) -> Self {
+                self.$field_name = value;
+                self
+            })*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
+
+make_request! {
+    /// Requests the framebuffers set up by the bootloader.
+    struct LimineFramebufferRequest: [0x9d5827dcd881dd75, 0xa3148604f6fab11b] => LimineFramebufferResponse;
+}
+
+make_request! {
+    /// Requests the memory map.
+    struct LimineMemmapRequest: [0x67cf3d9d378a806f, 0xe304acdfc50c3c62] => LimineMemmapResponse;
+}
+
+make_request! {
+    /// Requests the offset of the higher half direct map.
+    struct LimineHhdmRequest: [0x48dcf1cb8ad2b852, 0x63984e959a98244b] => LimineHhdmResponse;
+}
+
+make_request! {
+    /// Requests the bootloader to start the application processors.
+    struct LimineSmpRequest: [0x95a67b819a1b857e, 0xa0b61b723b6a73e0] => LimineSmpResponse {
+        /// Sets the SMP request flags. See [LimineSmpRequestFlags] for more information.
+        flags: LimineSmpRequestFlags = LimineSmpRequestFlags::empty()
+    };
+}
+
+make_request! {
+    /// Requests the modules loaded alongside the kernel.
+    struct LimineModuleRequest: [0x3e7e279702be32af, 0xca1c4f3bd1280cee] => LimineModuleResponse;
+}
+
+bitflags::bitflags! {
+    pub struct LimineSmpRequestFlags: u64 {
+        /// If set, the bootloader enables x2APIC if it is available.
+        const X2APIC = 1 << 0;
+    }
+}
@@ -0,0 +1,362 @@
+use super::{memory_map_type, StivaleMemoryMapEntryType};
+
+/// Returns the null-terminated string at `ptr` as a rust string, cut at the first invalid UTF-8
+/// byte. At most [Limits::max_string_len](crate::v2::Limits::max_string_len) bytes are scanned
+/// for the terminator.
+fn string_from_ptr(ptr: *const u8) -> &'static str {
+    if ptr.is_null() {
+        return "";
+    }
+
+    let max_length = crate::v2::limits().max_string_len;
+
+    // SAFETY: The bootloader guarantees that the string is null-terminated.
+    unsafe {
+        let length = crate::v2::utils::c_string_length_raw(ptr, max_length);
+        crate::v2::utils::lossy_string_from_slice(core::slice::from_raw_parts(ptr, length))
+    }
+}
+
+/// Returns the array of `count` pointers at `ptr` as a rust slice of references.
+fn slice_from_ptr<T>(ptr: *const *const T, count: u64) -> &'static [&'static T] {
+    if ptr.is_null() {
+        return &[];
+    }
+
+    // SAFETY: The bootloader guarantees that the array contains `count` valid pointers, which
+    // have the same layout as references.
+    unsafe { core::slice::from_raw_parts(ptr as *const &'static T, count as usize) }
+}
+
+/// A framebuffer set up by the bootloader.
+#[repr(C)]
+pub struct LimineFramebuffer {
+    /// The address of the framebuffer.
+    pub address: u64,
+    /// Width of the framebuffer in pixels.
+    pub width: u64,
+    /// Height of the framebuffer in pixels.
+    pub height: u64,
+    /// Pitch of the framebuffer in bytes.
+    pub pitch: u64,
+    /// The framebuffer bits per pixels.
+    pub bpp: u16,
+    /// Memory model of the framebuffer. If set to one, its RGB and all other values
+    /// are undefined.
+    pub memory_model: u8,
+    /// Size of the red mask in RGB.
+    pub red_mask_size: u8,
+    /// Shift of the red mask in RGB.
+    pub red_mask_shift: u8,
+    /// Size of the green mask in RGB.
+    pub green_mask_size: u8,
+    /// Shift of the green mask in RGB.
+    pub green_mask_shift: u8,
+    /// Size of the blue mask in RGB.
+    pub blue_mask_size: u8,
+    /// Shift of the blue mask in RGB.
+    pub blue_mask_shift: u8,
+    _unused: [u8; 7],
+    /// The size of the EDID blob in bytes.
+    pub edid_size: u64,
+    /// The address of the EDID blob, or zero if unavailable.
+    pub edid: u64,
+}
+
+impl LimineFramebuffer {
+    /// Creates a new framebuffer describing a RGB framebuffer at `address`. The colour mask
+    /// sizes and shifts are zeroed and should be filled in by the caller.
+    pub fn new(address: u64, width: u64, height: u64, pitch: u64, bpp: u16) -> Self {
+        Self {
+            address,
+            width,
+            height,
+            pitch,
+            bpp,
+            memory_model: 1,
+            red_mask_size: 0,
+            red_mask_shift: 0,
+            green_mask_size: 0,
+            green_mask_shift: 0,
+            blue_mask_size: 0,
+            blue_mask_shift: 0,
+            _unused: [0; 7],
+            edid_size: 0,
+            edid: 0,
+        }
+    }
+
+    /// Returns the size of the framebuffer.
+    pub fn size(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+
+    /// Returns the EDID blob of the display, or an empty slice if unavailable.
+    pub fn edid(&self) -> &[u8] {
+        if self.edid == 0 {
+            return &[];
+        }
+
+        unsafe { core::slice::from_raw_parts(self.edid as *const u8, self.edid_size as usize) }
+    }
+}
+
+#[repr(C)]
+pub struct LimineFramebufferResponse {
+    pub revision: u64,
+    framebuffer_count: u64,
+    framebuffers: *const *const LimineFramebuffer,
+}
+
+impl LimineFramebufferResponse {
+    /// Creates a new response pointing at the array of `count` pointers at `array`.
+    pub(super) fn new(count: u64, array: u64) -> Self {
+        Self {
+            revision: 0,
+            framebuffer_count: count,
+            framebuffers: array as *const *const LimineFramebuffer,
+        }
+    }
+
+    /// Returns the framebuffers set up by the bootloader.
+    pub fn framebuffers(&self) -> &'static [&'static LimineFramebuffer] {
+        slice_from_ptr(self.framebuffers, self.framebuffer_count)
+    }
+}
+
+/// A memory map entry. The entries are guaranteed to be sorted by base address, lowest
+/// to highest.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct LimineMemmapEntry {
+    /// Physical address of base of the memory section.
+    pub base: u64,
+    /// Length of this memory section.
+    pub length: u64,
+    /// The raw Limine type of this memory map entry. See [memory_map_type] for the possible
+    /// values.
+    pub entry_type: u64,
+}
+
+impl LimineMemmapEntry {
+    /// Creates a new memory map entry of the raw type `entry_type` spanning `length` bytes
+    /// from `base`.
+    pub fn new(base: u64, length: u64, entry_type: u64) -> Self {
+        Self {
+            base,
+            length,
+            entry_type,
+        }
+    }
+
+    /// Returns the end address of this memory region.
+    #[inline]
+    pub fn end_address(&self) -> u64 {
+        self.base + self.length
+    }
+
+    /// Returns the entry type of this memory region, translated into the type used by stivale2.
+    /// Unknown types are reported as [StivaleMemoryMapEntryType::Reserved].
+    #[inline]
+    pub fn entry_type(&self) -> StivaleMemoryMapEntryType {
+        match self.entry_type {
+            memory_map_type::USABLE => StivaleMemoryMapEntryType::Usable,
+            memory_map_type::ACPI_RECLAIMABLE => StivaleMemoryMapEntryType::AcpiReclaimable,
+            memory_map_type::ACPI_NVS => StivaleMemoryMapEntryType::AcpiNvs,
+            memory_map_type::BAD_MEMORY => StivaleMemoryMapEntryType::BadMemory,
+            memory_map_type::BOOTLOADER_RECLAIMABLE => {
+                StivaleMemoryMapEntryType::BootloaderReclaimable
+            }
+            memory_map_type::KERNEL_AND_MODULES => StivaleMemoryMapEntryType::Kernel,
+            memory_map_type::FRAMEBUFFER => StivaleMemoryMapEntryType::Framebuffer,
+            _ => StivaleMemoryMapEntryType::Reserved,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct LimineMemmapResponse {
+    pub revision: u64,
+    entry_count: u64,
+    entries: *const *const LimineMemmapEntry,
+}
+
+impl LimineMemmapResponse {
+    /// Creates a new response pointing at the array of `count` pointers at `array`.
+    pub(super) fn new(count: u64, array: u64) -> Self {
+        Self {
+            revision: 0,
+            entry_count: count,
+            entries: array as *const *const LimineMemmapEntry,
+        }
+    }
+
+    /// Returns the memory map entries.
+    pub fn entries(&self) -> &'static [&'static LimineMemmapEntry] {
+        slice_from_ptr(self.entries, self.entry_count)
+    }
+}
+
+#[repr(C)]
+pub struct LimineHhdmResponse {
+    pub revision: u64,
+    /// The virtual address offset of the beginning of the higher half direct map.
+    pub offset: u64,
+}
+
+impl LimineHhdmResponse {
+    /// Creates a new response reporting a higher half direct map at `offset`.
+    pub fn new(offset: u64) -> Self {
+        Self {
+            revision: 0,
+            offset,
+        }
+    }
+}
+
+/// SMP information structure of a single CPU.
+#[repr(C)]
+pub struct LimineSmpInfo {
+    /// ACPI Processor UID as specified by MADT.
+    pub processor_id: u32,
+    /// LAPIC ID as specified by MADT.
+    pub lapic_id: u32,
+    _reserved: u64,
+    /// Atomically writing the address of a `extern "C" fn(&'static LimineSmpInfo) -> !` to
+    /// this field makes the AP jump to it, with its own 64KiB stack. Unused for the BSP.
+    pub goto_address: u64,
+    /// Free for use by the kernel, for example to pass data to the AP.
+    pub extra_argument: u64,
+}
+
+#[repr(C)]
+pub struct LimineSmpResponse {
+    pub revision: u64,
+    /// Bit 0 is set if x2APIC was enabled.
+    pub flags: u32,
+    /// LAPIC ID of the BSP (bootstrap processor).
+    pub bsp_lapic_id: u32,
+    cpu_count: u64,
+    cpus: *const *const LimineSmpInfo,
+}
+
+impl LimineSmpResponse {
+    /// Return's the total number of logical CPUs (including BSP).
+    pub fn cpu_count(&self) -> u64 {
+        self.cpu_count
+    }
+
+    /// Returns the SMP information of all the CPUs (including BSP).
+    pub fn cpus(&self) -> &'static [&'static LimineSmpInfo] {
+        slice_from_ptr(self.cpus, self.cpu_count)
+    }
+}
+
+/// GUID or UUID as stored by the Limine protocol.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LimineUuid {
+    pub a: u32,
+    pub b: u16,
+    pub c: u16,
+    pub d: [u8; 8],
+}
+
+/// A file loaded by the bootloader, such as a module.
+#[repr(C)]
+pub struct LimineFile {
+    pub revision: u64,
+    /// Address where this file has been loaded.
+    pub address: u64,
+    /// Size of this file in bytes.
+    pub size: u64,
+    path: *const u8,
+    cmdline: *const u8,
+    /// Type of the media the file was loaded from.
+    pub media_type: u32,
+    _unused: u32,
+    /// IP of the TFTP server the file was loaded from, if any.
+    pub tftp_ip: u32,
+    /// Port of the TFTP server the file was loaded from, if any.
+    pub tftp_port: u32,
+    /// 1-based partition index of the volume the file was loaded from, or zero if unpartitioned.
+    pub partition_index: u32,
+    /// MBR disk ID of the volume the file was loaded from, if any.
+    pub mbr_disk_id: u32,
+    /// GPT disk UUID of the volume the file was loaded from, if any.
+    pub gpt_disk_uuid: LimineUuid,
+    /// GPT partition UUID of the volume the file was loaded from, if any.
+    pub gpt_part_uuid: LimineUuid,
+    /// Filesystem UUID of the volume the file was loaded from, if any.
+    pub part_uuid: LimineUuid,
+}
+
+impl LimineFile {
+    /// Creates a new file of `size` bytes loaded at `address`, with the null-terminated path
+    /// and command line at `path` and `cmdline`. The media information is zeroed.
+    pub fn new(address: u64, size: u64, path: *const u8, cmdline: *const u8) -> Self {
+        let uuid = LimineUuid {
+            a: 0,
+            b: 0,
+            c: 0,
+            d: [0; 8],
+        };
+
+        Self {
+            revision: 0,
+            address,
+            size,
+            path,
+            cmdline,
+            media_type: 0,
+            _unused: 0,
+            tftp_ip: 0,
+            tftp_port: 0,
+            partition_index: 0,
+            mbr_disk_id: 0,
+            gpt_disk_uuid: uuid,
+            gpt_part_uuid: uuid,
+            part_uuid: uuid,
+        }
+    }
+
+    /// Returns the path of the file within the volume it was loaded from, cut at the first
+    /// invalid UTF-8 byte.
+    pub fn path(&self) -> &'static str {
+        string_from_ptr(self.path)
+    }
+
+    /// Returns the command line associated with the file as specified in the config file, cut at
+    /// the first invalid UTF-8 byte.
+    pub fn cmdline(&self) -> &'static str {
+        string_from_ptr(self.cmdline)
+    }
+
+    /// Returns the contents of this file as a byte slice.
+    pub fn as_bytes(&self) -> &'static [u8] {
+        unsafe { core::slice::from_raw_parts(self.address as *const u8, self.size as usize) }
+    }
+}
+
+#[repr(C)]
+pub struct LimineModuleResponse {
+    pub revision: u64,
+    module_count: u64,
+    modules: *const *const LimineFile,
+}
+
+impl LimineModuleResponse {
+    /// Creates a new response pointing at the array of `count` pointers at `array`.
+    pub(super) fn new(count: u64, array: u64) -> Self {
+        Self {
+            revision: 0,
+            module_count: count,
+            modules: array as *const *const LimineFile,
+        }
+    }
+
+    /// Returns the modules loaded by the bootloader.
+    pub fn modules(&self) -> &'static [&'static LimineFile] {
+        slice_from_ptr(self.modules, self.module_count)
+    }
+}
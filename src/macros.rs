@@ -0,0 +1,161 @@
+/// Asserts at compile time that the fields of a `#[repr(C)]` structure lie at the offsets
+/// given by the specification, and optionally that the structure has the given size, so that
+/// a change to its layout breaks the build.
+macro_rules! assert_layout {
+    ($ty:ty $(: $size:literal)? { $($field:ident: $offset:expr),* $(,)? }) => {
+        #[allow(deprecated)]
+        const _: () = {
+            $(assert!(core::mem::size_of::<$ty>() == $size);)?
+            $(assert!(core::mem::offset_of!($ty, $field) == $offset);)*
+        };
+    };
+}
+
+/// Declares an unsafe raw constructor, which is only part of the public API if the `safe-only`
+/// feature is disabled. With the feature enabled, it stays available to the crate itself.
+macro_rules! raw_constructor {
+    ($(#[$attr:meta])* pub unsafe fn $($rest:tt)*) => {
+        #[cfg(not(feature = "safe-only"))]
+        $(#[$attr])*
+        pub unsafe fn $($rest)*
+
+        #[cfg(feature = "safe-only")]
+        $(#[$attr])*
+        #[allow(dead_code)]
+        pub(crate) unsafe fn $($rest)*
+    };
+}
+
+/// Implements `TryFrom<u64>` for bitflags types, failing with
+/// [Error::InvalidValue](crate::v2::Error::InvalidValue) if unknown bits are set.
+macro_rules! impl_try_from_bits {
+    ($($name:ty),* $(,)?) => {
+        $(impl core::convert::TryFrom<u64> for $name {
+            type Error = crate::v2::Error;
+
+            fn try_from(bits: u64) -> Result<Self, Self::Error> {
+                Self::from_bits(bits).ok_or(crate::v2::Error::InvalidValue(bits))
+            }
+        })*
+    };
+}
+
+/// Declares both a stivale header in the `.stivalehdr` section and a stivale2 header in the
+/// `.stivale2hdr` section from a single configuration, so that the kernel can be booted by
+/// bootloaders supporting either protocol.
+///
+/// Both headers leave the entry point unset, so the bootloader jumps to the entry point of the
+/// kernel ELF with a pointer to either boot structure, which can be told apart with
+/// [detect_protocol](crate::boot_info::detect_protocol).
+///
+/// ## Example
+/// ```rust,no_run
+/// static STACK: [u8; 4096] = [0; 4096];
+///
+/// stivale_boot::stivale_headers! {
+///     stack: &STACK[4095] as *const u8,
+///     higher_half: true,
+///     framebuffer: (0, 0, 32),
+/// }
+/// ```
+#[macro_export]
+macro_rules! stivale_headers {
+    (stack: $stack:expr, higher_half: $higher_half:expr $(,)?) => {
+        #[used]
+        #[link_section = ".stivalehdr"]
+        static __STIVALE_HEADER: $crate::v1::StivaleHeader = $crate::v1::StivaleHeader::new()
+            .stack($stack)
+            .flags($crate::v1::StivaleHeaderFlags::from_bits_truncate(
+                if $higher_half { 1 << 3 } else { 0 },
+            ));
+
+        #[used]
+        #[link_section = ".stivale2hdr"]
+        static __STIVALE2_HEADER: $crate::v2::StivaleHeader = $crate::v2::StivaleHeader::new()
+            .stack($stack)
+            .flags(if $higher_half { 1 << 1 } else { 0 });
+    };
+
+    (
+        stack: $stack:expr,
+        higher_half: $higher_half:expr,
+        framebuffer: ($width:expr, $height:expr, $bpp:expr) $(,)?
+    ) => {
+        #[used]
+        #[link_section = ".stivalehdr"]
+        static __STIVALE_HEADER: $crate::v1::StivaleHeader = $crate::v1::StivaleHeader::new()
+            .stack($stack)
+            .flags($crate::v1::StivaleHeaderFlags::from_bits_truncate(
+                (if $higher_half { 1 << 3 } else { 0 }) | 1 << 0,
+            ))
+            .framebuffer_width($width)
+            .framebuffer_height($height)
+            .framebuffer_bpp($bpp);
+
+        static __STIVALE2_FRAMEBUFFER_TAG: $crate::v2::StivaleFramebufferHeaderTag =
+            $crate::v2::StivaleFramebufferHeaderTag::new()
+                .framebuffer_width($width)
+                .framebuffer_height($height)
+                .framebuffer_bpp($bpp);
+
+        #[used]
+        #[link_section = ".stivale2hdr"]
+        static __STIVALE2_HEADER: $crate::v2::StivaleHeader = $crate::v2::StivaleHeader::new()
+            .stack($stack)
+            .flags(if $higher_half { 1 << 1 } else { 0 })
+            .tags(&__STIVALE2_FRAMEBUFFER_TAG as *const _ as *const ());
+    };
+}
+
+/// Declares a stivale2 header for a 32-bit kernel, entered in protected mode, along with a
+/// static stack of `stack_size` bytes and an entry point calling `entry`, a
+/// `fn(&'static StivaleStruct) -> !`. Header tags can optionally be passed with `tags`.
+///
+/// The bootloader enters the kernel with the boot structure pointer pushed on the stack of the
+/// header, which leaves it misaligned. The entry point realigns the stack to 16 bytes and
+/// clears the frame pointer with [switch_stack](crate::v2::switch_stack) before calling
+/// `entry`. Only i686 targets are supported; see [EntryMode](crate::v2::EntryMode) for the
+/// machine state at the entry point.
+///
+/// ## Example
+/// ```rust,ignore
+/// use stivale_boot::v2::StivaleStruct;
+///
+/// fn kmain(boot_info: &'static StivaleStruct) -> ! {
+///     loop {}
+/// }
+///
+/// stivale_boot::stivale2_protected_mode_entry! {
+///     entry: kmain,
+///     stack_size: 0x4000,
+/// }
+/// ```
+#[macro_export]
+macro_rules! stivale2_protected_mode_entry {
+    (entry: $entry:path, stack_size: $stack_size:expr $(, tags: $tags:expr)? $(,)?) => {
+        const _: () = {
+            #[cfg(not(target_arch = "x86"))]
+            compile_error!("stivale2_protected_mode_entry! only supports i686 targets");
+
+            static STACK: $crate::v2::KernelStack<{ $stack_size }> =
+                $crate::v2::KernelStack::new();
+
+            extern "C" fn __stivale2_start(boot_info: &'static $crate::v2::StivaleStruct) -> ! {
+                // The bootloader stack is the top of `STACK`, which is reused from its top as
+                // nothing is ever returned to.
+                unsafe { $crate::v2::switch_stack(STACK.top(), boot_info, __stivale2_main) }
+            }
+
+            extern "C" fn __stivale2_main(boot_info: &'static $crate::v2::StivaleStruct) -> ! {
+                $entry(boot_info)
+            }
+
+            #[used]
+            #[link_section = ".stivale2hdr"]
+            static __STIVALE2_HEADER: $crate::v2::StivaleHeader =
+                $crate::v2::StivaleHeader::new_protected_mode(STACK.top() as *const u8)
+                    .entry_point(__stivale2_start)
+                    $(.tags($tags))?;
+        };
+    };
+}
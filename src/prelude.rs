@@ -0,0 +1,28 @@
+//! The items most kernels need, so that they can be imported with a single `use` line.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use stivale_boot::prelude::*;
+//!
+//! fn kmain(boot_info: &'static StivaleStruct) {
+//!     if let Some(memory_map) = boot_info.memory_map() {
+//!         let usable = memory_map
+//!             .iter()
+//!             .filter(|entry| entry.entry_type() == StivaleMemoryMapEntryType::Usable);
+//!
+//!         for _entry in usable {}
+//!     }
+//!
+//!     assert_eq!(StivaleUartTag::IDENTIFIER, 0xb813f9b8dbc78797);
+//! }
+//! ```
+
+pub use crate::stivale_headers;
+pub use crate::v2::{
+    BufferedTerminal, SharedTerminal, Stivale5LevelPagingHeaderTag, StivaleCommandLineTag,
+    StivaleFramebufferHeaderTag, StivaleFramebufferTag, StivaleHeader, StivaleMemoryMapEntry,
+    StivaleMemoryMapEntryType, StivaleMemoryMapIter, StivaleMemoryMapTag, StivaleModule,
+    StivaleModuleTag, StivaleRsdpTag, StivaleSmpHeaderTag, StivaleSmpInfo, StivaleSmpTag,
+    StivaleStruct, StivaleTag, StivaleTerminalHeaderTag, StivaleTerminalTag, StivaleUartTag,
+    StivaleUnmapNullHeaderTag, UartWriter,
+};
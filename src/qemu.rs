@@ -0,0 +1,277 @@
+//! Host side harness booting a stivale2 kernel with Limine under QEMU and collecting its serial
+//! output, so that header layout and tag parsing can be checked against a real bootloader.
+//!
+//! The harness needs `qemu-system-x86_64`, `xorriso` and a Limine 3.x release (the last one
+//! supporting stivale2) to be available on the host. `tests/qemu.rs` boots the kernel in
+//! `tests/kernel` with it, and documents how to run it.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::string::String;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// The Limine files copied into the ISO image.
+const LIMINE_FILES: [&str; 3] = ["limine.sys", "limine-cd.bin", "limine-cd-efi.bin"];
+
+/// Errors returned by the harness.
+#[derive(Debug)]
+pub enum HarnessError {
+    /// Spawning a tool or accessing a file failed.
+    Io(io::Error),
+    /// A tool exited with a failure status.
+    ToolFailed(&'static str),
+    /// The kernel did not print the expected marker before the timeout.
+    Timeout(SerialOutput),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HarnessError::Io(error) => write!(f, "I/O error: {}", error),
+            HarnessError::ToolFailed(tool) => write!(f, "{} failed", tool),
+            HarnessError::Timeout(output) => {
+                write!(f, "timed out, serial output:\n{}", output.as_str())
+            }
+        }
+    }
+}
+
+impl std::error::Error for HarnessError {}
+
+impl From<io::Error> for HarnessError {
+    fn from(error: io::Error) -> Self {
+        HarnessError::Io(error)
+    }
+}
+
+fn run_tool(command: &mut Command, tool: &'static str) -> Result<(), HarnessError> {
+    if command.status()?.success() {
+        Ok(())
+    } else {
+        Err(HarnessError::ToolFailed(tool))
+    }
+}
+
+/// Builds a BIOS and UEFI bootable ISO image at `iso` booting `kernel` with the stivale2
+/// protocol and `command_line`. `limine` is the directory of a Limine 3.x binary release, which
+/// contains the Limine files and `limine-deploy`.
+pub fn build_iso(
+    kernel: &Path,
+    limine: &Path,
+    command_line: &str,
+    iso: &Path,
+) -> Result<(), HarnessError> {
+    let root = iso.with_extension("root");
+
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+
+    fs::create_dir_all(&root)?;
+    fs::copy(kernel, root.join("kernel.elf"))?;
+
+    for file in LIMINE_FILES.iter() {
+        fs::copy(limine.join(file), root.join(file))?;
+    }
+
+    fs::write(
+        root.join("limine.cfg"),
+        std::format!(
+            "TIMEOUT=0\n\n:stivale2 test\nPROTOCOL=stivale2\nKERNEL_PATH=boot:///kernel.elf\nKERNEL_CMDLINE={}\n",
+            command_line
+        ),
+    )?;
+
+    run_tool(
+        Command::new("xorriso")
+            .args(["-as", "mkisofs", "-b", "limine-cd.bin", "-no-emul-boot"])
+            .args(["-boot-load-size", "4", "-boot-info-table"])
+            .args(["--efi-boot", "limine-cd-efi.bin", "-efi-boot-part"])
+            .args(["--efi-boot-image", "--protective-msdos-label"])
+            .arg(&root)
+            .arg("-o")
+            .arg(iso)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+        "xorriso",
+    )?;
+
+    run_tool(
+        Command::new(limine.join("limine-deploy"))
+            .arg(iso)
+            .stdout(Stdio::null()),
+        "limine-deploy",
+    )
+}
+
+/// The serial output of a kernel run by [QemuHarness].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerialOutput(String);
+
+impl SerialOutput {
+    /// Returns the whole output, with invalid UTF-8 replaced.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether the output contains `pattern`.
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.0.contains(pattern)
+    }
+
+    /// Panics, printing the whole output, if it does not contain `pattern`.
+    #[track_caller]
+    pub fn assert_contains(&self, pattern: &str) {
+        assert!(
+            self.contains(pattern),
+            "serial output does not contain {:?}:\n{}",
+            pattern,
+            self.0
+        );
+    }
+
+    /// Returns an iterator over the lines of the output.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.0.lines()
+    }
+}
+
+/// Runs a bootable image under `qemu-system-x86_64`, with the first serial port connected to
+/// the harness.
+///
+/// ## Example
+/// ```rust,no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// use stivale_boot::qemu::{build_iso, QemuHarness};
+///
+/// let iso = Path::new("target/stivale2-test.iso");
+/// build_iso(Path::new("kernel.elf"), Path::new("limine"), "test", iso).unwrap();
+///
+/// let output = QemuHarness::new(iso)
+///     .timeout(Duration::from_secs(30))
+///     .run_until("TEST DONE")
+///     .unwrap();
+///
+/// output.assert_contains("memory map entries:");
+/// ```
+pub struct QemuHarness {
+    qemu: PathBuf,
+    image: PathBuf,
+    memory: String,
+    timeout: Duration,
+    args: Vec<String>,
+}
+
+impl QemuHarness {
+    /// Creates a harness booting `image` from the CD-ROM drive with 256M of memory and a
+    /// timeout of 60 seconds.
+    pub fn new(image: impl Into<PathBuf>) -> Self {
+        Self {
+            qemu: PathBuf::from("qemu-system-x86_64"),
+            image: image.into(),
+            memory: String::from("256M"),
+            timeout: Duration::from_secs(60),
+            args: Vec::new(),
+        }
+    }
+
+    /// Sets the QEMU binary to use.
+    pub fn qemu(mut self, qemu: impl Into<PathBuf>) -> Self {
+        self.qemu = qemu.into();
+        self
+    }
+
+    /// Sets the amount of guest memory, in the format of QEMU's `-m` option.
+    pub fn memory(mut self, memory: &str) -> Self {
+        self.memory = String::from(memory);
+        self
+    }
+
+    /// Sets the time after which the guest is killed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Appends an argument to the QEMU command line, such as `-smp 4` or `-bios OVMF.fd`.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(String::from(arg));
+        self
+    }
+
+    /// Boots the image and returns the serial output once the guest exits, or once the timeout
+    /// expires.
+    pub fn run(&self) -> Result<SerialOutput, HarnessError> {
+        self.run_inner(None)
+    }
+
+    /// Boots the image and returns the serial output as soon as it contains `marker`. Returns
+    /// [HarnessError::Timeout] if the guest exits or the timeout expires first.
+    pub fn run_until(&self, marker: &str) -> Result<SerialOutput, HarnessError> {
+        self.run_inner(Some(marker))
+    }
+
+    fn run_inner(&self, marker: Option<&str>) -> Result<SerialOutput, HarnessError> {
+        let mut child = Command::new(&self.qemu)
+            .arg("-cdrom")
+            .arg(&self.image)
+            .args(["-m", &self.memory])
+            .args(["-serial", "stdio", "-display", "none", "-no-reboot"])
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let reader = {
+            let output = output.clone();
+
+            thread::spawn(move || {
+                let mut buffer = [0; 256];
+
+                while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+                    output.lock().unwrap().extend_from_slice(&buffer[..read]);
+                }
+            })
+        };
+
+        let deadline = Instant::now() + self.timeout;
+        let contents =
+            || SerialOutput(String::from_utf8_lossy(&output.lock().unwrap()).into_owned());
+
+        loop {
+            if let Some(marker) = marker {
+                if contents().contains(marker) {
+                    break;
+                }
+            }
+
+            if child.try_wait()?.is_some() || Instant::now() >= deadline {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let _ = child.kill();
+        child.wait()?;
+        let _ = reader.join();
+
+        let output = contents();
+
+        match marker {
+            Some(marker) if !output.contains(marker) => Err(HarnessError::Timeout(output)),
+            _ => Ok(output),
+        }
+    }
+}
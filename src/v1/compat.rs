@@ -0,0 +1,174 @@
+//! Adapter exposing a stivale structure through the same accessors as the stivale2
+//! [StivaleStruct](crate::v2::StivaleStruct), so kernels can support both protocol revisions
+//! with a single code path.
+
+use crate::v2;
+
+use super::{StivaleMemoryMapIter, StivaleModuleIter, StivaleStruct, StivaleStructFlags};
+
+/// Wrapper around a stivale structure which returns the information in the form of stivale2
+/// tags. As the stivale structure does not contain any tags, the tags are built on the fly and
+/// returned by value.
+#[derive(Clone, Copy)]
+pub struct StivaleStructAdapter<'a> {
+    inner: &'a StivaleStruct,
+}
+
+impl<'a> StivaleStructAdapter<'a> {
+    /// Creates a new adapter over the provided stivale structure.
+    pub fn new(inner: &'a StivaleStruct) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the wrapped stivale structure.
+    pub fn inner(&self) -> &'a StivaleStruct {
+        self.inner
+    }
+
+    /// Returns the command line passed to the kernel, if any.
+    pub fn command_line(&self) -> Option<&'a str> {
+        if self.inner.command_line == 0 {
+            None
+        } else {
+            Some(self.inner.command_line())
+        }
+    }
+
+    /// Returns an iterator over the memory map, with the entries translated into the stivale2
+    /// format.
+    pub fn memory_map(&self) -> MemoryMapAdapterIter<'a> {
+        MemoryMapAdapterIter {
+            inner: self.inner.memory_map_iter(),
+        }
+    }
+
+    /// Returns the framebuffer tag if a framebuffer was set up by the bootloader.
+    pub fn framebuffer(&self) -> Option<v2::StivaleFramebufferTag> {
+        let inner = self.inner;
+
+        if inner.framebuffer_addr == 0 {
+            return None;
+        }
+
+        let mut tag = v2::StivaleFramebufferTag::new(
+            inner.framebuffer_addr,
+            inner.framebuffer_width,
+            inner.framebuffer_height,
+            inner.framebuffer_pitch,
+            inner.framebuffer_bpp,
+        );
+
+        if inner
+            .flags()
+            .contains(StivaleStructFlags::EXTENDED_COLOUR_INFO)
+        {
+            tag.memory_model = inner.memory_model;
+            tag.red_mask_size = inner.red_mask_size;
+            tag.red_mask_shift = inner.red_mask_shift;
+            tag.green_mask_size = inner.green_mask_size;
+            tag.green_mask_shift = inner.green_mask_shift;
+            tag.blue_mask_size = inner.blue_mask_size;
+            tag.blue_mask_shift = inner.blue_mask_shift;
+        }
+
+        Some(tag)
+    }
+
+    /// Returns an iterator over the modules, with the entries translated into the stivale2
+    /// format.
+    pub fn modules(&self) -> ModuleAdapterIter<'a> {
+        ModuleAdapterIter {
+            inner: self.inner.modules_iter(),
+        }
+    }
+
+    /// Returns the RSDP tag if the bootloader found the RSDP.
+    pub fn rsdp(&self) -> Option<v2::StivaleRsdpTag> {
+        if self.inner.rsdp_adddres == 0 {
+            None
+        } else {
+            Some(v2::StivaleRsdpTag::new(self.inner.rsdp_adddres))
+        }
+    }
+
+    /// Returns the SMBIOS tag if any of the SMBIOS entry points are available.
+    pub fn smbios(&self) -> Option<v2::StivaleSmbiosTag> {
+        let inner = self.inner;
+
+        if inner.smbios_entry_32 == 0 && inner.smbios_entry_64 == 0 {
+            None
+        } else {
+            Some(v2::StivaleSmbiosTag::new(
+                inner.smbios_entry_32,
+                inner.smbios_entry_64,
+            ))
+        }
+    }
+
+    /// Returns the epoch tag. The stivale structure always provides the epoch.
+    pub fn epoch(&self) -> Option<v2::StivaleEpochTag> {
+        Some(v2::StivaleEpochTag::new(self.inner.unix_epoch))
+    }
+
+    /// Returns the firmware tag. The stivale structure always provides the firmware type.
+    pub fn firmware(&self) -> Option<v2::StivaleFirmwareTag> {
+        let flags = if self.inner.flags().contains(StivaleStructFlags::BIOS) {
+            v2::StivaleFirmwareTagFlags::BIOS
+        } else {
+            v2::StivaleFirmwareTagFlags::empty()
+        };
+
+        Some(v2::StivaleFirmwareTag::new(flags))
+    }
+}
+
+impl StivaleStruct {
+    /// Returns an adapter exposing this structure through the stivale2 accessors.
+    pub fn as_v2(&self) -> StivaleStructAdapter<'_> {
+        StivaleStructAdapter::new(self)
+    }
+}
+
+/// Iterator over the memory map of a stivale structure, yielding stivale2 entries.
+#[derive(Clone)]
+pub struct MemoryMapAdapterIter<'a> {
+    inner: StivaleMemoryMapIter<'a>,
+}
+
+impl<'a> Iterator for MemoryMapAdapterIter<'a> {
+    type Item = v2::StivaleMemoryMapEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+
+        Some(v2::StivaleMemoryMapEntry::new(
+            entry.base,
+            entry.length,
+            entry.entry_type(),
+        ))
+    }
+}
+
+/// Iterator over the modules of a stivale structure, yielding stivale2 modules.
+#[derive(Clone)]
+pub struct ModuleAdapterIter<'a> {
+    inner: StivaleModuleIter<'a>,
+}
+
+impl<'a> Iterator for ModuleAdapterIter<'a> {
+    type Item = v2::StivaleModule;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let module = self.inner.next()?;
+
+        Some(v2::StivaleModule::new(
+            module.as_str(),
+            module.start,
+            module.end,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
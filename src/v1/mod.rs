@@ -2,10 +2,17 @@
 //! to be a simple to implement protocol which provides the kernel with most of the features one may
 //! need in a modern x86_64 context (although 32-bit x86 is also supported).
 
+mod compat;
 mod utils;
 
 use core::marker::PhantomData;
 
+use crate::v2::limits;
+use crate::v2::utils::checked_slice;
+
+pub use crate::v2::StivaleMemoryMapEntryType;
+pub use compat::*;
+
 union StivaleHeaderEntryPoint {
     func: extern "C" fn(&'static StivaleStruct) -> !,
     zero: u16,
@@ -51,8 +58,17 @@ pub struct StivaleHeader {
     entry_point: StivaleHeaderEntryPoint,
 }
 
+unsafe impl Send for StivaleHeader {}
+unsafe impl Sync for StivaleHeader {}
+
+impl Default for StivaleHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StivaleHeader {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             stack: core::ptr::null(),
             flags: StivaleHeaderFlags::empty(),
@@ -65,28 +81,28 @@ impl StivaleHeader {
 
     /// Sets the requested framebuffer width. Only parsed if a graphics mode is requested. If
     /// set to zero, the bootloader would pick the best possible video mode automatically (recommended).
-    pub fn framebuffer_width(mut self, framebuffer_width: u16) -> Self {
+    pub const fn framebuffer_width(mut self, framebuffer_width: u16) -> Self {
         self.framebuffer_width = framebuffer_width;
         self
     }
 
     /// Sets the requested framebuffer height. Only parsed if a graphics mode is requested. If
     /// set to zero, the bootloader would pick the best possible video mode automatically (recommended).
-    pub fn framebuffer_height(mut self, framebuffer_height: u16) -> Self {
+    pub const fn framebuffer_height(mut self, framebuffer_height: u16) -> Self {
         self.framebuffer_height = framebuffer_height;
         self
     }
 
     /// Sets the requested framebuffer bpp. Only parsed if a graphics mode is requested. If
     /// set to zero, the bootloader would pick the best possible video mode automatically (recommended).
-    pub fn framebuffer_bpp(mut self, framebuffer_bpp: u16) -> Self {
+    pub const fn framebuffer_bpp(mut self, framebuffer_bpp: u16) -> Self {
         self.framebuffer_bpp = framebuffer_bpp;
         self
     }
 
     /// Sets the provided stivale header flags. See the documentation of [StivaleHeaderFlags]
     /// for more information.
-    pub fn flags(mut self, flags: StivaleHeaderFlags) -> Self {
+    pub const fn flags(mut self, flags: StivaleHeaderFlags) -> Self {
         self.flags = flags;
         self
     }
@@ -95,14 +111,14 @@ impl StivaleHeader {
     /// It can only be set to NULL for 64-bit kernels. 32-bit kernels are mandated to
     /// provide a vaild stack. 64-bit and 32-bit valid stacks must be at least 256 bytes
     /// in usable space and must be 16 byte aligned addresses.
-    pub fn stack(mut self, stack: *const u8) -> Self {
+    pub const fn stack(mut self, stack: *const u8) -> Self {
         self.stack = stack;
         self
     }
 
     /// Sets the entry point address. If not zero, the bootloader would jump to the specified
     /// entry point instead of jumping to the entry point specified the kernel ELF.
-    pub fn entry_point(mut self, func: extern "C" fn(&'static StivaleStruct) -> !) -> Self {
+    pub const fn entry_point(mut self, func: extern "C" fn(&'static StivaleStruct) -> !) -> Self {
         self.entry_point = StivaleHeaderEntryPoint { func };
         self
     }
@@ -119,6 +135,8 @@ pub struct StivaleModule {
     /// ASCII 0-terminated string passed to the module as specified in
     /// the config file.
     pub string: [u8; 128],
+    /// Address of the next module in the list, or zero if this is the last one.
+    pub next: u64,
 }
 
 impl StivaleModule {
@@ -134,15 +152,40 @@ impl StivaleModule {
     pub fn as_str(&self) -> &str {
         self::utils::string_from_slice(&self.string)
     }
+
+    /// Returns the contents of this module as a byte slice.
+    ///
+    /// The module addresses are physical addresses, so the slice is valid as long as the module
+    /// is identity mapped, as it is when the kernel is entered, and its memory has not been
+    /// reclaimed. The slice is empty if the module lies outside of the address space of a
+    /// 32-bit kernel.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.start == 0 {
+            return &[];
+        }
+
+        unsafe { checked_slice(self.start, self.end.saturating_sub(self.start)) }
+            .unwrap_or_default()
+    }
+
+    /// Returns the next module in the modules linked list, if any.
+    pub fn next(&self) -> Option<&StivaleModule> {
+        if self.next == 0 {
+            None
+        } else {
+            Some(unsafe { &*(self.next as *const StivaleModule) })
+        }
+    }
 }
 
 /// Iterator over all the modules that were loaded.
 #[derive(Clone)]
 pub struct StivaleModuleIter<'a> {
-    /// A reference to the stivale structure.
-    sref: &'a StivaleStruct,
-    /// The index of the module entry that we are about to index.
-    current: u64,
+    /// The address of the module entry that we are about to yield.
+    next: u64,
+    /// The amount of modules left, as reported by the stivale structure and bounded by
+    /// [Limits::max_modules](crate::v2::Limits::max_modules).
+    remaining: u64,
     phantom: PhantomData<&'a StivaleModule>,
 }
 
@@ -150,45 +193,44 @@ impl<'a> Iterator for StivaleModuleIter<'a> {
     type Item = &'a StivaleModule;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current < self.sref.module_len {
-            let entry = &self.sref.modules_as_slice()[self.current as usize];
-            self.current += 1;
-
-            Some(entry)
-        } else {
-            None
+        if self.remaining == 0 || self.next == 0 {
+            return None;
         }
+
+        let entry = unsafe { &*(self.next as *const StivaleModule) };
+
+        self.next = entry.next;
+        self.remaining -= 1;
+
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // The list may be shorter than reported, but never longer.
+        (0, Some(self.remaining as usize))
     }
 }
 
-/// The type of a memory map entry. The entries are guaranteed to be sorted by base address,
-/// lowest to highest.
+/// Raw memory map entry types used by the stivale protocol. They match the stivale2 ones, except
+/// for the kernel and modules type which is 10 instead of 0x1001.
+pub mod memory_map_type {
+    pub const USABLE: u32 = 1;
+    pub const RESERVED: u32 = 2;
+    pub const ACPI_RECLAIMABLE: u32 = 3;
+    pub const ACPI_NVS: u32 = 4;
+    pub const BAD_MEMORY: u32 = 5;
+    pub const KERNEL_AND_MODULES: u32 = 10;
+    pub const BOOTLOADER_RECLAIMABLE: u32 = 0x1000;
+    pub const FRAMEBUFFER: u32 = 0x1002;
+}
+
+/// A memory map entry. The entries are guaranteed to be sorted by base address, lowest
+/// to highest.
 ///
 /// ## Alignment
 /// Usable and bootloader reclaimable entries are guaranteed to be 4096 byte aligned for both
 /// base and length. Usable and bootloader reclaimable entries are **guaranteed** not to overlap with
 /// any other entry.
-#[repr(u32)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum StivaleMemoryMapEntryType {
-    /// Usable memory.
-    Usable = 1,
-    /// Memory reserved by the system.
-    Reserved = 2,
-    /// ACPI memory that can be reclaimed.
-    AcpiReclaimable = 3,
-    /// ACPI memory that cannot be reclaimed.
-    AcpiNvs = 4,
-    /// Memory marked as defective (bad RAM).
-    BadMemory = 5,
-    /// Memory used by the bootloader that can be reclaimed after it's not being used anymore.
-    BootloaderReclaimable = 0x1000,
-    /// Memory containing the kernel and any modules.
-    Kernel = 0x1001,
-    /// Memory containing the framebuffer.
-    Framebuffer = 0x1002,
-}
-
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct StivaleMemoryMapEntry {
@@ -196,8 +238,9 @@ pub struct StivaleMemoryMapEntry {
     pub base: u64,
     /// Length of this memory section.
     pub length: u64,
-    /// The type of this memory map entry.
-    pub entry_type: StivaleMemoryMapEntryType,
+    /// The raw stivale type of this memory map entry. See [memory_map_type] for the possible
+    /// values.
+    pub entry_type: u32,
 
     padding: u32,
 }
@@ -209,11 +252,22 @@ impl StivaleMemoryMapEntry {
         self.base + self.length
     }
 
-    /// Returns the entry type of this memory region. External function is required
-    /// as reference the entry_type packed field is not aligned.
+    /// Returns the entry type of this memory region, translated into the type used by stivale2.
+    /// Unknown types are reported as [StivaleMemoryMapEntryType::Reserved].
     #[inline]
     pub fn entry_type(&self) -> StivaleMemoryMapEntryType {
-        self.entry_type
+        match self.entry_type {
+            memory_map_type::USABLE => StivaleMemoryMapEntryType::Usable,
+            memory_map_type::ACPI_RECLAIMABLE => StivaleMemoryMapEntryType::AcpiReclaimable,
+            memory_map_type::ACPI_NVS => StivaleMemoryMapEntryType::AcpiNvs,
+            memory_map_type::BAD_MEMORY => StivaleMemoryMapEntryType::BadMemory,
+            memory_map_type::KERNEL_AND_MODULES => StivaleMemoryMapEntryType::Kernel,
+            memory_map_type::BOOTLOADER_RECLAIMABLE => {
+                StivaleMemoryMapEntryType::BootloaderReclaimable
+            }
+            memory_map_type::FRAMEBUFFER => StivaleMemoryMapEntryType::Framebuffer,
+            _ => StivaleMemoryMapEntryType::Reserved,
+        }
     }
 }
 
@@ -242,12 +296,22 @@ impl<'a> Iterator for StivaleMemoryMapIter<'a> {
     }
 }
 
+bitflags::bitflags! {
+    /// Bitfield representing the flags passed by the bootloader in the stivale structure.
+    pub struct StivaleStructFlags: u64 {
+        /// The kernel was booted in a legacy BIOS mode, else it was booted in UEFI mode.
+        const BIOS = 1 << 0;
+        /// The extended colour information fields of the framebuffer are valid.
+        const EXTENDED_COLOUR_INFO = 1 << 1;
+    }
+}
+
 #[repr(C)]
 pub struct StivaleStruct {
     /// Address of the null-terminated command line.
     pub command_line: u64,
-    /// Pointer to the memory map array.
-    pub memory_map_array: [StivaleMemoryMapEntry; 0],
+    /// Address of the memory map array.
+    pub memory_map_addr: u64,
     /// Length of the memory map entries.
     pub memory_map_len: u64,
 
@@ -268,13 +332,17 @@ pub struct StivaleStruct {
     /// The length of modules that the stivale bootloader loaded according to the
     /// config.
     pub module_len: u64,
-    /// Pointer to the modules array.
-    pub modules: [StivaleModule; 0],
+    /// Address of the first module of the modules linked list.
+    pub modules: u64,
 
     /// UNIX epoch at boot, which is read from system RTC.
     pub unix_epoch: u64,
+    /// Flags passed by the bootloader. See [StivaleStructFlags] for more information.
     pub flags: u64,
 
+    /// Memory model of the framebuffer. If set to one, its RGB and all other values
+    /// are undefined. Only valid if [StivaleStructFlags::EXTENDED_COLOUR_INFO] is set.
+    pub memory_model: u8,
     /// Size of the red mask in RGB.
     pub red_mask_size: u8,
     /// Shift of the red mask in RGB.
@@ -296,36 +364,52 @@ pub struct StivaleStruct {
 }
 
 impl StivaleStruct {
-    /// Return's the modules array pointer as a rust slice.
-    pub fn modules_as_slice(&self) -> &[StivaleModule] {
-        unsafe { core::slice::from_raw_parts(self.modules.as_ptr(), self.module_len as usize) }
+    /// Returns the flags passed by the bootloader.
+    pub fn flags(&self) -> StivaleStructFlags {
+        StivaleStructFlags::from_bits_truncate(self.flags)
     }
 
-    /// Returns an iterator over all the modules that were loaded.
-    pub fn modules_iter(&self) -> StivaleModuleIter {
+    /// Returns the command line passed to the kernel as a rust string, cut at the first invalid
+    /// UTF-8 byte. At most [Limits::max_string_len](crate::v2::Limits::max_string_len) bytes
+    /// are scanned for the terminator, longer command lines are truncated.
+    pub fn command_line(&self) -> &str {
+        if self.command_line == 0 {
+            return "";
+        }
+
+        unsafe { self::utils::string_from_addr(self.command_line) }
+    }
+
+    /// Returns an iterator over all the modules that were loaded, up to
+    /// [Limits::max_modules](crate::v2::Limits::max_modules) of them.
+    pub fn modules_iter(&self) -> StivaleModuleIter<'_> {
         StivaleModuleIter {
-            sref: self,
-            current: 0,
-            phantom: PhantomData::default(),
+            next: self.modules,
+            remaining: self.module_len.min(limits().max_modules as u64),
+            phantom: PhantomData,
         }
     }
 
     /// Return's memory map entries pointer as a rust slice.
     pub fn memory_map_as_slice(&self) -> &[StivaleMemoryMapEntry] {
+        if self.memory_map_addr == 0 {
+            return &[];
+        }
+
         unsafe {
             core::slice::from_raw_parts(
-                self.memory_map_array.as_ptr(),
+                self.memory_map_addr as *const StivaleMemoryMapEntry,
                 self.memory_map_len as usize,
             )
         }
     }
 
     /// Returns an iterator over all the memory regions.
-    pub fn memory_map_iter(&self) -> StivaleMemoryMapIter {
+    pub fn memory_map_iter(&self) -> StivaleMemoryMapIter<'_> {
         StivaleMemoryMapIter {
             sref: self,
             current: 0x00,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         }
     }
 }
@@ -1,10 +1,20 @@
-/// Helper function to validate and create a string from a slice.
+/// Helper function to validate and create a string from a slice, cut at the first invalid UTF-8
+/// byte.
 pub(crate) fn string_from_slice(slice: &[u8]) -> &str {
-    let mut length = 0;
+    crate::v2::utils::lossy_string_from_slice(slice)
+}
 
-    while length < slice.len() && slice[length] != 0 {
-        length += 1;
-    }
+/// Helper function to validate and create a string from the null-terminated string at `addr`,
+/// cut at the first invalid UTF-8 byte. At most
+/// [Limits::max_string_len](crate::v2::Limits::max_string_len) bytes are scanned for the
+/// terminator.
+///
+/// ## Safety
+/// The string at `addr` must be readable up to its terminator or the scan limit.
+pub(crate) unsafe fn string_from_addr<'a>(addr: u64) -> &'a str {
+    let ptr = addr as *const u8;
+    let max_length = crate::v2::limits().max_string_len;
+    let length = crate::v2::utils::c_string_length_raw(ptr, max_length);
 
-    unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(slice.as_ptr(), length)) }
+    string_from_slice(core::slice::from_raw_parts(ptr, length))
 }
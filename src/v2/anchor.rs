@@ -0,0 +1,121 @@
+//! Support for anchored kernels. Kernels which are not ELF files (for example flat binaries)
+//! cannot provide a `.stivale2hdr` section, so they instead embed an anchor that the bootloader
+//! scans for, which describes where the kernel has to be loaded and where its header lives.
+
+use core::mem;
+
+use super::header::StivaleHeader;
+
+/// The magic at the start of every anchor.
+pub const STIVALE2_ANCHOR_MAGIC: [u8; 15] = *b"STIVALE2 ANCHOR";
+
+/// The stivale2 anchor. It has to be placed on a 16 byte boundary anywhere in the kernel image.
+///
+/// ## Example
+/// ```rust,no_run
+/// use core::ptr::addr_of;
+/// use stivale_boot::v2::{StivaleAnchor, StivaleHeader};
+///
+/// extern "C" {
+///     static __kernel_start: u8;
+///     static __bss_start: u8;
+///     static __bss_end: u8;
+/// }
+///
+/// static STIVALE_HDR: StivaleHeader = StivaleHeader::new();
+///
+/// #[used]
+/// static STIVALE_ANCHOR: StivaleAnchor = unsafe {
+///     StivaleAnchor::new(
+///         addr_of!(__kernel_start),
+///         addr_of!(__bss_start),
+///         addr_of!(__bss_end),
+///         &STIVALE_HDR,
+///     )
+/// };
+/// ```
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct StivaleAnchor {
+    anchor: [u8; 15],
+    bits: u8,
+    phys_load_addr: *const u8,
+    phys_bss_start: *const u8,
+    phys_bss_end: *const u8,
+    phys_stivale2hdr: *const StivaleHeader,
+}
+
+unsafe impl Send for StivaleAnchor {}
+unsafe impl Sync for StivaleAnchor {}
+
+impl StivaleAnchor {
+    /// Creates a new anchor for a kernel loaded at `load_addr`, whose bss section spans from
+    /// `bss_start` to `bss_end` and which has its stivale2 header at `header`. All of the
+    /// addresses are physical. The bitness of the kernel is taken from the target.
+    pub const fn new(
+        load_addr: *const u8,
+        bss_start: *const u8,
+        bss_end: *const u8,
+        header: *const StivaleHeader,
+    ) -> Self {
+        Self {
+            anchor: STIVALE2_ANCHOR_MAGIC,
+            bits: (mem::size_of::<usize>() * 8) as u8,
+            phys_load_addr: load_addr,
+            phys_bss_start: bss_start,
+            phys_bss_end: bss_end,
+            phys_stivale2hdr: header,
+        }
+    }
+
+    /// Scans `image` for an anchor and returns its offset in the image along with a copy of it.
+    pub fn find(image: &[u8]) -> Option<(usize, StivaleAnchor)> {
+        let size = mem::size_of::<StivaleAnchor>();
+        let mut offset = 0;
+
+        while offset + size <= image.len() {
+            if image[offset..offset + STIVALE2_ANCHOR_MAGIC.len()] == STIVALE2_ANCHOR_MAGIC {
+                // SAFETY: The bounds were checked above and the read does not require alignment.
+                let anchor = unsafe {
+                    core::ptr::read_unaligned(image.as_ptr().add(offset) as *const StivaleAnchor)
+                };
+
+                return Some((offset, anchor));
+            }
+
+            offset += 16;
+        }
+
+        None
+    }
+
+    /// Returns whether the anchor starts with the expected magic.
+    pub fn is_valid(&self) -> bool {
+        self.anchor == STIVALE2_ANCHOR_MAGIC && (self.bits == 32 || self.bits == 64)
+    }
+
+    /// Returns the bitness of the kernel, either 32 or 64.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Returns the physical address the kernel has to be loaded at.
+    pub fn phys_load_addr(&self) -> u64 {
+        self.phys_load_addr as u64
+    }
+
+    /// Returns the physical address of the start of the bss section.
+    pub fn phys_bss_start(&self) -> u64 {
+        self.phys_bss_start as u64
+    }
+
+    /// Returns the physical address of the end of the bss section.
+    pub fn phys_bss_end(&self) -> u64 {
+        self.phys_bss_end as u64
+    }
+
+    /// Returns the physical address of the stivale2 header.
+    pub fn phys_stivale2hdr(&self) -> u64 {
+        self.phys_stivale2hdr as u64
+    }
+}
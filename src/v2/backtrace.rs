@@ -0,0 +1,167 @@
+//! Walking of the frame pointer chain and printing of backtraces, symbolized with the kernel file
+//! when the `elf` feature is enabled.
+//!
+//! Walking the stack relies on frame pointers, so the kernel must be built with
+//! `-C force-frame-pointers=yes`. The walk stops at the first frame pointer which does not look
+//! like one, such as a null or misaligned pointer, or one which does not point up the stack.
+
+use core::fmt;
+use core::mem;
+
+use super::global::try_boot_info;
+use super::StivaleStruct;
+
+/// The maximum number of frames walked, in case the frame pointer chain loops.
+const MAX_FRAMES: usize = 64;
+
+/// The maximum distance between two consecutive frames, beyond which the frame pointer chain is
+/// considered corrupted.
+const MAX_FRAME_SIZE: usize = 0x100000;
+
+/// An iterator over the return addresses of the frames of the stack, innermost first.
+pub struct Backtrace {
+    frame_pointer: usize,
+    depth: usize,
+}
+
+impl Backtrace {
+    /// Starts walking the stack at the frame of the caller.
+    ///
+    /// ## Safety
+    /// The kernel must be built with frame pointers, so that the frame pointer register holds
+    /// the head of a readable frame pointer chain. See [Backtrace::from_frame_pointer].
+    #[inline(always)]
+    pub unsafe fn current() -> Self {
+        Self {
+            frame_pointer: frame_pointer(),
+            depth: 0,
+        }
+    }
+
+    /// Starts walking the stack at the frame whose frame pointer is `frame_pointer`, such as the
+    /// frame pointer saved by an exception handler.
+    ///
+    /// ## Safety
+    /// The frame pointer chain starting at `frame_pointer` must be readable.
+    pub unsafe fn from_frame_pointer(frame_pointer: usize) -> Self {
+        Self {
+            frame_pointer,
+            depth: 0,
+        }
+    }
+}
+
+impl Iterator for Backtrace {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let fp = self.frame_pointer;
+
+        if fp == 0 || !fp.is_multiple_of(mem::align_of::<usize>()) || self.depth == MAX_FRAMES {
+            return None;
+        }
+
+        // The saved frame pointer is followed by the return address on all supported
+        // architectures.
+        let frame = fp as *const usize;
+        let (next_fp, return_addr) = unsafe { (frame.read(), frame.add(1).read()) };
+
+        self.frame_pointer = if next_fp > fp && next_fp - fp <= MAX_FRAME_SIZE {
+            next_fp
+        } else {
+            0
+        };
+        self.depth += 1;
+
+        Some(return_addr as u64).filter(|&addr| addr != 0)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    fp
+}
+
+#[cfg(target_arch = "x86")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, ebp", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    fp
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, x29", out(reg) fp, options(nomem, nostack, preserves_flags))
+    };
+    fp
+}
+
+/// Prints a backtrace of the caller to `writer`, one frame per line, symbolized with the kernel
+/// file of the boot information registered with [init](super::init).
+///
+/// ## Safety
+/// The kernel must be built with frame pointers, see [Backtrace::current].
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{print_backtrace, UartWriter};
+///
+/// fn report(uart: &mut UartWriter) {
+///     // SAFETY: The kernel is built with `-C force-frame-pointers=yes`.
+///     let _ = unsafe { print_backtrace(uart) };
+/// }
+/// ```
+#[inline(always)]
+pub unsafe fn print_backtrace(writer: &mut impl fmt::Write) -> fmt::Result {
+    write_backtrace(writer, Backtrace::current(), try_boot_info())
+}
+
+/// Prints the return addresses of `backtrace` to `writer`, symbolized with the kernel file of
+/// `boot_info` if it is provided.
+///
+/// Frames are printed as `#0 0xffffffff80001234 kmain+0x34`, the symbol being omitted for
+/// addresses which cannot be symbolized.
+pub fn write_backtrace(
+    writer: &mut impl fmt::Write,
+    backtrace: Backtrace,
+    boot_info: Option<&StivaleStruct>,
+) -> fmt::Result {
+    writeln!(writer, "backtrace:")?;
+
+    for (index, addr) in backtrace.enumerate() {
+        write!(writer, "  #{} {:#018x}", index, addr)?;
+
+        // Look up the call instruction rather than the return address, which may belong to the
+        // next function if the call was the last instruction of the caller.
+        #[cfg(feature = "elf")]
+        if let Some((name, offset)) = boot_info.and_then(|info| symbolize(info, addr - 1)) {
+            write!(writer, " {}+{:#x}", name, offset + 1)?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    #[cfg(not(feature = "elf"))]
+    let _ = boot_info;
+
+    Ok(())
+}
+
+#[cfg(feature = "elf")]
+fn symbolize(boot_info: &StivaleStruct, addr: u64) -> Option<(&'static str, u64)> {
+    let slide = super::slide::KernelSlide::from_boot_info(boot_info);
+    let kernel_file = boot_info.kernel_file_bytes()?;
+
+    super::tag::symbolize(kernel_file, slide.unadjust(addr))
+}
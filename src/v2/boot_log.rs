@@ -0,0 +1,85 @@
+//! Logging of the boot information through the `log` crate, so that every kernel reports the
+//! same boot diagnostics.
+
+use log::{debug, info};
+
+use super::cmdline::ByteSize;
+use super::tag::{StivaleMemoryMapEntryType, StivaleSmpInfo};
+use super::StivaleStruct;
+
+/// Logs a summary of `boot_info`, one line per tag: the bootloader, the firmware, the memory
+/// map totals, the framebuffer mode, the processor count and the loaded modules. Summaries are
+/// logged at the info level, while details such as individual modules are logged at the debug
+/// level.
+pub fn log_boot_info(boot_info: &StivaleStruct) {
+    info!(
+        "bootloader: {} {}",
+        boot_info.bootloader_brand_lossy(),
+        boot_info.bootloader_version_lossy()
+    );
+
+    if let Some(firmware) = boot_info.firmware() {
+        info!("firmware: {:?}", firmware.kind());
+    }
+
+    if let Some(memory_map) = boot_info.memory_map() {
+        let entries = memory_map.as_slice();
+        let total_of = |entry_type| {
+            entries
+                .iter()
+                .filter(|entry| entry.entry_type == entry_type)
+                .map(|entry| entry.length)
+                .sum::<u64>()
+        };
+
+        info!(
+            "memory: {} entries, {} usable, {} bootloader reclaimable",
+            entries.len(),
+            ByteSize(total_of(StivaleMemoryMapEntryType::Usable)),
+            ByteSize(total_of(StivaleMemoryMapEntryType::BootloaderReclaimable))
+        );
+
+        for entry in entries {
+            debug!(
+                "memory: {:#018x}-{:#018x} {:?}, {}",
+                entry.base,
+                entry.base + entry.length,
+                entry.entry_type,
+                ByteSize(entry.length)
+            );
+        }
+    }
+
+    if let Some(framebuffer) = boot_info.framebuffer() {
+        info!(
+            "framebuffer: {}x{}x{} at {:#x}, pitch {}",
+            framebuffer.framebuffer_width,
+            framebuffer.framebuffer_height,
+            framebuffer.framebuffer_bpp,
+            framebuffer.framebuffer_addr,
+            framebuffer.framebuffer_pitch
+        );
+    }
+
+    if let Some(smp) = boot_info.smp() {
+        info!(
+            "smp: {} processors, BSP {} {}",
+            smp.cpu_count(),
+            StivaleSmpInfo::HARDWARE_ID_NAME,
+            smp.bsp_hardware_id()
+        );
+    }
+
+    if let Some(modules) = boot_info.modules() {
+        info!("modules: {}", modules.count());
+
+        for module in modules {
+            debug!(
+                "module: {} at {:#x}, {}",
+                module.as_str_lossy(),
+                module.start,
+                ByteSize(module.end.saturating_sub(module.start))
+            );
+        }
+    }
+}
@@ -0,0 +1,930 @@
+//! Bootloader side helpers used to lay out a stivale2 boot structure, and all of its tags, in
+//! memory before handing control to the kernel.
+
+use core::marker::PhantomData;
+use core::mem;
+
+use super::header::StivaleSmpHeaderTagFlags;
+use super::tag::*;
+use super::StivaleStruct;
+
+/// Errors that can occur while laying out the boot structure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildError {
+    /// The provided buffer is too small to hold the boot structure and its tags.
+    BufferTooSmall,
+    /// A string does not fit in the field it has to be stored in.
+    StringTooLong,
+    /// The end address of a range lies before its start address.
+    InvalidRange,
+    /// The buffer and the address it is mapped at do not share the same alignment.
+    Misaligned,
+    /// A pointer in the boot structure points outside of the buffer.
+    InvalidPointer,
+    /// A string contains a null byte, which would terminate it early.
+    EmbeddedNul,
+    /// A range is not page aligned.
+    UnalignedRange,
+    /// A range overlaps with a range which was already added.
+    OverlappingRanges,
+    /// The tag with this identifier is unknown to this crate, so its size cannot be determined.
+    UnknownTag(u64),
+    /// The entry count of the variable length tag with this identifier is above its
+    /// [Limits](super::Limits) field.
+    TooManyEntries(u64),
+    /// The tag chain is longer than [Limits::max_tags](super::Limits::max_tags).
+    TooManyTags,
+}
+
+/// Information required to build the SMP tag.
+pub struct StivaleSmpResponse<'a> {
+    /// The flags which were used to bring up the APs.
+    pub flags: StivaleSmpHeaderTagFlags,
+    /// LAPIC ID of the BSP (bootstrap processor).
+    #[cfg(not(target_arch = "aarch64"))]
+    pub bsp_lapic_id: u32,
+    /// MPIDR of the BSP (bootstrap processor).
+    #[cfg(target_arch = "aarch64")]
+    pub bsp_mpidr: u64,
+    /// The SMP information of every logical CPU (including BSP).
+    pub cpus: &'a [StivaleSmpInfo],
+}
+
+/// Description of the information the bootloader passes to the kernel. Every tag set to
+/// `Some` is written out by [write_boot_struct] in the order of the fields.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{
+///     write_boot_struct, BootResponse, StivaleEpochTag, StivaleFirmwareTag,
+///     StivaleFirmwareTagFlags, StivaleMemoryMapEntry, StivaleMemoryMapEntryType,
+/// };
+///
+/// // The tag constructors are `const`, so the parts of the response which do not change
+/// // between boots can be `static` templates.
+/// static FIRMWARE: StivaleFirmwareTag = StivaleFirmwareTag::new(StivaleFirmwareTagFlags::BIOS);
+/// static MEMORY_MAP: [StivaleMemoryMapEntry; 1] = [StivaleMemoryMapEntry::new(
+///     0x100000,
+///     0x700000,
+///     StivaleMemoryMapEntryType::Usable,
+/// )];
+///
+/// let epoch = StivaleEpochTag::new(1_600_000_000);
+/// let response = BootResponse {
+///     bootloader_brand: "example",
+///     firmware: Some(&FIRMWARE),
+///     memory_map: Some(&MEMORY_MAP),
+///     epoch: Some(&epoch),
+///     ..Default::default()
+/// };
+///
+/// let mut buffer = [0u8; 4096];
+/// write_boot_struct(&mut buffer, &response).unwrap();
+/// ```
+#[derive(Default)]
+pub struct BootResponse<'a> {
+    /// The bootloader brand string. Must be shorter than 64 bytes.
+    pub bootloader_brand: &'a str,
+    /// The bootloader version string. Must be shorter than 64 bytes.
+    pub bootloader_version: &'a str,
+    /// The kernel command line. The string is copied into the buffer and null-terminated.
+    pub command_line: Option<&'a str>,
+    pub memory_map: Option<&'a [StivaleMemoryMapEntry]>,
+    pub framebuffer: Option<&'a StivaleFramebufferTag>,
+    pub edid: Option<&'a [u8]>,
+    pub terminal: Option<&'a StivaleTerminalTag>,
+    pub modules: Option<&'a [StivaleModule]>,
+    pub rsdp: Option<&'a StivaleRsdpTag>,
+    pub smbios: Option<&'a StivaleSmbiosTag>,
+    pub epoch: Option<&'a StivaleEpochTag>,
+    pub firmware: Option<&'a StivaleFirmwareTag>,
+    pub efi_system_table: Option<&'a StivaleEfiSystemTableTag>,
+    pub kernel_file: Option<&'a StivaleKernelFileTag>,
+    pub kernel_file_v2: Option<&'a StivaleKernelFileV2Tag>,
+    pub kernel_slide: Option<&'a StivaleKernelSlideTag>,
+    pub kernel_base_addr: Option<&'a StivaleKernelBaseAddressTag>,
+    pub smp: Option<StivaleSmpResponse<'a>>,
+    pub pxe_info: Option<&'a StivalePxeInfoTag>,
+    pub uart: Option<&'a StivaleUartTag>,
+    pub dev_tree: Option<&'a StivaleDeviceTreeTag>,
+    pub vmap: Option<&'a StivaleVMapTag>,
+    pub pmrs: Option<&'a [StivalePmr]>,
+}
+
+/// Bump allocator over a caller provided buffer, used to lay out tags without requiring a heap.
+/// Every allocation is handed out as an exclusive borrow of a disjoint part of the buffer.
+///
+/// The arena also keeps track of the address at which the kernel will see the buffer, which is
+/// used to compute the values stored in the `next` pointers of the tags.
+pub struct TagArena<'b> {
+    /// The part of the buffer that has not been allocated yet.
+    remaining: &'b mut [u8],
+    /// The address the kernel will see the start of `remaining` at.
+    address: u64,
+    /// The host address of the start of `remaining`.
+    host_address: usize,
+}
+
+impl<'b> TagArena<'b> {
+    /// Creates a new arena over `buffer`, which is assumed to be accessed by the kernel at the
+    /// same address it has in the bootloader.
+    pub fn new(buffer: &'b mut [u8]) -> Self {
+        let address = super::utils::ptr_to_addr(buffer.as_ptr());
+
+        Self {
+            host_address: address as usize,
+            remaining: buffer,
+            address,
+        }
+    }
+
+    /// Creates a new arena over `buffer`, which will be accessed by the kernel at `base`.
+    /// The buffer and `base` must share the same alignment modulo 16.
+    pub fn with_base(buffer: &'b mut [u8], base: u64) -> Result<Self, BuildError> {
+        let host_address = buffer.as_ptr().addr();
+
+        if base.wrapping_sub(host_address as u64) & 0xf != 0 {
+            return Err(BuildError::Misaligned);
+        }
+
+        Ok(Self {
+            remaining: buffer,
+            address: base,
+            host_address,
+        })
+    }
+
+    /// Returns the amount of bytes left in the arena.
+    pub fn remaining(&self) -> usize {
+        self.remaining.len()
+    }
+
+    /// Returns the address the kernel will see `value` at. `value` must have been
+    /// allocated from this arena.
+    pub fn address_of<T: ?Sized>(&self, value: &T) -> u64 {
+        let offset = self.host_address - (value as *const T).cast::<u8>().addr();
+        self.address - offset as u64
+    }
+
+    /// Returns the amount of padding required to align the next allocation to `align`.
+    fn padding(&self, align: usize) -> usize {
+        let misalignment = self.host_address % align;
+
+        if misalignment == 0 {
+            0
+        } else {
+            align - misalignment
+        }
+    }
+
+    /// Takes the first `size` bytes of the remaining buffer, after aligning it to `align`.
+    fn take(&mut self, size: usize, align: usize) -> Result<&'b mut [u8], BuildError> {
+        let padding = self.padding(align);
+
+        let total = padding
+            .checked_add(size)
+            .filter(|total| *total <= self.remaining.len())
+            .ok_or(BuildError::BufferTooSmall)?;
+
+        let remaining = mem::take(&mut self.remaining);
+        let (allocation, rest) = remaining.split_at_mut(total);
+
+        self.remaining = rest;
+        self.address += total as u64;
+        self.host_address += total;
+
+        Ok(&mut allocation[padding..])
+    }
+
+    /// Takes everything left in the arena, aligned to `align`.
+    fn take_rest(&mut self, align: usize) -> Result<&'b mut [u8], BuildError> {
+        let padding = self.padding(align);
+
+        if padding > self.remaining.len() {
+            return Err(BuildError::BufferTooSmall);
+        }
+
+        self.take(self.remaining.len() - padding, align)
+    }
+
+    /// Gives back the unused end of an allocation made by [TagArena::take_rest].
+    fn give_back(&mut self, rest: &'b mut [u8]) {
+        debug_assert!(self.remaining.is_empty());
+
+        self.address -= rest.len() as u64;
+        self.host_address -= rest.len();
+        self.remaining = rest;
+    }
+
+    /// Copies `bytes` into the arena and returns the copy.
+    pub fn alloc_bytes(&mut self, bytes: &[u8], align: usize) -> Result<&'b mut [u8], BuildError> {
+        let allocation = self.take(bytes.len(), align)?;
+
+        allocation.copy_from_slice(bytes);
+        Ok(allocation)
+    }
+
+    /// Allocates `count` zeroed words.
+    pub(crate) fn alloc_words(&mut self, count: usize) -> Result<&'b mut [u64], BuildError> {
+        let size = count
+            .checked_mul(mem::size_of::<u64>())
+            .ok_or(BuildError::BufferTooSmall)?;
+        let allocation = self.take(size, mem::align_of::<u64>())?;
+        allocation.fill(0);
+
+        // SAFETY: The allocation is large enough and aligned for `count` words, and every bit
+        // pattern is a valid word.
+        Ok(unsafe { core::slice::from_raw_parts_mut(allocation.as_mut_ptr() as *mut u64, count) })
+    }
+
+    /// Copies `value` into the arena and returns a reference to the copy.
+    pub(crate) fn alloc<T>(&mut self, value: &T) -> Result<&'b mut T, BuildError> {
+        let allocation = self.take(mem::size_of::<T>(), mem::align_of::<T>())?;
+        let ptr = allocation.as_mut_ptr() as *mut T;
+
+        // SAFETY: The allocation is large enough and aligned for `T`, and it is exclusively
+        // borrowed for `'b`. The types stored in the arena are plain old data, so copying
+        // their bytes creates a valid value.
+        unsafe {
+            core::ptr::copy_nonoverlapping(value, ptr, 1);
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Copies `tag` into the arena and returns a reference to the copy. The identifier
+    /// of the copy is set to the one of `T` and its `next` pointer is zeroed.
+    pub fn push<T: StivaleTag>(&mut self, tag: &T) -> Result<&'b mut T, BuildError> {
+        let copy = self.alloc(tag)?;
+
+        // SAFETY: `StivaleTag` requires every tag to start with the tag header.
+        let header = unsafe { &mut *(copy as *mut T as *mut StivaleTagHeader) };
+        header.identifier = T::IDENTIFIER;
+        header.next = 0;
+
+        Ok(copy)
+    }
+
+    /// Copies the stivale struct into the arena and returns a reference to the copy.
+    pub fn push_struct(
+        &mut self,
+        stivale_struct: &StivaleStruct,
+    ) -> Result<&'b mut StivaleStruct, BuildError> {
+        self.alloc(stivale_struct)
+    }
+}
+
+/// Trait implemented by the tags made out of some fixed fields followed by a variable length
+/// array, the length of which is stored in the last fixed field.
+pub trait StivaleArrayTag: StivaleTag {
+    /// The type of the elements of the array.
+    type Entry;
+
+    /// The size of the fixed part of the tag, including the tag header.
+    const FIXED_SIZE: usize;
+
+    /// # Safety
+    /// `ptr` must be a pointer to a properly initialized tag with `count` entries.
+    unsafe fn from_raw_parts(ptr: *mut (), count: u64) -> *mut Self;
+}
+
+macro_rules! impl_array_tag {
+    ($($name:ident: $entry:ty = $fixed:expr;)*) => {
+        $(impl StivaleArrayTag for $name {
+            type Entry = $entry;
+
+            const FIXED_SIZE: usize = $fixed;
+
+            unsafe fn from_raw_parts(ptr: *mut (), count: u64) -> *mut Self {
+                Self::new_from_ptr_count(ptr, count)
+            }
+        })*
+    };
+}
+
+impl_array_tag! {
+    StivaleMemoryMapTag: StivaleMemoryMapEntry = 24;
+    StivaleEdidInfoTag: u8 = 24;
+    StivaleModuleTag: StivaleModule = 24;
+    StivaleSmpTag: StivaleSmpInfo = 40;
+    StivalePmrsTag: StivalePmr = 24;
+}
+
+/// Builds a variable length tag in a [TagArena], one entry at a time. While the builder is alive
+/// it holds on to the rest of the arena, which is given back once the tag is finished.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{ModuleTagBuilder, TagArena};
+///
+/// let mut buffer = [0u8; 512];
+/// let mut arena = TagArena::new(&mut buffer);
+/// let mut builder = ModuleTagBuilder::new(&mut arena).unwrap();
+///
+/// builder.push_module("initrd", 0x200000, 0x280000).unwrap();
+///
+/// let tag = builder.finish();
+/// assert_eq!(tag.as_slice()[0].as_str(), "initrd");
+/// ```
+pub struct ArrayTagBuilder<'a, 'b, T: StivaleArrayTag + ?Sized> {
+    arena: &'a mut TagArena<'b>,
+    /// Everything left in the arena when the builder was created, `None` once finished.
+    buffer: Option<&'b mut [u8]>,
+    count: usize,
+    phantom: PhantomData<&'b T>,
+}
+
+/// Builder for the [StivaleMemoryMapTag].
+pub type MemoryMapTagBuilder<'a, 'b> = ArrayTagBuilder<'a, 'b, StivaleMemoryMapTag>;
+/// Builder for the [StivaleEdidInfoTag].
+pub type EdidInfoTagBuilder<'a, 'b> = ArrayTagBuilder<'a, 'b, StivaleEdidInfoTag>;
+/// Builder for the [StivaleModuleTag].
+pub type ModuleTagBuilder<'a, 'b> = ArrayTagBuilder<'a, 'b, StivaleModuleTag>;
+/// Builder for the [StivaleSmpTag].
+pub type SmpTagBuilder<'a, 'b> = ArrayTagBuilder<'a, 'b, StivaleSmpTag>;
+/// Builder for the [StivalePmrsTag].
+pub type PmrsTagBuilder<'a, 'b> = ArrayTagBuilder<'a, 'b, StivalePmrsTag>;
+
+impl<'a, 'b, T: StivaleArrayTag + ?Sized> ArrayTagBuilder<'a, 'b, T> {
+    /// Starts building a new tag in `arena`.
+    pub fn new(arena: &'a mut TagArena<'b>) -> Result<Self, BuildError> {
+        let buffer = arena.take_rest(8)?;
+
+        if buffer.len() < T::FIXED_SIZE {
+            arena.give_back(buffer);
+            return Err(BuildError::BufferTooSmall);
+        }
+
+        buffer[..T::FIXED_SIZE].fill(0);
+        buffer[..8].copy_from_slice(&T::IDENTIFIER.to_ne_bytes());
+
+        Ok(Self {
+            arena,
+            buffer: Some(buffer),
+            count: 0,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the amount of entries pushed so far.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns whether no entry was pushed so far.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn buffer(&mut self) -> &mut [u8] {
+        self.buffer
+            .as_deref_mut()
+            .expect("builder already finished")
+    }
+
+    /// Returns the byte offset of the end of the tag.
+    fn size(&self) -> usize {
+        T::FIXED_SIZE + self.count * mem::size_of::<T::Entry>()
+    }
+
+    /// Copies the entries in `entries` at the end of the array.
+    pub fn extend_from_slice(&mut self, entries: &[T::Entry]) -> Result<(), BuildError> {
+        let start = self.size();
+        let length = mem::size_of_val(entries);
+        let buffer = self.buffer();
+
+        if start + length > buffer.len() {
+            return Err(BuildError::BufferTooSmall);
+        }
+
+        // SAFETY: The destination was bounds checked above. The entries are plain old data so
+        // their bytes can be copied.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                entries.as_ptr() as *const u8,
+                buffer[start..].as_mut_ptr(),
+                length,
+            );
+        }
+
+        self.count += entries.len();
+
+        let count_offset = T::FIXED_SIZE - 8;
+        let count = (self.count as u64).to_ne_bytes();
+        self.buffer()[count_offset..T::FIXED_SIZE].copy_from_slice(&count);
+
+        Ok(())
+    }
+
+    /// Appends `entry` at the end of the array.
+    pub fn push(&mut self, entry: T::Entry) -> Result<(), BuildError> {
+        self.extend_from_slice(core::slice::from_ref(&entry))
+    }
+
+    /// Finishes the tag and returns a reference to it. The unused part of the arena is given
+    /// back to it.
+    pub fn finish(mut self) -> &'b mut T {
+        let size = self.size();
+        let buffer = self.buffer.take().expect("builder already finished");
+        let (tag, rest) = buffer.split_at_mut(size);
+
+        self.arena.give_back(rest);
+
+        // SAFETY: The tag was fully initialized with `count` entries, the buffer is aligned to
+        // 8 bytes and it is exclusively borrowed for `'b`.
+        unsafe { &mut *T::from_raw_parts(tag.as_mut_ptr() as *mut (), self.count as u64) }
+    }
+}
+
+impl<'a, 'b, T: StivaleArrayTag + ?Sized> Drop for ArrayTagBuilder<'a, 'b, T> {
+    fn drop(&mut self) {
+        // Give back the whole buffer if the tag was never finished.
+        if let Some(buffer) = self.buffer.take() {
+            self.arena.give_back(buffer);
+        }
+    }
+}
+
+impl<'a, 'b> ArrayTagBuilder<'a, 'b, StivaleModuleTag> {
+    /// Appends a module named `name` spanning from `begin` to `end`. Names longer than
+    /// 127 bytes are truncated.
+    pub fn push_module(&mut self, name: &str, begin: u64, end: u64) -> Result<(), BuildError> {
+        if end < begin {
+            return Err(BuildError::InvalidRange);
+        }
+
+        self.push(StivaleModule::new(name, begin, end))
+    }
+
+    /// Same as [push_module](Self::push_module), but returns an error instead of truncating
+    /// names longer than 127 bytes.
+    pub fn push_module_checked(
+        &mut self,
+        name: &str,
+        begin: u64,
+        end: u64,
+    ) -> Result<(), BuildError> {
+        if end < begin {
+            return Err(BuildError::InvalidRange);
+        }
+
+        self.push(StivaleModule::try_new(name, begin, end)?)
+    }
+}
+
+impl<'a, 'b> ArrayTagBuilder<'a, 'b, StivaleSmpTag> {
+    /// Sets the flags which were used to bring up the APs.
+    pub fn flags(&mut self, flags: StivaleSmpHeaderTagFlags) -> &mut Self {
+        self.buffer()[16..24].copy_from_slice(&flags.bits().to_ne_bytes());
+        self
+    }
+
+    /// Sets the LAPIC ID of the BSP (bootstrap processor).
+    #[cfg(not(target_arch = "aarch64"))]
+    pub fn bsp_lapic_id(&mut self, bsp_lapic_id: u32) -> &mut Self {
+        self.buffer()[24..28].copy_from_slice(&bsp_lapic_id.to_ne_bytes());
+        self
+    }
+
+    /// Sets the MPIDR of the BSP (bootstrap processor).
+    #[cfg(target_arch = "aarch64")]
+    pub fn bsp_mpidr(&mut self, bsp_mpidr: u64) -> &mut Self {
+        self.buffer()[24..32].copy_from_slice(&bsp_mpidr.to_ne_bytes());
+        self
+    }
+}
+
+impl<'a, 'b> ArrayTagBuilder<'a, 'b, StivalePmrsTag> {
+    /// Appends a protected memory range spanning `length` bytes from `base`. Both `base` and
+    /// `length` must be page aligned, and the range must not overlap with the ranges which were
+    /// already appended.
+    pub fn push_pmr(
+        &mut self,
+        base: u64,
+        length: u64,
+        permissions: PmrPermissions,
+    ) -> Result<(), BuildError> {
+        const PAGE_SIZE: u64 = 0x1000;
+
+        if (base | length) & (PAGE_SIZE - 1) != 0 {
+            return Err(BuildError::UnalignedRange);
+        }
+
+        let end = base.checked_add(length).ok_or(BuildError::InvalidRange)?;
+
+        if length == 0 {
+            return Err(BuildError::InvalidRange);
+        }
+
+        let count = self.count;
+        let buffer = self.buffer();
+
+        for index in 0..count {
+            let offset = <StivalePmrsTag as StivaleArrayTag>::FIXED_SIZE
+                + index * mem::size_of::<StivalePmr>();
+            let mut word = [0; 8];
+
+            word.copy_from_slice(&buffer[offset..offset + 8]);
+            let other_base = u64::from_ne_bytes(word);
+            word.copy_from_slice(&buffer[offset + 8..offset + 16]);
+            // Entries pushed without going through this function may extend past the end of
+            // the address space.
+            let other_end = other_base.saturating_add(u64::from_ne_bytes(word));
+
+            if base < other_end && other_base < end {
+                return Err(BuildError::OverlappingRanges);
+            }
+        }
+
+        self.push(StivalePmr::new(base, length, permissions))
+    }
+}
+
+/// Describes where the bootloader placed the kernel, used to compute the kernel slide and to
+/// fill in the tags describing it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KernelPlacement {
+    link_base: u64,
+    virtual_base: u64,
+    physical_base: u64,
+}
+
+impl KernelPlacement {
+    /// Creates a new placement for a kernel linked at the virtual address `link_base` and
+    /// loaded at the virtual address `virtual_base`, backed by physical memory starting at
+    /// `physical_base`. The slide has to be a positive offset, so `virtual_base` must not be
+    /// lower than `link_base`.
+    pub fn new(link_base: u64, virtual_base: u64, physical_base: u64) -> Result<Self, BuildError> {
+        if virtual_base < link_base {
+            return Err(BuildError::InvalidRange);
+        }
+
+        Ok(Self {
+            link_base,
+            virtual_base,
+            physical_base,
+        })
+    }
+
+    /// Returns the slide applied over the link address of the kernel.
+    pub fn slide(&self) -> u64 {
+        self.virtual_base - self.link_base
+    }
+
+    /// Applies the slide to the link time address `addr`, such as the entry point or the
+    /// base of a PMR.
+    pub fn apply_slide(&self, addr: u64) -> u64 {
+        addr.wrapping_add(self.slide())
+    }
+
+    /// Returns the kernel slide tag describing this placement.
+    pub fn slide_tag(&self) -> StivaleKernelSlideTag {
+        StivaleKernelSlideTag::new(self.slide())
+    }
+
+    /// Returns the kernel base address tag describing this placement.
+    pub fn base_address_tag(&self) -> StivaleKernelBaseAddressTag {
+        StivaleKernelBaseAddressTag::new(self.physical_base, self.virtual_base)
+    }
+}
+
+/// Trait implemented by the targets a boot structure can be written to, such as the memory of
+/// a virtual machine, which a hypervisor may not have mapped into its own address space.
+pub trait MemoryWriter {
+    /// Writes `bytes` at the (guest physical) address `address`.
+    fn write(&mut self, address: u64, bytes: &[u8]) -> Result<(), BuildError>;
+}
+
+/// Writer over a buffer which will be accessed by the kernel at `base`.
+struct SliceWriter<'b> {
+    buffer: &'b mut [u8],
+    base: u64,
+}
+
+impl<'b> MemoryWriter for SliceWriter<'b> {
+    fn write(&mut self, address: u64, bytes: &[u8]) -> Result<(), BuildError> {
+        let start = address
+            .checked_sub(self.base)
+            .ok_or(BuildError::BufferTooSmall)? as usize;
+
+        self.buffer
+            .get_mut(start..start + bytes.len())
+            .ok_or(BuildError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+
+        Ok(())
+    }
+}
+
+/// Returns the raw representation of `value`.
+pub(super) fn bytes_of<T: ?Sized>(value: &T) -> &[u8] {
+    // SAFETY: The types passed in here are plain old data without any implicit padding.
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, mem::size_of_val(value)) }
+}
+
+/// Writes the boot structure sequentially, keeping track of the tag chain.
+struct TagStream<'w, W: MemoryWriter> {
+    writer: &'w mut W,
+    /// The address the next object will be written at.
+    address: u64,
+    /// The address of the `next` field that has to point to the next tag.
+    link: u64,
+}
+
+impl<'w, W: MemoryWriter> TagStream<'w, W> {
+    /// Writes the tag made out of the header and `parts`, and links it into the chain.
+    fn push_parts(&mut self, identifier: u64, parts: &[&[u8]]) -> Result<(), BuildError> {
+        let address = (self.address + 7) & !7;
+        let header = [identifier, 0];
+
+        self.writer.write(address, bytes_of(&header))?;
+        self.address = address + mem::size_of::<StivaleTagHeader>() as u64;
+
+        for part in parts {
+            self.writer.write(self.address, part)?;
+            self.address += part.len() as u64;
+        }
+
+        self.writer.write(self.link, &address.to_ne_bytes())?;
+        self.link = address + 8;
+
+        Ok(())
+    }
+
+    fn push<T: StivaleTag>(&mut self, tag: &T) -> Result<(), BuildError> {
+        let body = &bytes_of(tag)[mem::size_of::<StivaleTagHeader>()..];
+        self.push_parts(T::IDENTIFIER, &[body])
+    }
+
+    fn push_array<T: StivaleArrayTag + ?Sized>(
+        &mut self,
+        fields: &[u8],
+        entries: &[T::Entry],
+    ) -> Result<(), BuildError> {
+        let count = (entries.len() as u64).to_ne_bytes();
+        self.push_parts(T::IDENTIFIER, &[fields, &count, bytes_of(entries)])
+    }
+}
+
+/// Lays out the [StivaleStruct], every tag in `response` and their variable length payloads
+/// contiguously in `buffer`, and returns the address of the [StivaleStruct] that has to be passed
+/// to the kernel.
+///
+/// The buffer is assumed to be accessed by the kernel at the same address it has in the
+/// bootloader; see [write_boot_struct_at] if that is not the case.
+pub fn write_boot_struct(buffer: &mut [u8], response: &BootResponse) -> Result<u64, BuildError> {
+    let base = super::utils::ptr_to_addr(buffer.as_ptr());
+    write_boot_struct_at(buffer, base, response)
+}
+
+/// Same as [write_boot_struct], but all the internal pointers are computed as if the buffer was
+/// located at `base`.
+pub fn write_boot_struct_at(
+    buffer: &mut [u8],
+    base: u64,
+    response: &BootResponse,
+) -> Result<u64, BuildError> {
+    build_into_guest(&mut SliceWriter { buffer, base }, base, response)
+}
+
+/// Lays out the boot structure through `writer`, starting at the (guest physical) address `gpa`,
+/// and returns the address of the [StivaleStruct] that has to be passed to the kernel. This
+/// allows hypervisors to build the boot structure directly in the memory of the guest.
+pub fn build_into_guest<W: MemoryWriter>(
+    writer: &mut W,
+    gpa: u64,
+    response: &BootResponse,
+) -> Result<u64, BuildError> {
+    let mut stivale_struct = StivaleStruct::new();
+    stivale_struct.try_set_bootloader_brand(response.bootloader_brand)?;
+    stivale_struct.try_set_bootloader_version(response.bootloader_version)?;
+
+    let address = (gpa + 7) & !7;
+    writer.write(address, bytes_of(&stivale_struct))?;
+
+    let mut stream = TagStream {
+        writer,
+        address: address + mem::size_of::<StivaleStruct>() as u64,
+        link: address + 128,
+    };
+
+    if let Some(command_line) = response.command_line {
+        if command_line.contains('\0') {
+            return Err(BuildError::EmbeddedNul);
+        }
+
+        let string = stream.address;
+
+        stream.writer.write(string, command_line.as_bytes())?;
+        stream
+            .writer
+            .write(string + command_line.len() as u64, &[0])?;
+        stream.address += command_line.len() as u64 + 1;
+
+        stream.push(&StivaleCommandLineTag::new(string))?;
+    }
+
+    if let Some(memory_map) = response.memory_map {
+        stream.push_array::<StivaleMemoryMapTag>(&[], memory_map)?;
+    }
+
+    if let Some(framebuffer) = response.framebuffer {
+        stream.push(framebuffer)?;
+    }
+
+    if let Some(edid) = response.edid {
+        stream.push_array::<StivaleEdidInfoTag>(&[], edid)?;
+    }
+
+    if let Some(terminal) = response.terminal {
+        stream.push(terminal)?;
+    }
+
+    if let Some(modules) = response.modules {
+        stream.push_array::<StivaleModuleTag>(&[], modules)?;
+    }
+
+    macro_rules! push_fixed {
+        ($($field:ident),*) => {
+            $(if let Some(tag) = response.$field {
+                stream.push(tag)?;
+            })*
+        };
+    }
+
+    push_fixed!(
+        rsdp,
+        smbios,
+        epoch,
+        firmware,
+        efi_system_table,
+        kernel_file,
+        kernel_file_v2,
+        kernel_slide,
+        kernel_base_addr
+    );
+
+    if let Some(smp) = &response.smp {
+        // The flags followed by the LAPIC ID of the BSP and the unused field, or the MPIDR of
+        // the BSP on aarch64.
+        let mut fields = [0; 16];
+
+        fields[..8].copy_from_slice(&smp.flags.bits().to_ne_bytes());
+        #[cfg(not(target_arch = "aarch64"))]
+        fields[8..12].copy_from_slice(&smp.bsp_lapic_id.to_ne_bytes());
+        #[cfg(target_arch = "aarch64")]
+        fields[8..16].copy_from_slice(&smp.bsp_mpidr.to_ne_bytes());
+
+        stream.push_array::<StivaleSmpTag>(&fields, smp.cpus)?;
+    }
+
+    push_fixed!(pxe_info, uart, dev_tree, vmap);
+
+    if let Some(pmrs) = response.pmrs {
+        stream.push_array::<StivalePmrsTag>(&[], pmrs)?;
+    }
+
+    Ok(address)
+}
+
+/// Copies `command_line` into `buffer`, null-terminating it, and returns a command line tag
+/// pointing at the copy. The buffer must stay valid (and must not be modified) until the kernel
+/// is done with the tag, so it usually is a `static` buffer.
+pub fn write_command_line(
+    buffer: &mut [u8],
+    command_line: &str,
+) -> Result<StivaleCommandLineTag, BuildError> {
+    if command_line.contains('\0') {
+        return Err(BuildError::EmbeddedNul);
+    }
+
+    if command_line.len() >= buffer.len() {
+        return Err(BuildError::StringTooLong);
+    }
+
+    buffer[..command_line.len()].copy_from_slice(command_line.as_bytes());
+    buffer[command_line.len()] = 0;
+
+    Ok(StivaleCommandLineTag::new(super::utils::ptr_to_addr(
+        buffer.as_ptr(),
+    )))
+}
+
+fn read_u64(buffer: &[u8], offset: usize) -> Result<u64, BuildError> {
+    let bytes = buffer
+        .get(offset..offset + 8)
+        .ok_or(BuildError::InvalidPointer)?;
+
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes);
+    Ok(u64::from_ne_bytes(word))
+}
+
+/// Rewrites the internal pointers of a boot structure that was laid out at `old_base` so that
+/// it can be accessed at `new_base` instead. `stivale_struct` is the address of the
+/// [StivaleStruct] returned by [write_boot_struct_at], and `buffer` must contain it and every
+/// tag.
+///
+/// This rewrites the tag chain and the payload pointers which point inside of the buffer (such
+/// as the command line string). Pointers outside of the buffer are left untouched.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{rebase, write_boot_struct_at, BootResponse, StivaleEpochTag};
+///
+/// let epoch = StivaleEpochTag::new(1_600_000_000);
+/// let response = BootResponse {
+///     command_line: Some("nosmp"),
+///     epoch: Some(&epoch),
+///     ..Default::default()
+/// };
+///
+/// // The structure is aligned up, so it does not start at the beginning of the buffer.
+/// let mut buffer = [0u8; 512];
+/// let addr = write_boot_struct_at(&mut buffer, 0x1003, &response).unwrap();
+/// assert_eq!(addr, 0x1008);
+///
+/// // The `tags` field of the structure, at offset 128.
+/// let tags = |buffer: &[u8]| {
+///     let mut tags = [0; 8];
+///     tags.copy_from_slice(&buffer[5 + 128..5 + 136]);
+///     u64::from_ne_bytes(tags)
+/// };
+/// assert_eq!(tags(&buffer), 0x1098);
+///
+/// // It now points at the first tag as seen from 0x8003.
+/// rebase(&mut buffer, 0x1003, 0x8003, addr).unwrap();
+/// assert_eq!(tags(&buffer), 0x8098);
+/// ```
+pub fn rebase(
+    buffer: &mut [u8],
+    old_base: u64,
+    new_base: u64,
+    stivale_struct: u64,
+) -> Result<(), BuildError> {
+    let end = old_base
+        .checked_add(buffer.len() as u64)
+        .ok_or(BuildError::InvalidPointer)?;
+    let translate = |addr: u64| -> Result<usize, BuildError> {
+        if addr >= old_base && addr < end {
+            Ok((addr - old_base) as usize)
+        } else {
+            Err(BuildError::InvalidPointer)
+        }
+    };
+    let rebase_addr = |addr: u64| {
+        (addr - old_base)
+            .checked_add(new_base)
+            .ok_or(BuildError::InvalidPointer)
+    };
+
+    let struct_offset = translate(stivale_struct)?;
+
+    // The whole chain is validated before rewriting anything, so that an invalid tag does not
+    // leave the buffer half rebased.
+    for rewrite in [false, true] {
+        // Offset of the `next` pointer of the current tag, starting with the `tags` field of
+        // the stivale struct.
+        let mut link = struct_offset + 128;
+        let mut remaining = buffer.len() / mem::size_of::<StivaleTagHeader>();
+
+        loop {
+            let next = read_u64(buffer, link)?;
+
+            if next == 0 {
+                break;
+            }
+
+            if remaining == 0 {
+                return Err(BuildError::InvalidPointer);
+            }
+
+            let tag = translate(next)?;
+            let identifier = read_u64(buffer, tag)?;
+
+            // Make sure the whole header is inside of the buffer.
+            read_u64(buffer, tag + 8)?;
+            let rebased = rebase_addr(next)?;
+
+            if rewrite {
+                buffer[link..link + 8].copy_from_slice(&rebased.to_ne_bytes());
+            }
+
+            if identifier == StivaleCommandLineTag::IDENTIFIER {
+                let command_line = read_u64(buffer, tag + 16)?;
+
+                if translate(command_line).is_ok() {
+                    let rebased = rebase_addr(command_line)?;
+
+                    if rewrite {
+                        buffer[tag + 16..tag + 24].copy_from_slice(&rebased.to_ne_bytes());
+                    }
+                }
+            }
+
+            link = tag + 8;
+            remaining -= 1;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,303 @@
+//! A bump allocator carving its heap out of the memory map, so that kernels can use `alloc`
+//! before their real heap exists.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::convert::TryFrom;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::error::Error;
+use super::hhdm::hhdm_offset;
+use super::tag::{StivaleMemoryMapEntryType, StivaleMemoryMapTag};
+use super::StivaleStruct;
+
+/// A [GlobalAlloc] handing out memory from a single region by bumping a pointer. Freed memory is
+/// never reused, which makes it suitable only for the allocations made early during boot.
+///
+/// The allocator starts empty and fails every allocation until [init](Self::init) gives it the
+/// largest usable memory region which contains neither the kernel nor any module.
+///
+/// ## Example
+/// ```rust
+/// use core::alloc::{GlobalAlloc, Layout};
+/// use stivale_boot::v2::{
+///     round_trip, BootResponse, BumpAllocator, StivaleMemoryMapEntry, StivaleMemoryMapEntryType,
+/// };
+///
+/// static ALLOCATOR: BumpAllocator = BumpAllocator::new();
+///
+/// let heap = Box::leak(vec![0u8; 0x10000].into_boxed_slice());
+/// let memory_map = [StivaleMemoryMapEntry::new(
+///     heap.as_ptr() as u64,
+///     heap.len() as u64,
+///     StivaleMemoryMapEntryType::Usable,
+/// )];
+/// let response = BootResponse {
+///     memory_map: Some(&memory_map),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 4096]));
+/// ALLOCATOR.init(round_trip(buffer, &response).unwrap()).unwrap();
+///
+/// let ptr = unsafe { ALLOCATOR.alloc(Layout::from_size_align(64, 16).unwrap()) };
+/// assert!(heap.as_ptr_range().contains(&(ptr as *const u8)));
+/// assert_eq!(ALLOCATOR.remaining(), heap.as_ptr_range().end as usize - ptr as usize - 64);
+/// ```
+pub struct BumpAllocator {
+    next: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl BumpAllocator {
+    /// Creates an allocator without memory, to be initialized with [init](Self::init).
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+        }
+    }
+
+    /// Gives the allocator the largest usable region of the memory map, excluding the kernel
+    /// file and the modules. The region is accessed through the direct map if one was
+    /// registered with [register_hhdm_offset](super::register_hhdm_offset), and through the
+    /// identity mapping otherwise.
+    ///
+    /// Returns [Error::AlreadyInitialized] if the allocator was already initialized and
+    /// [Error::OutOfMemory] if no usable memory is left.
+    pub fn init(&self, boot_info: &StivaleStruct) -> Result<(), Error> {
+        let memory_map = boot_info
+            .memory_map()
+            .ok_or(Error::missing::<StivaleMemoryMapTag>())?;
+
+        let kernel = boot_info
+            .kernel_file_v2()
+            .map(|tag| tag.kernel_start..tag.kernel_start.saturating_add(tag.kernel_size));
+        let modules = boot_info
+            .modules()
+            .into_iter()
+            .flat_map(|tag| tag.iter())
+            .map(|module| module.start..module.end);
+        let excluded = kernel.into_iter().chain(modules).map(|range| {
+            let physical = |addr: u64| match hhdm_offset() {
+                Some(offset) if addr >= offset => addr - offset,
+                _ => addr,
+            };
+
+            physical(range.start)..physical(range.end)
+        });
+
+        let region = largest_free_region(memory_map, excluded).ok_or(Error::OutOfMemory)?;
+        let offset = hhdm_offset().unwrap_or(0);
+        let start = usize::try_from(region.start.saturating_add(offset)).ok();
+        let end = usize::try_from(region.end.saturating_add(offset)).unwrap_or(usize::MAX);
+
+        match start {
+            Some(start) if start < end => self.init_range(start, end),
+            _ => Err(Error::OutOfMemory),
+        }
+    }
+
+    fn init_range(&self, start: usize, end: usize) -> Result<(), Error> {
+        self.end
+            .compare_exchange(0, end, Ordering::AcqRel, Ordering::Acquire)
+            .map_err(|_| Error::AlreadyInitialized)?;
+        // Address zero marks the allocator as uninitialized, so it is never handed out.
+        self.next.store(start.max(1), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes left to allocate, ignoring alignment.
+    pub fn remaining(&self) -> usize {
+        let end = self.end.load(Ordering::Acquire);
+
+        end.saturating_sub(self.next.load(Ordering::Acquire))
+    }
+}
+
+impl Default for BumpAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for BumpAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let end = self.end.load(Ordering::Acquire);
+        let mut next = self.next.load(Ordering::Acquire);
+
+        loop {
+            // The region is not initialized yet.
+            if next == 0 {
+                return core::ptr::null_mut();
+            }
+
+            let start = match next.checked_add(layout.align() - 1) {
+                Some(addr) => addr & !(layout.align() - 1),
+                None => return core::ptr::null_mut(),
+            };
+
+            let new_next = match start.checked_add(layout.size()) {
+                Some(addr) if addr <= end => addr,
+                _ => return core::ptr::null_mut(),
+            };
+
+            match self.next.compare_exchange_weak(
+                next,
+                new_next,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return start as *mut u8,
+                Err(current) => next = current,
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+/// Returns the largest part of a usable memory region which overlaps none of the `excluded`
+/// ranges.
+fn largest_free_region<I>(memory_map: &StivaleMemoryMapTag, excluded: I) -> Option<Range<u64>>
+where
+    I: Iterator<Item = Range<u64>> + Clone,
+{
+    let mut largest: Option<Range<u64>> = None;
+
+    let usable = memory_map
+        .iter()
+        .filter(|entry| entry.entry_type() == StivaleMemoryMapEntryType::Usable);
+
+    for entry in usable {
+        let (base, end) = (entry.base, entry.end_address());
+
+        // A free part starts either at the start of the region or at the end of an excluded
+        // range, and extends up to the next excluded range.
+        let starts = core::iter::once(base).chain(
+            excluded
+                .clone()
+                .map(|range| range.end)
+                .filter(|&addr| addr > base && addr < end),
+        );
+
+        for start in starts {
+            if excluded
+                .clone()
+                .any(|range| range.start <= start && start < range.end)
+            {
+                continue;
+            }
+
+            let free_end = excluded
+                .clone()
+                .map(|range| range.start)
+                .filter(|&addr| addr > start)
+                .fold(end, u64::min);
+
+            if largest
+                .as_ref()
+                .is_none_or(|range| free_end - start > range.end - range.start)
+            {
+                largest = Some(start..free_end);
+            }
+        }
+    }
+
+    largest
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::*;
+    use crate::v2::{round_trip, BootResponse, StivaleMemoryMapEntry};
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn alloc_fails_until_initialized() {
+        let allocator = BumpAllocator::new();
+
+        assert!(unsafe { allocator.alloc(layout(8, 8)) }.is_null());
+        assert_eq!(allocator.remaining(), 0);
+    }
+
+    #[test]
+    fn alloc_aligns_and_bumps() {
+        let allocator = BumpAllocator::new();
+        allocator.init_range(0x1001, 0x2000).unwrap();
+
+        let first = unsafe { allocator.alloc(layout(0x10, 0x100)) };
+        let second = unsafe { allocator.alloc(layout(1, 1)) };
+
+        assert_eq!(first as usize, 0x1100);
+        assert_eq!(second as usize, 0x1110);
+        assert_eq!(allocator.remaining(), 0x2000 - 0x1111);
+    }
+
+    #[test]
+    fn alloc_fails_once_exhausted() {
+        let allocator = BumpAllocator::new();
+        allocator.init_range(0x1000, 0x1100).unwrap();
+
+        assert!(unsafe { allocator.alloc(layout(0x101, 1)) }.is_null());
+        assert_eq!(
+            unsafe { allocator.alloc(layout(0x100, 1)) } as usize,
+            0x1000
+        );
+        assert!(unsafe { allocator.alloc(layout(1, 1)) }.is_null());
+        assert_eq!(allocator.remaining(), 0);
+    }
+
+    #[test]
+    fn alloc_fails_instead_of_wrapping_around() {
+        let allocator = BumpAllocator::new();
+        allocator.init_range(usize::MAX - 0x10, usize::MAX).unwrap();
+
+        // Aligning up the next address overflows.
+        assert!(unsafe { allocator.alloc(layout(1, 0x100)) }.is_null());
+        // The end of the allocation overflows.
+        assert!(unsafe { allocator.alloc(layout(usize::MAX / 2, 1)) }.is_null());
+        assert_eq!(allocator.remaining(), 0x10);
+    }
+
+    #[test]
+    fn init_twice_fails() {
+        let allocator = BumpAllocator::new();
+        allocator.init_range(0x1000, 0x2000).unwrap();
+
+        assert_eq!(
+            allocator.init_range(0x3000, 0x4000),
+            Err(Error::AlreadyInitialized)
+        );
+        assert_eq!(allocator.remaining(), 0x1000);
+    }
+
+    #[test]
+    fn largest_free_region_skips_excluded_ranges() {
+        let memory_map = [
+            StivaleMemoryMapEntry::new(0x1000, 0xf000, StivaleMemoryMapEntryType::Usable),
+            StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Usable),
+            StivaleMemoryMapEntry::new(0x200000, 0x1000000, StivaleMemoryMapEntryType::Reserved),
+        ];
+        let response = BootResponse {
+            memory_map: Some(&memory_map),
+            ..Default::default()
+        };
+        let boot_info = round_trip(Box::leak(Box::new([0u8; 512])), &response).unwrap();
+        let memory_map = boot_info.memory_map().unwrap();
+
+        let kernel = 0x120000..0x180000;
+        let region = largest_free_region(memory_map, core::iter::once(kernel));
+        assert_eq!(region, Some(0x180000..0x200000));
+
+        let everything = 0..0x200000;
+        let region = largest_free_region(memory_map, core::iter::once(everything));
+        assert_eq!(region, None);
+    }
+}
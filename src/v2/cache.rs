@@ -0,0 +1,146 @@
+//! Boot information whose tag lookups are resolved on first use and cached, so that repeated
+//! accesses to the same tag do not walk the tag chain again.
+
+use core::mem;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::index::slot_of;
+use super::tag::*;
+use super::{array_tag_at, limits, utils, StivaleStruct};
+
+/// Slot value of a tag which is not resolved yet.
+const UNRESOLVED: u64 = 0;
+/// Slot value of a tag which was looked up and is missing.
+const MISSING: u64 = u64::MAX;
+
+/// Wrapper around a [StivaleStruct] caching the address of each known tag the first time it is
+/// looked up. Unlike [TagIndex](super::TagIndex), only the tags which are actually used are
+/// resolved, and the wrapper can be shared between processors.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootResponse, CachedBootInfo, StivaleEpochTag};
+///
+/// let epoch = StivaleEpochTag::new(1_600_000_000);
+/// let response = BootResponse {
+///     epoch: Some(&epoch),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 256]));
+/// let boot_info = round_trip(buffer, &response).unwrap();
+/// let cached = CachedBootInfo::new(boot_info);
+///
+/// // The first call walks the chain, the following ones use the cached address.
+/// assert_eq!(cached.tag::<StivaleEpochTag>().unwrap().epoch, 1_600_000_000);
+/// assert_eq!(cached.tag::<StivaleEpochTag>().unwrap().epoch, 1_600_000_000);
+/// ```
+pub struct CachedBootInfo<'a> {
+    stivale_struct: &'a StivaleStruct,
+    /// The address of each tag of [KNOWN_TAGS], in the same order.
+    slots: [AtomicU64; KNOWN_TAGS.len()],
+}
+
+impl<'a> CachedBootInfo<'a> {
+    /// Wraps `stivale_struct`, without resolving any tag.
+    pub const fn new(stivale_struct: &'a StivaleStruct) -> Self {
+        Self {
+            stivale_struct,
+            slots: [const { AtomicU64::new(UNRESOLVED) }; KNOWN_TAGS.len()],
+        }
+    }
+
+    /// Returns the wrapped boot information.
+    pub fn get(&self) -> &'a StivaleStruct {
+        self.stivale_struct
+    }
+
+    /// Returns the address of the tag in `slot`, resolving it if needed. Concurrent callers may
+    /// both walk the chain, in which case they store the same address.
+    fn resolve(&self, slot: usize) -> Option<u64> {
+        let cell = self.slots.get(slot)?;
+
+        let addr = match cell.load(Ordering::Acquire) {
+            UNRESOLVED => {
+                let addr = self
+                    .stivale_struct
+                    .get_tag(KNOWN_TAGS[slot])
+                    .unwrap_or(MISSING);
+                cell.store(addr, Ordering::Release);
+                addr
+            }
+            addr => addr,
+        };
+
+        Some(addr).filter(|addr| *addr != MISSING)
+    }
+
+    /// Returns the fixed size tag of type `T`.
+    pub fn tag<T: StivaleTag>(&self) -> Option<&'a T> {
+        // SAFETY: The slot holds a tag with the identifier of `T`, and `StivaleTag` requires `T`
+        // to be plain old data starting with the tag header.
+        self.resolve(const { slot_of(T::IDENTIFIER) })
+            .map(|addr| unsafe { &*utils::addr_to_ptr::<T>(addr) })
+    }
+
+    fn array_tag<T: StivaleTag + ?Sized>(
+        &self,
+        count_offset: usize,
+        limit: usize,
+    ) -> Option<(*mut (), u64)> {
+        array_tag_at(
+            self.resolve(const { slot_of(T::IDENTIFIER) })?,
+            count_offset,
+            limit,
+        )
+    }
+
+    /// Same as [StivaleStruct::framebuffer].
+    pub fn framebuffer(&self) -> Option<&'a StivaleFramebufferTag> {
+        self.tag()
+    }
+
+    /// Same as [StivaleStruct::terminal].
+    pub fn terminal(&self) -> Option<&'a StivaleTerminalTag> {
+        self.tag()
+    }
+
+    /// Same as [StivaleStruct::memory_map].
+    pub fn memory_map(&self) -> Option<&'a StivaleMemoryMapTag> {
+        self.array_tag::<StivaleMemoryMapTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_memory_map_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleMemoryMapTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::edid_info].
+    pub fn edid_info(&self) -> Option<&'a StivaleEdidInfoTag> {
+        self.array_tag::<StivaleEdidInfoTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleEdidInfoTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::modules].
+    pub fn modules(&self) -> Option<&'a StivaleModuleTag> {
+        self.array_tag::<StivaleModuleTag>(mem::size_of::<StivaleTagHeader>(), limits().max_modules)
+            .map(|(ptr, count)| unsafe { &*StivaleModuleTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::smp].
+    pub fn smp(&self) -> Option<&'a StivaleSmpTag> {
+        self.array_tag::<StivaleSmpTag>(32, limits().max_cpus)
+            .map(|(ptr, count)| unsafe { &*StivaleSmpTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::pmrs].
+    pub fn pmrs(&self) -> Option<&'a StivalePmrsTag> {
+        self.array_tag::<StivalePmrsTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivalePmrsTag::new_from_ptr_count(ptr, count) })
+    }
+}
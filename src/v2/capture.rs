@@ -0,0 +1,295 @@
+//! Boot structure captures: memory images of the boot structure, its tags and the strings they
+//! point to, dumped by a kernel booted by a real bootloader and later replayed on the host with
+//! [StivaleStructReader], so that tag parsing can be tested against authentic bootloader output.
+//!
+//! A capture is made of a header followed by regions of memory, all integers being stored in
+//! native byte order:
+//!
+//! | Offset | Size | Description                                  |
+//! |--------|------|----------------------------------------------|
+//! | 0      | 8    | The magic, `STV2CAPT`                        |
+//! | 8      | 8    | The address of the [StivaleStruct]           |
+//! | 16     | 8    | The amount of regions                        |
+//! | 24     | ...  | The regions                                  |
+//!
+//! Every region starts with its address and its length, as 8 byte integers, followed by its
+//! bytes, padded with zeros to a multiple of 8 bytes.
+//!
+//! Captures are checked in under `tests/fixtures` and parsed by the unit tests of this module.
+
+use core::convert::TryFrom;
+use core::mem;
+
+use super::error::Error;
+use super::reader::{AddressTranslator, StivaleStructReader, TranslationRegion};
+use super::tag::*;
+use super::StivaleStruct;
+
+/// The magic at the start of every capture.
+pub const CAPTURE_MAGIC: [u8; 8] = *b"STV2CAPT";
+
+const HEADER_SIZE: usize = 24;
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(8)?)?);
+    Some(u64::from_ne_bytes(word))
+}
+
+/// A boot structure capture, validated by [BootCapture::parse].
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootCapture, BootResponse, StivaleUartTag};
+///
+/// let uart = StivaleUartTag::new(0x3f8);
+/// let response = BootResponse {
+///     bootloader_brand: "Limine",
+///     bootloader_version: "2.0",
+///     command_line: Some("quiet"),
+///     uart: Some(&uart),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 512]));
+/// let boot_info = round_trip(buffer, &response).unwrap();
+///
+/// let mut capture = Vec::new();
+/// unsafe { stivale_boot::v2::write_capture(boot_info, |bytes| capture.extend_from_slice(bytes)) };
+///
+/// let capture = BootCapture::parse(&capture).unwrap();
+/// let reader = capture.reader().unwrap();
+/// assert_eq!(reader.bootloader_brand(), b"Limine");
+/// assert_eq!(reader.command_line(), Some(&b"quiet"[..]));
+/// assert_eq!(reader.tag::<StivaleUartTag>().unwrap().address, 0x3f8);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BootCapture<'a> {
+    bytes: &'a [u8],
+    struct_address: u64,
+    region_count: usize,
+}
+
+impl<'a> BootCapture<'a> {
+    /// Validates the capture in `bytes`. Returns [Error::Truncated] if the magic is missing or
+    /// a region extends past the end of `bytes`.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.get(..8) != Some(&CAPTURE_MAGIC[..]) {
+            return Err(Error::Truncated);
+        }
+
+        let struct_address = read_u64(bytes, 8).ok_or(Error::Truncated)?;
+        let region_count = read_u64(bytes, 16)
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or(Error::Truncated)?;
+
+        let capture = Self {
+            bytes,
+            struct_address,
+            region_count,
+        };
+
+        if capture.regions().count() != region_count {
+            return Err(Error::Truncated);
+        }
+
+        Ok(capture)
+    }
+
+    /// Returns the address of the [StivaleStruct].
+    pub fn struct_address(&self) -> u64 {
+        self.struct_address
+    }
+
+    /// Returns an iterator over the regions of the capture, the offsets being relative to the
+    /// start of the capture.
+    pub fn regions(&self) -> CaptureRegions<'a> {
+        CaptureRegions {
+            bytes: self.bytes,
+            offset: HEADER_SIZE,
+            remaining: self.region_count,
+        }
+    }
+
+    /// Returns a reader over the captured boot structure.
+    pub fn reader(&self) -> Option<StivaleStructReader<'a, CaptureTranslator<'a>>> {
+        StivaleStructReader::new(
+            self.bytes,
+            self.struct_address,
+            CaptureTranslator { capture: *self },
+        )
+    }
+}
+
+/// Iterator over the regions of a [BootCapture].
+#[derive(Debug, Clone)]
+pub struct CaptureRegions<'a> {
+    bytes: &'a [u8],
+    /// The offset of the next region header.
+    offset: usize,
+    remaining: usize,
+}
+
+impl Iterator for CaptureRegions<'_> {
+    type Item = TranslationRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let address = read_u64(self.bytes, self.offset)?;
+        let length = usize::try_from(read_u64(self.bytes, self.offset + 8)?).ok()?;
+        let offset = self.offset + 16;
+        let end = offset.checked_add(length)?;
+
+        if end > self.bytes.len() {
+            return None;
+        }
+
+        self.offset = end.checked_next_multiple_of(8)?;
+        self.remaining -= 1;
+
+        Some(TranslationRegion {
+            address,
+            offset,
+            length,
+        })
+    }
+}
+
+/// Translator mapping addresses to the regions of a [BootCapture].
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureTranslator<'a> {
+    capture: BootCapture<'a>,
+}
+
+impl AddressTranslator for CaptureTranslator<'_> {
+    fn translate(&self, address: u64) -> Option<usize> {
+        self.capture.regions().find_map(|region| {
+            let delta = usize::try_from(address.checked_sub(region.address)?).ok()?;
+
+            if delta < region.length {
+                region.offset.checked_add(delta)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Writes a capture of `stivale_struct` to `sink`: the struct, every tag and the command line.
+/// The kernel usually sends the capture to the host over a serial port or a debug channel, and
+/// the host stores it as a fixture for [BootCapture::parse].
+///
+/// # Safety
+/// The tag chain and the command line must be mapped, and the counts of the variable length
+/// tags must be valid.
+pub unsafe fn write_capture(stivale_struct: &StivaleStruct, mut sink: impl FnMut(&[u8])) {
+    let max_tags = super::limits().max_tags;
+    let mut tag_count = 0;
+    let mut region_count = 1;
+    let mut tags = stivale_struct.tags;
+
+    while tags != 0 && tag_count < max_tags {
+        let tag = super::utils::addr_to_ptr::<StivaleTagHeader>(super::to_hhdm(tags));
+
+        if (*tag).identifier == StivaleCommandLineTag::IDENTIFIER {
+            region_count += 1;
+        }
+
+        tag_count += 1;
+        region_count += 1;
+        tags = (*tag).next;
+    }
+
+    sink(&CAPTURE_MAGIC);
+    sink(&super::utils::ptr_to_addr(stivale_struct).to_ne_bytes());
+    sink(&(region_count as u64).to_ne_bytes());
+
+    let mut region = |address: u64, bytes: &[u8]| {
+        sink(&address.to_ne_bytes());
+        sink(&(bytes.len() as u64).to_ne_bytes());
+        sink(bytes);
+        sink(&[0; 8][..bytes.len().next_multiple_of(8) - bytes.len()]);
+    };
+
+    let struct_bytes = core::slice::from_raw_parts(
+        stivale_struct as *const StivaleStruct as *const u8,
+        mem::size_of::<StivaleStruct>(),
+    );
+
+    region(super::utils::ptr_to_addr(stivale_struct), struct_bytes);
+
+    // The tags are recorded at the addresses found in the chain, so that the capture can be
+    // translated without knowing the higher half offset.
+    let mut tags = stivale_struct.tags;
+
+    for _ in 0..tag_count {
+        let tag = super::utils::addr_to_ptr::<StivaleTagHeader>(super::to_hhdm(tags));
+        let bytes = core::slice::from_raw_parts(
+            tag as *const u8,
+            tag_size(tag).unwrap_or(mem::size_of::<StivaleTagHeader>()),
+        );
+
+        region(tags, bytes);
+
+        if (*tag).identifier == StivaleCommandLineTag::IDENTIFIER {
+            let tag = &*(tag as *const StivaleCommandLineTag);
+            let string = tag.as_cstr_bytes();
+
+            // Include the null terminator.
+            let string = core::slice::from_raw_parts(string.as_ptr(), string.len() + 1);
+            region(tag.command_line, string);
+        }
+
+        tags = (*tag).next;
+    }
+}
+
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+    use crate::v2::StivaleMemoryMapEntryType;
+
+    /// A capture written by [write_capture] on a little endian host, for a boot response with a
+    /// command line, a memory map, an epoch and a UART.
+    const ROUND_TRIP: &[u8] = include_bytes!("../../tests/fixtures/round_trip.capture");
+
+    #[test]
+    fn parse_recorded_capture() {
+        let capture = BootCapture::parse(ROUND_TRIP).unwrap();
+        assert_eq!(capture.regions().count(), 6);
+
+        let reader = capture.reader().unwrap();
+        assert_eq!(reader.bootloader_brand(), b"Limine");
+        assert_eq!(reader.bootloader_version(), b"2.0");
+        assert_eq!(reader.command_line(), Some(&b"console=ttyS0 quiet"[..]));
+        assert_eq!(reader.tags().count(), 4);
+
+        let memory_map = reader.memory_map().unwrap();
+        assert_eq!(memory_map.len(), 3);
+
+        let entry = memory_map.get(1).unwrap();
+        assert_eq!(entry.base, 0x100000);
+        assert_eq!(entry.length, 0x7ee0000);
+        assert_eq!(entry.entry_type, StivaleMemoryMapEntryType::Usable as u32);
+
+        assert_eq!(
+            reader.tag::<StivaleEpochTag>().unwrap().epoch,
+            1_600_000_000
+        );
+        assert_eq!(reader.tag::<StivaleUartTag>().unwrap().address, 0x3f8);
+    }
+
+    #[test]
+    fn reject_truncated_capture() {
+        let truncated = &ROUND_TRIP[..ROUND_TRIP.len() - 8];
+
+        assert_eq!(BootCapture::parse(truncated).unwrap_err(), Error::Truncated);
+        assert_eq!(
+            BootCapture::parse(&ROUND_TRIP[..16]).unwrap_err(),
+            Error::Truncated
+        );
+    }
+}
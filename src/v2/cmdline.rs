@@ -0,0 +1,295 @@
+//! Parsing of the kernel command line found in the [StivaleCommandLineTag] into `key=value`
+//! arguments, without allocating.
+
+use core::fmt;
+
+use super::tag::StivaleCommandLineTag;
+
+/// A kernel command line made out of whitespace separated arguments, each of which is either a
+/// flag such as `nosmp` or a `key=value` pair such as `loglevel=3`. Values containing
+/// whitespace can be surrounded by double quotes, which are stripped.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::CommandLine;
+///
+/// let cmdline = CommandLine::new("nosmp loglevel=3 root=\"/dev/sda 1\"");
+///
+/// assert!(cmdline.flag("nosmp"));
+/// assert_eq!(cmdline.value("loglevel"), Some("3"));
+/// assert_eq!(cmdline.value("root"), Some("/dev/sda 1"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommandLine<'a> {
+    cmdline: &'a str,
+}
+
+impl<'a> CommandLine<'a> {
+    /// Creates a new command line parser over `cmdline`.
+    pub fn new(cmdline: &'a str) -> Self {
+        Self { cmdline }
+    }
+
+    /// Returns the raw command line.
+    pub fn as_str(&self) -> &'a str {
+        self.cmdline
+    }
+
+    /// Returns an iterator over the `(key, value)` pairs of the command line. The value is
+    /// `None` for flags.
+    pub fn args(&self) -> CommandLineArgs<'a> {
+        CommandLineArgs {
+            remaining: self.cmdline,
+        }
+    }
+
+    /// Returns whether the argument `key` is present, either as a flag or with a value.
+    pub fn flag(&self, key: &str) -> bool {
+        self.args().any(|(name, _)| name == key)
+    }
+
+    /// Returns the value of the last argument named `key`, so that later arguments override
+    /// earlier ones. Returns `None` if the argument is absent or is a flag.
+    pub fn value(&self, key: &str) -> Option<&'a str> {
+        self.args()
+            .filter(|(name, _)| *name == key)
+            .last()
+            .and_then(|(_, value)| value)
+    }
+
+    /// Parses the last argument named `key` as a `T`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{ByteSize, CommandLine, CommandLineError};
+    ///
+    /// let cmdline = CommandLine::new("maxcpus=4 quiet heap=16M loglevel=high");
+    ///
+    /// assert_eq!(cmdline.get::<u64>("maxcpus"), Ok(4));
+    /// assert_eq!(cmdline.get::<bool>("quiet"), Ok(true));
+    /// assert_eq!(cmdline.get::<ByteSize>("heap"), Ok(ByteSize(16 * 1024 * 1024)));
+    /// assert_eq!(cmdline.get::<u8>("loglevel"), Err(CommandLineError::Invalid));
+    /// assert_eq!(cmdline.get::<u8>("nosmp"), Err(CommandLineError::Missing));
+    /// ```
+    pub fn get<T: FromArgument<'a>>(&self, key: &str) -> Result<T, CommandLineError> {
+        let (_, value) = self
+            .args()
+            .filter(|(name, _)| *name == key)
+            .last()
+            .ok_or(CommandLineError::Missing)?;
+
+        T::from_argument(value)
+    }
+}
+
+/// Errors returned while extracting a typed argument from the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandLineError {
+    /// The argument is not present.
+    Missing,
+    /// The argument is a flag, but a value is required.
+    MissingValue,
+    /// The value of the argument could not be parsed.
+    Invalid,
+}
+
+/// Types which can be parsed from the value of a command line argument.
+pub trait FromArgument<'a>: Sized {
+    /// Parses the value of an argument, `None` if the argument is a flag.
+    fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError>;
+}
+
+impl<'a> FromArgument<'a> for &'a str {
+    fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError> {
+        value.ok_or(CommandLineError::MissingValue)
+    }
+}
+
+/// Flags are true, as are the values `1`, `true`, `yes` and `on`. The values `0`, `false`,
+/// `no` and `off` are false.
+impl<'a> FromArgument<'a> for bool {
+    fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError> {
+        match value {
+            None | Some("1") | Some("true") | Some("yes") | Some("on") => Ok(true),
+            Some("0") | Some("false") | Some("no") | Some("off") => Ok(false),
+            Some(_) => Err(CommandLineError::Invalid),
+        }
+    }
+}
+
+/// Parses an unsigned integer, in hexadecimal if prefixed with `0x`.
+fn parse_u64(value: &str) -> Result<u64, CommandLineError> {
+    let result = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    result.map_err(|_| CommandLineError::Invalid)
+}
+
+macro_rules! impl_from_argument_unsigned {
+    ($($ty:ty),*) => {
+        $(impl<'a> FromArgument<'a> for $ty {
+            fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError> {
+                let value = parse_u64(value.ok_or(CommandLineError::MissingValue)?)?;
+                core::convert::TryFrom::try_from(value).map_err(|_| CommandLineError::Invalid)
+            }
+        })*
+    };
+}
+
+macro_rules! impl_from_argument_signed {
+    ($($ty:ty),*) => {
+        $(impl<'a> FromArgument<'a> for $ty {
+            fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError> {
+                let value = value.ok_or(CommandLineError::MissingValue)?;
+                value.parse().map_err(|_| CommandLineError::Invalid)
+            }
+        })*
+    };
+}
+
+impl_from_argument_unsigned!(u8, u16, u32, u64, usize);
+impl_from_argument_signed!(i8, i16, i32, i64, isize);
+
+/// A size in bytes, parsed from an integer optionally followed by a `K`, `M` or `G` suffix
+/// (case insensitive) for KiB, MiB and GiB. It is displayed in the largest binary unit it
+/// reaches, up to TiB, with one decimal.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::ByteSize;
+///
+/// assert_eq!(ByteSize(512).to_string(), "512 B");
+/// assert_eq!(ByteSize(1536).to_string(), "1.5 KiB");
+/// assert_eq!(ByteSize(0x7f00000).to_string(), "127.0 MiB");
+/// assert_eq!(ByteSize(0x3_ffff_ffff).to_string(), "16.0 GiB");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut unit = 0;
+
+        while unit + 1 < UNITS.len() && self.0 >= 1 << (10 * (unit + 2)) {
+            unit += 1;
+        }
+
+        // Round to the nearest tenth, which can carry over into the next unit.
+        let divisor = 1u128 << (10 * (unit + 1));
+        let mut tenths = (self.0 as u128 * 10 + divisor / 2) / divisor;
+
+        if tenths >= 10240 && unit + 1 < UNITS.len() {
+            unit += 1;
+            tenths = (tenths + 512) / 1024;
+        }
+
+        write!(f, "{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+    }
+}
+
+impl<'a> FromArgument<'a> for ByteSize {
+    fn from_argument(value: Option<&'a str>) -> Result<Self, CommandLineError> {
+        let value = value.ok_or(CommandLineError::MissingValue)?;
+
+        let (number, shift) = match value.as_bytes().last() {
+            Some(b'k') | Some(b'K') => (&value[..value.len() - 1], 10),
+            Some(b'm') | Some(b'M') => (&value[..value.len() - 1], 20),
+            Some(b'g') | Some(b'G') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+
+        let number = parse_u64(number)?;
+
+        number
+            .checked_mul(1 << shift)
+            .map(ByteSize)
+            .ok_or(CommandLineError::Invalid)
+    }
+}
+
+/// Iterator over the arguments of a [CommandLine].
+#[derive(Debug, Clone)]
+pub struct CommandLineArgs<'a> {
+    /// The part of the command line that has not been parsed yet.
+    remaining: &'a str,
+}
+
+impl<'a> Iterator for CommandLineArgs<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let arg = self.remaining.trim_start();
+
+        if arg.is_empty() {
+            self.remaining = arg;
+            return None;
+        }
+
+        // Find the end of the argument, skipping over whitespace in quoted values.
+        let mut quoted = false;
+        let end = arg
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+
+                c.is_whitespace() && !quoted
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(arg.len());
+
+        self.remaining = &arg[end..];
+        let arg = &arg[..end];
+
+        Some(match arg.find('=') {
+            Some(index) => {
+                let value = &arg[index + 1..];
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+
+                (&arg[..index], Some(value))
+            }
+            None => (arg, None),
+        })
+    }
+}
+
+impl StivaleCommandLineTag {
+    /// Returns a parser over the command line. If the command line is not valid UTF-8, only
+    /// the part before the first invalid byte is parsed.
+    pub fn parse(&self) -> CommandLine<'_> {
+        CommandLine::new(super::utils::lossy_string_from_slice(self.as_cstr_bytes()))
+    }
+
+    /// Returns an iterator over the `(key, value)` pairs of the command line. See
+    /// [CommandLine::args].
+    pub fn args(&self) -> CommandLineArgs<'_> {
+        self.parse().args()
+    }
+
+    /// Returns whether the argument `key` is present. See [CommandLine::flag].
+    pub fn flag(&self, key: &str) -> bool {
+        self.parse().flag(key)
+    }
+
+    /// Returns the value of the argument `key`. See [CommandLine::value].
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.parse().value(key)
+    }
+
+    /// Parses the argument `key` as a `T`. See [CommandLine::get].
+    pub fn get<'a, T: FromArgument<'a>>(&'a self, key: &str) -> Result<T, CommandLineError> {
+        self.parse().get(key)
+    }
+}
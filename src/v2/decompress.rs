@@ -0,0 +1,230 @@
+//! Decompression of gzip and zlib compressed modules with `miniz_oxide`, so that compressed
+//! initrds can be used without allocating.
+
+use miniz_oxide::inflate::core::inflate_flags::*;
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+use super::tag::StivaleModule;
+
+/// Size of the window required by deflate streams, which is also the size of the buffer used
+/// by [Inflater].
+pub const INFLATE_WINDOW_SIZE: usize = 32768;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// The only compression method supported by gzip, deflate.
+const GZIP_METHOD_DEFLATE: u8 = 8;
+
+const GZIP_FLAG_HCRC: u8 = 1 << 1;
+const GZIP_FLAG_EXTRA: u8 = 1 << 2;
+const GZIP_FLAG_NAME: u8 = 1 << 3;
+const GZIP_FLAG_COMMENT: u8 = 1 << 4;
+
+/// Errors returned while decompressing a module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecompressError {
+    /// The data is neither gzip nor zlib compressed.
+    UnknownFormat,
+    /// The gzip header is malformed or truncated.
+    InvalidHeader,
+    /// The output buffer is too small to hold the decompressed data.
+    OutputTooSmall,
+    /// The compressed data is corrupt or truncated.
+    Corrupt,
+}
+
+/// The compression format of some data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    /// The data is gzip compressed, as produced by `gzip`.
+    Gzip,
+    /// The data is zlib compressed.
+    Zlib,
+    /// The data is not compressed in a recognised format.
+    None,
+}
+
+impl Compression {
+    /// Detects the compression format of `data` from its header.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [cmf, flg, ..]
+                if cmf & 0xf == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) =>
+            {
+                Compression::Zlib
+            }
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Returns the raw deflate stream of the gzip member in `data`.
+fn gzip_stream(data: &[u8]) -> Result<&[u8], DecompressError> {
+    let header = data.get(..10).ok_or(DecompressError::InvalidHeader)?;
+
+    if header[..2] != GZIP_MAGIC || header[2] != GZIP_METHOD_DEFLATE {
+        return Err(DecompressError::InvalidHeader);
+    }
+
+    let flags = header[3];
+    let mut offset = 10;
+
+    if flags & GZIP_FLAG_EXTRA != 0 {
+        let length = data
+            .get(offset..offset + 2)
+            .ok_or(DecompressError::InvalidHeader)?;
+        offset += 2 + u16::from_le_bytes([length[0], length[1]]) as usize;
+    }
+
+    // Skip the null-terminated file name and comment.
+    for flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let string = data.get(offset..).ok_or(DecompressError::InvalidHeader)?;
+            let length = string
+                .iter()
+                .position(|c| *c == 0)
+                .ok_or(DecompressError::InvalidHeader)?;
+            offset += length + 1;
+        }
+    }
+
+    if flags & GZIP_FLAG_HCRC != 0 {
+        offset += 2;
+    }
+
+    data.get(offset..).ok_or(DecompressError::InvalidHeader)
+}
+
+/// Returns the deflate stream in `data` along with the flags required to decompress it.
+fn deflate_stream(data: &[u8]) -> Result<(&[u8], u32), DecompressError> {
+    match Compression::detect(data) {
+        Compression::Gzip => Ok((gzip_stream(data)?, 0)),
+        Compression::Zlib => Ok((data, TINFL_FLAG_PARSE_ZLIB_HEADER)),
+        Compression::None => Err(DecompressError::UnknownFormat),
+    }
+}
+
+/// Returns the decompressed size stored in the trailer of gzip compressed `data`, modulo 2^32.
+pub fn gzip_decompressed_size(data: &[u8]) -> Option<u32> {
+    if Compression::detect(data) != Compression::Gzip || data.len() < 18 {
+        return None;
+    }
+
+    let mut size = [0; 4];
+    size.copy_from_slice(&data[data.len() - 4..]);
+    Some(u32::from_le_bytes(size))
+}
+
+/// Decompresses the gzip or zlib compressed `data` into `out`, returning the amount of bytes
+/// written.
+pub fn decompress_into(data: &[u8], out: &mut [u8]) -> Result<usize, DecompressError> {
+    let (stream, flags) = deflate_stream(data)?;
+    let mut decompressor = DecompressorOxide::new();
+
+    let (status, _, written) = decompress(
+        &mut decompressor,
+        stream,
+        out,
+        0,
+        flags | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+    );
+
+    match status {
+        TINFLStatus::Done => Ok(written),
+        TINFLStatus::HasMoreOutput => Err(DecompressError::OutputTooSmall),
+        _ => Err(DecompressError::Corrupt),
+    }
+}
+
+/// Streaming decompressor for gzip or zlib compressed data, producing the decompressed data in
+/// chunks through a 32 KiB window provided by the caller.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{Inflater, StivaleModule, INFLATE_WINDOW_SIZE};
+///
+/// fn checksum(module: &StivaleModule) -> u8 {
+///     let mut window = [0; INFLATE_WINDOW_SIZE];
+///     let mut inflater = Inflater::new(module.as_bytes(), &mut window).unwrap();
+///     let mut sum = 0u8;
+///
+///     while let Some(chunk) = inflater.next_chunk().unwrap() {
+///         sum = chunk.iter().fold(sum, |sum, byte| sum.wrapping_add(*byte));
+///     }
+///
+///     sum
+/// }
+/// ```
+pub struct Inflater<'a, 'w> {
+    decompressor: DecompressorOxide,
+    /// The part of the deflate stream that has not been consumed yet.
+    input: &'a [u8],
+    window: &'w mut [u8; INFLATE_WINDOW_SIZE],
+    /// The position in `window` where the next chunk starts.
+    position: usize,
+    flags: u32,
+    done: bool,
+}
+
+impl<'a, 'w> Inflater<'a, 'w> {
+    /// Creates a new streaming decompressor over the gzip or zlib compressed `data`.
+    pub fn new(
+        data: &'a [u8],
+        window: &'w mut [u8; INFLATE_WINDOW_SIZE],
+    ) -> Result<Self, DecompressError> {
+        let (input, flags) = deflate_stream(data)?;
+
+        Ok(Self {
+            decompressor: DecompressorOxide::new(),
+            input,
+            window,
+            position: 0,
+            flags,
+            done: false,
+        })
+    }
+
+    /// Decompresses the next chunk, returning `None` once all of the data was decompressed.
+    /// The chunk is only valid until the next call, as the window is reused.
+    pub fn next_chunk(&mut self) -> Result<Option<&[u8]>, DecompressError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let (status, consumed, written) = decompress(
+            &mut self.decompressor,
+            self.input,
+            &mut self.window[..],
+            self.position,
+            self.flags,
+        );
+
+        self.input = &self.input[consumed..];
+
+        match status {
+            TINFLStatus::Done => self.done = true,
+            TINFLStatus::HasMoreOutput => {}
+            _ => return Err(DecompressError::Corrupt),
+        }
+
+        let start = self.position;
+        self.position = (self.position + written) % INFLATE_WINDOW_SIZE;
+
+        Ok(Some(&self.window[start..start + written]))
+    }
+}
+
+impl StivaleModule {
+    /// Returns the compression format of this module.
+    pub fn compression(&self) -> Compression {
+        Compression::detect(self.as_bytes())
+    }
+
+    /// Decompresses this gzip or zlib compressed module into `out`, returning the amount of
+    /// bytes written. See [gzip_decompressed_size] to size the buffer of gzip compressed
+    /// modules.
+    pub fn decompress_into(&self, out: &mut [u8]) -> Result<usize, DecompressError> {
+        decompress_into(self.as_bytes(), out)
+    }
+}
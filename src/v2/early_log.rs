@@ -0,0 +1,232 @@
+//! A fixed-size ring buffer recording the output of the kernel before a console is available,
+//! so that early messages can be replayed once the terminal or the UART is set up.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+
+use super::lock::RawLock;
+
+/// The contents of an [EarlyLog], protected by its lock.
+struct Ring<const N: usize> {
+    buffer: [u8; N],
+    /// The position the next byte is written to.
+    head: usize,
+    /// The amount of bytes in the buffer.
+    len: usize,
+    /// The amount of bytes that were overwritten because the buffer was full.
+    dropped: usize,
+}
+
+/// A lock-protected ring buffer of `N` bytes recording early boot messages. Once full, the
+/// oldest messages are overwritten. With the `critical-section` feature, the lock is held inside
+/// a critical section, so that messages can also be recorded from interrupt handlers.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::EarlyLog;
+///
+/// static EARLY_LOG: EarlyLog<4096> = EarlyLog::new();
+///
+/// EARLY_LOG.write_fmt(format_args!("booting {}\n", "kernel"));
+///
+/// let mut replayed = String::new();
+/// EARLY_LOG.replay(&mut replayed).unwrap();
+/// assert_eq!(replayed, "booting kernel\n");
+/// ```
+pub struct EarlyLog<const N: usize> {
+    lock: RawLock,
+    ring: UnsafeCell<Ring<N>>,
+}
+
+// The ring is only accessed with the lock held.
+unsafe impl<const N: usize> Sync for EarlyLog<N> {}
+
+impl<const N: usize> EarlyLog<N> {
+    /// Creates a new empty log.
+    pub const fn new() -> Self {
+        Self {
+            lock: RawLock::new(),
+            ring: UnsafeCell::new(Ring {
+                buffer: [0; N],
+                head: 0,
+                len: 0,
+                dropped: 0,
+            }),
+        }
+    }
+
+    fn with_ring<R>(&self, f: impl FnOnce(&mut Ring<N>) -> R) -> R {
+        let _guard = self.lock.lock();
+        f(unsafe { &mut *self.ring.get() })
+    }
+
+    /// Appends `s` to the log.
+    pub fn write_str(&self, s: &str) {
+        self.with_ring(|ring| {
+            for byte in s.bytes() {
+                if N == 0 {
+                    ring.dropped += 1;
+                    continue;
+                }
+
+                ring.buffer[ring.head] = byte;
+                ring.head = (ring.head + 1) % N;
+
+                if ring.len == N {
+                    ring.dropped += 1;
+                } else {
+                    ring.len += 1;
+                }
+            }
+        })
+    }
+
+    /// Appends the formatted `args` to the log, allowing the use of `write!` on a shared
+    /// reference.
+    pub fn write_fmt(&self, args: fmt::Arguments<'_>) {
+        let _ = fmt::Write::write_fmt(&mut &*self, args);
+    }
+
+    /// Returns the amount of bytes in the log.
+    pub fn len(&self) -> usize {
+        self.with_ring(|ring| ring.len)
+    }
+
+    /// Returns whether the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the amount of bytes that were overwritten because the log was full.
+    pub fn dropped(&self) -> usize {
+        self.with_ring(|ring| ring.dropped)
+    }
+
+    /// Empties the log.
+    pub fn clear(&self) {
+        self.with_ring(|ring| {
+            ring.head = 0;
+            ring.len = 0;
+            ring.dropped = 0;
+        })
+    }
+
+    /// Writes the contents of the log to `out`, oldest first. Characters which were partially
+    /// overwritten are skipped, and invalid UTF-8 is replaced with `U+FFFD`.
+    ///
+    /// The log is locked while replaying, so `out` must not write to it.
+    pub fn replay(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        self.with_ring(|ring| {
+            let start = (ring.head + N - ring.len) % N.max(1);
+            let (first, second) = if start + ring.len <= N {
+                (&ring.buffer[start..start + ring.len], &[][..])
+            } else {
+                (&ring.buffer[start..], &ring.buffer[..ring.head])
+            };
+
+            let (mut first, mut second) = (first, second);
+
+            // The oldest character may have been partially overwritten, leaving continuation
+            // bytes which may wrap around the end of the buffer.
+            if ring.dropped != 0 {
+                let continuation = |bytes: &[u8]| {
+                    bytes
+                        .iter()
+                        .take_while(|byte| **byte & 0xc0 == 0x80)
+                        .count()
+                };
+
+                first = &first[continuation(first)..];
+
+                if first.is_empty() {
+                    second = &second[continuation(second)..];
+                }
+            }
+
+            super::utils::write_lossy(out, [first, second])
+        })
+    }
+}
+
+impl<const N: usize> Default for EarlyLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for &EarlyLog<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        EarlyLog::write_str(self, s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use super::*;
+
+    fn replay<const N: usize>(log: &EarlyLog<N>) -> String {
+        let mut replayed = String::new();
+        log.replay(&mut replayed).unwrap();
+        replayed
+    }
+
+    #[test]
+    fn oldest_bytes_are_overwritten() {
+        let log = EarlyLog::<8>::new();
+        log.write_str("abcdefghij");
+
+        assert_eq!(replay(&log), "cdefghij");
+        assert_eq!((log.len(), log.dropped()), (8, 2));
+    }
+
+    #[test]
+    fn partially_overwritten_character_is_skipped() {
+        let log = EarlyLog::<8>::new();
+        log.write_str("a\u{e9}bcdef");
+        assert_eq!(replay(&log), "a\u{e9}bcdef");
+
+        // Overwrite the `a` and the first byte of the `é`.
+        log.write_str("gh");
+        assert_eq!(replay(&log), "bcdefgh");
+    }
+
+    #[test]
+    fn character_split_by_the_end_of_the_buffer() {
+        let log = EarlyLog::<8>::new();
+
+        // The `é` is written to the last and the first byte of the buffer.
+        log.write_str("abcdefg\u{e9}");
+        assert_eq!(replay(&log), "bcdefg\u{e9}");
+    }
+
+    #[test]
+    fn partially_overwritten_character_split_by_the_end_of_the_buffer() {
+        let log = EarlyLog::<4>::new();
+
+        // The emoji fills the buffer, with its last byte wrapped around.
+        log.write_str("a\u{1f600}");
+        assert_eq!(replay(&log), "\u{1f600}");
+
+        // Overwrite its first byte, leaving continuation bytes on both sides of the end.
+        log.write_str("b");
+        assert_eq!(replay(&log), "b");
+    }
+
+    #[test]
+    fn empty_log() {
+        let log = EarlyLog::<0>::new();
+        log.write_str("lost");
+
+        assert_eq!(replay(&log), "");
+        assert_eq!((log.len(), log.dropped()), (0, 4));
+
+        let log = EarlyLog::<8>::new();
+        log.write_str("abc");
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(replay(&log), "");
+    }
+}
@@ -0,0 +1,277 @@
+//! Parsing of the EDID information found in the [StivaleEdidInfoTag], which describes the
+//! manufacturer of the display and the display modes it supports.
+
+use super::tag::StivaleEdidInfoTag;
+
+/// The fixed pattern at the start of every EDID base block.
+pub const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+/// Size of the EDID base block.
+const EDID_BLOCK_SIZE: usize = 128;
+
+/// Offset of the established timings bitmap.
+const ESTABLISHED_TIMINGS: usize = 35;
+/// Offset of the standard timings.
+const STANDARD_TIMINGS: usize = 38;
+/// Offset of the first detailed timing descriptor, which holds the preferred timing.
+const DETAILED_TIMINGS: usize = 54;
+
+/// The display modes of the established timings bitmap, indexed by bit starting from the most
+/// significant bit of the first byte.
+const ESTABLISHED_MODES: [DisplayMode; 17] = [
+    DisplayMode::new(720, 400, 70),
+    DisplayMode::new(720, 400, 88),
+    DisplayMode::new(640, 480, 60),
+    DisplayMode::new(640, 480, 67),
+    DisplayMode::new(640, 480, 72),
+    DisplayMode::new(640, 480, 75),
+    DisplayMode::new(800, 600, 56),
+    DisplayMode::new(800, 600, 60),
+    DisplayMode::new(800, 600, 72),
+    DisplayMode::new(800, 600, 75),
+    DisplayMode::new(832, 624, 75),
+    DisplayMode::new(1024, 768, 87),
+    DisplayMode::new(1024, 768, 60),
+    DisplayMode::new(1024, 768, 70),
+    DisplayMode::new(1024, 768, 75),
+    DisplayMode::new(1280, 1024, 75),
+    DisplayMode::new(1152, 870, 75),
+];
+
+/// Errors returned while parsing EDID information.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdidError {
+    /// The information is smaller than the EDID base block.
+    Truncated,
+    /// The base block does not start with the EDID header.
+    InvalidHeader,
+    /// The checksum of the base block is incorrect.
+    InvalidChecksum,
+}
+
+/// A display mode supported by the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayMode {
+    /// Horizontal resolution in pixels.
+    pub width: u32,
+    /// Vertical resolution in pixels.
+    pub height: u32,
+    /// Refresh rate in Hz.
+    pub refresh_rate: u32,
+}
+
+impl DisplayMode {
+    const fn new(width: u32, height: u32, refresh_rate: u32) -> Self {
+        Self {
+            width,
+            height,
+            refresh_rate,
+        }
+    }
+}
+
+/// A detailed timing descriptor, describing a display mode down to its sync pulses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetailedTiming {
+    /// Pixel clock in kHz.
+    pub pixel_clock: u32,
+    /// Horizontal active pixels.
+    pub horizontal_active: u16,
+    /// Horizontal blanking pixels.
+    pub horizontal_blanking: u16,
+    /// Vertical active lines.
+    pub vertical_active: u16,
+    /// Vertical blanking lines.
+    pub vertical_blanking: u16,
+    /// Horizontal sync offset from the end of the active area, in pixels.
+    pub horizontal_sync_offset: u16,
+    /// Horizontal sync pulse width in pixels.
+    pub horizontal_sync_width: u16,
+    /// Vertical sync offset from the end of the active area, in lines.
+    pub vertical_sync_offset: u16,
+    /// Vertical sync pulse width in lines.
+    pub vertical_sync_width: u16,
+    /// Whether the mode is interlaced.
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    /// Parses the 18 byte descriptor in `bytes`, returning `None` if it is not a detailed
+    /// timing descriptor.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let pixel_clock = u16::from_le_bytes([bytes[0], bytes[1]]) as u32 * 10;
+
+        if pixel_clock == 0 {
+            return None;
+        }
+
+        let high = |low: u8, high: u8| low as u16 | (high as u16) << 8;
+
+        Some(Self {
+            pixel_clock,
+            horizontal_active: high(bytes[2], bytes[4] >> 4),
+            horizontal_blanking: high(bytes[3], bytes[4] & 0xf),
+            vertical_active: high(bytes[5], bytes[7] >> 4),
+            vertical_blanking: high(bytes[6], bytes[7] & 0xf),
+            horizontal_sync_offset: high(bytes[8], bytes[11] >> 6),
+            horizontal_sync_width: high(bytes[9], (bytes[11] >> 4) & 0x3),
+            vertical_sync_offset: (bytes[10] >> 4) as u16 | (((bytes[11] >> 2) & 0x3) as u16) << 4,
+            vertical_sync_width: (bytes[10] & 0xf) as u16 | ((bytes[11] & 0x3) as u16) << 4,
+            interlaced: bytes[17] & 0x80 != 0,
+        })
+    }
+
+    /// Returns the display mode described by this timing. The refresh rate is rounded to the
+    /// nearest Hz.
+    pub fn mode(&self) -> DisplayMode {
+        let total = (self.horizontal_active as u64 + self.horizontal_blanking as u64)
+            * (self.vertical_active as u64 + self.vertical_blanking as u64);
+        let refresh_rate = (self.pixel_clock as u64 * 1000 + total / 2)
+            .checked_div(total)
+            .unwrap_or(0);
+
+        DisplayMode::new(
+            self.horizontal_active as u32,
+            self.vertical_active as u32,
+            refresh_rate as u32,
+        )
+    }
+}
+
+/// A validated EDID base block.
+#[derive(Clone, Copy)]
+pub struct Edid<'a> {
+    block: &'a [u8],
+}
+
+impl<'a> Edid<'a> {
+    /// Parses the EDID base block at the start of `bytes`, verifying its header and checksum.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, EdidError> {
+        let block = bytes.get(..EDID_BLOCK_SIZE).ok_or(EdidError::Truncated)?;
+
+        if block[..8] != EDID_HEADER {
+            return Err(EdidError::InvalidHeader);
+        }
+
+        if block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(EdidError::InvalidChecksum);
+        }
+
+        Ok(Self { block })
+    }
+
+    /// Returns the three letter PNP ID of the manufacturer, for example `*b"DEL"`.
+    pub fn manufacturer_id(&self) -> [u8; 3] {
+        let id = u16::from_be_bytes([self.block[8], self.block[9]]);
+        let letter = |shift: u16| b'A' - 1 + ((id >> shift) & 0x1f) as u8;
+
+        [letter(10), letter(5), letter(0)]
+    }
+
+    /// Returns the manufacturer product code.
+    pub fn product_code(&self) -> u16 {
+        u16::from_le_bytes([self.block[10], self.block[11]])
+    }
+
+    /// Returns the serial number, zero if unused.
+    pub fn serial_number(&self) -> u32 {
+        u32::from_le_bytes([
+            self.block[12],
+            self.block[13],
+            self.block[14],
+            self.block[15],
+        ])
+    }
+
+    /// Returns the year of manufacture.
+    pub fn manufacture_year(&self) -> u16 {
+        1990 + self.block[17] as u16
+    }
+
+    /// Returns the EDID version and revision, for example `(1, 4)`.
+    pub fn version(&self) -> (u8, u8) {
+        (self.block[18], self.block[19])
+    }
+
+    /// Returns the amount of extension blocks following the base block.
+    pub fn extension_count(&self) -> u8 {
+        self.block[126]
+    }
+
+    /// Returns the preferred timing of the display, stored in the first detailed timing
+    /// descriptor.
+    pub fn preferred_timing(&self) -> Option<DetailedTiming> {
+        DetailedTiming::parse(&self.block[DETAILED_TIMINGS..DETAILED_TIMINGS + 18])
+    }
+
+    /// Returns an iterator over the display modes of the established and standard timings.
+    pub fn supported_modes(&self) -> SupportedModes<'a> {
+        SupportedModes {
+            block: self.block,
+            index: 0,
+        }
+    }
+}
+
+/// Iterator over the display modes advertised by the established and standard timings of an
+/// [Edid].
+#[derive(Clone)]
+pub struct SupportedModes<'a> {
+    block: &'a [u8],
+    /// Index of the next established timing bit, followed by the standard timings.
+    index: usize,
+}
+
+impl<'a> SupportedModes<'a> {
+    /// Decodes the standard timing at `index`, returning `None` if it is unused.
+    fn standard_timing(&self, index: usize) -> Option<DisplayMode> {
+        let offset = STANDARD_TIMINGS + index * 2;
+        let (first, second) = (self.block[offset], self.block[offset + 1]);
+
+        if first == 0x00 || (first == 0x01 && second == 0x01) {
+            return None;
+        }
+
+        let width = (first as u32 + 31) * 8;
+        let height = match second >> 6 {
+            // EDID versions prior to 1.3 use 1:1 instead of 16:10.
+            0 if self.block[18] == 1 && self.block[19] < 3 => width,
+            0 => width * 10 / 16,
+            1 => width * 3 / 4,
+            2 => width * 4 / 5,
+            _ => width * 9 / 16,
+        };
+
+        Some(DisplayMode::new(width, height, (second & 0x3f) as u32 + 60))
+    }
+}
+
+impl<'a> Iterator for SupportedModes<'a> {
+    type Item = DisplayMode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < ESTABLISHED_MODES.len() + 8 {
+            let index = self.index;
+            self.index += 1;
+
+            if index < ESTABLISHED_MODES.len() {
+                let byte = self.block[ESTABLISHED_TIMINGS + index / 8];
+
+                if byte & (0x80 >> (index % 8)) != 0 {
+                    return Some(ESTABLISHED_MODES[index]);
+                }
+            } else if let Some(mode) = self.standard_timing(index - ESTABLISHED_MODES.len()) {
+                return Some(mode);
+            }
+        }
+
+        None
+    }
+}
+
+impl StivaleEdidInfoTag {
+    /// Parses the EDID information, verifying its header and checksum.
+    pub fn parse(&self) -> Result<Edid<'_>, EdidError> {
+        Edid::parse(self.as_slice())
+    }
+}
@@ -0,0 +1,129 @@
+//! The setup done before calling the kernel main function of the [main] attribute: registering
+//! the boot information globally and for panics, installing a logger writing to the terminal
+//! and the UART, and checking the tag chain.
+
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use super::lock::RawLock;
+use super::shared::SharedTerminal;
+use super::uart::UartWriter;
+use super::StivaleStruct;
+
+/// Turns `fn kmain(boot_info: &'static StivaleStruct) -> !` into the entry point of a 64-bit
+/// kernel. The attribute declares:
+///
+/// - a stivale2 header in the `.stivale2hdr` section, entering the kernel on a static stack of
+///   `stack_size` bytes, `0x10000` by default. Header tags can be passed with `tags`, which
+///   otherwise default to requesting a terminal;
+/// - an entry point calling [enter_main] with the main function;
+/// - a panic handler printing the panic through [panic_print](super::panic_print).
+///
+/// The kernel must be built with `-C force-frame-pointers=yes`, as the panic handler prints a
+/// backtrace.
+///
+/// 32-bit kernels are entered in protected mode, and must use
+/// [stivale2_protected_mode_entry](crate::stivale2_protected_mode_entry) instead.
+///
+/// ## Example
+/// ```rust,ignore
+/// #![no_std]
+/// #![no_main]
+///
+/// use stivale_boot::v2::StivaleStruct;
+///
+/// #[stivale_boot::v2::main(stack_size = 0x4000)]
+/// fn kmain(boot_info: &'static StivaleStruct) -> ! {
+///     log::info!("booted by {}", boot_info.bootloader_brand());
+///
+///     loop {}
+/// }
+/// ```
+pub use stivale_boot_macros::main;
+
+/// The logger installed by [enter_main], writing every record as a line to the terminal and to
+/// the UART.
+struct BootLogger {
+    terminal: SharedTerminal,
+    lock: RawLock,
+    uart: UnsafeCell<Option<UartWriter>>,
+}
+
+// The UART is only accessed with the lock held.
+unsafe impl Sync for BootLogger {}
+
+static LOGGER: BootLogger = BootLogger {
+    terminal: SharedTerminal::new(),
+    lock: RawLock::new(),
+    uart: UnsafeCell::new(None),
+};
+
+impl Log for BootLogger {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let _ = writeln!(&self.terminal, "[{:<5}] {}", record.level(), record.args());
+
+        let _guard = self.lock.lock();
+
+        // SAFETY: The lock is held.
+        if let Some(uart) = unsafe { &mut *self.uart.get() } {
+            let _ = writeln!(uart, "[{:<5}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Prepares the kernel environment from `boot_info`, then calls `kmain`. Called by the entry
+/// point declared by the [main] attribute, in this order:
+///
+/// 1. Registers `boot_info` with [init](super::init) and
+///    [register_panic_output](super::register_panic_output), keeping any boot information
+///    registered before.
+/// 2. Installs a logger writing to the terminal and the UART, if the boot information has them
+///    and no logger was installed before, and enables the info level.
+/// 3. Panics if the tag chain is longer than [Limits::max_tags](super::Limits::max_tags), which
+///    usually means that it is corrupted or loops.
+/// 4. Logs a summary of the boot information with [log_boot_info](super::log_boot_info).
+///
+/// ## Safety
+/// `boot_info` must be the boot information the kernel was entered with by the bootloader: the
+/// UART described by its UART tag is written to, and the panic handler relies on it being
+/// registered.
+pub unsafe fn enter_main(
+    boot_info: &'static StivaleStruct,
+    kmain: fn(&'static StivaleStruct) -> !,
+) -> ! {
+    let _ = super::global::init(boot_info);
+    super::panic::register_panic_output(boot_info);
+
+    if let Some(terminal) = boot_info.terminal() {
+        LOGGER.terminal.init(terminal);
+    }
+
+    if let Some(uart) = boot_info.uart() {
+        let _guard = LOGGER.lock.lock();
+
+        // SAFETY: The lock is held, and the UART tag was provided by the bootloader, as
+        // required by the caller.
+        unsafe { *LOGGER.uart.get() = Some(UartWriter::new(uart)) };
+    }
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    // Walks the whole chain, as no tag has this identifier.
+    if let Err(error) = boot_info.try_get_tag(0) {
+        panic!("invalid boot information: {}", error);
+    }
+
+    super::boot_log::log_boot_info(boot_info);
+
+    kmain(boot_info)
+}
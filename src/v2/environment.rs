@@ -0,0 +1,64 @@
+//! A condensed view of the platform the kernel was booted on, so that platform dependent
+//! initialization can branch on a single value.
+
+use super::tag::FirmwareKind;
+use super::StivaleStruct;
+
+/// The platform features reported by the boot structure, as detected by
+/// [BootEnvironment::detect].
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{
+///     round_trip, BootEnvironment, BootResponse, FirmwareKind, StivaleFirmwareTag,
+///     StivaleFirmwareTagFlags, StivaleSmbiosTag,
+/// };
+///
+/// let firmware = StivaleFirmwareTag::new(StivaleFirmwareTagFlags::BIOS);
+/// let smbios = StivaleSmbiosTag::new(0xf0000, 0);
+/// let response = BootResponse {
+///     firmware: Some(&firmware),
+///     smbios: Some(&smbios),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 4096]));
+/// let environment = BootEnvironment::detect(round_trip(buffer, &response).unwrap());
+///
+/// assert_eq!(environment.firmware, Some(FirmwareKind::Bios));
+/// assert!(environment.smbios);
+/// assert!(!environment.pxe && !environment.efi_system_table && !environment.device_tree);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BootEnvironment {
+    /// The firmware the kernel was booted from, or `None` if the firmware tag is missing.
+    pub firmware: Option<FirmwareKind>,
+    /// Whether the kernel was loaded over the network using PXE.
+    pub pxe: bool,
+    /// Whether the EFI system table is available for calling runtime services.
+    pub efi_system_table: bool,
+    /// Whether the ACPI RSDP is available.
+    pub acpi: bool,
+    /// Whether an SMBIOS entry point is available.
+    pub smbios: bool,
+    /// Whether a device tree blob is available.
+    pub device_tree: bool,
+}
+
+impl BootEnvironment {
+    /// Detects the platform features reported by `boot_info`.
+    pub fn detect(boot_info: &StivaleStruct) -> Self {
+        Self {
+            firmware: boot_info.firmware().map(|tag| tag.kind()),
+            pxe: boot_info.pxe_info().is_some(),
+            efi_system_table: boot_info.efi_boot_state().runtime_system_table().is_some(),
+            acpi: boot_info.rsdp().is_some_and(|tag| tag.rsdp != 0),
+            smbios: boot_info
+                .smbios()
+                .is_some_and(|tag| tag.smbios_entry_32 != 0 || tag.smbios_entry_64 != 0),
+            device_tree: boot_info
+                .dev_tree()
+                .is_some_and(|tag| tag.address != 0 && tag.size != 0),
+        }
+    }
+}
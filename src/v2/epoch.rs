@@ -0,0 +1,189 @@
+//! Conversion of the UNIX epoch found in the [StivaleEpochTag] into a calendar date and time.
+
+#[cfg(any(feature = "time", feature = "chrono"))]
+use core::convert::TryFrom;
+use core::fmt;
+use core::time::Duration;
+
+use super::tag::StivaleEpochTag;
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// A date and time in UTC, broken down into its calendar fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    /// The year, for example 2021.
+    pub year: u32,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 59.
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Converts the amount of seconds since the UNIX epoch into a date and time.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::DateTime;
+    ///
+    /// let date_time = DateTime::from_unix_seconds(1627819200);
+    /// assert_eq!((date_time.year, date_time.month, date_time.day), (2021, 8, 1));
+    /// assert_eq!(date_time.hour, 12);
+    /// ```
+    pub fn from_unix_seconds(seconds: u64) -> Self {
+        let days = seconds / SECONDS_PER_DAY;
+        let time = seconds % SECONDS_PER_DAY;
+
+        // Shift the epoch to 0000-03-01, so that leap days are at the end of the year.
+        let days = days + 719468;
+        let era = days / 146097;
+        let day_of_era = days % 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+        Self {
+            year: year as u32,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// Formats the date and time in the ISO 8601 format, for example `2021-08-01T12:00:00Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Reads the time stamp counter of the current processor.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn read_tsc() -> u64 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::_rdtsc;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::_rdtsc;
+
+    unsafe { _rdtsc() }
+}
+
+/// A wall clock derived from the epoch at boot and a monotonic tick counter, such as the time
+/// stamp counter, for kernels which do not have an RTC driver yet.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{BootClock, StivaleStruct};
+///
+/// fn log_time(boot_info: &StivaleStruct, tsc_frequency: u64) {
+///     if let Some(epoch) = boot_info.epoch() {
+///         let clock = BootClock::with_tsc(epoch, tsc_frequency);
+///
+///         // ...
+///
+///         let _ = (clock.now_unix(), clock.uptime());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BootClock {
+    epoch: u64,
+    /// The tick count when the clock was created.
+    start: u64,
+    ticks: fn() -> u64,
+    ticks_per_second: u64,
+}
+
+impl BootClock {
+    /// Creates a new clock starting at the epoch in `tag`, reading the current tick count from
+    /// `ticks` which increases `ticks_per_second` times per second.
+    ///
+    /// The epoch is only precise to the second and is taken as the time this clock is
+    /// created, so this should happen early during boot.
+    ///
+    /// ## Panics
+    /// Panics if `ticks_per_second` is zero.
+    pub fn new(tag: &StivaleEpochTag, ticks: fn() -> u64, ticks_per_second: u64) -> Self {
+        assert!(ticks_per_second != 0, "the tick frequency must not be zero");
+
+        Self {
+            epoch: tag.epoch,
+            start: ticks(),
+            ticks,
+            ticks_per_second,
+        }
+    }
+
+    /// Creates a new clock starting at the epoch in `tag`, counting time with the time stamp
+    /// counter running at `tsc_frequency` Hz. The time stamp counter must be invariant.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn with_tsc(tag: &StivaleEpochTag, tsc_frequency: u64) -> Self {
+        Self::new(tag, read_tsc, tsc_frequency)
+    }
+
+    /// Returns the time elapsed since this clock was created.
+    pub fn uptime(&self) -> Duration {
+        let ticks = (self.ticks)().wrapping_sub(self.start);
+        let seconds = ticks / self.ticks_per_second;
+        let nanos =
+            (ticks % self.ticks_per_second) as u128 * 1_000_000_000 / self.ticks_per_second as u128;
+
+        Duration::new(seconds, nanos as u32)
+    }
+
+    /// Returns the current amount of seconds since the UNIX epoch.
+    pub fn now_unix(&self) -> u64 {
+        self.epoch + self.uptime().as_secs()
+    }
+
+    /// Returns the current date and time.
+    pub fn now(&self) -> DateTime {
+        DateTime::from_unix_seconds(self.now_unix())
+    }
+}
+
+impl StivaleEpochTag {
+    /// Returns the amount of seconds since the UNIX epoch at boot.
+    pub fn unix_seconds(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the date and time at boot.
+    pub fn date_time(&self) -> DateTime {
+        DateTime::from_unix_seconds(self.epoch)
+    }
+
+    /// Returns the date and time at boot as a `time` crate date time, or `None` if it is out of
+    /// its range.
+    #[cfg(feature = "time")]
+    pub fn to_offset_date_time(&self) -> Option<time::OffsetDateTime> {
+        let seconds = i64::try_from(self.epoch).ok()?;
+        time::OffsetDateTime::from_unix_timestamp(seconds).ok()
+    }
+
+    /// Returns the date and time at boot as a `chrono` crate date time, or `None` if it is out
+    /// of its range.
+    #[cfg(feature = "chrono")]
+    pub fn to_chrono(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let seconds = i64::try_from(self.epoch).ok()?;
+        chrono::DateTime::from_timestamp(seconds, 0)
+    }
+}
@@ -0,0 +1,133 @@
+//! A single error type covering the failures of the crate, so that kernels can propagate them
+//! with `?` through their own error handling.
+
+use core::fmt;
+use core::str::Utf8Error;
+
+use super::builder::BuildError;
+use super::cmdline::CommandLineError;
+#[cfg(feature = "miniz")]
+use super::decompress::DecompressError;
+use super::edid::EdidError;
+use super::tag::{tag_name, StivaleTag};
+
+/// Errors returned while reading or building boot information.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The bootloader did not provide the tag with this identifier.
+    MissingTag(u64),
+    /// An address or a range is not suitably aligned.
+    Misaligned,
+    /// A structure is smaller than its format requires.
+    Truncated,
+    /// The boot structure exceeds one of the configured [Limits](super::Limits).
+    LimitExceeded,
+    /// A global was already initialized, such as the boot information registered with
+    /// [init](super::init).
+    AlreadyInitialized,
+    /// No usable memory is left to allocate from.
+    OutOfMemory,
+    /// A raw value does not correspond to any variant of an enumeration, or has unknown flags
+    /// set.
+    InvalidValue(u64),
+    /// A string is not valid UTF-8.
+    InvalidUtf8(Utf8Error),
+    /// Building a boot structure failed.
+    Build(BuildError),
+    /// Extracting an argument from the command line failed.
+    CommandLine(CommandLineError),
+    /// Parsing EDID information failed.
+    Edid(EdidError),
+    /// Decompressing a module failed.
+    #[cfg(feature = "miniz")]
+    Decompress(DecompressError),
+}
+
+impl Error {
+    /// Returns the error reported when the tag of type `T` is missing.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use stivale_boot::v2::{Error, StivaleMemoryMapTag, StivaleStruct};
+    ///
+    /// fn usable_memory(boot_info: &StivaleStruct) -> Result<u64, Error> {
+    ///     let memory_map = boot_info
+    ///         .memory_map()
+    ///         .ok_or(Error::missing::<StivaleMemoryMapTag>())?;
+    ///
+    ///     Ok(memory_map.iter().map(|entry| entry.length).sum())
+    /// }
+    /// ```
+    pub fn missing<T: StivaleTag + ?Sized>() -> Self {
+        Error::MissingTag(T::IDENTIFIER)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingTag(identifier) => match tag_name(*identifier) {
+                Some(name) => write!(f, "missing {}", name),
+                None => write!(f, "missing tag {:#018x}", identifier),
+            },
+            Error::Misaligned => f.write_str("misaligned address or range"),
+            Error::Truncated => f.write_str("truncated structure"),
+            Error::LimitExceeded => f.write_str("boot structure exceeds the configured limits"),
+            Error::AlreadyInitialized => f.write_str("already initialized"),
+            Error::OutOfMemory => f.write_str("no usable memory left"),
+            Error::InvalidValue(value) => write!(f, "invalid value {:#x}", value),
+            Error::InvalidUtf8(error) => write!(f, "invalid UTF-8: {}", error),
+            Error::Build(error) => write!(f, "failed to build boot information: {:?}", error),
+            Error::CommandLine(error) => write!(f, "invalid command line: {:?}", error),
+            Error::Edid(error) => write!(f, "invalid EDID information: {:?}", error),
+            #[cfg(feature = "miniz")]
+            Error::Decompress(error) => write!(f, "failed to decompress module: {:?}", error),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::InvalidUtf8(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(error: Utf8Error) -> Self {
+        Error::InvalidUtf8(error)
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(error: BuildError) -> Self {
+        match error {
+            BuildError::Misaligned | BuildError::UnalignedRange => Error::Misaligned,
+            error => Error::Build(error),
+        }
+    }
+}
+
+impl From<CommandLineError> for Error {
+    fn from(error: CommandLineError) -> Self {
+        Error::CommandLine(error)
+    }
+}
+
+impl From<EdidError> for Error {
+    fn from(error: EdidError) -> Self {
+        match error {
+            EdidError::Truncated => Error::Truncated,
+            error => Error::Edid(error),
+        }
+    }
+}
+
+#[cfg(feature = "miniz")]
+impl From<DecompressError> for Error {
+    fn from(error: DecompressError) -> Self {
+        Error::Decompress(error)
+    }
+}
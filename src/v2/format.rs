@@ -0,0 +1,112 @@
+//! `defmt` formatting of the tags, so that kernels logging with `defmt` over a serial port get
+//! compact structured boot logs. Addresses are formatted as hexadecimal numbers.
+
+use core::sync::atomic::Ordering;
+
+use defmt::{Format, Formatter};
+
+use super::tag::*;
+use super::utils::LossyStr;
+
+impl Format for LossyStr<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        match self.to_str() {
+            Some(string) => defmt::write!(f, "{=str}", string),
+            None => defmt::write!(f, "{}", defmt::Display2Format(self)),
+        }
+    }
+}
+
+impl Format for StivaleMemoryMapEntryType {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            Self::Usable => defmt::write!(f, "Usable"),
+            Self::Reserved => defmt::write!(f, "Reserved"),
+            Self::AcpiReclaimable => defmt::write!(f, "AcpiReclaimable"),
+            Self::AcpiNvs => defmt::write!(f, "AcpiNvs"),
+            Self::BadMemory => defmt::write!(f, "BadMemory"),
+            Self::BootloaderReclaimable => defmt::write!(f, "BootloaderReclaimable"),
+            Self::Kernel => defmt::write!(f, "Kernel"),
+            Self::Framebuffer => defmt::write!(f, "Framebuffer"),
+        }
+    }
+}
+
+/// Implements [Format] for each type with the provided format string, the arguments being
+/// evaluated with the value bound to the provided name.
+macro_rules! impl_format {
+    ($($name:ident |$this:ident| $format:literal $(, $arg:expr)* ;)*) => {
+        $(impl Format for $name {
+            fn format(&self, f: Formatter<'_>) {
+                let $this = self;
+                defmt::write!(f, $format $(, $arg)*)
+            }
+        })*
+    };
+}
+
+impl_format! {
+    StivaleCommandLineTag |tag| "StivaleCommandLineTag {{ command_line: {=u64:#x}, string: {} }}",
+        tag.command_line, tag.as_str_lossy();
+    StivaleMemoryMapEntry |entry| "{=u64:#x}-{=u64:#x} {}",
+        entry.base, entry.end_address(), entry.entry_type();
+    StivaleMemoryMapTag |tag| "StivaleMemoryMapTag {{ entries: {=[?]} }}",
+        tag.as_slice();
+    StivaleFramebufferTag |tag| "StivaleFramebufferTag {{ framebuffer_addr: {=u64:#x}, geometry: {=u16}x{=u16}x{=u16}, pitch: {=u16}, memory_model: {=u8} }}",
+        tag.framebuffer_addr, tag.framebuffer_width, tag.framebuffer_height,
+        tag.framebuffer_bpp, tag.framebuffer_pitch, tag.memory_model;
+    StivaleEdidInfoTag |tag| "StivaleEdidInfoTag {{ size: {=usize} }}",
+        tag.as_slice().len();
+    StivaleTerminalTag |tag| "StivaleTerminalTag {{ flags: {=u32:#x}, cols: {=u16}, rows: {=u16}, term_write_addr: {=u64:#x} }}",
+        tag.flags, tag.cols, tag.rows, tag.term_write_addr;
+    StivaleModule |module| "{=u64:#x}-{=u64:#x} {}",
+        module.start, module.end, module.as_str_lossy();
+    StivaleModuleTag |tag| "StivaleModuleTag {{ modules: {=[?]} }}",
+        tag.as_slice();
+    StivaleRsdpTag |tag| "StivaleRsdpTag {{ rsdp: {=u64:#x} }}",
+        tag.rsdp;
+    StivaleSmbiosTag |tag| "StivaleSmbiosTag {{ flags: {=u64:#x}, smbios_entry_32: {=u64:#x}, smbios_entry_64: {=u64:#x} }}",
+        tag.flags, tag.smbios_entry_32, tag.smbios_entry_64;
+    StivaleEpochTag |tag| "StivaleEpochTag {{ epoch: {=u64} }}",
+        tag.epoch;
+    StivaleFirmwareTag |tag| "StivaleFirmwareTag {{ flags: {=u64:#x} }}",
+        tag.flags.bits();
+    StivaleEfiSystemTableTag |tag| "StivaleEfiSystemTableTag {{ system_table_addr: {=u64:#x} }}",
+        tag.system_table_addr;
+    StivaleKernelFileTag |tag| "StivaleKernelFileTag {{ kernel_file_addr: {=u64:#x} }}",
+        tag.kernel_file_addr;
+    StivaleKernelFileV2Tag |tag| "StivaleKernelFileV2Tag {{ kernel_start: {=u64:#x}, kernel_size: {=u64} }}",
+        tag.kernel_start, tag.kernel_size;
+    StivaleKernelSlideTag |tag| "StivaleKernelSlideTag {{ kernel_slide: {=u64:#x} }}",
+        tag.kernel_slide;
+    StivaleKernelBaseAddressTag |tag| "StivaleKernelBaseAddressTag {{ physical_base_address: {=u64:#x}, virtual_base_address: {=u64:#x} }}",
+        tag.physical_base_address, tag.virtual_base_address;
+    StivalePxeInfoTag |tag| "StivalePxeInfoTag {{ server_ip: {=[u8; 4]} }}",
+        tag.server_addr().octets();
+    StivaleUartTag |tag| "StivaleUartTag {{ address: {=u64:#x} }}",
+        tag.address;
+    StivaleDeviceTreeTag |tag| "StivaleDeviceTreeTag {{ address: {=u64:#x}, size: {=u64} }}",
+        tag.address, tag.size;
+    StivaleVMapTag |tag| "StivaleVMapTag {{ address: {=u64:#x} }}",
+        tag.address;
+    StivalePmr |pmr| "{=u64:#x}-{=u64:#x} {=u64:#x}",
+        pmr.base, pmr.base.wrapping_add(pmr.size), pmr.permissions.bits();
+    StivalePmrsTag |tag| "StivalePmrsTag {{ pmrs: {=[?]} }}",
+        tag.as_slice();
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+impl_format! {
+    StivaleSmpInfo |info| "CPU {=u32}: LAPIC ID {=u32}, goto_address: {=u64:#x}",
+        info.acpi_processor_uid, info.lapic_id, info.goto_address.load(Ordering::Relaxed);
+    StivaleSmpTag |tag| "StivaleSmpTag {{ flags: {=u64:#x}, bsp_lapic_id: {=u32}, cpus: {=[?]} }}",
+        tag.flags.bits(), tag.bsp_lapic_id, tag.as_slice();
+}
+
+#[cfg(target_arch = "aarch64")]
+impl_format! {
+    StivaleSmpInfo |info| "CPU {=u32}: MPIDR {=u64:#x}, GIC interface {=u32}, goto_address: {=u64:#x}",
+        info.processor_id, info.mpidr, info.gic_iface_no, info.goto_address.load(Ordering::Relaxed);
+    StivaleSmpTag |tag| "StivaleSmpTag {{ flags: {=u64:#x}, bsp_mpidr: {=u64:#x}, cpus: {=[?]} }}",
+        tag.flags.bits(), tag.bsp_mpidr, tag.as_slice();
+}
@@ -0,0 +1,285 @@
+//! Drawing primitives over the framebuffer described by the framebuffer tag. Rows are filled and
+//! copied with word sized stores, as byte-wise accesses to a framebuffer mapped as MMIO are
+//! very slow.
+
+use core::convert::TryFrom;
+use core::mem;
+
+use super::tag::StivaleFramebufferTag;
+
+const WORD: usize = mem::size_of::<u64>();
+
+/// Stores `word` into the `count` words at `dst`.
+#[cfg(target_arch = "x86_64")]
+unsafe fn store_words(dst: *mut u64, count: usize, word: u64) {
+    core::arch::asm!(
+        "rep stosq",
+        inout("rcx") count => _,
+        inout("rdi") dst => _,
+        in("rax") word,
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Stores `word` into the `count` words at `dst`.
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn store_words(dst: *mut u64, count: usize, word: u64) {
+    for index in 0..count {
+        dst.add(index).write_volatile(word);
+    }
+}
+
+/// Copies the `count` words at `src` to `dst`, lowest address first.
+#[cfg(target_arch = "x86_64")]
+unsafe fn copy_words(dst: *mut u64, src: *const u64, count: usize) {
+    core::arch::asm!(
+        "rep movsq",
+        inout("rcx") count => _,
+        inout("rdi") dst => _,
+        inout("rsi") src => _,
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Copies the `count` words at `src` to `dst`, lowest address first.
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn copy_words(dst: *mut u64, src: *const u64, count: usize) {
+    for index in 0..count {
+        dst.add(index)
+            .write_volatile(src.add(index).read_volatile());
+    }
+}
+
+/// Writes a linear framebuffer with 16, 24 or 32 bits per pixel.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{Framebuffer, StivaleFramebufferTag};
+///
+/// let mut pixels = vec![0u32; 64 * 48];
+/// let mut tag = StivaleFramebufferTag::new(pixels.as_mut_ptr() as u64, 64, 48, 64 * 4, 32);
+/// (tag.red_mask_size, tag.red_mask_shift) = (8, 16);
+/// (tag.green_mask_size, tag.green_mask_shift) = (8, 8);
+/// (tag.blue_mask_size, tag.blue_mask_shift) = (8, 0);
+///
+/// let mut framebuffer = unsafe { Framebuffer::new(&tag) }.unwrap();
+/// let blue = framebuffer.color(0, 0, 0xff);
+///
+/// framebuffer.fill_rect(8, 8, 16, 4, blue);
+/// framebuffer.scroll(8, 0);
+///
+/// assert_eq!(pixels[8], 0x0000ff);
+/// assert_eq!(pixels[8 * 64 + 8], 0);
+/// ```
+pub struct Framebuffer {
+    base: *mut u8,
+    width: usize,
+    height: usize,
+    /// The amount of bytes between the start of two rows.
+    pitch: usize,
+    /// The amount of bytes per pixel.
+    bytes_per_pixel: usize,
+    masks: [(u8, u8); 3],
+}
+
+impl Framebuffer {
+    /// Creates a writer over the framebuffer described by `tag`. Returns `None` if the
+    /// framebuffer is not an RGB framebuffer with 16, 24 or 32 bits per pixel, or if it lies
+    /// outside of the address space.
+    ///
+    /// ## Safety
+    /// The framebuffer must be mapped, and must not be accessed through anything else while the
+    /// writer is alive.
+    pub unsafe fn new(tag: &StivaleFramebufferTag) -> Option<Self> {
+        if tag.memory_model != 1 || !matches!(tag.framebuffer_bpp, 16 | 24 | 32) {
+            return None;
+        }
+
+        let bytes_per_pixel = tag.framebuffer_bpp as usize / 8;
+
+        if (tag.framebuffer_pitch as usize) < tag.framebuffer_width as usize * bytes_per_pixel {
+            return None;
+        }
+
+        // The framebuffer can lie above 4GiB, out of reach of 32-bit kernels.
+        let address = super::to_hhdm(tag.framebuffer_addr);
+        let length = tag.framebuffer_pitch as u64 * tag.framebuffer_height as u64;
+        usize::try_from(address.checked_add(length)?).ok()?;
+
+        Some(Self {
+            base: super::utils::addr_to_mut_ptr(address),
+            width: tag.framebuffer_width as usize,
+            height: tag.framebuffer_height as usize,
+            pitch: tag.framebuffer_pitch as usize,
+            bytes_per_pixel,
+            masks: [
+                (tag.red_mask_size, tag.red_mask_shift),
+                (tag.green_mask_size, tag.green_mask_shift),
+                (tag.blue_mask_size, tag.blue_mask_shift),
+            ],
+        })
+    }
+
+    /// Returns the width of the framebuffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the framebuffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the native pixel value of the provided colour, according to the colour masks.
+    pub fn color(&self, red: u8, green: u8, blue: u8) -> u32 {
+        [red, green, blue]
+            .iter()
+            .zip(self.masks.iter())
+            .map(|(value, (size, shift))| {
+                let value = (*value as u32) >> 8u32.saturating_sub(*size as u32);
+                value.checked_shl(*shift as u32).unwrap_or(0)
+            })
+            .fold(0, |pixel, component| pixel | component)
+    }
+
+    /// Returns the byte pattern of `color` repeated over a word, starting at a pixel boundary.
+    /// Returns `None` for 24 bit pixels, which do not evenly divide a word.
+    fn pattern(&self, color: u32) -> Option<[u8; WORD]> {
+        let pixel = color.to_le_bytes();
+        let mut pattern = [0; WORD];
+
+        if !WORD.is_multiple_of(self.bytes_per_pixel) {
+            return None;
+        }
+
+        for (index, byte) in pattern.iter_mut().enumerate() {
+            *byte = pixel[index % self.bytes_per_pixel];
+        }
+
+        Some(pattern)
+    }
+
+    /// Fills the `count` pixels starting at pixel `x` of row `y` with `color`.
+    fn fill_span(&mut self, x: usize, y: usize, count: usize, color: u32) {
+        let start = unsafe { self.base.add(y * self.pitch + x * self.bytes_per_pixel) };
+        let length = count * self.bytes_per_pixel;
+
+        let pattern = match self.pattern(color) {
+            Some(pattern) => pattern,
+            None => {
+                let pixel = color.to_le_bytes();
+
+                for offset in 0..length {
+                    unsafe {
+                        start
+                            .add(offset)
+                            .write_volatile(pixel[offset % self.bytes_per_pixel])
+                    };
+                }
+
+                return;
+            }
+        };
+
+        unsafe {
+            // Store the bytes up to the first aligned word one at a time.
+            let head = start.align_offset(WORD).min(length);
+
+            for offset in 0..head {
+                start.add(offset).write_volatile(pattern[offset % WORD]);
+            }
+
+            // The pattern starts at a pixel boundary, so it has to be rotated to line up with
+            // the first aligned word.
+            let mut rotated = [0; WORD];
+
+            for (index, byte) in rotated.iter_mut().enumerate() {
+                *byte = pattern[(head + index) % WORD];
+            }
+
+            let words = (length - head) / WORD;
+            store_words(
+                start.add(head) as *mut u64,
+                words,
+                u64::from_ne_bytes(rotated),
+            );
+
+            for offset in head + words * WORD..length {
+                start.add(offset).write_volatile(pattern[offset % WORD]);
+            }
+        }
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `color`. Pixels outside of the framebuffer are ignored.
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.fill_span(x, y, 1, color);
+        }
+    }
+
+    /// Fills the rectangle of `width` by `height` pixels at (`x`, `y`) with `color`, clipped to
+    /// the framebuffer.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+
+        if width == 0 {
+            return;
+        }
+
+        for row in y..y + height {
+            self.fill_span(x, row, width, color);
+        }
+    }
+
+    /// Fills the whole framebuffer with `color`.
+    pub fn clear(&mut self, color: u32) {
+        self.fill_rect(0, 0, self.width, self.height, color);
+    }
+
+    /// Copies the row `src` over the row `dst`.
+    fn copy_row(&mut self, dst: usize, src: usize) {
+        let length = self.width * self.bytes_per_pixel;
+
+        unsafe {
+            let dst = self.base.add(dst * self.pitch);
+            let src = self.base.add(src * self.pitch) as *const u8;
+
+            // Word copies require both rows to share the same alignment.
+            let head = if dst.align_offset(WORD) == src.align_offset(WORD) {
+                dst.align_offset(WORD).min(length)
+            } else {
+                length
+            };
+
+            for offset in 0..head {
+                dst.add(offset)
+                    .write_volatile(src.add(offset).read_volatile());
+            }
+
+            let words = (length - head) / WORD;
+            copy_words(
+                dst.add(head) as *mut u64,
+                src.add(head) as *const u64,
+                words,
+            );
+
+            for offset in head + words * WORD..length {
+                dst.add(offset)
+                    .write_volatile(src.add(offset).read_volatile());
+            }
+        }
+    }
+
+    /// Moves the contents of the framebuffer up by `rows` pixel rows, filling the rows revealed
+    /// at the bottom with `color`.
+    pub fn scroll(&mut self, rows: usize, color: u32) {
+        let rows = rows.min(self.height);
+
+        for row in 0..self.height - rows {
+            self.copy_row(row, row + rows);
+        }
+
+        self.fill_rect(0, self.height - rows, self.width, rows, color);
+    }
+}
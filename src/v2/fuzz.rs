@@ -0,0 +1,142 @@
+//! `arbitrary` support for the builder side types, generating randomized boot information which
+//! still follows the stivale2 specification, for property testing kernels.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::builder::StivaleSmpResponse;
+use super::header::StivaleSmpHeaderTagFlags;
+use super::tag::*;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+impl<'a> Arbitrary<'a> for StivaleMemoryMapEntryType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            StivaleMemoryMapEntryType::Usable,
+            StivaleMemoryMapEntryType::Reserved,
+            StivaleMemoryMapEntryType::AcpiReclaimable,
+            StivaleMemoryMapEntryType::AcpiNvs,
+            StivaleMemoryMapEntryType::BadMemory,
+            StivaleMemoryMapEntryType::BootloaderReclaimable,
+            StivaleMemoryMapEntryType::Kernel,
+            StivaleMemoryMapEntryType::Framebuffer,
+        ])?)
+    }
+}
+
+/// A memory map of 1 to 64 entries, sorted by base address, page aligned and without overlaps.
+///
+/// ## Example
+/// ```rust
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use stivale_boot::v2::ArbitraryMemoryMap;
+///
+/// let mut u = Unstructured::new(&[0x5a; 256]);
+/// let memory_map = ArbitraryMemoryMap::arbitrary(&mut u).unwrap();
+///
+/// for pair in memory_map.0.windows(2) {
+///     assert!(pair[0].base + pair[0].length <= pair[1].base);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArbitraryMemoryMap(pub Vec<StivaleMemoryMapEntry>);
+
+impl<'a> Arbitrary<'a> for ArbitraryMemoryMap {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let count = u.int_in_range(1..=64)?;
+        let mut base = u.int_in_range(0..=0x100u64)? * PAGE_SIZE;
+        let mut entries = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let length = u.int_in_range(1..=0x4000u64)? * PAGE_SIZE;
+
+            entries.push(StivaleMemoryMapEntry::new(
+                base,
+                length,
+                StivaleMemoryMapEntryType::arbitrary(u)?,
+            ));
+
+            base += length + u.int_in_range(0..=0x10u64)? * PAGE_SIZE;
+        }
+
+        Ok(Self(entries))
+    }
+}
+
+/// A list of up to 16 modules with page aligned, non overlapping ranges located above 16MiB and
+/// strings which fit in the module string field.
+pub struct ArbitraryModules(pub Vec<StivaleModule>);
+
+impl<'a> Arbitrary<'a> for ArbitraryModules {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let count = u.int_in_range(0..=16)?;
+        let mut start = 0x1000000;
+        let mut modules = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let mut string = String::arbitrary(u)?.replace('\0', "");
+
+            while string.len() > 127 {
+                string.pop();
+            }
+
+            let end = start + u.int_in_range(0..=0x1000u64)? * PAGE_SIZE;
+            modules.push(StivaleModule::try_new(&string, start, end).expect("valid module"));
+
+            start = end + PAGE_SIZE;
+        }
+
+        Ok(Self(modules))
+    }
+}
+
+/// An SMP topology of 1 to 256 processors with unique physical IDs (LAPIC IDs on x86, MPIDRs
+/// on aarch64), one of which is the BSP.
+pub struct ArbitrarySmp {
+    pub flags: StivaleSmpHeaderTagFlags,
+    pub bsp_hardware_id: u64,
+    pub cpus: Vec<StivaleSmpInfo>,
+}
+
+impl ArbitrarySmp {
+    /// Returns the SMP information to pass in a [BootResponse](super::BootResponse).
+    pub fn response(&self) -> StivaleSmpResponse<'_> {
+        StivaleSmpResponse {
+            flags: self.flags,
+            #[cfg(not(target_arch = "aarch64"))]
+            bsp_lapic_id: self.bsp_hardware_id as u32,
+            #[cfg(target_arch = "aarch64")]
+            bsp_mpidr: self.bsp_hardware_id,
+            cpus: &self.cpus,
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for ArbitrarySmp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let count = u.int_in_range(1..=256u32)?;
+        let mut hardware_id = 0;
+        let mut cpus = Vec::with_capacity(count as usize);
+
+        for uid in 0..count {
+            // Skipping IDs keeps them unique while allowing sparse topologies.
+            hardware_id += u.int_in_range(0..=3)?;
+            #[cfg(not(target_arch = "aarch64"))]
+            cpus.push(StivaleSmpInfo::new(uid, hardware_id));
+            #[cfg(target_arch = "aarch64")]
+            cpus.push(StivaleSmpInfo::new(uid, uid, hardware_id as u64));
+            hardware_id += 1;
+        }
+
+        let bsp_hardware_id = u.choose(&cpus)?.hardware_id();
+
+        Ok(Self {
+            flags: StivaleSmpHeaderTagFlags::from_bits_truncate(u64::arbitrary(u)?),
+            bsp_hardware_id,
+            cpus,
+        })
+    }
+}
@@ -0,0 +1,58 @@
+//! A global reference to the boot information, set once at entry, so that subsystems do not have
+//! to be handed the [StivaleStruct] by their callers.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use super::error::Error;
+use super::StivaleStruct;
+
+/// The boot information registered with [init], null until then.
+static BOOT_INFO: AtomicPtr<StivaleStruct> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Registers `boot_info` as the boot information of the kernel, returned by [boot_info] from then
+/// on. Returns [Error::AlreadyInitialized] if boot information was already registered, in which
+/// case the registered one is kept.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{self, StivaleStruct};
+///
+/// extern "C" fn _start(boot_info: &'static StivaleStruct) -> ! {
+///     v2::init(boot_info).unwrap();
+///     kmain()
+/// }
+///
+/// fn kmain() -> ! {
+///     let memory_map = v2::boot_info().memory_map();
+///     loop {}
+/// }
+/// ```
+pub fn init(boot_info: &'static StivaleStruct) -> Result<(), Error> {
+    let ptr = boot_info as *const StivaleStruct as *mut StivaleStruct;
+
+    BOOT_INFO
+        .compare_exchange(
+            core::ptr::null_mut(),
+            ptr,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .map(|_| ())
+        .map_err(|_| Error::AlreadyInitialized)
+}
+
+/// Returns the boot information registered with [init], or `None` if none was registered yet.
+pub fn try_boot_info() -> Option<&'static StivaleStruct> {
+    let ptr = BOOT_INFO.load(Ordering::Acquire);
+
+    // SAFETY: Only `'static` references are ever stored.
+    unsafe { ptr.as_ref() }
+}
+
+/// Returns the boot information registered with [init].
+///
+/// ## Panics
+/// Panics if no boot information was registered yet.
+pub fn boot_info() -> &'static StivaleStruct {
+    try_boot_info().expect("the boot information was not registered with init")
+}
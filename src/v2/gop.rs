@@ -0,0 +1,75 @@
+//! Conversion of the UEFI Graphics Output Protocol mode of the `uefi` crate into a framebuffer
+//! tag, for bootloaders setting up the framebuffer through GOP.
+
+use core::convert::TryFrom;
+
+use uefi::proto::console::gop::{ModeInfo, PixelFormat};
+
+use super::error::Error;
+use super::tag::StivaleFramebufferTag;
+
+/// Returns the size and shift of a colour channel mask.
+fn mask_size_shift(mask: u32) -> (u8, u8) {
+    if mask == 0 {
+        return (0, 0);
+    }
+
+    (mask.count_ones() as u8, mask.trailing_zeros() as u8)
+}
+
+fn to_u16(value: usize) -> Result<u16, Error> {
+    u16::try_from(value).map_err(|_| Error::InvalidValue(value as u64))
+}
+
+impl StivaleFramebufferTag {
+    /// Creates a framebuffer tag describing the GOP mode `mode`, whose framebuffer is at
+    /// `framebuffer_addr`, as returned by `GraphicsOutput::frame_buffer`.
+    ///
+    /// Fails with [Error::InvalidValue] if the mode has no framebuffer, which is the case of
+    /// [PixelFormat::BltOnly] modes, or if its dimensions do not fit in the tag.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use stivale_boot::v2::{Error, StivaleFramebufferTag};
+    /// use uefi::proto::console::gop::GraphicsOutput;
+    ///
+    /// fn framebuffer_tag(gop: &mut GraphicsOutput) -> Result<StivaleFramebufferTag, Error> {
+    ///     let mode = gop.current_mode_info();
+    ///     let framebuffer_addr = gop.frame_buffer().as_mut_ptr() as u64;
+    ///
+    ///     StivaleFramebufferTag::from_gop_mode(&mode, framebuffer_addr)
+    /// }
+    /// ```
+    pub fn from_gop_mode(mode: &ModeInfo, framebuffer_addr: u64) -> Result<Self, Error> {
+        // The masks of the red, green and blue channels, and of all the bits of a pixel.
+        let (red, green, blue, pixel) = match mode.pixel_format() {
+            PixelFormat::Rgb => (0xff, 0xff00, 0xff_0000, u32::MAX),
+            PixelFormat::Bgr => (0xff_0000, 0xff00, 0xff, u32::MAX),
+            PixelFormat::Bitmask => {
+                let mask = mode.pixel_bitmask().unwrap_or_default();
+                let pixel = mask.red | mask.green | mask.blue | mask.reserved;
+
+                (mask.red, mask.green, mask.blue, pixel)
+            }
+            PixelFormat::BltOnly => return Err(Error::InvalidValue(PixelFormat::BltOnly as u64)),
+        };
+
+        // Pixels are made of whole bytes, up to the highest bit used.
+        let bpp = (32 - pixel.leading_zeros() as usize).div_ceil(8) * 8;
+        let (width, height) = mode.resolution();
+
+        let mut tag = Self::new(
+            framebuffer_addr,
+            to_u16(width)?,
+            to_u16(height)?,
+            to_u16(mode.stride() * bpp / 8)?,
+            to_u16(bpp)?,
+        );
+
+        (tag.red_mask_size, tag.red_mask_shift) = mask_size_shift(red);
+        (tag.green_mask_size, tag.green_mask_shift) = mask_size_shift(green);
+        (tag.blue_mask_size, tag.blue_mask_shift) = mask_size_shift(blue);
+
+        Ok(tag)
+    }
+}
@@ -1,70 +1,158 @@
 use super::StivaleStruct;
 
 macro_rules! make_header_tag {
-	($(#[$meta:meta])* struct $name:ident: $id:expr;) => {
-		make_header_tag!($(#[$meta])* struct $name: $id => {};);
-	};
+    ($(#[$meta:meta])* struct $name:ident: $id:expr;) => {
+        make_header_tag!($(#[$meta])* struct $name: $id => {};);
+    };
 
-	($(#[$meta:meta])* struct $name:ident: $id:expr => {$($(#[$field_meta:meta])* $field_name:ident : $field_ty:ty = $field_default:expr),*};) => {
+    ($(#[$meta:meta])* struct $name:ident: $id:expr => {$($(#[$field_meta:meta])* $field_vis:vis $field_name:ident : $field_ty:ty = $field_default:expr),*};) => {
         $(#[$meta])*
         #[repr(C, packed)]
         pub struct $name {
-			identifier: u64,
-			next: *const (),
-			$($field_name: $field_ty),*
-		}
+            identifier: u64,
+            next: HeaderPointer<*const ()>,
+            $($(#[$field_meta])* $field_vis $field_name: $field_ty),*
+        }
 
         #[allow(deprecated)] unsafe impl Send for $name {}
         #[allow(deprecated)] unsafe impl Sync for $name {}
 
         #[allow(deprecated)] impl $name {
-			pub const fn new() -> Self {
-				Self {
-					identifier: $id,
-					next: core::ptr::null(),
-					$($field_name: $field_default),*
-				}
-			}
-
-			pub const fn next(mut self, next: *const ()) -> Self {
-				self.next = next;
-				self
-			}
-
-			$($(#[$field_meta])* pub const fn $field_name(mut self, value: $field_ty) -> Self {
-				self.$field_name = value;
-				self
-			})*
-		}
-	};
+            /// The unique identifier of this header tag.
+            pub const IDENTIFIER: u64 = $id;
+
+            pub const fn new() -> Self {
+                Self {
+                    identifier: $id,
+                    next: HeaderPointer::new(core::ptr::null()),
+                    $($field_name: $field_default),*
+                }
+            }
+
+            pub const fn next(mut self, next: *const ()) -> Self {
+                self.next = HeaderPointer::new(next);
+                self
+            }
+
+            $($(#[$field_meta])* pub const fn $field_name(mut self, value: $field_ty) -> Self {
+                self.$field_name = value;
+                self
+            })*
+        }
+    };
+}
+
+/// A pointer stored in a 64-bit header field. On 32-bit targets the pointer is followed by the
+/// zeroed upper half of the field, so that the headers have the same layout for 32-bit and
+/// 64-bit kernels. Pointers cannot be cast to integers in constant expressions, which is why
+/// the field is not a plain `u64`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct HeaderPointer<T> {
+    ptr: T,
+    #[cfg(target_pointer_width = "32")]
+    high: u32,
+}
+
+impl<T> HeaderPointer<T> {
+    const fn new(ptr: T) -> Self {
+        Self {
+            ptr,
+            #[cfg(target_pointer_width = "32")]
+            high: 0,
+        }
+    }
+}
+
+/// The machine state a kernel is entered in, which the bootloader picks from the ELF class of
+/// the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryMode {
+    /// 32-bit protected mode, used for ELF32 kernels. Paging is disabled and the segments are
+    /// flat. The stack pointer is set to the stack of the header, which is required, and the
+    /// pointer to the [StivaleStruct] is pushed on it followed by an invalid return address, as
+    /// for a cdecl call. The stack is therefore not 16 bytes aligned at the entry point.
+    ProtectedMode,
+    /// 64-bit long mode, used for ELF64 kernels. Paging is enabled with the higher half direct
+    /// map, and the pointer to the [StivaleStruct] is passed in `rdi`. Without a stack in the
+    /// header, the bootloader provides one.
+    LongMode,
+}
+
+impl EntryMode {
+    /// The entry mode of kernels built for the current target.
+    #[cfg(target_arch = "x86")]
+    pub const CURRENT: EntryMode = EntryMode::ProtectedMode;
+
+    /// The entry mode of kernels built for the current target.
+    #[cfg(target_arch = "x86_64")]
+    pub const CURRENT: EntryMode = EntryMode::LongMode;
+
+    /// Returns whether paging is enabled at the entry point.
+    pub const fn paging_enabled(self) -> bool {
+        matches!(self, EntryMode::LongMode)
+    }
+
+    /// Returns whether the header must provide a stack.
+    pub const fn requires_stack(self) -> bool {
+        matches!(self, EntryMode::ProtectedMode)
+    }
+
+    /// Returns whether the header flags and tags affecting paging, such as higher half
+    /// pointers, protected memory ranges and 5-level paging, are honoured by the bootloader.
+    pub const fn supports_paging_options(self) -> bool {
+        self.paging_enabled()
+    }
 }
 
+#[derive(Clone, Copy)]
 union StivaleHeaderEntryPoint {
     func: extern "C" fn(&'static StivaleStruct) -> !,
-    zero: u64,
+    zero: usize,
 }
 
 #[repr(C, packed)]
 pub struct StivaleHeader {
-    entry_point: StivaleHeaderEntryPoint,
-    stack: *const u8,
+    entry_point: HeaderPointer<StivaleHeaderEntryPoint>,
+    stack: HeaderPointer<*const u8>,
     flags: u64,
-    tags: *const (),
+    tags: HeaderPointer<*const ()>,
 }
 
+assert_layout!(StivaleHeader: 32 { entry_point: 0, stack: 8, flags: 16, tags: 24 });
+
 impl StivaleHeader {
     pub const fn new() -> Self {
         Self {
-            entry_point: StivaleHeaderEntryPoint { zero: 0 },
-            stack: core::ptr::null(),
+            entry_point: HeaderPointer::new(StivaleHeaderEntryPoint { zero: 0 }),
+            stack: HeaderPointer::new(core::ptr::null()),
             flags: 0,
-            tags: core::ptr::null(),
+            tags: HeaderPointer::new(core::ptr::null()),
         }
     }
 
+    /// Creates a new header for a 32-bit kernel, which is entered in protected mode with
+    /// paging disabled. Unlike 64-bit kernels, 32-bit kernels must provide a stack, so it is
+    /// required here. The flags only affecting long mode, such as higher half pointers and
+    /// protected memory ranges, are ignored by the bootloader for 32-bit kernels.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use stivale_boot::v2::StivaleHeader;
+    ///
+    /// static STACK: [u8; 4096] = [0; 4096];
+    ///
+    /// #[link_section = ".stivale2hdr"]
+    /// #[used]
+    /// static HEADER: StivaleHeader = StivaleHeader::new_protected_mode(&STACK[4095]);
+    /// ```
+    pub const fn new_protected_mode(stack: *const u8) -> Self {
+        Self::new().stack(stack)
+    }
+
     /// Returns the stack pointer placed in this header.
     pub fn get_stack(&self) -> *const u8 {
-        self.stack
+        self.stack.ptr
     }
 
     /// Returns the flags stored in this header.
@@ -72,13 +160,29 @@ impl StivaleHeader {
         self.flags
     }
 
+    /// Returns the address of the entry point stored in this header, or zero if the
+    /// entry point of the kernel ELF is to be used.
+    pub fn get_entry_point(&self) -> u64 {
+        // SAFETY: Both fields of the union are pointer sized, so reading the address is
+        // always valid.
+        unsafe { self.entry_point.ptr.zero as u64 }
+    }
+
+    /// Returns the address of the first header tag.
+    pub fn get_tags(&self) -> *const () {
+        self.tags.ptr
+    }
+
+    /// Sets the entry point of the kernel. It is called with the C calling convention, so the
+    /// pointer to the [StivaleStruct] is passed in `rdi` to 64-bit kernels and on the stack to
+    /// 32-bit kernels, which is what the bootloader does.
     pub const fn entry_point(mut self, func: extern "C" fn(&'static StivaleStruct) -> !) -> Self {
-        self.entry_point = StivaleHeaderEntryPoint { func };
+        self.entry_point = HeaderPointer::new(StivaleHeaderEntryPoint { func });
         self
     }
 
     pub const fn stack(mut self, stack: *const u8) -> Self {
-        self.stack = stack;
+        self.stack = HeaderPointer::new(stack);
         self
     }
 
@@ -88,7 +192,7 @@ impl StivaleHeader {
     }
 
     pub const fn tags(mut self, tags: *const ()) -> Self {
-        self.tags = tags;
+        self.tags = HeaderPointer::new(tags);
         self
     }
 }
@@ -98,9 +202,9 @@ make_header_tag!(
     /// framebuffer video mode. Omitting this tag will make the bootloader default to a
     /// CGA-compatible text mode, if supported.
     struct StivaleFramebufferHeaderTag: 0x3ecc1bc43d0f7971 => {
-        framebuffer_width: u16 = 0,
-        framebuffer_height: u16 = 0,
-        framebuffer_bpp: u16 = 0,
+        pub framebuffer_width: u16 = 0,
+        pub framebuffer_height: u16 = 0,
+        pub framebuffer_bpp: u16 = 0,
         _padding: u16 = 0
     };
 );
@@ -111,7 +215,7 @@ make_header_tag!(
     /// header tag **must** be specified when passing this header tag, and this tag may inhibit
     /// the WC MTRR framebuffer feature.
     struct StivaleTerminalHeaderTag: 0xa85d499b1823be72 => {
-        flags: u64 = 0
+        pub flags: u64 = 0
     };
 );
 
@@ -125,9 +229,11 @@ bitflags::bitflags! {
     }
 }
 
+impl_try_from_bits!(StivaleSmpHeaderTagFlags);
+
 make_header_tag!(
     struct StivaleSmpHeaderTag: 0x1ab015085f3273df => {
-        flags: StivaleSmpHeaderTagFlags = StivaleSmpHeaderTagFlags::XAPIC
+        pub flags: StivaleSmpHeaderTagFlags = StivaleSmpHeaderTagFlags::XAPIC
     };
 );
 
@@ -166,9 +272,41 @@ make_header_tag!(
         ///    (CGA text mode if available)
         ///
         /// All other values undefined.
-        preference: u64 = 0
+        pub preference: u64 = 0
+    };
+);
+
+/// Implements `Default` for the header and the header tags, creating them through their `new`
+/// constructor.
+macro_rules! impl_default_header {
+    ($($name:ident),*) => {
+        $(#[allow(deprecated)] impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        })*
     };
+}
+
+impl_default_header!(
+    StivaleHeader,
+    StivaleFramebufferHeaderTag,
+    StivaleTerminalHeaderTag,
+    StivaleSmpHeaderTag,
+    StivaleMtrrHeaderTag,
+    Stivale5LevelPagingHeaderTag,
+    StivaleUnmapNullHeaderTag,
+    StivaleAnyVideoTag
 );
 
 unsafe impl Send for StivaleHeader {}
 unsafe impl Sync for StivaleHeader {}
+
+assert_layout!(StivaleFramebufferHeaderTag: 24 {
+    next: 8,
+    framebuffer_width: 16,
+    framebuffer_height: 18,
+    framebuffer_bpp: 20,
+});
+assert_layout!(StivaleTerminalHeaderTag: 24 { next: 8, flags: 16 });
+assert_layout!(StivaleSmpHeaderTag: 24 { next: 8, flags: 16 });
@@ -0,0 +1,67 @@
+//! Conversion between physical addresses and their higher half direct map (HHDM) counterparts,
+//! as described by the [StivaleVMapTag].
+//!
+//! The offset of the direct map can be registered once for the whole crate with
+//! [register_hhdm_offset]. Accessors which dereference bootloader provided addresses, such as
+//! [StivaleModule::as_bytes](super::StivaleModule::as_bytes), then translate physical addresses
+//! through the direct map, so they keep working after the kernel dropped the identity mapping.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::tag::StivaleVMapTag;
+
+/// The registered direct map offset, zero if none was registered.
+static HHDM_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Registers `offset` as the start of the higher half direct map, used by the accessors of the
+/// crate to translate physical addresses.
+///
+/// ## Safety
+/// The whole physical memory the boot information refers to must be mapped at `offset` in the
+/// current address space for as long as the accessors of the crate are used, as they
+/// dereference the translated addresses.
+pub unsafe fn register_hhdm_offset(offset: u64) {
+    HHDM_OFFSET.store(offset, Ordering::Release);
+}
+
+/// Returns the registered direct map offset, if any.
+pub fn hhdm_offset() -> Option<u64> {
+    match HHDM_OFFSET.load(Ordering::Acquire) {
+        0 => None,
+        offset => Some(offset),
+    }
+}
+
+/// Translates `addr` through the registered direct map if it is a physical address, that is if
+/// it lies below the direct map. Higher half addresses and addresses translated without a
+/// registered direct map are returned unchanged, as are addresses which would overflow once
+/// translated.
+pub fn to_hhdm(addr: u64) -> u64 {
+    match hhdm_offset() {
+        Some(offset) if addr < offset => addr.checked_add(offset).unwrap_or(addr),
+        _ => addr,
+    }
+}
+
+impl StivaleVMapTag {
+    /// Returns the address the physical address `phys_addr` is mapped at in the direct map.
+    pub fn phys_to_virt(&self, phys_addr: u64) -> u64 {
+        self.address.wrapping_add(phys_addr)
+    }
+
+    /// Returns the physical address mapped at `virt_addr` in the direct map, or `None` if the
+    /// address lies below it.
+    pub fn virt_to_phys(&self, virt_addr: u64) -> Option<u64> {
+        virt_addr.checked_sub(self.address)
+    }
+
+    /// Registers the direct map described by this tag for the whole crate. See
+    /// [register_hhdm_offset].
+    ///
+    /// ## Safety
+    /// The page tables of the bootloader, or ones mapping the physical memory the same way, must
+    /// be in use for as long as the accessors of the crate are used.
+    pub unsafe fn register(&self) {
+        register_hhdm_offset(self.address);
+    }
+}
@@ -0,0 +1,260 @@
+//! Bootloader side parsing of stivale2 kernel images, used to locate the stivale2 header and
+//! the header tags the kernel requested.
+
+use core::mem;
+
+use super::anchor::StivaleAnchor;
+#[allow(deprecated)]
+use super::header::{
+    Stivale5LevelPagingHeaderTag, StivaleAnyVideoTag, StivaleFramebufferHeaderTag, StivaleHeader,
+    StivaleMtrrHeaderTag, StivaleSmpHeaderTag, StivaleTerminalHeaderTag, StivaleUnmapNullHeaderTag,
+};
+
+#[cfg(feature = "elf")]
+use crate::elf::ElfFile;
+
+#[cfg(feature = "elf")]
+pub use crate::elf::ElfError;
+
+/// Errors returned while parsing a kernel image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageError {
+    /// The kernel ELF could not be parsed.
+    #[cfg(feature = "elf")]
+    Elf(ElfError),
+    /// The image does not contain a stivale2 header.
+    MissingHeader,
+    /// The stivale2 header is smaller than expected.
+    TruncatedHeader,
+}
+
+#[cfg(feature = "elf")]
+impl From<ElfError> for ImageError {
+    fn from(error: ElfError) -> Self {
+        Self::Elf(error)
+    }
+}
+
+/// Describes how kernel addresses map to offsets in the image.
+enum AddressMapping {
+    /// The image is an ELF file loaded according to its program headers.
+    #[cfg(feature = "elf")]
+    Elf,
+    /// The image is loaded as is, starting at `load_addr`.
+    Flat { load_addr: u64 },
+}
+
+/// A kernel image containing a stivale2 header.
+pub struct StivaleKernelImage<'a> {
+    image: &'a [u8],
+    mapping: AddressMapping,
+    header: &'a StivaleHeader,
+}
+
+impl<'a> StivaleKernelImage<'a> {
+    /// Parses the kernel ELF in `image` and locates its `.stivale2hdr` section.
+    #[cfg(feature = "elf")]
+    pub fn from_elf(image: &'a [u8]) -> Result<Self, ImageError> {
+        let elf = ElfFile::parse(image)?;
+        let section = elf
+            .section_by_name(b".stivale2hdr")?
+            .ok_or(ImageError::MissingHeader)?;
+
+        let data = elf.section_data(&section)?;
+
+        Ok(Self {
+            image,
+            mapping: AddressMapping::Elf,
+            header: Self::header_from_bytes(data)?,
+        })
+    }
+
+    /// Scans `image` for a stivale2 anchor and locates the stivale2 header it points to. The
+    /// image is expected to be loaded as is at the load address specified in the anchor.
+    pub fn from_anchored(image: &'a [u8]) -> Result<(Self, StivaleAnchor), ImageError> {
+        let (_, anchor) = StivaleAnchor::find(image).ok_or(ImageError::MissingHeader)?;
+
+        let load_addr = anchor.phys_load_addr();
+        let offset = anchor
+            .phys_stivale2hdr()
+            .checked_sub(load_addr)
+            .ok_or(ImageError::MissingHeader)?;
+
+        let data = image
+            .get(offset as usize..)
+            .ok_or(ImageError::MissingHeader)?;
+
+        let this = Self {
+            image,
+            mapping: AddressMapping::Flat { load_addr },
+            header: Self::header_from_bytes(data)?,
+        };
+
+        Ok((this, anchor))
+    }
+
+    fn header_from_bytes(data: &'a [u8]) -> Result<&'a StivaleHeader, ImageError> {
+        if data.len() < mem::size_of::<StivaleHeader>() {
+            return Err(ImageError::TruncatedHeader);
+        }
+
+        // SAFETY: The header is packed, so it has no alignment requirements, and the length
+        // of the data was checked above.
+        Ok(unsafe { &*(data.as_ptr() as *const StivaleHeader) })
+    }
+
+    /// Returns the stivale2 header of the kernel.
+    pub fn header(&self) -> &'a StivaleHeader {
+        self.header
+    }
+
+    /// Translates the kernel virtual address `addr` into an offset in the image.
+    pub fn offset_of(&self, addr: u64) -> Option<usize> {
+        match &self.mapping {
+            #[cfg(feature = "elf")]
+            AddressMapping::Elf => ElfFile::parse(self.image).ok()?.offset_of(addr),
+            AddressMapping::Flat { load_addr } => {
+                let offset = addr.checked_sub(*load_addr)? as usize;
+                (offset < self.image.len()).then_some(offset)
+            }
+        }
+    }
+
+    /// Returns an iterator over the raw header tags requested by the kernel.
+    pub fn header_tags(&self) -> RawHeaderTagIter<'a, '_> {
+        RawHeaderTagIter {
+            image: self,
+            next: self.header.get_tags() as u64,
+            remaining: self.image.len() / 16,
+        }
+    }
+
+    /// Returns an iterator over the header tags requested by the kernel, known tags being
+    /// parsed into their types.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use stivale_boot::v2::{HeaderTag, StivaleKernelImage};
+    ///
+    /// fn requested_resolution(image: &[u8]) -> Option<(u16, u16)> {
+    ///     let (kernel, _) = StivaleKernelImage::from_anchored(image).ok()?;
+    ///
+    ///     kernel.typed_header_tags().find_map(|tag| match tag {
+    ///         HeaderTag::Framebuffer(tag) => Some((tag.framebuffer_width, tag.framebuffer_height)),
+    ///         _ => None,
+    ///     })
+    /// }
+    /// ```
+    pub fn typed_header_tags(&self) -> HeaderTagIter<'a, '_> {
+        HeaderTagIter {
+            raw: self.header_tags(),
+        }
+    }
+}
+
+/// A header tag as found in the kernel image.
+#[derive(Clone, Copy)]
+pub struct RawHeaderTag<'a> {
+    /// The identifier of the header tag.
+    pub identifier: u64,
+    /// The kernel virtual address of the header tag.
+    pub address: u64,
+    /// The bytes of the image starting at the header tag, up to the end of the image.
+    pub data: &'a [u8],
+}
+
+/// Iterator over the header tags of a kernel image.
+pub struct RawHeaderTagIter<'a, 'i> {
+    image: &'i StivaleKernelImage<'a>,
+    /// The address of the header tag we are about to read.
+    next: u64,
+    /// Upper bound on the amount of tags left, guarding against loops in the chain.
+    remaining: usize,
+}
+
+impl<'a, 'i> Iterator for RawHeaderTagIter<'a, 'i> {
+    type Item = RawHeaderTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == 0 || self.remaining == 0 {
+            return None;
+        }
+
+        let address = self.next;
+        let offset = self.image.offset_of(address)?;
+        let data = self.image.image.get(offset..)?;
+
+        if data.len() < 16 {
+            self.next = 0;
+            return None;
+        }
+
+        let mut word = [0; 8];
+        word.copy_from_slice(&data[0..8]);
+        let identifier = u64::from_le_bytes(word);
+        word.copy_from_slice(&data[8..16]);
+
+        self.next = u64::from_le_bytes(word);
+        self.remaining -= 1;
+
+        Some(RawHeaderTag {
+            identifier,
+            address,
+            data,
+        })
+    }
+}
+
+/// A header tag found in a kernel image, parsed into its type if it is known to this crate and
+/// large enough.
+#[derive(Clone, Copy)]
+#[allow(deprecated)]
+pub enum HeaderTag<'a> {
+    Framebuffer(&'a StivaleFramebufferHeaderTag),
+    Terminal(&'a StivaleTerminalHeaderTag),
+    Smp(&'a StivaleSmpHeaderTag),
+    Mtrr(&'a StivaleMtrrHeaderTag),
+    FiveLevelPaging(&'a Stivale5LevelPagingHeaderTag),
+    UnmapNull(&'a StivaleUnmapNullHeaderTag),
+    AnyVideo(&'a StivaleAnyVideoTag),
+    /// A header tag unknown to this crate, or truncated by the end of the image.
+    Unknown(RawHeaderTag<'a>),
+}
+
+impl<'a> HeaderTag<'a> {
+    /// Parses `raw` into its type if it is known.
+    #[allow(deprecated)]
+    pub fn parse(raw: RawHeaderTag<'a>) -> Self {
+        /// Returns the header tag of type `T` at the start of `data`, if it is large enough.
+        fn cast<T>(data: &[u8]) -> Option<&T> {
+            // SAFETY: The header tags are packed, so they have no alignment requirements.
+            (data.len() >= mem::size_of::<T>()).then(|| unsafe { &*(data.as_ptr() as *const T) })
+        }
+
+        let tag = match raw.identifier {
+            StivaleFramebufferHeaderTag::IDENTIFIER => cast(raw.data).map(Self::Framebuffer),
+            StivaleTerminalHeaderTag::IDENTIFIER => cast(raw.data).map(Self::Terminal),
+            StivaleSmpHeaderTag::IDENTIFIER => cast(raw.data).map(Self::Smp),
+            StivaleMtrrHeaderTag::IDENTIFIER => cast(raw.data).map(Self::Mtrr),
+            Stivale5LevelPagingHeaderTag::IDENTIFIER => cast(raw.data).map(Self::FiveLevelPaging),
+            StivaleUnmapNullHeaderTag::IDENTIFIER => cast(raw.data).map(Self::UnmapNull),
+            StivaleAnyVideoTag::IDENTIFIER => cast(raw.data).map(Self::AnyVideo),
+            _ => None,
+        };
+
+        tag.unwrap_or(Self::Unknown(raw))
+    }
+}
+
+/// Iterator over the header tags of a kernel image, parsed with [HeaderTag::parse].
+pub struct HeaderTagIter<'a, 'i> {
+    raw: RawHeaderTagIter<'a, 'i>,
+}
+
+impl<'a, 'i> Iterator for HeaderTagIter<'a, 'i> {
+    type Item = HeaderTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.raw.next().map(HeaderTag::parse)
+    }
+}
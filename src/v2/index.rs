@@ -0,0 +1,147 @@
+//! An index of the tags of a boot structure, built with a single walk of the tag chain, after
+//! which looking up a tag no longer requires traversing the chain.
+
+use core::mem;
+
+use super::tag::*;
+use super::{array_tag_at, limits, utils, StivaleStruct};
+
+/// Returns the position of `identifier` in [KNOWN_TAGS], or `KNOWN_TAGS.len()` if it is not a
+/// known tag.
+pub(super) const fn slot_of(identifier: u64) -> usize {
+    let mut slot = 0;
+
+    while slot < KNOWN_TAGS.len() && KNOWN_TAGS[slot] != identifier {
+        slot += 1;
+    }
+
+    slot
+}
+
+/// The addresses of every tag known to this crate, recorded by [TagIndex::build].
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootResponse, StivaleUartTag, TagIndex};
+///
+/// let uart = StivaleUartTag::new(0x3f8);
+/// let response = BootResponse {
+///     uart: Some(&uart),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 256]));
+/// let boot_info = round_trip(buffer, &response).unwrap();
+/// let index = TagIndex::build(boot_info);
+///
+/// assert_eq!(index.tag::<StivaleUartTag>().unwrap().address, 0x3f8);
+/// assert!(index.memory_map().is_none());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TagIndex {
+    /// The address of each tag of [KNOWN_TAGS], in the same order, or 0 if it is missing.
+    addresses: [u64; KNOWN_TAGS.len()],
+}
+
+impl TagIndex {
+    /// Walks the tag chain of `stivale_struct` once, recording the first tag of each known type.
+    /// At most [Limits::max_tags](super::Limits) tags are walked.
+    pub fn build(stivale_struct: &StivaleStruct) -> Self {
+        let mut addresses = [0; KNOWN_TAGS.len()];
+        let mut current_tag = unsafe { core::ptr::addr_of!(stivale_struct.tags).read_volatile() };
+        let mut walked = 0;
+
+        while current_tag != 0 && walked < limits().max_tags {
+            let addr = super::to_hhdm(current_tag);
+            let tag = utils::addr_to_ptr::<StivaleTagHeader>(addr);
+
+            unsafe {
+                let slot = slot_of(core::ptr::addr_of!((*tag).identifier).read_volatile());
+
+                if slot < addresses.len() && addresses[slot] == 0 {
+                    addresses[slot] = addr;
+                }
+
+                current_tag = core::ptr::addr_of!((*tag).next).read_volatile();
+            }
+
+            walked += 1;
+        }
+
+        Self { addresses }
+    }
+
+    /// Returns the address of the tag with the provided identifier, or `None` if it is missing
+    /// or not known to this crate.
+    pub fn get(&self, identifier: u64) -> Option<u64> {
+        self.addresses
+            .get(slot_of(identifier))
+            .copied()
+            .filter(|addr| *addr != 0)
+    }
+
+    /// Returns the fixed size tag of type `T`.
+    pub fn tag<T: StivaleTag>(&self) -> Option<&'static T> {
+        let slot = const { slot_of(T::IDENTIFIER) };
+
+        self.addresses
+            .get(slot)
+            .filter(|addr| **addr != 0)
+            // SAFETY: The slot holds a tag with the identifier of `T`, and `StivaleTag`
+            // requires `T` to be plain old data starting with the tag header.
+            .map(|addr| unsafe { &*utils::addr_to_ptr::<T>(*addr) })
+    }
+
+    fn array_tag<T: StivaleTag + ?Sized>(
+        &self,
+        count_offset: usize,
+        limit: usize,
+    ) -> Option<(*mut (), u64)> {
+        let addr = *self.addresses.get(const { slot_of(T::IDENTIFIER) })?;
+
+        if addr == 0 {
+            return None;
+        }
+
+        array_tag_at(addr, count_offset, limit)
+    }
+
+    /// Same as [StivaleStruct::memory_map].
+    pub fn memory_map(&self) -> Option<&'static StivaleMemoryMapTag> {
+        self.array_tag::<StivaleMemoryMapTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_memory_map_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleMemoryMapTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::edid_info].
+    pub fn edid_info(&self) -> Option<&'static StivaleEdidInfoTag> {
+        self.array_tag::<StivaleEdidInfoTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleEdidInfoTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::modules].
+    pub fn modules(&self) -> Option<&'static StivaleModuleTag> {
+        self.array_tag::<StivaleModuleTag>(mem::size_of::<StivaleTagHeader>(), limits().max_modules)
+            .map(|(ptr, count)| unsafe { &*StivaleModuleTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::smp].
+    pub fn smp(&self) -> Option<&'static StivaleSmpTag> {
+        self.array_tag::<StivaleSmpTag>(32, limits().max_cpus)
+            .map(|(ptr, count)| unsafe { &*StivaleSmpTag::new_from_ptr_count(ptr, count) })
+    }
+
+    /// Same as [StivaleStruct::pmrs].
+    pub fn pmrs(&self) -> Option<&'static StivalePmrsTag> {
+        self.array_tag::<StivalePmrsTag>(
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivalePmrsTag::new_from_ptr_count(ptr, count) })
+    }
+}
@@ -0,0 +1,74 @@
+//! Upper bounds on the amount of data read from the boot structure, so that a corrupted or
+//! malicious structure cannot make the accessors iterate over unbounded garbage memory.
+//!
+//! The limits are shared by the whole crate. The defaults are far above what bootloaders
+//! produce, and can be changed with [set_limits].
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Upper bounds on the amount of data read from the boot structure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// The maximum amount of tags walked in the tag chain.
+    pub max_tags: usize,
+    /// The maximum amount of memory map entries.
+    pub max_memory_map_entries: usize,
+    /// The maximum amount of modules.
+    pub max_modules: usize,
+    /// The maximum amount of processors in the SMP tag.
+    pub max_cpus: usize,
+    /// The maximum amount of entries of the other variable length tags, such as the protected
+    /// memory ranges and the bytes of the EDID information.
+    pub max_array_entries: usize,
+    /// The maximum amount of bytes scanned for the terminator of a string, such as the command
+    /// line.
+    pub max_string_len: usize,
+}
+
+impl Limits {
+    /// The default limits.
+    pub const DEFAULT: Limits = Limits {
+        max_tags: 256,
+        max_memory_map_entries: 4096,
+        max_modules: 1024,
+        max_cpus: 4096,
+        max_array_entries: 4096,
+        max_string_len: super::tag::MAX_COMMAND_LINE_LEN,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static MAX_TAGS: AtomicUsize = AtomicUsize::new(Limits::DEFAULT.max_tags);
+static MAX_MEMORY_MAP_ENTRIES: AtomicUsize =
+    AtomicUsize::new(Limits::DEFAULT.max_memory_map_entries);
+static MAX_MODULES: AtomicUsize = AtomicUsize::new(Limits::DEFAULT.max_modules);
+static MAX_CPUS: AtomicUsize = AtomicUsize::new(Limits::DEFAULT.max_cpus);
+static MAX_ARRAY_ENTRIES: AtomicUsize = AtomicUsize::new(Limits::DEFAULT.max_array_entries);
+static MAX_STRING_LEN: AtomicUsize = AtomicUsize::new(Limits::DEFAULT.max_string_len);
+
+/// Sets the limits used by the accessors of the whole crate.
+pub fn set_limits(limits: Limits) {
+    MAX_TAGS.store(limits.max_tags, Ordering::Relaxed);
+    MAX_MEMORY_MAP_ENTRIES.store(limits.max_memory_map_entries, Ordering::Relaxed);
+    MAX_MODULES.store(limits.max_modules, Ordering::Relaxed);
+    MAX_CPUS.store(limits.max_cpus, Ordering::Relaxed);
+    MAX_ARRAY_ENTRIES.store(limits.max_array_entries, Ordering::Relaxed);
+    MAX_STRING_LEN.store(limits.max_string_len, Ordering::Relaxed);
+}
+
+/// Returns the limits used by the accessors of the crate.
+pub fn limits() -> Limits {
+    Limits {
+        max_tags: MAX_TAGS.load(Ordering::Relaxed),
+        max_memory_map_entries: MAX_MEMORY_MAP_ENTRIES.load(Ordering::Relaxed),
+        max_modules: MAX_MODULES.load(Ordering::Relaxed),
+        max_cpus: MAX_CPUS.load(Ordering::Relaxed),
+        max_array_entries: MAX_ARRAY_ENTRIES.load(Ordering::Relaxed),
+        max_string_len: MAX_STRING_LEN.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,89 @@
+//! The lock serializing the writers shared between processors and interrupt handlers.
+//!
+//! By default this is a plain spin lock, so a writer must not be used from an interrupt handler
+//! which may have interrupted a write on the same processor. With the `critical-section`
+//! feature, the lock is held inside a critical section of the `critical-section` crate, which
+//! kernels implement by masking interrupts. Writes can then not be interrupted, and the writers
+//! can be used from interrupt handlers.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A spin lock, entering a critical section while held with the `critical-section` feature.
+pub(crate) struct RawLock {
+    locked: AtomicBool,
+}
+
+/// Releases the [RawLock] it was returned by, and leaves the critical section, when dropped.
+pub(crate) struct LockGuard<'a> {
+    lock: &'a RawLock,
+    #[cfg(feature = "critical-section")]
+    restore: critical_section::RestoreState,
+}
+
+impl RawLock {
+    pub(crate) const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Takes the lock, waiting for it to be released first.
+    pub(crate) fn lock(&self) -> LockGuard<'_> {
+        // SAFETY: The critical section is released by the guard, in the order it was acquired.
+        #[cfg(feature = "critical-section")]
+        let restore = unsafe { critical_section::acquire() };
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        LockGuard {
+            lock: self,
+            #[cfg(feature = "critical-section")]
+            restore,
+        }
+    }
+
+    /// Takes the lock if it is not held, returning `None` otherwise.
+    pub(crate) fn try_lock(&self) -> Option<LockGuard<'_>> {
+        // SAFETY: The critical section is released by the guard, or right away if the lock is
+        // held.
+        #[cfg(feature = "critical-section")]
+        let restore = unsafe { critical_section::acquire() };
+
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            #[cfg(feature = "critical-section")]
+            unsafe {
+                critical_section::release(restore)
+            };
+
+            return None;
+        }
+
+        Some(LockGuard {
+            lock: self,
+            #[cfg(feature = "critical-section")]
+            restore,
+        })
+    }
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+
+        // SAFETY: The critical section was acquired when the lock was taken.
+        #[cfg(feature = "critical-section")]
+        unsafe {
+            critical_section::release(self.restore)
+        };
+    }
+}
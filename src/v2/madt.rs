@@ -0,0 +1,150 @@
+//! Cross-checking of the CPUs reported by the SMP tag against the ACPI MADT, to diagnose CPUs
+//! which the firmware disabled or which the bootloader did not start.
+
+use acpi::madt::{Madt, MadtEntry};
+use acpi::{AcpiHandler, AcpiResult, AcpiTables};
+
+use super::tag::StivaleSmpTag;
+
+/// A CPU described by the MADT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MadtCpu {
+    /// The ACPI processor UID, which is the processor ID for local APIC entries.
+    pub processor_uid: u32,
+    /// The hardware ID of the CPU: its local APIC ID on x86 and its MPIDR on aarch64.
+    pub hardware_id: u64,
+    /// Whether the CPU is enabled by the firmware.
+    pub enabled: bool,
+    /// Whether the CPU is disabled but can be enabled at runtime.
+    pub online_capable: bool,
+}
+
+impl MadtCpu {
+    fn from_entry(entry: &MadtEntry<'_>) -> Option<Self> {
+        const ENABLED: u32 = 1 << 0;
+        const APIC_ONLINE_CAPABLE: u32 = 1 << 1;
+        const GICC_ONLINE_CAPABLE: u32 = 1 << 3;
+
+        let (processor_uid, hardware_id, flags, online_capable) = match entry {
+            MadtEntry::LocalApic(entry) => (
+                entry.processor_id as u32,
+                entry.apic_id as u64,
+                entry.flags,
+                APIC_ONLINE_CAPABLE,
+            ),
+            MadtEntry::LocalX2Apic(entry) => (
+                entry.processor_uid,
+                entry.x2apic_id as u64,
+                entry.flags,
+                APIC_ONLINE_CAPABLE,
+            ),
+            MadtEntry::Gicc(entry) => (
+                entry.processor_uid,
+                entry.mpidr,
+                entry.flags,
+                GICC_ONLINE_CAPABLE,
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            processor_uid,
+            hardware_id,
+            enabled: flags & ENABLED != 0,
+            online_capable: flags & online_capable != 0,
+        })
+    }
+}
+
+/// A difference between the CPUs described by the MADT and the ones reported by the SMP tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuDiscrepancy {
+    /// The CPU is described by the MADT but disabled by the firmware, so the bootloader could
+    /// not start it.
+    DisabledByFirmware(MadtCpu),
+    /// The CPU is enabled in the MADT but missing from the SMP tag, usually because the
+    /// bootloader failed to start it or only supports a limited amount of CPUs.
+    MissingFromSmp(MadtCpu),
+    /// The CPU with this hardware ID is reported by the SMP tag but not described by the MADT.
+    UnknownToMadt(u64),
+}
+
+/// The amount of CPUs found while cross-checking the SMP tag against the MADT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuCounts {
+    /// The amount of CPUs enabled in the MADT.
+    pub madt_enabled: usize,
+    /// The amount of CPUs disabled in the MADT.
+    pub madt_disabled: usize,
+    /// The amount of CPUs reported by the SMP tag.
+    pub smp: usize,
+}
+
+impl StivaleSmpTag {
+    /// Compares the CPUs of this tag with the CPUs described by the MADT, matching them by
+    /// hardware ID, and calls `report` for every discrepancy.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use acpi::{AcpiHandler, AcpiTables};
+    /// use stivale_boot::v2::{CpuDiscrepancy, StivaleSmpTag};
+    ///
+    /// fn not_started<H: AcpiHandler>(smp: &StivaleSmpTag, tables: &AcpiTables<H>) -> usize {
+    ///     let mut not_started = 0;
+    ///
+    ///     smp.cross_check_madt(tables, |discrepancy| {
+    ///         if let CpuDiscrepancy::MissingFromSmp(_) = discrepancy {
+    ///             not_started += 1;
+    ///         }
+    ///     })
+    ///     .unwrap();
+    ///
+    ///     not_started
+    /// }
+    /// ```
+    pub fn cross_check_madt<H: AcpiHandler>(
+        &self,
+        tables: &AcpiTables<H>,
+        mut report: impl FnMut(CpuDiscrepancy),
+    ) -> AcpiResult<CpuCounts> {
+        let madt = tables.find_table::<Madt>()?;
+        let madt_cpus = || {
+            madt.get()
+                .entries()
+                .filter_map(|entry| MadtCpu::from_entry(&entry))
+        };
+        let cpus = self.as_slice();
+
+        let mut counts = CpuCounts {
+            smp: cpus.len(),
+            ..Default::default()
+        };
+
+        for cpu in madt_cpus() {
+            if !cpu.enabled {
+                counts.madt_disabled += 1;
+                report(CpuDiscrepancy::DisabledByFirmware(cpu));
+                continue;
+            }
+
+            counts.madt_enabled += 1;
+
+            if !cpus
+                .iter()
+                .any(|info| info.hardware_id() == cpu.hardware_id)
+            {
+                report(CpuDiscrepancy::MissingFromSmp(cpu));
+            }
+        }
+
+        for info in cpus {
+            let hardware_id = info.hardware_id();
+
+            if !madt_cpus().any(|cpu| cpu.hardware_id == hardware_id) {
+                report(CpuDiscrepancy::UnknownToMadt(hardware_id));
+            }
+        }
+
+        Ok(counts)
+    }
+}
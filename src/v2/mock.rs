@@ -0,0 +1,408 @@
+//! Construction of synthetic boot information in heap memory, so that kernel subsystems such as
+//! physical memory managers and module loaders can be unit tested on the host.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use super::builder::{write_boot_struct, BootResponse, BuildError, StivaleSmpResponse};
+use super::header::StivaleSmpHeaderTagFlags;
+use super::tag::*;
+use super::StivaleStruct;
+
+std::thread_local! {
+    /// The output of the mock terminal of the current thread.
+    static TERMINAL_OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The `term_write` function of the mock terminal, recording the output of the current thread.
+extern "C" fn mock_term_write(string: *const i8, length: u64) {
+    let bytes = unsafe { core::slice::from_raw_parts(string as *const u8, length as usize) };
+    TERMINAL_OUTPUT.with(|output| output.borrow_mut().extend_from_slice(bytes));
+}
+
+/// Builder of a fully linked [StivaleStruct] living in heap memory.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{MockStivaleStruct, StivaleMemoryMapEntryType};
+///
+/// let boot_info = MockStivaleStruct::new()
+///     .memory_region(0x100000, 0x7f00000, StivaleMemoryMapEntryType::Usable)
+///     .module("initrd", b"hello".to_vec())
+///     .cpus(4)
+///     .build()
+///     .unwrap();
+///
+/// let memory_map = boot_info.memory_map().unwrap();
+/// assert_eq!(memory_map.iter().map(|entry| entry.length).sum::<u64>(), 0x7f00000);
+///
+/// let module = boot_info.modules().unwrap().find("initrd").unwrap();
+/// assert_eq!(module.as_bytes(), b"hello");
+/// assert_eq!(boot_info.smp().unwrap().cpu_count(), 4);
+/// ```
+///
+/// Code calling through the function pointers provided by the bootloader can be exercised too:
+/// ```rust
+/// use stivale_boot::v2::{MockStivaleStruct, StivaleSmpInfo};
+///
+/// extern "C" fn ap_main(_: &'static StivaleSmpInfo) -> ! {
+///     loop {}
+/// }
+///
+/// let boot_info = MockStivaleStruct::new().terminal(80, 25).cpus(2).build().unwrap();
+///
+/// boot_info.terminal().unwrap().term_write()("hello");
+/// assert_eq!(boot_info.terminal_output(), "hello");
+///
+/// let ap = &boot_info.smp().unwrap().as_slice()[1];
+/// unsafe { ap.start(ap_main, 0x8000, 42) };
+///
+/// let starts = boot_info.ap_starts();
+/// assert_eq!(starts.len(), 1);
+/// assert_eq!((starts[0].hardware_id, starts[0].target_stack, starts[0].extra), (1, 0x8000, 42));
+/// ```
+pub struct MockStivaleStruct {
+    bootloader_brand: String,
+    bootloader_version: String,
+    command_line: Option<String>,
+    memory_map: Vec<StivaleMemoryMapEntry>,
+    modules: Vec<(String, Vec<u8>)>,
+    framebuffer: Option<(u16, u16, u16)>,
+    terminal: Option<(u16, u16)>,
+    cpus: Option<usize>,
+    epoch: Option<u64>,
+}
+
+impl MockStivaleStruct {
+    /// Creates a new builder for boot information without any tag.
+    pub fn new() -> Self {
+        Self {
+            bootloader_brand: String::from("mock"),
+            bootloader_version: String::from("0.0.0"),
+            command_line: None,
+            memory_map: Vec::new(),
+            modules: Vec::new(),
+            framebuffer: None,
+            terminal: None,
+            cpus: None,
+            epoch: None,
+        }
+    }
+
+    /// Sets the bootloader brand and version.
+    pub fn bootloader(mut self, brand: &str, version: &str) -> Self {
+        self.bootloader_brand = String::from(brand);
+        self.bootloader_version = String::from(version);
+        self
+    }
+
+    /// Sets the command line.
+    pub fn command_line(mut self, command_line: &str) -> Self {
+        self.command_line = Some(String::from(command_line));
+        self
+    }
+
+    /// Adds a memory map entry. Entries are reported in the order they are added.
+    pub fn memory_region(
+        mut self,
+        base: u64,
+        length: u64,
+        entry_type: StivaleMemoryMapEntryType,
+    ) -> Self {
+        self.memory_map
+            .push(StivaleMemoryMapEntry::new(base, length, entry_type));
+        self
+    }
+
+    /// Adds a module with the provided string, backed by `data`.
+    pub fn module(mut self, string: &str, data: Vec<u8>) -> Self {
+        self.modules.push((String::from(string), data));
+        self
+    }
+
+    /// Adds a framebuffer of the provided size, backed by zeroed heap memory.
+    pub fn framebuffer(mut self, width: u16, height: u16, bpp: u16) -> Self {
+        self.framebuffer = Some((width, height, bpp));
+        self
+    }
+
+    /// Adds a terminal tag of the provided size. Its `term_write` function records the output,
+    /// which is retrieved with [MockBootInfo::terminal_output].
+    pub fn terminal(mut self, cols: u16, rows: u16) -> Self {
+        self.terminal = Some((cols, rows));
+        self
+    }
+
+    /// Adds an SMP tag describing `count` processors, the first one being the BSP.
+    pub fn cpus(mut self, count: usize) -> Self {
+        self.cpus = Some(count);
+        self
+    }
+
+    /// Adds an epoch tag with the provided UNIX epoch.
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Lays out the boot information in heap memory, which is leaked, as the tag accessors of
+    /// [StivaleStruct] return `'static` references into it.
+    pub fn build(self) -> Result<MockBootInfo, BuildError> {
+        let mut modules = Vec::new();
+
+        for (string, data) in self.modules {
+            let data: &'static [u8] = data.leak();
+            let start = super::utils::ptr_to_addr(data.as_ptr());
+
+            modules.push(StivaleModule::try_new(
+                &string,
+                start,
+                start + data.len() as u64,
+            )?);
+        }
+
+        let framebuffer = self.framebuffer.map(|(width, height, bpp)| {
+            let pitch = width * bpp.div_ceil(8);
+            let memory: &'static mut [u8] = vec![0u8; pitch as usize * height as usize].leak();
+            let mut tag = StivaleFramebufferTag::new(
+                super::utils::ptr_to_addr(memory.as_ptr()),
+                width,
+                height,
+                pitch,
+                bpp,
+            );
+
+            // Describe the usual 32-bit xRGB layout.
+            tag.red_mask_size = 8;
+            tag.red_mask_shift = 16;
+            tag.green_mask_size = 8;
+            tag.green_mask_shift = 8;
+            tag.blue_mask_size = 8;
+            tag.blue_mask_shift = 0;
+
+            tag
+        });
+
+        let cpus = (0..self.cpus.unwrap_or(0))
+            .map(|index| {
+                #[cfg(not(target_arch = "aarch64"))]
+                return StivaleSmpInfo::new(index as u32, index as u32);
+                #[cfg(target_arch = "aarch64")]
+                return StivaleSmpInfo::new(index as u32, index as u32, index as u64);
+            })
+            .collect::<Vec<_>>();
+        let epoch = self.epoch.map(StivaleEpochTag::new);
+        let terminal = self.terminal.map(|(cols, rows)| {
+            TERMINAL_OUTPUT.with(|output| output.borrow_mut().clear());
+            StivaleTerminalTag::new(
+                cols,
+                rows,
+                super::utils::ptr_to_addr(mock_term_write as *const ()),
+            )
+        });
+
+        let response = BootResponse {
+            bootloader_brand: &self.bootloader_brand,
+            bootloader_version: &self.bootloader_version,
+            command_line: self.command_line.as_deref(),
+            memory_map: (!self.memory_map.is_empty()).then_some(&self.memory_map[..]),
+            framebuffer: framebuffer.as_ref(),
+            terminal: terminal.as_ref(),
+            modules: (!modules.is_empty()).then_some(&modules[..]),
+            epoch: epoch.as_ref(),
+            smp: self.cpus.map(|_| StivaleSmpResponse {
+                flags: StivaleSmpHeaderTagFlags::empty(),
+                #[cfg(not(target_arch = "aarch64"))]
+                bsp_lapic_id: 0,
+                #[cfg(target_arch = "aarch64")]
+                bsp_mpidr: 0,
+                cpus: &cpus,
+            }),
+            ..Default::default()
+        };
+
+        // Grow the buffer until everything fits. It is made out of u64s to align the struct.
+        let mut words = 512;
+
+        loop {
+            let mut buffer = vec![0u64; words];
+            let bytes = unsafe {
+                core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, words * 8)
+            };
+
+            match write_boot_struct(bytes, &response) {
+                Ok(address) => {
+                    buffer.leak();
+
+                    // SAFETY: The struct was written at `address`, in memory which is never freed.
+                    let boot_info =
+                        unsafe { &*super::utils::addr_to_ptr::<StivaleStruct>(address) };
+
+                    return Ok(MockBootInfo { boot_info });
+                }
+                Err(BuildError::BufferTooSmall) => words *= 2,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl Default for MockStivaleStruct {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Boot information built by [MockStivaleStruct], dereferencing to the [StivaleStruct]. The
+/// memory backing the tags, the modules and the framebuffer is leaked, so that the `'static`
+/// references returned by the tag accessors stay valid once this is dropped.
+pub struct MockBootInfo {
+    boot_info: &'static StivaleStruct,
+}
+
+/// The start of an application processor requested through [StivaleSmpInfo::start], as
+/// recorded by a [MockBootInfo].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MockApStart {
+    /// The physical ID of the processor, see [StivaleSmpInfo::hardware_id].
+    pub hardware_id: u64,
+    /// The address the processor was asked to jump to.
+    pub goto_address: u64,
+    /// The stack the processor was asked to use.
+    pub target_stack: u64,
+    /// The extra argument passed to the processor.
+    pub extra: u64,
+}
+
+impl MockBootInfo {
+    /// Returns the boot information, which stays valid once this is dropped, such as for
+    /// registering it with [init](super::init).
+    pub fn boot_info(&self) -> &'static StivaleStruct {
+        self.boot_info
+    }
+
+    /// Returns the output written to the mock terminal by the current thread since the boot
+    /// information was built, with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn terminal_output(&self) -> String {
+        TERMINAL_OUTPUT.with(|output| String::from_utf8_lossy(&output.borrow()).into_owned())
+    }
+
+    /// Returns the output written to the mock terminal by the current thread and clears it.
+    pub fn take_terminal_output(&self) -> String {
+        TERMINAL_OUTPUT.with(|output| {
+            let output = core::mem::take(&mut *output.borrow_mut());
+            String::from_utf8_lossy(&output).into_owned()
+        })
+    }
+
+    /// Returns the application processors which were started, in the order of the SMP tag.
+    /// Nothing is ever run on them, so the recorded entry points can be checked without
+    /// executing them.
+    pub fn ap_starts(&self) -> Vec<MockApStart> {
+        self.smp()
+            .map(|smp| {
+                smp.as_slice()
+                    .iter()
+                    .filter(|cpu| cpu.goto_address() != 0)
+                    .map(|cpu| MockApStart {
+                        hardware_id: cpu.hardware_id(),
+                        goto_address: cpu.goto_address(),
+                        target_stack: cpu.target_stack(),
+                        extra: cpu.extra(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl core::ops::Deref for MockBootInfo {
+    type Target = StivaleStruct;
+
+    fn deref(&self) -> &StivaleStruct {
+        self.boot_info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn ap_main(_: &'static StivaleSmpInfo) -> ! {
+        unreachable!("the mock never runs the application processors")
+    }
+
+    #[test]
+    fn terminal_output_is_recorded() {
+        let boot_info = MockStivaleStruct::new().terminal(80, 25).build().unwrap();
+        let term_write = boot_info.terminal().unwrap().term_write();
+
+        term_write("hello ");
+        term_write("world");
+        assert_eq!(boot_info.terminal_output(), "hello world");
+
+        assert_eq!(boot_info.take_terminal_output(), "hello world");
+        assert_eq!(boot_info.terminal_output(), "");
+    }
+
+    #[test]
+    fn terminal_output_replaces_invalid_utf8() {
+        let boot_info = MockStivaleStruct::new().terminal(80, 25).build().unwrap();
+
+        boot_info.terminal().unwrap().write_bytes(b"a\xffb");
+        assert_eq!(boot_info.terminal_output(), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn terminal_output_is_cleared_by_build() {
+        let first = MockStivaleStruct::new().terminal(80, 25).build().unwrap();
+        first.terminal().unwrap().term_write()("stale");
+
+        let second = MockStivaleStruct::new().terminal(80, 25).build().unwrap();
+        assert_eq!(second.terminal_output(), "");
+    }
+
+    #[test]
+    fn ap_starts_are_recorded_in_order() {
+        let boot_info = MockStivaleStruct::new().cpus(4).build().unwrap();
+        assert!(boot_info.ap_starts().is_empty());
+
+        let cpus = boot_info.smp().unwrap().as_slice();
+
+        unsafe {
+            cpus[3].start(ap_main, 0x9000, 3);
+            cpus[1].start(ap_main, 0x8000, 1);
+        }
+
+        let starts = boot_info.ap_starts();
+        let goto_address = crate::v2::utils::ptr_to_addr(ap_main as *const ());
+
+        assert_eq!(
+            starts,
+            [
+                MockApStart {
+                    hardware_id: 1,
+                    goto_address,
+                    target_stack: 0x8000,
+                    extra: 1,
+                },
+                MockApStart {
+                    hardware_id: 3,
+                    goto_address,
+                    target_stack: 0x9000,
+                    extra: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ap_starts_without_smp() {
+        let boot_info = MockStivaleStruct::new().build().unwrap();
+
+        assert!(boot_info.smp().is_none());
+        assert!(boot_info.ap_starts().is_empty());
+    }
+}
@@ -2,27 +2,169 @@
 //! modern version of the legacy stivale protocol which provides the kernel with most of the features
 //! one may need. The stivale2 protocol also supports 32-bit systems.
 
-use core::mem;
-
+use core::{mem, ptr};
+
+mod anchor;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+mod backtrace;
+#[cfg(feature = "log")]
+mod boot_log;
+mod builder;
+#[cfg(feature = "alloc-bootstrap")]
+mod bump;
+mod cache;
+#[cfg(feature = "fixtures")]
+mod capture;
+mod cmdline;
+#[cfg(feature = "miniz")]
+mod decompress;
+mod early_log;
+mod edid;
+#[cfg(feature = "macros")]
+mod entry;
+mod environment;
+mod epoch;
+mod error;
+#[cfg(feature = "defmt")]
+mod format;
+mod framebuffer;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod global;
+#[cfg(feature = "uefi")]
+mod gop;
 mod header;
+mod hhdm;
+mod image;
+mod index;
+mod limits;
+mod lock;
+#[cfg(feature = "acpi")]
+mod madt;
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod mtrr;
+#[cfg(feature = "multiboot2")]
+mod multiboot2;
+#[cfg(feature = "alloc")]
+mod owned;
+#[cfg(feature = "heapless")]
+mod owned_heapless;
+#[cfg(feature = "x86_64")]
+mod paging;
+#[cfg(feature = "panic")]
+mod panic;
+#[cfg(feature = "pmm")]
+mod pmm;
+mod reader;
+#[cfg(feature = "std")]
+mod report;
+mod roundtrip;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shared;
+mod slide;
+mod smbios;
+mod snapshot;
+mod stack;
 mod tag;
-mod utils;
-
+mod tags_mut;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+mod term_stack;
+mod terminal;
+mod uart;
+pub(crate) mod utils;
+mod version;
+mod video;
+
+pub use anchor::*;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+pub use backtrace::*;
+#[cfg(feature = "log")]
+pub use boot_log::*;
+pub use builder::*;
+#[cfg(feature = "alloc-bootstrap")]
+pub use bump::*;
+pub use cache::*;
+#[cfg(feature = "fixtures")]
+pub use capture::*;
+pub use cmdline::*;
+#[cfg(feature = "miniz")]
+pub use decompress::*;
+pub use early_log::*;
+pub use edid::*;
+#[cfg(feature = "macros")]
+pub use entry::*;
+pub use environment::*;
+pub use epoch::*;
+pub use error::*;
+pub use framebuffer::*;
+#[cfg(feature = "arbitrary")]
+pub use fuzz::*;
+pub use global::*;
 pub use header::*;
+pub use hhdm::*;
+pub use image::*;
+pub use index::*;
+pub use limits::*;
+#[cfg(feature = "acpi")]
+pub use madt::*;
+#[cfg(feature = "mock")]
+pub use mock::*;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use mtrr::*;
+#[cfg(feature = "multiboot2")]
+pub use multiboot2::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+#[cfg(feature = "heapless")]
+pub use owned_heapless::*;
+#[cfg(feature = "x86_64")]
+pub use paging::*;
+#[cfg(feature = "panic")]
+pub use panic::*;
+#[cfg(feature = "pmm")]
+pub use pmm::*;
+pub use reader::*;
+#[cfg(feature = "std")]
+pub use report::*;
+pub use roundtrip::*;
+pub use shared::*;
+pub use slide::*;
+pub use smbios::*;
+pub use snapshot::*;
+pub use stack::*;
 pub use tag::*;
+pub use tags_mut::*;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+pub use term_stack::*;
+pub use terminal::*;
+pub use uart::*;
+pub use utils::{FixedCStr, LossyStr};
+pub use version::*;
+pub use video::*;
 
 #[repr(C)]
 pub struct StivaleStruct {
-    bootloader_brand: [u8; 64],
-    bootloader_version: [u8; 64],
+    bootloader_brand: FixedCStr<64>,
+    bootloader_version: FixedCStr<64>,
     tags: u64,
 }
 
+assert_layout!(StivaleStruct: 136 { bootloader_brand: 0, bootloader_version: 64, tags: 128 });
+
+impl Default for StivaleStruct {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StivaleStruct {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            bootloader_brand: [0; 64],
-            bootloader_version: [0; 64],
+            bootloader_brand: FixedCStr::new(),
+            bootloader_version: FixedCStr::new(),
             tags: 0x00,
         }
     }
@@ -32,192 +174,583 @@ impl StivaleStruct {
     // and then the stivale struct is only allowed to be updated if its made by the user itself
     // (its required to fill up these fields if you are making a stivale2 bootloader :^)).
     pub fn add_tag(&mut self, header: StivaleTagHeader) {
-        self.tags = &header as *const StivaleTagHeader as u64;
+        self.tags = utils::ptr_to_addr(&header as *const StivaleTagHeader);
     }
 
+    /// Sets the bootloader brand, truncated on a character boundary to 63 bytes, leaving room
+    /// for the null terminator, and at its first null byte. See
+    /// [StivaleStruct::try_set_bootloader_brand] to reject such brands instead.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::StivaleStruct;
+    ///
+    /// let mut stivale_struct = StivaleStruct::new();
+    /// let brand = "x".repeat(100);
+    ///
+    /// assert!(stivale_struct.try_set_bootloader_brand(&brand).is_err());
+    /// assert_eq!(stivale_struct.bootloader_brand(), "");
+    ///
+    /// stivale_struct.set_bootloader_brand(&brand);
+    /// assert_eq!(stivale_struct.bootloader_brand(), &brand[..63]);
+    /// ```
     pub fn set_bootloader_brand(&mut self, brand: &str) {
-        self.bootloader_brand[..brand.len()].copy_from_slice(brand.as_bytes());
+        self.bootloader_brand.set_truncated(brand)
     }
 
+    /// Sets the bootloader version, truncated on a character boundary to 63 bytes, leaving room
+    /// for the null terminator, and at its first null byte. See
+    /// [StivaleStruct::try_set_bootloader_version] to reject such versions instead.
     pub fn set_bootloader_version(&mut self, version: &str) {
-        self.bootloader_version[..version.len()].copy_from_slice(version.as_bytes());
+        self.bootloader_version.set_truncated(version)
     }
 
+    /// Sets the bootloader brand. Returns an error if it does not fit in 63 bytes, leaving room
+    /// for the null terminator, or if it contains a null byte.
+    pub fn try_set_bootloader_brand(&mut self, brand: &str) -> Result<(), BuildError> {
+        self.bootloader_brand.try_set(brand)
+    }
+
+    /// Sets the bootloader version. Returns an error if it does not fit in 63 bytes, leaving
+    /// room for the null terminator, or if it contains a null byte.
+    pub fn try_set_bootloader_version(&mut self, version: &str) -> Result<(), BuildError> {
+        self.bootloader_version.try_set(version)
+    }
+
+    /// Returns the bootloader brand. If it is not valid UTF-8, it is cut at the first invalid
+    /// byte; see [StivaleStruct::bootloader_brand_lossy] to display all of it.
     pub fn bootloader_brand(&self) -> &str {
-        utils::string_from_slice(&self.bootloader_brand)
+        self.bootloader_brand.as_str()
     }
 
+    /// Returns the bootloader version. If it is not valid UTF-8, it is cut at the first invalid
+    /// byte; see [StivaleStruct::bootloader_version_lossy] to display all of it.
     pub fn bootloader_version(&self) -> &str {
-        utils::string_from_slice(&self.bootloader_version)
+        self.bootloader_version.as_str()
+    }
+
+    /// Returns the bootloader version parsed into its numeric components, or `None` if it does
+    /// not contain a version number. See [BootloaderVersion::parse].
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use stivale_boot::v2::{BootloaderVersion, StivaleStruct};
+    ///
+    /// fn has_terminal_callback(boot_info: &StivaleStruct) -> bool {
+    ///     boot_info.bootloader_brand() == "Limine"
+    ///         && boot_info
+    ///             .bootloader_version_parsed()
+    ///             .map_or(false, |version| version >= BootloaderVersion::new(2, 0, 0))
+    /// }
+    /// ```
+    pub fn bootloader_version_parsed(&self) -> Option<BootloaderVersion<'_>> {
+        BootloaderVersion::parse(self.bootloader_version())
+    }
+
+    /// Returns the bootloader brand, displayed with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn bootloader_brand_lossy(&self) -> LossyStr<'_> {
+        self.bootloader_brand.as_str_lossy()
+    }
+
+    /// Returns the bootloader version, displayed with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn bootloader_version_lossy(&self) -> LossyStr<'_> {
+        self.bootloader_version.as_str_lossy()
+    }
+
+    /// Returns the structure as seen through the higher half direct map at `offset`.
+    ///
+    /// This is meant for kernels which did not request higher half pointers and switch to their
+    /// own page tables without an identity mapping. The physical addresses found in the tags are
+    /// only translated once the offset is registered for the whole crate with
+    /// [register_hhdm_offset], which this function does not do. A structure which already lies
+    /// in the higher half, at or above `offset`, is returned as is.
+    ///
+    /// ## Safety
+    /// The physical memory holding this structure must be mapped at `offset` in the current
+    /// address space, and stay mapped for as long as the returned reference is used.
+    pub unsafe fn with_hhdm(&self, offset: u64) -> &'static StivaleStruct {
+        let addr = utils::ptr_to_addr(self);
+
+        match addr.checked_add(offset) {
+            Some(virt_addr) if addr < offset => &*utils::addr_to_ptr::<StivaleStruct>(virt_addr),
+            _ => &*(self as *const StivaleStruct),
+        }
+    }
+
+    /// Returns a cursor over the tags of this structure, through which bootloaders can fix up
+    /// the tags in place or remove them from the chain before handing the structure to the
+    /// kernel. See [TagsMut].
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{
+    ///     write_boot_struct, BootResponse, StivaleEpochTag, StivaleStruct, StivaleTag,
+    ///     StivaleUartTag,
+    /// };
+    ///
+    /// let epoch = StivaleEpochTag::new(1_600_000_000);
+    /// let uart = StivaleUartTag::new(0x3f8);
+    /// let response = BootResponse {
+    ///     epoch: Some(&epoch),
+    ///     uart: Some(&uart),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let mut buffer = [0u8; 4096];
+    /// let addr = write_boot_struct(&mut buffer, &response).unwrap();
+    /// let stivale_struct = unsafe { &mut *(addr as *mut StivaleStruct) };
+    ///
+    /// let mut tags = stivale_struct.iter_tags_mut();
+    ///
+    /// while let Some(header) = tags.next_tag() {
+    ///     if header.identifier == StivaleEpochTag::IDENTIFIER {
+    ///         // The kernel did not request the epoch.
+    ///         tags.remove();
+    ///     } else if let Some(uart) = tags.current_as::<StivaleUartTag>() {
+    ///         uart.address += 0xffff_8000_0000_0000;
+    ///     }
+    /// }
+    ///
+    /// assert!(stivale_struct.epoch().is_none());
+    /// assert_eq!(stivale_struct.uart().unwrap().address, 0xffff_8000_0000_03f8);
+    /// ```
+    pub fn iter_tags_mut(&mut self) -> TagsMut<'_> {
+        TagsMut::new(&mut self.tags)
     }
 
+    /// Returns the address of the tag with the provided identifier, or `None` if it is missing
+    /// or if the tag chain is longer than [Limits::max_tags].
     pub fn get_tag(&self, identifier: u64) -> Option<u64> {
-        let mut current_tag = self.tags as *const StivaleTagHeader;
+        self.try_get_tag(identifier).ok().flatten()
+    }
+
+    /// Returns the address of the tag with the provided identifier, or `None` if it is missing.
+    /// Returns [Error::LimitExceeded] if the tag chain is longer than [Limits::max_tags], which
+    /// usually means that it is corrupted or loops.
+    pub fn try_get_tag(&self, identifier: u64) -> Result<Option<u64>, Error> {
+        let max_tags = limits().max_tags;
+
+        // The tag list lives in memory written by the bootloader, so it is read with volatile
+        // loads to keep the compiler from making assumptions about its contents.
+        let mut current_tag = unsafe { ptr::addr_of!(self.tags).read_volatile() };
+        let mut walked = 0;
+
+        while current_tag != 0 {
+            if walked == max_tags {
+                return Err(Error::LimitExceeded);
+            }
 
-        while !current_tag.is_null() {
-            let tag = unsafe { &*current_tag };
+            let current_tag_addr = to_hhdm(current_tag);
+            let tag = utils::addr_to_ptr::<StivaleTagHeader>(current_tag_addr);
 
-            if tag.identifier == identifier {
-                return Some(current_tag as u64);
+            unsafe {
+                if ptr::addr_of!((*tag).identifier).read_volatile() == identifier {
+                    return Ok(Some(current_tag_addr));
+                }
+
+                current_tag = ptr::addr_of!((*tag).next).read_volatile();
             }
 
-            current_tag = tag.next as *const StivaleTagHeader;
+            walked += 1;
         }
 
-        None
+        Ok(None)
     }
 
-    pub fn command_line(&self) -> Option<&'static StivaleCommandLineTag> {
-        self.get_tag(0xe5e76a1b4597a781)
-            .map(|addr| unsafe { &*(addr as *const StivaleCommandLineTag) })
+    /// Returns the range of physical memory spanned by this structure, its tags including their
+    /// variable length arrays, and the command line, so that kernels know what to preserve when
+    /// reclaiming bootloader memory. Tags past [Limits::max_tags] are ignored.
+    ///
+    /// The memory the tags point to, such as the modules, the kernel file or the framebuffer, is
+    /// not included. Higher half addresses are translated through the registered direct map, or
+    /// the one described by the [StivaleVMapTag] if none was registered.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{round_trip, BootResponse};
+    ///
+    /// let response = BootResponse {
+    ///     command_line: Some("quiet"),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let buffer = Box::leak(Box::new([0u8; 4096]));
+    /// let range = buffer.as_ptr_range();
+    /// let stivale_struct = round_trip(buffer, &response).unwrap();
+    /// let span = stivale_struct.span();
+    ///
+    /// assert!(span.contains(&(range.start as u64)));
+    /// assert!(span.end <= range.end as u64);
+    /// ```
+    pub fn span(&self) -> core::ops::Range<u64> {
+        let offset = hhdm_offset().or_else(|| self.vmap().map(|tag| tag.address));
+        let physical = |addr: u64| match offset {
+            Some(offset) if addr >= offset => addr - offset,
+            _ => addr,
+        };
+
+        let start = physical(utils::ptr_to_addr(self));
+        let mut span = start..start + mem::size_of::<Self>() as u64;
+        let mut extend = |addr: u64, size: usize| {
+            let start = physical(addr);
+
+            span.start = span.start.min(start);
+            span.end = span.end.max(start.saturating_add(size as u64));
+        };
+
+        let mut current_tag = unsafe { ptr::addr_of!(self.tags).read_volatile() };
+
+        for _ in 0..limits().max_tags {
+            if current_tag == 0 {
+                break;
+            }
+
+            let tag = utils::addr_to_ptr::<StivaleTagHeader>(to_hhdm(current_tag));
+
+            unsafe {
+                // Tags unknown to this crate, or with an entry count above the limits, are
+                // assumed to only be made of their header.
+                extend(
+                    current_tag,
+                    tag::tag_size(tag).unwrap_or(mem::size_of::<StivaleTagHeader>()),
+                );
+
+                if (*tag).identifier == StivaleCommandLineTag::IDENTIFIER {
+                    let tag = &*(tag as *const StivaleCommandLineTag);
+
+                    if tag.command_line != 0 {
+                        // Include the null terminator.
+                        extend(tag.command_line, tag.as_cstr_bytes().len() + 1);
+                    }
+                }
+
+                current_tag = ptr::addr_of!((*tag).next).read_volatile();
+            }
+        }
+
+        span
     }
 
-    pub fn memory_map(&self) -> Option<&'static StivaleMemoryMapTag> {
-        self.get_tag(0x2187f79e8612de07).map(|addr| {
-            let ptr = addr as *mut u8;
+    /// Copies this structure, its tags and the command line into `buffer`, rewriting the
+    /// pointers between them, and returns the copy. This is the memory covered by
+    /// [StivaleStruct::span], so all of it can be reclaimed once copied. Fails with
+    /// [BuildError::UnknownTag] if a tag unknown to this crate is reachable, as its payload
+    /// could not be copied, with [BuildError::TooManyEntries] if the entry count of a tag is
+    /// above the [Limits], and with [BuildError::TooManyTags] if the chain is longer than
+    /// [Limits::max_tags].
+    ///
+    /// The copy points to the same modules, kernel file and framebuffer as the original. Its
+    /// internal pointers are the addresses of `buffer` itself, so it must not be accessed
+    /// through another mapping. The buffer must be `'static`, as the tag accessors of the copy
+    /// return `'static` references into it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{round_trip, BootResponse};
+    ///
+    /// let response = BootResponse {
+    ///     command_line: Some("quiet"),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let buffer = Box::leak(Box::new([0u8; 4096]));
+    /// let original = buffer.as_mut_ptr();
+    /// let copy_buffer = Box::leak(Box::new([0u8; 4096]));
+    /// let copy = round_trip(buffer, &response)
+    ///     .unwrap()
+    ///     .clone_into(copy_buffer)
+    ///     .unwrap();
+    ///
+    /// // The original can be reclaimed.
+    /// unsafe { original.write_bytes(0, 4096) };
+    /// assert_eq!(copy.command_line().unwrap().as_str(), Ok("quiet"));
+    /// ```
+    pub fn clone_into(
+        &self,
+        buffer: &'static mut [u8],
+    ) -> Result<&'static StivaleStruct, BuildError> {
+        let base = utils::ptr_to_addr(buffer.as_ptr());
+        let capacity = buffer.len();
+        let mut next_offset = (base.wrapping_neg() % 8) as usize;
+
+        let mut allocate = |size: usize| {
+            let start = next_offset;
+            let end = start
+                .checked_add(size)
+                .filter(|&end| end <= capacity)
+                .ok_or(BuildError::BufferTooSmall)?;
+
+            next_offset = end.next_multiple_of(8);
+            Ok(start)
+        };
+
+        let write_u64 = |buffer: &mut [u8], offset: usize, value: u64| {
+            buffer[offset..offset + 8].copy_from_slice(&value.to_ne_bytes());
+        };
+
+        let struct_offset = allocate(mem::size_of::<Self>())?;
+        let struct_bytes = unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        };
+        buffer[struct_offset..struct_offset + struct_bytes.len()].copy_from_slice(struct_bytes);
+
+        // The offset of the pointer to the next tag, starting with the `tags` field.
+        let mut link = struct_offset + mem::offset_of!(StivaleStruct, tags);
+        write_u64(buffer, link, 0);
+
+        let mut current_tag = unsafe { ptr::addr_of!(self.tags).read_volatile() };
+
+        for _ in 0..limits().max_tags {
+            if current_tag == 0 {
+                break;
+            }
+
+            let tag = utils::addr_to_ptr::<StivaleTagHeader>(to_hhdm(current_tag));
+            let size = unsafe { tag::tag_size(tag) }?;
+            let tag_offset = allocate(size)?;
+
+            // The size is bounded by the buffer the tag is copied to.
+            let tag_bytes = unsafe { core::slice::from_raw_parts(tag as *const u8, size) };
+            buffer[tag_offset..tag_offset + size].copy_from_slice(tag_bytes);
+            write_u64(buffer, link, base + tag_offset as u64);
+
+            link = tag_offset + mem::offset_of!(StivaleTagHeader, next);
+            write_u64(buffer, link, 0);
+
             unsafe {
-                let count = *(ptr.add(mem::size_of::<StivaleTagHeader>()) as *const u64);
-                let memory_map_ptr = StivaleMemoryMapTag::new_from_ptr_count(ptr as *mut (), count);
-                &*memory_map_ptr
+                if (*tag).identifier == StivaleCommandLineTag::IDENTIFIER {
+                    let tag = &*(tag as *const StivaleCommandLineTag);
+
+                    if tag.command_line != 0 {
+                        let string = tag.as_cstr_bytes();
+                        let string_offset = allocate(string.len() + 1)?;
+
+                        buffer[string_offset..string_offset + string.len()].copy_from_slice(string);
+                        buffer[string_offset + string.len()] = 0;
+                        write_u64(
+                            buffer,
+                            tag_offset + mem::offset_of!(StivaleCommandLineTag, command_line),
+                            base + string_offset as u64,
+                        );
+                    }
+                }
+
+                current_tag = ptr::addr_of!((*tag).next).read_volatile();
             }
-        })
+        }
+
+        if current_tag != 0 {
+            return Err(BuildError::TooManyTags);
+        }
+
+        let buffer: &'static [u8] = buffer;
+        Ok(unsafe { &*(buffer.as_ptr().add(struct_offset) as *const StivaleStruct) })
+    }
+
+    /// Returns the address of the variable length tag with the provided identifier along with
+    /// its entry count, read at `count_offset`. Returns `None` if the tag is missing or if the
+    /// count is above `limit`.
+    fn get_array_tag(
+        &self,
+        identifier: u64,
+        count_offset: usize,
+        limit: usize,
+    ) -> Option<(*mut (), u64)> {
+        array_tag_at(self.get_tag(identifier)?, count_offset, limit)
+    }
+
+    pub fn command_line(&self) -> Option<&'static StivaleCommandLineTag> {
+        self.get_tag(StivaleCommandLineTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleCommandLineTag>(addr)) })
+    }
+
+    pub fn memory_map(&self) -> Option<&'static StivaleMemoryMapTag> {
+        self.get_array_tag(
+            StivaleMemoryMapTag::IDENTIFIER,
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_memory_map_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleMemoryMapTag::new_from_ptr_count(ptr, count) })
     }
 
     pub fn framebuffer(&self) -> Option<&'static StivaleFramebufferTag> {
-        self.get_tag(0x506461d2950408fa)
-            .map(|addr| unsafe { &*(addr as *const StivaleFramebufferTag) })
+        self.get_tag(StivaleFramebufferTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleFramebufferTag>(addr)) })
     }
 
     pub fn edid_info(&self) -> Option<&'static StivaleEdidInfoTag> {
-        self.get_tag(0x968609d7af96b845).map(|addr| {
-            let ptr = addr as *mut u8;
-            unsafe {
-                let count = *(ptr.add(mem::size_of::<StivaleTagHeader>()) as *const u64);
-                let edid_ptr = StivaleEdidInfoTag::new_from_ptr_count(ptr as *mut (), count);
-                &*edid_ptr
-            }
-        })
+        self.get_array_tag(
+            StivaleEdidInfoTag::IDENTIFIER,
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleEdidInfoTag::new_from_ptr_count(ptr, count) })
     }
 
     #[allow(deprecated)]
     pub fn mtrr(&self) -> Option<&'static StivaleMtrrTag> {
-        self.get_tag(0x6bc1a78ebe871172)
-            .map(|addr| unsafe { &*(addr as *const StivaleMtrrTag) })
+        self.get_tag(StivaleMtrrTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleMtrrTag>(addr)) })
     }
 
     pub fn terminal(&self) -> Option<&'static StivaleTerminalTag> {
-        self.get_tag(0xc2b3f4c3233b0974)
-            .map(|addr| unsafe { &*(addr as *const StivaleTerminalTag) })
+        self.get_tag(StivaleTerminalTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleTerminalTag>(addr)) })
     }
 
     pub fn modules(&self) -> Option<&'static StivaleModuleTag> {
-        self.get_tag(0x4b6fe466aade04ce).map(|addr| {
-            let ptr = addr as *mut u8;
-            unsafe {
-                let count = *(ptr.add(mem::size_of::<StivaleTagHeader>()) as *const u64);
-                let module_ptr = StivaleModuleTag::new_from_ptr_count(ptr as *mut (), count);
-                &*module_ptr
-            }
-        })
+        self.get_array_tag(
+            StivaleModuleTag::IDENTIFIER,
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_modules,
+        )
+        .map(|(ptr, count)| unsafe { &*StivaleModuleTag::new_from_ptr_count(ptr, count) })
     }
 
     pub fn rsdp(&self) -> Option<&'static StivaleRsdpTag> {
-        self.get_tag(0x9e1786930a375e78)
-            .map(|addr| unsafe { &*(addr as *const StivaleRsdpTag) })
+        self.get_tag(StivaleRsdpTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleRsdpTag>(addr)) })
     }
 
     pub fn smbios(&self) -> Option<&'static StivaleSmbiosTag> {
-        self.get_tag(0x274bd246c62bf7d1)
-            .map(|addr| unsafe { &*(addr as *const StivaleSmbiosTag) })
+        self.get_tag(StivaleSmbiosTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleSmbiosTag>(addr)) })
     }
 
     pub fn epoch(&self) -> Option<&'static StivaleEpochTag> {
-        self.get_tag(0x566a7bed888e1407)
-            .map(|addr| unsafe { &*(addr as *const StivaleEpochTag) })
+        self.get_tag(StivaleEpochTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleEpochTag>(addr)) })
     }
 
     pub fn firmware(&self) -> Option<&'static StivaleFirmwareTag> {
-        self.get_tag(0x359d837855e3858c)
-            .map(|addr| unsafe { &*(addr as *const StivaleFirmwareTag) })
+        self.get_tag(StivaleFirmwareTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleFirmwareTag>(addr)) })
     }
 
     pub fn efi_system_table(&self) -> Option<&'static StivaleEfiSystemTableTag> {
-        self.get_tag(0x4bc5ec15845b558e)
-            .map(|addr| unsafe { &*(addr as *const StivaleEfiSystemTableTag) })
+        self.get_tag(StivaleEfiSystemTableTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleEfiSystemTableTag>(addr)) })
+    }
+
+    /// Returns the state of the firmware services, combining the firmware and EFI system table
+    /// tags. See [EfiBootState] for the guarantees that hold after boot services were exited.
+    pub fn efi_boot_state(&self) -> EfiBootState {
+        match self.firmware().map(|tag| tag.kind()) {
+            Some(FirmwareKind::Bios) => EfiBootState::Bios,
+            Some(FirmwareKind::Uefi) => EfiBootState::RuntimeOnly {
+                system_table: self
+                    .efi_system_table()
+                    .map(|tag| tag.system_table_addr)
+                    .filter(|addr| *addr != 0),
+            },
+            None => EfiBootState::Unknown,
+        }
+    }
+
+    /// Returns the EFI system table as a runtime system table of the `uefi` crate, or `None`
+    /// if the kernel was not booted from UEFI or the table is not available. The boot services
+    /// view of the table is never provided, as boot services were exited by the bootloader.
+    ///
+    /// ## Safety
+    /// See [StivaleEfiSystemTableTag::system_table].
+    #[cfg(feature = "uefi")]
+    pub unsafe fn efi_runtime_system_table(
+        &self,
+    ) -> Option<uefi::table::SystemTable<uefi::table::Runtime>> {
+        let addr = self.efi_boot_state().runtime_system_table()?;
+        uefi::table::SystemTable::from_ptr(utils::addr_to_mut_ptr(addr))
     }
 
     pub fn kernel_file(&self) -> Option<&'static StivaleKernelFileTag> {
-        self.get_tag(0xe599d90c2975584a)
-            .map(|addr| unsafe { &*(addr as *const StivaleKernelFileTag) })
+        self.get_tag(StivaleKernelFileTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleKernelFileTag>(addr)) })
+    }
+
+    /// Returns the raw kernel file as a byte slice, preferring the kernel file v2 tag, which
+    /// provides the size of the file, over the kernel file tag.
+    pub fn kernel_file_bytes(&self) -> Option<&'static [u8]> {
+        match self.kernel_file_v2() {
+            Some(tag) => Some(tag.as_bytes()),
+            None => self.kernel_file().map(|tag| tag.as_bytes()),
+        }
     }
 
     pub fn kernel_slide(&self) -> Option<&'static StivaleKernelSlideTag> {
-        self.get_tag(0xee80847d01506c57)
-            .map(|addr| unsafe { &*(addr as *const StivaleKernelSlideTag) })
+        self.get_tag(StivaleKernelSlideTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleKernelSlideTag>(addr)) })
     }
 
-    pub fn smp(&self) -> Option<&'static StivaleSmpTag> {
-        self.get_tag(0x34d1d96339647025).map(|addr| {
-            let ptr = addr as *mut u8;
-            unsafe {
-                // +32 calculated from the definition of the struct, offset to the cpu_count
-                let count = *(ptr.add(32) as *const u64);
-                let smp_ptr = StivaleSmpTag::new_from_ptr_count(ptr as *mut (), count);
-                &*smp_ptr
-            }
-        })
+    /// Returns whether the kernel was loaded without KASLR. The kernel file v2 tag does not
+    /// carry any flags, so the KASLR state is derived from the kernel slide tag instead. The
+    /// kernel is assumed to be unslid if the tag is missing.
+    pub fn kaslr_disabled(&self) -> bool {
+        self.kernel_slide().is_none_or(|tag| tag.kaslr_disabled())
     }
 
-    pub fn smp_mut(&mut self) -> Option<&'static mut StivaleSmpTag> {
-        self.get_tag(0x34d1d96339647025).map(|addr| {
-            let ptr = addr as *mut u8;
-            unsafe {
-                // +32 calculated from the definition of the struct, offset to the cpu_count
-                let count = *(ptr.add(32) as *const u64);
-                let smp_ptr = StivaleSmpTag::new_from_ptr_count(ptr as *mut (), count);
-                &mut *smp_ptr
-            }
-        })
+    pub fn smp(&self) -> Option<&'static StivaleSmpTag> {
+        self.get_array_tag(StivaleSmpTag::IDENTIFIER, 32, limits().max_cpus)
+            .map(|(ptr, count)| unsafe { &*StivaleSmpTag::new_from_ptr_count(ptr, count) })
     }
 
     pub fn pxe_info(&self) -> Option<&'static StivalePxeInfoTag> {
-        self.get_tag(0x29d1e96239247032)
-            .map(|addr| unsafe { &*(addr as *const StivalePxeInfoTag) })
+        self.get_tag(StivalePxeInfoTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivalePxeInfoTag>(addr)) })
+    }
+
+    /// Returns the medium the kernel was booted from. The bootloader only provides the PXE info
+    /// tag when the kernel was loaded over the network.
+    pub fn boot_medium(&self) -> BootMedium {
+        if self.pxe_info().is_some() {
+            BootMedium::Pxe
+        } else {
+            BootMedium::Disk
+        }
     }
 
     pub fn uart(&self) -> Option<&'static StivaleUartTag> {
-        self.get_tag(0xb813f9b8dbc78797)
-            .map(|addr| unsafe { &*(addr as *const StivaleUartTag) })
+        self.get_tag(StivaleUartTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleUartTag>(addr)) })
     }
 
     pub fn dev_tree(&self) -> Option<&'static StivaleDeviceTreeTag> {
-        self.get_tag(0xabb29bd49a2833fa)
-            .map(|addr| unsafe { &*(addr as *const StivaleDeviceTreeTag) })
+        self.get_tag(StivaleDeviceTreeTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleDeviceTreeTag>(addr)) })
     }
 
     pub fn vmap(&self) -> Option<&'static StivaleVMapTag> {
-        self.get_tag(0xb0ed257db18cb58f)
-            .map(|addr| unsafe { &*(addr as *const StivaleVMapTag) })
+        self.get_tag(StivaleVMapTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleVMapTag>(addr)) })
     }
 
     pub fn kernel_file_v2(&self) -> Option<&'static StivaleKernelFileV2Tag> {
-        self.get_tag(0x37c13018a02c6ea2)
-            .map(|addr| unsafe { &*(addr as *const StivaleKernelFileV2Tag) })
+        self.get_tag(StivaleKernelFileV2Tag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleKernelFileV2Tag>(addr)) })
     }
 
     pub fn pmrs(&self) -> Option<&'static StivalePmrsTag> {
-        self.get_tag(0x5df266a64047b6bd).map(|addr| {
-            let ptr = addr as *mut u8;
-            unsafe {
-                let count = *(ptr.add(mem::size_of::<StivaleTagHeader>()) as *const u64);
-                let pmrs_ptr = StivalePmrsTag::new_from_ptr_count(ptr as *mut (), count);
-                &*pmrs_ptr
-            }
-        })
+        self.get_array_tag(
+            StivalePmrsTag::IDENTIFIER,
+            mem::size_of::<StivaleTagHeader>(),
+            limits().max_array_entries,
+        )
+        .map(|(ptr, count)| unsafe { &*StivalePmrsTag::new_from_ptr_count(ptr, count) })
     }
 
     pub fn kernel_base_addr(&self) -> Option<&'static StivaleKernelBaseAddressTag> {
-        self.get_tag(0x060d78874a2a8af0)
-            .map(|addr| unsafe { &*(addr as *const StivaleKernelBaseAddressTag) })
+        self.get_tag(StivaleKernelBaseAddressTag::IDENTIFIER)
+            .map(|addr| unsafe { &*(utils::addr_to_ptr::<StivaleKernelBaseAddressTag>(addr)) })
     }
 }
+
+/// Returns the variable length tag at `addr` along with its entry count, read at
+/// `count_offset`. Returns `None` if the count is above `limit`.
+fn array_tag_at(addr: u64, count_offset: usize, limit: usize) -> Option<(*mut (), u64)> {
+    let ptr = utils::addr_to_mut_ptr::<u8>(addr);
+    let count = unsafe { (ptr.add(count_offset) as *const u64).read_volatile() };
+
+    if count > limit as u64 {
+        return None;
+    }
+
+    Some((ptr as *mut (), count))
+}
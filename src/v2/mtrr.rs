@@ -0,0 +1,209 @@
+//! Decoding of the variable range MTRRs, which describe the caching policy the firmware and the
+//! bootloader set up for physical memory. This supersedes the deprecated [StivaleMtrrTag],
+//! which only reports that the framebuffer was made write-combining.
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::__cpuid;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::__cpuid;
+use core::ops::Range;
+
+#[allow(deprecated)]
+use super::tag::StivaleMtrrTag;
+
+/// The maximum amount of variable range MTRR pairs kept by [MemoryTypeRanges].
+pub const MAX_VARIABLE_MTRRS: usize = 32;
+
+const IA32_MTRRCAP: u32 = 0xfe;
+const IA32_MTRR_DEF_TYPE: u32 = 0x2ff;
+const IA32_MTRR_PHYSBASE0: u32 = 0x200;
+
+/// The bit of `IA32_MTRR_PHYSMASKn` set when the pair is in use.
+const PHYSMASK_VALID: u64 = 1 << 11;
+/// The bits of `IA32_MTRR_PHYSBASEn` and `IA32_MTRR_PHYSMASKn` holding an address.
+const ADDRESS_MASK: u64 = !0xfff;
+
+/// The caching type of a memory range, as encoded in the MTRRs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryType {
+    Uncacheable,
+    WriteCombining,
+    WriteThrough,
+    WriteProtected,
+    WriteBack,
+}
+
+impl MemoryType {
+    /// Decodes the memory type in the low byte of `raw`, returning `None` for reserved
+    /// encodings.
+    pub fn from_raw(raw: u64) -> Option<Self> {
+        match raw & 0xff {
+            0 => Some(MemoryType::Uncacheable),
+            1 => Some(MemoryType::WriteCombining),
+            4 => Some(MemoryType::WriteThrough),
+            5 => Some(MemoryType::WriteProtected),
+            6 => Some(MemoryType::WriteBack),
+            _ => None,
+        }
+    }
+}
+
+/// A physical memory range and its caching type, decoded from a variable range MTRR pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryTypeRange {
+    /// The physical addresses covered by the pair.
+    pub range: Range<u64>,
+    /// The caching type of the range.
+    pub cache_type: MemoryType,
+}
+
+/// A snapshot of the variable range MTRR pairs.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::MemoryTypeRanges;
+///
+/// let ranges = unsafe { MemoryTypeRanges::read() };
+///
+/// for range in ranges.iter() {
+///     let _ = (range.range, range.cache_type);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryTypeRanges {
+    default_type: u64,
+    /// The raw `(IA32_MTRR_PHYSBASEn, IA32_MTRR_PHYSMASKn)` pairs.
+    pairs: [(u64, u64); MAX_VARIABLE_MTRRS],
+    count: usize,
+    /// The mask of the bits of a physical address supported by the processor.
+    physical_mask: u64,
+}
+
+impl MemoryTypeRanges {
+    /// Creates a snapshot from the raw value of `IA32_MTRR_DEF_TYPE` and the raw
+    /// `(IA32_MTRR_PHYSBASEn, IA32_MTRR_PHYSMASKn)` pairs, for a processor supporting
+    /// `physical_address_bits` bits of physical address. Pairs past [MAX_VARIABLE_MTRRS] are
+    /// ignored.
+    pub fn from_raw(default_type: u64, pairs: &[(u64, u64)], physical_address_bits: u8) -> Self {
+        let count = pairs.len().min(MAX_VARIABLE_MTRRS);
+        let mut saved = [(0, 0); MAX_VARIABLE_MTRRS];
+        saved[..count].copy_from_slice(&pairs[..count]);
+
+        Self {
+            default_type,
+            pairs: saved,
+            count,
+            physical_mask: 1u64
+                .checked_shl(physical_address_bits as u32)
+                .map_or(u64::MAX, |limit| limit - 1),
+        }
+    }
+
+    /// Reads the MTRR model specific registers of the current processor.
+    ///
+    /// ## Safety
+    /// This must run in ring 0 on a processor supporting MTRRs, as reading model specific
+    /// registers otherwise faults.
+    pub unsafe fn read() -> Self {
+        let count = (rdmsr(IA32_MTRRCAP) & 0xff) as usize;
+        let mut pairs = [(0, 0); MAX_VARIABLE_MTRRS];
+
+        for (i, pair) in pairs.iter_mut().enumerate().take(count) {
+            let msr = IA32_MTRR_PHYSBASE0 + 2 * i as u32;
+            *pair = (rdmsr(msr), rdmsr(msr + 1));
+        }
+
+        Self::from_raw(
+            rdmsr(IA32_MTRR_DEF_TYPE),
+            &pairs[..count.min(MAX_VARIABLE_MTRRS)],
+            physical_address_bits(),
+        )
+    }
+
+    /// Returns the caching type of memory not covered by any range, or `None` if the MTRRs are
+    /// disabled, in which case all memory is uncacheable.
+    pub fn default_type(&self) -> Option<MemoryType> {
+        // Bit 11 of IA32_MTRR_DEF_TYPE enables the MTRRs.
+        if self.default_type & (1 << 11) == 0 {
+            return None;
+        }
+
+        MemoryType::from_raw(self.default_type)
+    }
+
+    /// Returns an iterator over the ranges of the pairs in use. Pairs with a reserved memory
+    /// type or a mask which does not describe a contiguous range are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = MemoryTypeRange> + '_ {
+        let physical_mask = self.physical_mask & ADDRESS_MASK;
+
+        self.pairs[..self.count]
+            .iter()
+            .filter(|(_, mask)| mask & PHYSMASK_VALID != 0)
+            .filter_map(move |(base, mask)| {
+                let mask = mask & physical_mask;
+                let length = (!mask & physical_mask).wrapping_add(0x1000);
+
+                if mask == 0 || !length.is_power_of_two() {
+                    return None;
+                }
+
+                let start = base & physical_mask;
+
+                Some(MemoryTypeRange {
+                    range: start..start + length,
+                    cache_type: MemoryType::from_raw(*base)?,
+                })
+            })
+    }
+
+    /// Returns the caching type of the physical address `addr` according to the variable
+    /// ranges, applying the precedence rules for overlapping ranges. Returns the default type
+    /// when no range covers it.
+    pub fn type_of(&self, addr: u64) -> Option<MemoryType> {
+        let mut found = None;
+
+        for range in self.iter().filter(|range| range.range.contains(&addr)) {
+            found = Some(match (found, range.cache_type) {
+                (None, cache_type) => cache_type,
+                (Some(MemoryType::Uncacheable), _) | (_, MemoryType::Uncacheable) => {
+                    MemoryType::Uncacheable
+                }
+                (Some(MemoryType::WriteThrough), MemoryType::WriteBack)
+                | (Some(MemoryType::WriteBack), MemoryType::WriteThrough) => {
+                    MemoryType::WriteThrough
+                }
+                (Some(previous), _) => previous,
+            });
+        }
+
+        found.or_else(|| self.default_type())
+    }
+}
+
+#[allow(deprecated)]
+impl StivaleMtrrTag {
+    /// Reads the variable range MTRRs, which include the write-combining range the bootloader
+    /// set up for the framebuffer. See [MemoryTypeRanges::read].
+    ///
+    /// ## Safety
+    /// See [MemoryTypeRanges::read].
+    pub unsafe fn memory_type_ranges(&self) -> MemoryTypeRanges {
+        MemoryTypeRanges::read()
+    }
+}
+
+unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    core::arch::asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    ((high as u64) << 32) | low as u64
+}
+
+/// Returns the amount of physical address bits supported by the processor.
+fn physical_address_bits() -> u8 {
+    if __cpuid(0x8000_0000).eax >= 0x8000_0008 {
+        __cpuid(0x8000_0008).eax as u8
+    } else {
+        // Processors without the leaf support 36 bits.
+        36
+    }
+}
@@ -0,0 +1,250 @@
+//! Conversion of a Multiboot2 boot information structure into a [BootResponse], so that
+//! chainloaders and shims booted through Multiboot2 can boot stivale2 kernels.
+
+use core::convert::TryFrom;
+use core::mem;
+
+use super::builder::BootResponse;
+use super::tag::*;
+
+/// Errors returned while converting a Multiboot2 information structure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Multiboot2Error {
+    /// The information structure or one of its tags is truncated.
+    Truncated,
+    /// A string is not valid UTF-8 or is not null-terminated.
+    InvalidString,
+    /// The storage cannot hold all of the memory map entries or modules.
+    TooManyEntries,
+}
+
+const TAG_END: u32 = 0;
+const TAG_COMMAND_LINE: u32 = 1;
+const TAG_MODULE: u32 = 3;
+const TAG_MEMORY_MAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ACPI_OLD: u32 = 14;
+const TAG_ACPI_NEW: u32 = 15;
+
+/// The framebuffer type of direct RGB framebuffers.
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Multiboot2Error> {
+    let mut word = [0; 4];
+    word.copy_from_slice(
+        bytes
+            .get(offset..offset + 4)
+            .ok_or(Multiboot2Error::Truncated)?,
+    );
+    Ok(u32::from_le_bytes(word))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Multiboot2Error> {
+    let mut word = [0; 8];
+    word.copy_from_slice(
+        bytes
+            .get(offset..offset + 8)
+            .ok_or(Multiboot2Error::Truncated)?,
+    );
+    Ok(u64::from_le_bytes(word))
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Result<u8, Multiboot2Error> {
+    bytes.get(offset).copied().ok_or(Multiboot2Error::Truncated)
+}
+
+/// Returns the null-terminated string at the start of `bytes`, without the terminator.
+fn read_str(bytes: &[u8]) -> Result<&str, Multiboot2Error> {
+    let length = bytes
+        .iter()
+        .position(|c| *c == 0)
+        .ok_or(Multiboot2Error::InvalidString)?;
+
+    core::str::from_utf8(&bytes[..length]).map_err(|_| Multiboot2Error::InvalidString)
+}
+
+fn entry_type(raw: u32) -> StivaleMemoryMapEntryType {
+    match raw {
+        1 => StivaleMemoryMapEntryType::Usable,
+        3 => StivaleMemoryMapEntryType::AcpiReclaimable,
+        4 => StivaleMemoryMapEntryType::AcpiNvs,
+        5 => StivaleMemoryMapEntryType::BadMemory,
+        _ => StivaleMemoryMapEntryType::Reserved,
+    }
+}
+
+/// Returns the Multiboot2 information structure at `address` as a byte slice.
+///
+/// ## Safety
+/// `address` must point to a valid Multiboot2 information structure, as passed in EBX by a
+/// Multiboot2 bootloader, which must stay valid and unmodified for `'a`.
+pub unsafe fn multiboot2_info_from_ptr<'a>(address: usize) -> &'a [u8] {
+    let total_size = *core::ptr::with_exposed_provenance::<u32>(address);
+    core::slice::from_raw_parts(
+        core::ptr::with_exposed_provenance(address),
+        total_size as usize,
+    )
+}
+
+/// Storage for the tags converted from a Multiboot2 information structure, holding up to `M`
+/// memory map entries and `N` modules.
+pub struct Multiboot2Storage<const M: usize, const N: usize> {
+    memory_map: [StivaleMemoryMapEntry; M],
+    modules: [StivaleModule; N],
+    framebuffer: StivaleFramebufferTag,
+    rsdp: StivaleRsdpTag,
+}
+
+impl<const M: usize, const N: usize> Default for Multiboot2Storage<M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const M: usize, const N: usize> Multiboot2Storage<M, N> {
+    pub fn new() -> Self {
+        Self {
+            memory_map: core::array::from_fn(|_| {
+                StivaleMemoryMapEntry::new(0, 0, StivaleMemoryMapEntryType::Reserved)
+            }),
+            modules: core::array::from_fn(|_| StivaleModule::new("", 0, 0)),
+            framebuffer: StivaleFramebufferTag::new(0, 0, 0, 0, 0),
+            rsdp: StivaleRsdpTag::new(0),
+        }
+    }
+
+    /// Converts the Multiboot2 information structure in `info` into a [BootResponse] with the
+    /// memory map, modules, framebuffer, RSDP and command line it contains.
+    ///
+    /// The RSDP tag points at the copy of the RSDP inside of `info`, so `info` must be
+    /// accessible by the kernel at the same address. Framebuffers which are not direct RGB
+    /// framebuffers, or whose dimensions do not fit in the stivale2 framebuffer tag, are
+    /// skipped.
+    pub fn convert<'a>(&'a mut self, info: &'a [u8]) -> Result<BootResponse<'a>, Multiboot2Error> {
+        let total_size = read_u32(info, 0)? as usize;
+        let info = info.get(..total_size).ok_or(Multiboot2Error::Truncated)?;
+
+        let mut command_line = None;
+        let mut memory_map_len = 0;
+        let mut module_len = 0;
+        let mut has_framebuffer = false;
+        let mut has_rsdp = false;
+
+        let mut offset = 8;
+
+        loop {
+            let kind = read_u32(info, offset)?;
+            let size = read_u32(info, offset + 4)? as usize;
+
+            if size < 8 {
+                return Err(Multiboot2Error::Truncated);
+            }
+
+            let tag = info
+                .get(offset..offset + size)
+                .ok_or(Multiboot2Error::Truncated)?;
+
+            match kind {
+                TAG_END => break,
+                TAG_COMMAND_LINE => command_line = Some(read_str(&tag[8..])?),
+                TAG_MODULE => {
+                    let module = self
+                        .modules
+                        .get_mut(module_len)
+                        .ok_or(Multiboot2Error::TooManyEntries)?;
+
+                    *module = StivaleModule::new(
+                        read_str(tag.get(16..).ok_or(Multiboot2Error::Truncated)?)?,
+                        read_u32(tag, 8)? as u64,
+                        read_u32(tag, 12)? as u64,
+                    );
+
+                    module_len += 1;
+                }
+                TAG_MEMORY_MAP => {
+                    let entry_size = read_u32(tag, 8)? as usize;
+
+                    if entry_size < 24 {
+                        return Err(Multiboot2Error::Truncated);
+                    }
+
+                    for entry in tag[16..].chunks_exact(entry_size) {
+                        let slot = self
+                            .memory_map
+                            .get_mut(memory_map_len)
+                            .ok_or(Multiboot2Error::TooManyEntries)?;
+
+                        *slot = StivaleMemoryMapEntry::new(
+                            read_u64(entry, 0)?,
+                            read_u64(entry, 8)?,
+                            entry_type(read_u32(entry, 16)?),
+                        );
+
+                        memory_map_len += 1;
+                    }
+                }
+                TAG_FRAMEBUFFER => {
+                    if let Some(framebuffer) = Self::framebuffer(tag)? {
+                        self.framebuffer = framebuffer;
+                        has_framebuffer = true;
+                    }
+                }
+                // Prefer the new RSDP, which is also valid for ACPI 1.0 aware kernels.
+                TAG_ACPI_OLD | TAG_ACPI_NEW if !has_rsdp || kind == TAG_ACPI_NEW => {
+                    self.rsdp = StivaleRsdpTag::new(super::utils::ptr_to_addr(tag[8..].as_ptr()));
+                    has_rsdp = true;
+                }
+                _ => {}
+            }
+
+            // Tags are padded to 8 bytes.
+            offset += (size + 7) & !7;
+        }
+
+        Ok(BootResponse {
+            command_line,
+            memory_map: Some(&self.memory_map[..memory_map_len]),
+            framebuffer: has_framebuffer.then_some(&self.framebuffer),
+            modules: Some(&self.modules[..module_len]),
+            rsdp: has_rsdp.then_some(&self.rsdp),
+            ..Default::default()
+        })
+    }
+
+    fn framebuffer(tag: &[u8]) -> Result<Option<StivaleFramebufferTag>, Multiboot2Error> {
+        if read_u8(tag, 29)? != FRAMEBUFFER_TYPE_RGB {
+            return Ok(None);
+        }
+
+        let dimensions = (
+            u16::try_from(read_u32(tag, 20)?),
+            u16::try_from(read_u32(tag, 24)?),
+            u16::try_from(read_u32(tag, 16)?),
+        );
+
+        let (width, height, pitch) = match dimensions {
+            (Ok(width), Ok(height), Ok(pitch)) => (width, height, pitch),
+            _ => return Ok(None),
+        };
+
+        let mut framebuffer = StivaleFramebufferTag::new(
+            read_u64(tag, 8)?,
+            width,
+            height,
+            pitch,
+            read_u8(tag, 28)? as u16,
+        );
+
+        // The colour info starts after the reserved field, right behind the type.
+        let colour_info = 29 + 1 + mem::size_of::<u16>();
+
+        framebuffer.red_mask_shift = read_u8(tag, colour_info)?;
+        framebuffer.red_mask_size = read_u8(tag, colour_info + 1)?;
+        framebuffer.green_mask_shift = read_u8(tag, colour_info + 2)?;
+        framebuffer.green_mask_size = read_u8(tag, colour_info + 3)?;
+        framebuffer.blue_mask_shift = read_u8(tag, colour_info + 4)?;
+        framebuffer.blue_mask_size = read_u8(tag, colour_info + 5)?;
+
+        Ok(Some(framebuffer))
+    }
+}
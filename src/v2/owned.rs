@@ -0,0 +1,125 @@
+//! Owned copies of the variable length tags, so that kernels can keep their contents after
+//! reclaiming the bootloader reclaimable memory holding the tags.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::header::StivaleSmpHeaderTagFlags;
+use super::tag::*;
+
+/// An owned copy of a [StivaleModule].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedModule {
+    /// Address where this module has been loaded.
+    pub start: u64,
+    /// End address of this module.
+    pub end: u64,
+    /// The string of this module, with invalid UTF-8 replaced by `U+FFFD`.
+    pub string: String,
+}
+
+impl OwnedModule {
+    /// Returns the size of this module.
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+impl From<&StivaleModule> for OwnedModule {
+    fn from(module: &StivaleModule) -> Self {
+        Self {
+            start: module.start,
+            end: module.end,
+            string: module.as_str_lossy().to_string(),
+        }
+    }
+}
+
+/// An owned copy of a [StivaleSmpInfo], without the fields used to start the processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OwnedCpu {
+    /// The ACPI Processor UID of the processor.
+    pub processor_id: u32,
+    /// The physical ID of the processor: its LAPIC ID on x86 and its MPIDR on aarch64.
+    pub hardware_id: u64,
+}
+
+impl From<&StivaleSmpInfo> for OwnedCpu {
+    fn from(info: &StivaleSmpInfo) -> Self {
+        Self {
+            processor_id: info.processor_id(),
+            hardware_id: info.hardware_id(),
+        }
+    }
+}
+
+/// An owned copy of a [StivaleSmpTag]. Processors can only be started through the tag, so
+/// this is meant for keeping the processor topology once all of them were started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedSmpInfo {
+    /// The flags of the tag.
+    pub flags: StivaleSmpHeaderTagFlags,
+    /// The physical ID of the BSP: its LAPIC ID on x86 and its MPIDR on aarch64.
+    pub bsp_hardware_id: u64,
+    /// The processors, including the BSP.
+    pub cpus: Vec<OwnedCpu>,
+}
+
+impl StivaleMemoryMapTag {
+    /// Returns an owned copy of the memory map entries.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{
+    ///     round_trip, BootResponse, StivaleMemoryMapEntry, StivaleMemoryMapEntryType,
+    /// };
+    ///
+    /// let memory_map = [
+    ///     StivaleMemoryMapEntry::new(0x0, 0x9f000, StivaleMemoryMapEntryType::Usable),
+    ///     StivaleMemoryMapEntry::new(0x100000, 0x700000, StivaleMemoryMapEntryType::Usable),
+    /// ];
+    /// let response = BootResponse {
+    ///     memory_map: Some(&memory_map),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let buffer = Box::leak(Box::new([0u8; 512]));
+    /// let entries = round_trip(buffer, &response)
+    ///     .unwrap()
+    ///     .memory_map()
+    ///     .unwrap()
+    ///     .to_vec();
+    ///
+    /// assert_eq!(entries.len(), 2);
+    /// assert_eq!(entries[1].base, 0x100000);
+    /// ```
+    pub fn to_vec(&self) -> Vec<StivaleMemoryMapEntry> {
+        self.as_slice().to_vec()
+    }
+}
+
+impl StivaleModuleTag {
+    /// Returns owned copies of the modules. The contents of the modules are not copied, as they
+    /// do not lie in bootloader reclaimable memory.
+    pub fn to_vec(&self) -> Vec<OwnedModule> {
+        self.as_slice().iter().map(OwnedModule::from).collect()
+    }
+}
+
+impl StivalePmrsTag {
+    /// Returns an owned copy of the protected memory ranges.
+    pub fn to_vec(&self) -> Vec<StivalePmr> {
+        self.as_slice().to_vec()
+    }
+}
+
+impl StivaleSmpTag {
+    /// Returns an owned copy of the processor topology described by this tag.
+    pub fn to_owned(&self) -> OwnedSmpInfo {
+        OwnedSmpInfo {
+            flags: self.flags,
+            bsp_hardware_id: self.bsp_hardware_id(),
+            cpus: self.as_slice().iter().map(OwnedCpu::from).collect(),
+        }
+    }
+}
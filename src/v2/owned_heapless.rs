@@ -0,0 +1,136 @@
+//! Owned copies of the strings of the boot information in `heapless` collections, for kernels
+//! which need to keep them after reclaiming the bootloader reclaimable memory but have not set
+//! up a global allocator yet.
+
+use heapless::{String, Vec};
+
+use super::builder::BuildError;
+use super::cmdline::CommandLine;
+use super::error::Error;
+use super::tag::*;
+use super::StivaleStruct;
+
+/// Copies `s` into a `heapless` string, failing with [BuildError::StringTooLong] if it does not
+/// fit.
+fn copy_str<const N: usize>(s: &str) -> Result<String<N>, Error> {
+    let mut string = String::new();
+
+    string
+        .push_str(s)
+        .map_err(|_| Error::Build(BuildError::StringTooLong))?;
+
+    Ok(string)
+}
+
+/// A [StivaleModule] copied into a `heapless` string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaplessModule {
+    /// Address where this module has been loaded.
+    pub start: u64,
+    /// End address of this module.
+    pub end: u64,
+    /// The string of this module, cut at the first invalid UTF-8 byte.
+    pub string: String<128>,
+}
+
+impl HeaplessModule {
+    /// Returns the size of this module.
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+impl From<&StivaleModule> for HeaplessModule {
+    fn from(module: &StivaleModule) -> Self {
+        Self {
+            start: module.start,
+            end: module.end,
+            // The string field holds at most 127 bytes before its null terminator.
+            string: copy_str(module.as_str()).unwrap_or_default(),
+        }
+    }
+}
+
+/// A command line argument copied into `heapless` strings of up to `L` bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaplessArgument<const L: usize> {
+    /// The key of the argument, or the whole argument for flags.
+    pub key: String<L>,
+    /// The value of the argument, `None` for flags.
+    pub value: Option<String<L>>,
+}
+
+impl StivaleStruct {
+    /// Returns a copy of the bootloader brand, cut at the first invalid UTF-8 byte.
+    pub fn bootloader_brand_heapless(&self) -> String<64> {
+        copy_str(self.bootloader_brand()).unwrap_or_default()
+    }
+
+    /// Returns a copy of the bootloader version, cut at the first invalid UTF-8 byte.
+    pub fn bootloader_version_heapless(&self) -> String<64> {
+        copy_str(self.bootloader_version()).unwrap_or_default()
+    }
+}
+
+impl StivaleModuleTag {
+    /// Returns copies of up to `N` modules, failing with [BuildError::BufferTooSmall] if there
+    /// are more of them. The contents of the modules are not copied, as they do not lie in
+    /// bootloader reclaimable memory.
+    pub fn to_heapless<const N: usize>(&self) -> Result<Vec<HeaplessModule, N>, Error> {
+        self.as_slice().iter().map(HeaplessModule::from).try_fold(
+            Vec::new(),
+            |mut modules, module| {
+                modules
+                    .push(module)
+                    .map_err(|_| Error::Build(BuildError::BufferTooSmall))?;
+
+                Ok(modules)
+            },
+        )
+    }
+}
+
+impl CommandLine<'_> {
+    /// Returns copies of up to `N` arguments, with keys and values of up to `L` bytes. Fails
+    /// with [BuildError::BufferTooSmall] if there are more arguments, and with
+    /// [BuildError::StringTooLong] if a key or value is longer.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::CommandLine;
+    ///
+    /// let args = CommandLine::new("nosmp root=\"/dev/sda 1\"")
+    ///     .to_heapless::<8, 32>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(args[0].key, "nosmp");
+    /// assert_eq!(args[0].value, None);
+    /// assert_eq!(args[1].key, "root");
+    /// assert_eq!(args[1].value.as_deref(), Some("/dev/sda 1"));
+    /// ```
+    pub fn to_heapless<const N: usize, const L: usize>(
+        &self,
+    ) -> Result<Vec<HeaplessArgument<L>, N>, Error> {
+        self.args().try_fold(Vec::new(), |mut args, (key, value)| {
+            let arg = HeaplessArgument {
+                key: copy_str(key)?,
+                value: value.map(copy_str).transpose()?,
+            };
+
+            args.push(arg)
+                .map_err(|_| Error::Build(BuildError::BufferTooSmall))?;
+
+            Ok(args)
+        })
+    }
+}
+
+impl StivaleCommandLineTag {
+    /// Returns copies of up to `N` arguments, with keys and values of up to `L` bytes. See
+    /// [CommandLine::to_heapless].
+    pub fn to_heapless<const N: usize, const L: usize>(
+        &self,
+    ) -> Result<Vec<HeaplessArgument<L>, N>, Error> {
+        self.parse().to_heapless()
+    }
+}
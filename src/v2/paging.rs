@@ -0,0 +1,319 @@
+//! Mapping of the protected memory ranges with the page table mappers of the `x86_64` crate, so
+//! that a kernel requesting fully virtual mappings can recreate the mappings of its image with
+//! the permissions it was linked with, and construction of the higher half direct map.
+
+use core::ops::Range;
+
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{
+    FrameAllocator, Mapper, OffsetPageTable, Page, PageSize, PageTable, PageTableFlags, PhysFrame,
+    Size2MiB, Size4KiB,
+};
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::error::Error;
+use super::tag::{
+    StivaleKernelBaseAddressTag, StivaleMemoryMapTag, StivalePmr, StivalePmrsTag, StivaleVMapTag,
+};
+use super::StivaleStruct;
+
+/// Errors returned while mapping the protected memory ranges.
+#[derive(Debug)]
+pub enum PmrMapError {
+    /// A range is not page aligned.
+    Misaligned,
+    /// A range lies below the virtual base address of the kernel, or outside of the virtual or
+    /// physical address space.
+    InvalidAddress,
+    /// Mapping a page failed.
+    Map(MapToError<Size4KiB>),
+}
+
+impl From<MapToError<Size4KiB>> for PmrMapError {
+    fn from(error: MapToError<Size4KiB>) -> Self {
+        Self::Map(error)
+    }
+}
+
+/// Returns the page table flags matching the permissions of `pmr`. Ranges which are not
+/// executable are mapped with the no-execute bit, which requires `EFER.NXE` to be set.
+pub fn pmr_page_flags(pmr: &StivalePmr) -> PageTableFlags {
+    let mut flags = PageTableFlags::PRESENT;
+
+    if pmr.is_writable() {
+        flags |= PageTableFlags::WRITABLE;
+    }
+
+    if !pmr.is_executable() {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    flags
+}
+
+/// Maps every page of every protected memory range to the physical memory the kernel was
+/// loaded at, with the permissions of the range.
+///
+/// The TLB is not flushed, as this is meant to populate page tables which are not active yet.
+/// Flush the TLB or reload `CR3` before relying on the new mappings.
+///
+/// ## Safety
+/// `base` must describe where the kernel was actually loaded, as creating mappings to the
+/// wrong physical memory can cause undefined behaviour once they are used.
+pub unsafe fn map_pmrs<M, A>(
+    pmrs: &StivalePmrsTag,
+    base: &StivaleKernelBaseAddressTag,
+    mapper: &mut M,
+    frame_allocator: &mut A,
+) -> Result<(), PmrMapError>
+where
+    M: Mapper<Size4KiB>,
+    A: FrameAllocator<Size4KiB>,
+{
+    for pmr in pmrs.iter() {
+        if pmr.size == 0 {
+            continue;
+        }
+
+        let flags = pmr_page_flags(pmr);
+
+        let last = pmr
+            .base
+            .checked_add(pmr.size - 1)
+            .and_then(|last| VirtAddr::try_new(last).ok())
+            .ok_or(PmrMapError::InvalidAddress)?;
+        let first = VirtAddr::try_new(pmr.base).map_err(|_| PmrMapError::InvalidAddress)?;
+
+        let start =
+            Page::<Size4KiB>::from_start_address(first).map_err(|_| PmrMapError::Misaligned)?;
+        let end = Page::<Size4KiB>::containing_address(last);
+
+        for page in Page::range_inclusive(start, end) {
+            let phys_addr = base
+                .virt_to_phys(page.start_address().as_u64())
+                .and_then(|addr| PhysAddr::try_new(addr).ok())
+                .ok_or(PmrMapError::InvalidAddress)?;
+            let frame = PhysFrame::containing_address(phys_addr);
+
+            mapper.map_to(page, frame, flags, frame_allocator)?.ignore();
+        }
+    }
+
+    Ok(())
+}
+
+/// The page table flags of the direct map.
+const HHDM_FLAGS: PageTableFlags = PageTableFlags::PRESENT
+    .union(PageTableFlags::WRITABLE)
+    .union(PageTableFlags::NO_EXECUTE);
+
+/// The page table flags of the framebuffer in the direct map. With 4KiB pages, the `HUGE_PAGE`
+/// bit is the PAT bit, which together with `WRITE_THROUGH` selects the fifth PAT entry, set to
+/// write-combining by the bootloader.
+const HHDM_FRAMEBUFFER_FLAGS: PageTableFlags = HHDM_FLAGS
+    .union(PageTableFlags::WRITE_THROUGH)
+    .union(PageTableFlags::HUGE_PAGE);
+
+/// The amount of memory the bootloader maps in the direct map regardless of the memory map.
+const HHDM_LOW_MEMORY: u64 = 0x1_0000_0000;
+
+/// Builds a direct map matching the one set up by the bootloader in a fresh level 4 page table,
+/// and returns the frame of the table, to be loaded into `CR3`.
+///
+/// Like the bootloader's, the direct map covers the first 4GiB and every memory map entry at the
+/// offset given by the [StivaleVMapTag], using 2MiB pages where possible. The framebuffer is
+/// mapped write-combining and the rest write-back, all of it writable and not executable, which
+/// requires `EFER.NXE` to be set. The kernel image is not mapped, see [map_pmrs].
+///
+/// The page tables are allocated from `frame_allocator`, such as the
+/// [FrameAllocator](super::FrameAllocator) of the `pmm` feature, and accessed through the current
+/// direct map, so the frames it returns must lie in it.
+///
+/// Fails with [Error::InvalidValue] if the offset of the direct map is not canonical, or if a
+/// page of the memory map entries or the framebuffer does not fit in the direct map, with the
+/// physical address of the page.
+///
+/// ## Safety
+/// The frames returned by `frame_allocator` must be unused.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{build_hhdm, StivaleStruct};
+/// use x86_64::structures::paging::{FrameAllocator, Size4KiB};
+///
+/// unsafe fn page_tables(
+///     boot_info: &StivaleStruct,
+///     frames: &mut impl FrameAllocator<Size4KiB>,
+/// ) -> u64 {
+///     let pml4 = build_hhdm(boot_info, frames).unwrap();
+///
+///     // Map the kernel image with `map_pmrs` before loading the tables into CR3.
+///     pml4.start_address().as_u64()
+/// }
+/// ```
+pub unsafe fn build_hhdm<A>(
+    boot_info: &StivaleStruct,
+    frame_allocator: &mut A,
+) -> Result<PhysFrame, Error>
+where
+    A: FrameAllocator<Size4KiB>,
+{
+    let offset = boot_info
+        .vmap()
+        .ok_or(Error::missing::<StivaleVMapTag>())?
+        .address;
+    let memory_map = boot_info
+        .memory_map()
+        .ok_or(Error::missing::<StivaleMemoryMapTag>())?;
+
+    let framebuffer = boot_info.framebuffer().map(|tag| {
+        let start = match tag.framebuffer_addr {
+            addr if addr >= offset => addr - offset,
+            addr => addr,
+        };
+        let size = tag.framebuffer_pitch as u64 * tag.framebuffer_height as u64;
+
+        align_down(start)..align_up(start.saturating_add(size))
+    });
+
+    let offset_addr = VirtAddr::try_new(offset).map_err(|_| Error::InvalidValue(offset))?;
+
+    let pml4_frame = frame_allocator.allocate_frame().ok_or(Error::OutOfMemory)?;
+    let pml4_addr = pml4_frame.start_address().as_u64();
+    let pml4 = &mut *offset
+        .checked_add(pml4_addr)
+        .and_then(|addr| VirtAddr::try_new(addr).ok())
+        .ok_or(Error::InvalidValue(pml4_addr))?
+        .as_mut_ptr::<PageTable>();
+    pml4.zero();
+
+    let mut builder = HhdmBuilder {
+        mapper: OffsetPageTable::new(pml4, offset_addr),
+        frame_allocator,
+        offset,
+        framebuffer: framebuffer.clone().unwrap_or(0..0),
+    };
+
+    builder.map(0..HHDM_LOW_MEMORY)?;
+
+    for entry in memory_map.iter() {
+        builder.map(align_down(entry.base)..align_up(entry.base.saturating_add(entry.length)))?;
+    }
+
+    if let Some(framebuffer) = framebuffer {
+        for addr in framebuffer.step_by(Size4KiB::SIZE as usize) {
+            builder.map_4kib(addr, HHDM_FRAMEBUFFER_FLAGS)?;
+        }
+    }
+
+    Ok(pml4_frame)
+}
+
+struct HhdmBuilder<'a, A> {
+    mapper: OffsetPageTable<'a>,
+    frame_allocator: &'a mut A,
+    offset: u64,
+    framebuffer: Range<u64>,
+}
+
+impl<A: FrameAllocator<Size4KiB>> HhdmBuilder<'_, A> {
+    /// Maps the page aligned physical `range`, except the framebuffer.
+    unsafe fn map(&mut self, range: Range<u64>) -> Result<(), Error> {
+        let mut addr = range.start;
+
+        while addr < range.end {
+            let huge_end = addr.saturating_add(Size2MiB::SIZE);
+            let overlaps_framebuffer =
+                addr < self.framebuffer.end && self.framebuffer.start < huge_end;
+
+            if addr.is_multiple_of(Size2MiB::SIZE) && huge_end <= range.end && !overlaps_framebuffer
+            {
+                if self.map_2mib(addr)? {
+                    addr = huge_end;
+                    continue;
+                }
+
+                // Part of the page is already mapped with 4KiB pages, so map the rest likewise.
+                for page_addr in (addr..huge_end).step_by(Size4KiB::SIZE as usize) {
+                    self.map_4kib(page_addr, HHDM_FLAGS)?;
+                }
+
+                addr = huge_end;
+            } else {
+                if !self.framebuffer.contains(&addr) {
+                    self.map_4kib(addr, HHDM_FLAGS)?;
+                }
+
+                addr += Size4KiB::SIZE;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the address of the physical address `addr` in the direct map, along with `addr`
+    /// itself, failing with [Error::InvalidValue] if either is outside of the address space.
+    fn addresses(&self, addr: u64) -> Result<(VirtAddr, PhysAddr), Error> {
+        let virt_addr = self
+            .offset
+            .checked_add(addr)
+            .and_then(|virt_addr| VirtAddr::try_new(virt_addr).ok());
+        let phys_addr = PhysAddr::try_new(addr).ok();
+
+        virt_addr.zip(phys_addr).ok_or(Error::InvalidValue(addr))
+    }
+
+    /// Maps the 2MiB page at the physical address `addr`. Returns `false` if part of the page is
+    /// already mapped with 4KiB pages.
+    unsafe fn map_2mib(&mut self, addr: u64) -> Result<bool, Error> {
+        let (virt_addr, phys_addr) = self.addresses(addr)?;
+        let page = Page::<Size2MiB>::containing_address(virt_addr);
+        let frame = PhysFrame::<Size2MiB>::containing_address(phys_addr);
+
+        match self
+            .mapper
+            .map_to(page, frame, HHDM_FLAGS, self.frame_allocator)
+        {
+            Ok(flush) => {
+                flush.ignore();
+                Ok(true)
+            }
+            Err(MapToError::FrameAllocationFailed) => Err(Error::OutOfMemory),
+            Err(MapToError::ParentEntryHugePage) => Ok(true),
+            Err(MapToError::PageAlreadyMapped(_)) => Ok(false),
+        }
+    }
+
+    /// Maps the 4KiB page at the physical address `addr`. Pages which are already mapped, all of
+    /// them at the same offset, are left as is.
+    unsafe fn map_4kib(&mut self, addr: u64, flags: PageTableFlags) -> Result<(), Error> {
+        let (virt_addr, phys_addr) = self.addresses(addr)?;
+        let page = Page::<Size4KiB>::containing_address(virt_addr);
+        let frame = PhysFrame::<Size4KiB>::containing_address(phys_addr);
+
+        match self.mapper.map_to(page, frame, flags, self.frame_allocator) {
+            Ok(flush) => flush.ignore(),
+            Err(MapToError::FrameAllocationFailed) => return Err(Error::OutOfMemory),
+            Err(MapToError::ParentEntryHugePage) | Err(MapToError::PageAlreadyMapped(_)) => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn align_down(addr: u64) -> u64 {
+    addr & !(Size4KiB::SIZE - 1)
+}
+
+fn align_up(addr: u64) -> u64 {
+    addr.saturating_add(Size4KiB::SIZE - 1) & !(Size4KiB::SIZE - 1)
+}
+
+#[cfg(feature = "pmm")]
+unsafe impl FrameAllocator<Size4KiB> for super::pmm::FrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        let addr = self.alloc()?;
+
+        Some(PhysFrame::containing_address(PhysAddr::new(addr)))
+    }
+}
@@ -0,0 +1,105 @@
+//! Printing of panics through the output facilities provided by the bootloader, so that kernels
+//! do not have to write their own panic printing code.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use super::uart::UartWriter;
+use super::StivaleStruct;
+
+/// The boot information used to print panics, null if none was registered.
+static PANIC_BOOT_INFO: AtomicPtr<StivaleStruct> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Registers the boot information whose terminal and UART are used by [panic_print].
+pub fn register_panic_output(boot_info: &'static StivaleStruct) {
+    PANIC_BOOT_INFO.store(
+        boot_info as *const StivaleStruct as *mut StivaleStruct,
+        Ordering::Release,
+    );
+}
+
+/// Writer forwarding its output to the stivale2 terminal and to the UART, whichever are
+/// available.
+struct PanicWriter<T: Fn(&str)> {
+    term_write: Option<T>,
+    uart: Option<UartWriter>,
+}
+
+impl<T: Fn(&str)> Write for PanicWriter<T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(term_write) = &self.term_write {
+            term_write(s);
+        }
+
+        if let Some(uart) = &mut self.uart {
+            uart.write_str(s)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the panic message and location described by `info` to the terminal and the UART of
+/// the boot information registered with [register_panic_output]. Nothing is printed if no boot
+/// information was registered.
+///
+/// The terminal is written first, as it is what the user is most likely looking at, followed by
+/// the UART, which may be captured by a host. The message is followed by a backtrace, see
+/// [print_backtrace](super::print_backtrace).
+///
+/// ## Safety
+/// The boot information registered with [register_panic_output] must be the one provided by the
+/// bootloader, as its UART is written to, and the kernel must be built with frame pointers, see
+/// [Backtrace::current](super::Backtrace::current).
+///
+/// ## Example
+/// ```rust,ignore
+/// use core::panic::PanicInfo;
+///
+/// #[panic_handler]
+/// fn panic(info: &PanicInfo) -> ! {
+///     // SAFETY: The kernel is built with `-C force-frame-pointers=yes`, and registered the boot
+///     // information it was entered with.
+///     unsafe { stivale_boot::v2::panic_print(info) };
+///
+///     loop {}
+/// }
+/// ```
+pub unsafe fn panic_print(info: &PanicInfo) {
+    let boot_info = PANIC_BOOT_INFO.load(Ordering::Acquire);
+
+    if boot_info.is_null() {
+        return;
+    }
+
+    let boot_info = &*boot_info;
+
+    let mut writer = PanicWriter {
+        term_write: boot_info.terminal().map(|terminal| terminal.term_write()),
+        // SAFETY: The caller guarantees that the boot information was provided by the bootloader.
+        uart: boot_info.uart().map(|uart| UartWriter::new(uart)),
+    };
+
+    let _ = write!(writer, "\nkernel panic: {}", info.message());
+
+    if let Some(location) = info.location() {
+        let _ = write!(
+            writer,
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+
+    let _ = writeln!(writer);
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+    // SAFETY: The caller guarantees that the kernel is built with frame pointers.
+    let _ = super::backtrace::write_backtrace(
+        &mut writer,
+        super::backtrace::Backtrace::current(),
+        Some(boot_info),
+    );
+}
@@ -0,0 +1,359 @@
+//! A bitmap physical frame allocator initialized from the memory map, for kernels which do not
+//! need anything more elaborate to manage physical memory.
+
+use core::convert::TryFrom;
+
+use super::error::Error;
+use super::hhdm::to_hhdm;
+use super::tag::{StivaleMemoryMapEntryType, StivaleMemoryMapTag};
+use super::utils::addr_to_mut_ptr;
+
+/// The size of the frames handed out by the [FrameAllocator].
+pub const FRAME_SIZE: u64 = 0x1000;
+
+const BITS: usize = u64::BITS as usize;
+
+/// A physical frame allocator tracking each frame below the end of the usable memory with one bit
+/// of a bitmap, which is stored in usable memory itself.
+///
+/// Frames are identified by their physical address. The first frame is never handed out, so
+/// that a null address is never returned.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{
+///     register_hhdm_offset, round_trip, BootResponse, FrameAllocator, StivaleMemoryMapEntry,
+///     StivaleMemoryMapEntryType, FRAME_SIZE,
+/// };
+///
+/// // Direct map 64KiB of host memory at the physical address 1MiB.
+/// let memory = Box::leak(vec![0u8; 0x11000].into_boxed_slice());
+/// let aligned = (memory.as_ptr() as u64 + FRAME_SIZE - 1) & !(FRAME_SIZE - 1);
+/// // SAFETY: The frames of the memory map lie in the leaked memory once translated.
+/// unsafe { register_hhdm_offset(aligned - 0x100000) };
+///
+/// let memory_map = [StivaleMemoryMapEntry::new(
+///     0x100000,
+///     0x10000,
+///     StivaleMemoryMapEntryType::Usable,
+/// )];
+/// let response = BootResponse {
+///     memory_map: Some(&memory_map),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 4096]));
+/// let boot_info = round_trip(buffer, &response).unwrap();
+/// let mut frames = unsafe { FrameAllocator::new(boot_info.memory_map().unwrap()) }.unwrap();
+///
+/// // One of the 16 usable frames holds the bitmap.
+/// assert_eq!(frames.free_frames(), 15);
+///
+/// let run = frames.alloc_contiguous(4).unwrap();
+/// let frame = frames.alloc().unwrap();
+/// assert_eq!(run % FRAME_SIZE, 0);
+/// assert_eq!(frames.free_frames(), 10);
+///
+/// frames.free_contiguous(run, 4);
+/// frames.free(frame);
+/// assert_eq!(frames.free_frames(), 15);
+/// ```
+pub struct FrameAllocator {
+    bitmap: &'static mut [u64],
+    frame_count: usize,
+    free_frames: usize,
+    next: usize,
+}
+
+impl FrameAllocator {
+    /// Creates an allocator managing the usable entries of `memory_map`, the bitmap being stored
+    /// in the first usable entry large enough to hold it. The memory is accessed through the
+    /// direct map if one was registered with [register_hhdm_offset](super::register_hhdm_offset),
+    /// and through the identity mapping otherwise.
+    ///
+    /// Returns [Error::OutOfMemory] if no usable entry can hold the bitmap.
+    ///
+    /// ## Safety
+    /// The usable memory must be mapped and unused, and must not be handed out by anything else
+    /// while the allocator exists.
+    pub unsafe fn new(memory_map: &StivaleMemoryMapTag) -> Result<Self, Error> {
+        let usable_frames = || {
+            memory_map
+                .iter()
+                .filter(|entry| entry.entry_type() == StivaleMemoryMapEntryType::Usable)
+                .filter_map(|entry| {
+                    let start = align_up(entry.base)? / FRAME_SIZE;
+                    let end = entry.end_address() / FRAME_SIZE;
+
+                    Some(start..end).filter(|frames| !frames.is_empty())
+                })
+        };
+
+        let frame_count = usable_frames().map(|frames| frames.end).max().unwrap_or(0);
+        let frame_count = usize::try_from(frame_count).map_err(|_| Error::LimitExceeded)?;
+        let words = frame_count.div_ceil(BITS);
+        let bitmap_frames = ((words * 8) as u64).div_ceil(FRAME_SIZE);
+
+        let bitmap_start = usable_frames()
+            .find(|frames| frames.end - frames.start >= bitmap_frames)
+            .map(|frames| frames.start)
+            .ok_or(Error::OutOfMemory)?;
+
+        let bitmap = core::slice::from_raw_parts_mut(
+            addr_to_mut_ptr::<u64>(to_hhdm(bitmap_start * FRAME_SIZE)),
+            words,
+        );
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = Self {
+            bitmap,
+            frame_count,
+            free_frames: 0,
+            next: 0,
+        };
+
+        for frames in usable_frames() {
+            for frame in frames {
+                allocator.set_free(frame as usize);
+            }
+        }
+
+        for frame in bitmap_start..bitmap_start + bitmap_frames {
+            allocator.set_used(frame as usize);
+        }
+
+        if frame_count != 0 {
+            allocator.set_used(0);
+        }
+
+        Ok(allocator)
+    }
+
+    /// Returns the number of frames tracked by the allocator, which is the number of frames
+    /// below the end of the last usable entry.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the number of frames which can still be allocated.
+    pub fn free_frames(&self) -> usize {
+        self.free_frames
+    }
+
+    /// Allocates a frame, returning its physical address, or `None` if no frame is free.
+    pub fn alloc(&mut self) -> Option<u64> {
+        self.alloc_contiguous(1)
+    }
+
+    /// Allocates `count` physically contiguous frames, returning the physical address of the
+    /// first one, or `None` if no run of `count` free frames exists.
+    pub fn alloc_contiguous(&mut self, count: usize) -> Option<u64> {
+        if count == 0 || count > self.free_frames {
+            return None;
+        }
+
+        // Start searching after the last allocation, wrapping around once.
+        let start = self
+            .find_run(self.next, self.frame_count, count)
+            .or_else(|| {
+                let to = self.next.saturating_add(count - 1).min(self.frame_count);
+                self.find_run(0, to, count)
+            })?;
+
+        for frame in start..start + count {
+            self.set_used(frame);
+        }
+
+        self.next = start + count;
+        Some(start as u64 * FRAME_SIZE)
+    }
+
+    /// Frees the frame at the physical address `addr`.
+    ///
+    /// ## Panics
+    /// Panics if `addr` is not the address of a frame tracked by the allocator.
+    pub fn free(&mut self, addr: u64) {
+        self.free_contiguous(addr, 1)
+    }
+
+    /// Frees the `count` contiguous frames starting at the physical address `addr`.
+    ///
+    /// ## Panics
+    /// Panics if any of the frames is not tracked by the allocator.
+    pub fn free_contiguous(&mut self, addr: u64, count: usize) {
+        assert_eq!(addr % FRAME_SIZE, 0, "misaligned frame address");
+
+        let start = (addr / FRAME_SIZE) as usize;
+        assert!(
+            start != 0 && start.saturating_add(count) <= self.frame_count,
+            "frame not tracked by the allocator"
+        );
+
+        for frame in start..start + count {
+            self.set_free(frame);
+        }
+    }
+
+    /// Returns whether the frame at the physical address `addr` is free.
+    pub fn is_free(&self, addr: u64) -> bool {
+        let frame = (addr / FRAME_SIZE) as usize;
+
+        frame < self.frame_count && !self.is_used(frame)
+    }
+
+    fn find_run(&self, from: usize, to: usize, count: usize) -> Option<usize> {
+        let mut run_start = from;
+        let mut frame = from;
+
+        while frame < to {
+            // Skip fully used words at once.
+            if frame.is_multiple_of(BITS) && self.bitmap[frame / BITS] == u64::MAX {
+                frame += BITS;
+                run_start = frame;
+                continue;
+            }
+
+            if self.is_used(frame) {
+                run_start = frame + 1;
+            } else if frame + 1 - run_start == count {
+                return Some(run_start);
+            }
+
+            frame += 1;
+        }
+
+        None
+    }
+
+    fn is_used(&self, frame: usize) -> bool {
+        self.bitmap[frame / BITS] & (1 << (frame % BITS)) != 0
+    }
+
+    fn set_used(&mut self, frame: usize) {
+        if !self.is_used(frame) {
+            self.bitmap[frame / BITS] |= 1 << (frame % BITS);
+            self.free_frames -= 1;
+        }
+    }
+
+    fn set_free(&mut self, frame: usize) {
+        if self.is_used(frame) {
+            self.bitmap[frame / BITS] &= !(1 << (frame % BITS));
+            self.free_frames += 1;
+        }
+    }
+}
+
+fn align_up(addr: u64) -> Option<u64> {
+    Some(addr.checked_add(FRAME_SIZE - 1)? & !(FRAME_SIZE - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Range;
+    use std::vec;
+
+    use super::*;
+
+    /// Returns an allocator tracking `frame_count` frames, of which the frames in `free` are
+    /// free, without going through the memory map.
+    fn allocator(frame_count: usize, free: Range<usize>) -> FrameAllocator {
+        let mut allocator = FrameAllocator {
+            bitmap: vec![u64::MAX; frame_count.div_ceil(BITS)].leak(),
+            frame_count,
+            free_frames: 0,
+            next: 0,
+        };
+
+        for frame in free {
+            allocator.set_free(frame);
+        }
+
+        allocator
+    }
+
+    #[test]
+    fn alloc_contiguous_continues_after_last_allocation() {
+        let mut frames = allocator(16, 1..16);
+
+        assert_eq!(frames.alloc_contiguous(4), Some(FRAME_SIZE));
+        frames.free_contiguous(FRAME_SIZE, 4);
+
+        // The freed frames are only reused once the search wraps around.
+        assert_eq!(frames.alloc_contiguous(4), Some(5 * FRAME_SIZE));
+        assert_eq!(frames.free_frames(), 11);
+    }
+
+    #[test]
+    fn alloc_contiguous_wraps_around() {
+        let mut frames = allocator(16, 1..16);
+
+        assert_eq!(frames.alloc_contiguous(8), Some(FRAME_SIZE));
+        frames.free_contiguous(FRAME_SIZE, 8);
+
+        // The only run of 12 frames starts before the last allocation and extends past it.
+        assert_eq!(frames.alloc_contiguous(12), Some(FRAME_SIZE));
+        assert_eq!(frames.free_frames(), 3);
+
+        assert_eq!(frames.alloc_contiguous(3), Some(13 * FRAME_SIZE));
+        assert_eq!(frames.alloc(), None);
+    }
+
+    #[test]
+    fn alloc_contiguous_needs_a_run() {
+        let mut frames = allocator(16, 1..16);
+
+        for frame in (2..16).step_by(2) {
+            frames.set_used(frame);
+        }
+
+        assert_eq!(frames.free_frames(), 8);
+        assert_eq!(frames.alloc_contiguous(0), None);
+        assert_eq!(frames.alloc_contiguous(2), None);
+        assert_eq!(frames.alloc_contiguous(9), None);
+        assert_eq!(frames.free_frames(), 8);
+    }
+
+    #[test]
+    fn alloc_skips_used_words() {
+        let mut frames = allocator(200, 130..200);
+
+        assert_eq!(frames.alloc(), Some(130 * FRAME_SIZE));
+        assert!(!frames.is_free(130 * FRAME_SIZE));
+        assert!(frames.is_free(131 * FRAME_SIZE));
+        assert!(!frames.is_free(200 * FRAME_SIZE));
+    }
+
+    #[test]
+    fn free_contiguous_up_to_the_last_frame() {
+        let mut frames = allocator(16, 1..16);
+
+        assert_eq!(frames.alloc_contiguous(15), Some(FRAME_SIZE));
+        frames.free_contiguous(FRAME_SIZE, 15);
+        assert_eq!(frames.free_frames(), 15);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame not tracked by the allocator")]
+    fn free_contiguous_past_the_last_frame() {
+        allocator(16, 1..16).free_contiguous(8 * FRAME_SIZE, 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame not tracked by the allocator")]
+    fn free_contiguous_with_overflowing_count() {
+        allocator(16, 1..16).free_contiguous(FRAME_SIZE, usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame not tracked by the allocator")]
+    fn free_first_frame() {
+        allocator(16, 1..16).free(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "misaligned frame address")]
+    fn free_misaligned_frame() {
+        allocator(16, 1..16).free(FRAME_SIZE + 1);
+    }
+}
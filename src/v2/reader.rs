@@ -0,0 +1,547 @@
+//! Zero-copy reader for boot structures living in a byte buffer, such as the memory of a guest
+//! inspected by an emulator or a debugger. Addresses found in the structure are never
+//! dereferenced, they are translated to offsets in the buffer by a user supplied translator
+//! instead.
+
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::mem;
+
+use super::builder::StivaleArrayTag;
+use super::tag::*;
+
+/// Translates the addresses found in the boot structure to offsets in the buffer being read.
+pub trait AddressTranslator {
+    /// Returns the offset of `address` in the buffer, or `None` if it is not part of it.
+    fn translate(&self, address: u64) -> Option<usize>;
+}
+
+impl<F: Fn(u64) -> Option<usize>> AddressTranslator for F {
+    fn translate(&self, address: u64) -> Option<usize> {
+        self(address)
+    }
+}
+
+/// Translator for a buffer holding the contiguous range of memory starting at `base`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffsetTranslator {
+    /// The address of the first byte of the buffer.
+    pub base: u64,
+}
+
+impl AddressTranslator for OffsetTranslator {
+    fn translate(&self, address: u64) -> Option<usize> {
+        usize::try_from(address.checked_sub(self.base)?).ok()
+    }
+}
+
+/// A range of addresses backed by a range of the buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TranslationRegion {
+    /// The first address of the range.
+    pub address: u64,
+    /// The offset in the buffer the first address is found at.
+    pub offset: usize,
+    /// The length of the range in bytes.
+    pub length: usize,
+}
+
+/// Translator backed by a table of regions, for buffers made of several ranges of memory, such
+/// as the separately dumped boot structure, command line and modules of a guest concatenated
+/// together.
+#[derive(Debug, Clone, Copy)]
+pub struct RegionTranslator<'t> {
+    regions: &'t [TranslationRegion],
+}
+
+impl<'t> RegionTranslator<'t> {
+    /// Creates a new translator over `regions`. The first region containing an address is used
+    /// to translate it.
+    pub fn new(regions: &'t [TranslationRegion]) -> Self {
+        Self { regions }
+    }
+}
+
+impl AddressTranslator for RegionTranslator<'_> {
+    fn translate(&self, address: u64) -> Option<usize> {
+        self.regions.iter().find_map(|region| {
+            let delta = usize::try_from(address.checked_sub(region.address)?).ok()?;
+
+            if delta < region.length {
+                region.offset.checked_add(delta)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Marker trait for the types which are valid for any bit pattern, and thus can be copied out
+/// of an untrusted buffer.
+///
+/// # Safety
+/// The type must not contain any padding, references, enums or other types with invalid
+/// bit patterns.
+pub unsafe trait Plain: Sized {}
+
+macro_rules! impl_plain {
+    ($($name:ty),*) => {
+        $(unsafe impl Plain for $name {})*
+    };
+}
+
+impl_plain!(
+    u8,
+    StivaleFramebufferTag,
+    StivaleTerminalTag,
+    StivaleRsdpTag,
+    StivaleEpochTag,
+    StivaleFirmwareTag,
+    StivaleEfiSystemTableTag,
+    StivaleKernelFileTag,
+    StivaleKernelSlideTag,
+    StivaleCommandLineTag,
+    StivaleSmbiosTag,
+    StivaleModule,
+    StivaleSmpInfo,
+    StivalePxeInfoTag,
+    StivaleUartTag,
+    StivaleDeviceTreeTag,
+    StivaleVMapTag,
+    StivaleKernelFileV2Tag,
+    StivalePmr,
+    StivaleKernelBaseAddressTag
+);
+
+/// Copies a `T` out of `bytes` at `offset`.
+pub(super) fn read<T: Plain>(bytes: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(mem::size_of::<T>())?;
+    let bytes = bytes.get(offset..end)?;
+
+    // SAFETY: The bounds were checked above, the read does not require alignment and `T` is
+    // valid for any bit pattern.
+    Some(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(8)?)?);
+    Some(u64::from_ne_bytes(word))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let mut word = [0; 4];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(4)?)?);
+    Some(u32::from_ne_bytes(word))
+}
+
+/// Returns the bytes of the null-terminated string starting at `offset`, without the
+/// terminator.
+fn c_string(bytes: &[u8], offset: usize) -> Option<&[u8]> {
+    let string = bytes.get(offset..)?;
+    let length = string.iter().position(|c| *c == 0)?;
+
+    Some(&string[..length])
+}
+
+/// Reader over a [StivaleStruct](super::StivaleStruct) located in a byte buffer.
+pub struct StivaleStructReader<'a, T: AddressTranslator> {
+    memory: &'a [u8],
+    translator: T,
+    /// Offset of the stivale struct in `memory`.
+    offset: usize,
+}
+
+impl<'a> StivaleStructReader<'a, OffsetTranslator> {
+    /// Creates a new reader for the stivale struct at the start of `bytes`, in which every
+    /// address is an offset from the start of the buffer. No pointer is ever dereferenced, so
+    /// this is suited to fuzzing and property testing the tag walking on the host.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{StivaleStructReader, StivaleUartTag, StivaleTag};
+    ///
+    /// let mut bytes = [0u8; 160];
+    /// bytes[..6].copy_from_slice(b"Limine");
+    /// // The tag list starts right after the struct.
+    /// bytes[128..136].copy_from_slice(&136u64.to_ne_bytes());
+    /// bytes[136..144].copy_from_slice(&StivaleUartTag::IDENTIFIER.to_ne_bytes());
+    /// bytes[152..160].copy_from_slice(&0x3f8u64.to_ne_bytes());
+    ///
+    /// let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+    /// assert_eq!(reader.bootloader_brand(), b"Limine");
+    /// assert_eq!(reader.tag::<StivaleUartTag>().unwrap().address, 0x3f8);
+    /// ```
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        Self::new(bytes, 0, OffsetTranslator { base: 0 })
+    }
+}
+
+impl<'a, T: AddressTranslator> StivaleStructReader<'a, T> {
+    /// Creates a new reader over `memory` for the stivale struct at `address`. Returns `None`
+    /// if the struct does not fit in the buffer.
+    pub fn new(memory: &'a [u8], address: u64, translator: T) -> Option<Self> {
+        let offset = translator.translate(address)?;
+
+        // Make sure the whole struct is part of the buffer.
+        read_u64(memory, offset.checked_add(128)?)?;
+
+        Some(Self {
+            memory,
+            translator,
+            offset,
+        })
+    }
+
+    /// Returns the bootloader brand, without the null terminator.
+    pub fn bootloader_brand(&self) -> &'a [u8] {
+        let brand = &self.memory[self.offset..self.offset + 64];
+        let length = brand.iter().position(|c| *c == 0).unwrap_or(64);
+
+        &brand[..length]
+    }
+
+    /// Returns the bootloader version, without the null terminator.
+    pub fn bootloader_version(&self) -> &'a [u8] {
+        let version = &self.memory[self.offset + 64..self.offset + 128];
+        let length = version.iter().position(|c| *c == 0).unwrap_or(64);
+
+        &version[..length]
+    }
+
+    /// Returns an iterator over all the tags.
+    pub fn tags(&self) -> RawTagIter<'a, '_, T> {
+        RawTagIter {
+            reader: self,
+            next: read_u64(self.memory, self.offset + 128).unwrap_or(0),
+            remaining: self.memory.len() / mem::size_of::<StivaleTagHeader>(),
+        }
+    }
+
+    /// Returns the first tag with the provided identifier.
+    pub fn find(&self, identifier: u64) -> Option<RawTag<'a>> {
+        self.tags().find(|tag| tag.identifier == identifier)
+    }
+
+    /// Returns a copy of the tag of type `U`.
+    pub fn tag<U: StivaleTag + Plain>(&self) -> Option<U> {
+        read(self.find(U::IDENTIFIER)?.data, 0)
+    }
+
+    /// Returns a reader over the entries of the variable length tag of type `U`.
+    pub fn array<U>(&self) -> Option<ArrayReader<'a, U::Entry>>
+    where
+        U: StivaleArrayTag + ?Sized,
+        U::Entry: Plain,
+    {
+        ArrayReader::new(self.find(U::IDENTIFIER)?.data, U::FIXED_SIZE)
+    }
+
+    /// Returns a reader over the entries of the memory map.
+    pub fn memory_map(&self) -> Option<ArrayReader<'a, RawMemoryMapEntry>> {
+        ArrayReader::new(
+            self.find(StivaleMemoryMapTag::IDENTIFIER)?.data,
+            <StivaleMemoryMapTag as StivaleArrayTag>::FIXED_SIZE,
+        )
+    }
+
+    /// Returns the command line, without the null terminator.
+    pub fn command_line(&self) -> Option<&'a [u8]> {
+        let tag = self.tag::<StivaleCommandLineTag>()?;
+        self.string_at(tag.command_line)
+    }
+
+    /// Returns the null-terminated string at `address`, without the null terminator.
+    pub fn string_at(&self, address: u64) -> Option<&'a [u8]> {
+        c_string(self.memory, self.translator.translate(address)?)
+    }
+
+    /// Returns the `length` bytes at `address`.
+    pub fn bytes_at(&self, address: u64, length: usize) -> Option<&'a [u8]> {
+        let offset = self.translator.translate(address)?;
+        self.memory.get(offset..offset.checked_add(length)?)
+    }
+}
+
+/// A tag as found in the buffer.
+#[derive(Clone, Copy)]
+pub struct RawTag<'a> {
+    /// The identifier of the tag.
+    pub identifier: u64,
+    /// The address of the tag.
+    pub address: u64,
+    /// The bytes of the buffer starting at the tag, up to the end of the buffer.
+    pub data: &'a [u8],
+}
+
+/// Iterator over the tags of a [StivaleStructReader].
+pub struct RawTagIter<'a, 'r, T: AddressTranslator> {
+    reader: &'r StivaleStructReader<'a, T>,
+    /// The address of the tag we are about to read.
+    next: u64,
+    /// Upper bound on the amount of tags left, guarding against loops in the chain.
+    remaining: usize,
+}
+
+impl<'a, 'r, T: AddressTranslator> Iterator for RawTagIter<'a, 'r, T> {
+    type Item = RawTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == 0 || self.remaining == 0 {
+            return None;
+        }
+
+        let address = self.next;
+        let offset = self.reader.translator.translate(address)?;
+        let data = self.reader.memory.get(offset..)?;
+
+        let identifier = read_u64(data, 0)?;
+        self.next = read_u64(data, 8)?;
+        self.remaining -= 1;
+
+        Some(RawTag {
+            identifier,
+            address,
+            data,
+        })
+    }
+}
+
+/// A memory map entry with its type left undecoded, as read from an untrusted buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawMemoryMapEntry {
+    /// Physical address of base of the memory section.
+    pub base: u64,
+    /// Length of this memory section.
+    pub length: u64,
+    /// The raw type of this memory map entry.
+    pub entry_type: u32,
+}
+
+/// Types which can be read as an element of an [ArrayReader].
+pub trait ArrayEntry: Sized {
+    /// The size of the entry in the buffer.
+    const SIZE: usize;
+
+    /// Reads the entry at `offset` in `bytes`.
+    fn read(bytes: &[u8], offset: usize) -> Option<Self>;
+}
+
+impl<P: Plain> ArrayEntry for P {
+    const SIZE: usize = mem::size_of::<P>();
+
+    fn read(bytes: &[u8], offset: usize) -> Option<Self> {
+        read(bytes, offset)
+    }
+}
+
+impl ArrayEntry for RawMemoryMapEntry {
+    const SIZE: usize = mem::size_of::<StivaleMemoryMapEntry>();
+
+    fn read(bytes: &[u8], offset: usize) -> Option<Self> {
+        Some(Self {
+            base: read_u64(bytes, offset)?,
+            length: read_u64(bytes, offset + 8)?,
+            entry_type: read_u32(bytes, offset + 16)?,
+        })
+    }
+}
+
+/// Reader over the entries of a variable length tag. Entries are copied out of the buffer.
+#[derive(Clone)]
+pub struct ArrayReader<'a, E: ArrayEntry> {
+    /// The bytes of the array.
+    entries: &'a [u8],
+    count: usize,
+    /// The index of the entry that we are about to read.
+    current: usize,
+    phantom: PhantomData<E>,
+}
+
+impl<'a, E: ArrayEntry> ArrayReader<'a, E> {
+    pub(super) fn new(tag: &'a [u8], fixed_size: usize) -> Option<Self> {
+        let count = read_u64(tag, fixed_size - 8)? as usize;
+        let entries = tag.get(fixed_size..)?;
+
+        // Only expose the entries which are actually part of the buffer.
+        let count = count.min(entries.len() / E::SIZE);
+
+        Some(Self {
+            entries,
+            count,
+            current: 0,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the total amount of entries.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns a copy of the entry at `index`.
+    pub fn get(&self, index: usize) -> Option<E> {
+        if index < self.count {
+            E::read(self.entries, index * E::SIZE)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, E: ArrayEntry> Iterator for ArrayReader<'a, E> {
+    type Item = E;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.get(self.current)?;
+        self.current += 1;
+
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The offset of the first tag, right after the stivale struct.
+    const FIRST_TAG: usize = 136;
+
+    fn write_u64(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_ne_bytes());
+    }
+
+    /// Writes a tag header at `offset`, linking it to the tag at `next`.
+    fn write_tag(bytes: &mut [u8], offset: usize, identifier: u64, next: u64) {
+        write_u64(bytes, offset, identifier);
+        write_u64(bytes, offset + 8, next);
+    }
+
+    #[test]
+    fn struct_must_fit() {
+        assert!(StivaleStructReader::from_bytes(&[0; 135]).is_none());
+        assert!(StivaleStructReader::from_bytes(&[0; 136]).is_some());
+
+        let translator = OffsetTranslator { base: 0x1000 };
+        assert!(StivaleStructReader::new(&[0; 136], 0x10, translator).is_none());
+    }
+
+    #[test]
+    fn tag_pointing_at_itself() {
+        let mut bytes = [0u8; 160];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(
+            &mut bytes,
+            FIRST_TAG,
+            StivaleUartTag::IDENTIFIER,
+            FIRST_TAG as u64,
+        );
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+
+        assert_eq!(reader.tags().count(), bytes.len() / 16);
+        assert!(reader.find(StivaleEpochTag::IDENTIFIER).is_none());
+        assert_eq!(reader.tag::<StivaleUartTag>().unwrap().address, 0);
+    }
+
+    #[test]
+    fn tags_pointing_at_each_other() {
+        let mut bytes = [0u8; 256];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(&mut bytes, FIRST_TAG, StivaleUartTag::IDENTIFIER, 160);
+        write_tag(
+            &mut bytes,
+            160,
+            StivaleEpochTag::IDENTIFIER,
+            FIRST_TAG as u64,
+        );
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        let identifiers = reader.tags().map(|tag| tag.identifier);
+
+        assert!(identifiers.take(4).eq([
+            StivaleUartTag::IDENTIFIER,
+            StivaleEpochTag::IDENTIFIER,
+            StivaleUartTag::IDENTIFIER,
+            StivaleEpochTag::IDENTIFIER,
+        ]));
+        assert_eq!(reader.tags().count(), bytes.len() / 16);
+        assert!(reader.find(StivaleRsdpTag::IDENTIFIER).is_none());
+    }
+
+    #[test]
+    fn tag_outside_of_the_buffer() {
+        let mut bytes = [0u8; 160];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(&mut bytes, FIRST_TAG, StivaleUartTag::IDENTIFIER, u64::MAX);
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.tags().count(), 1);
+
+        write_u64(&mut bytes, 128, 0x1000);
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.tags().count(), 0);
+    }
+
+    #[test]
+    fn truncated_tag_header() {
+        let mut bytes = [0u8; FIRST_TAG + 12];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_u64(&mut bytes, FIRST_TAG, StivaleUartTag::IDENTIFIER);
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.tags().count(), 0);
+    }
+
+    #[test]
+    fn truncated_tag_body() {
+        let mut bytes = [0u8; FIRST_TAG + 20];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(&mut bytes, FIRST_TAG, StivaleUartTag::IDENTIFIER, 0);
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.tags().count(), 1);
+        assert!(reader.tag::<StivaleUartTag>().is_none());
+    }
+
+    #[test]
+    fn truncated_memory_map() {
+        let mut bytes = [0u8; FIRST_TAG + 24 + 24 + 12];
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(&mut bytes, FIRST_TAG, StivaleMemoryMapTag::IDENTIFIER, 0);
+        write_u64(&mut bytes, FIRST_TAG + 16, u64::MAX);
+        write_u64(&mut bytes, FIRST_TAG + 24, 0x100000);
+        write_u64(&mut bytes, FIRST_TAG + 32, 0x1000);
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        let memory_map = reader.memory_map().unwrap();
+
+        // Only the entry which fits in the buffer is exposed, despite the count.
+        assert_eq!(memory_map.len(), 1);
+        assert_eq!(memory_map.get(0).unwrap().base, 0x100000);
+        assert!(memory_map.get(1).is_none());
+    }
+
+    #[test]
+    fn unterminated_command_line() {
+        let mut bytes = [b'x'; FIRST_TAG + 24 + 8];
+        bytes[..128].fill(0);
+        write_u64(&mut bytes, 128, FIRST_TAG as u64);
+        write_tag(&mut bytes, FIRST_TAG, StivaleCommandLineTag::IDENTIFIER, 0);
+        write_u64(&mut bytes, FIRST_TAG + 16, FIRST_TAG as u64 + 24);
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.command_line(), None);
+
+        let last = bytes.len() - 1;
+        bytes[last] = 0;
+
+        let reader = StivaleStructReader::from_bytes(&bytes).unwrap();
+        assert_eq!(reader.command_line(), Some(&b"xxxxxxx"[..]));
+    }
+}
@@ -0,0 +1,257 @@
+//! Human readable reports of boot structures found in raw memory dumps, such as the ones taken
+//! with GDB or the QEMU monitor, for debugging what a kernel is being handed by its bootloader.
+
+use core::fmt;
+
+use std::string::{String, ToString};
+
+use super::builder::StivaleArrayTag;
+use super::cmdline::ByteSize;
+use super::reader::{
+    read, AddressTranslator, ArrayReader, OffsetTranslator, RawMemoryMapEntry, RawTag,
+    StivaleStructReader,
+};
+use super::tag::*;
+use super::utils::LossyStr;
+
+/// Report of the boot structure read by a [StivaleStructReader], listing every tag along with
+/// its decoded contents.
+pub struct BootReport<'r, 'a, T: AddressTranslator> {
+    reader: &'r StivaleStructReader<'a, T>,
+}
+
+impl<'r, 'a, T: AddressTranslator> BootReport<'r, 'a, T> {
+    /// Creates a new report over the boot structure read by `reader`.
+    pub fn new(reader: &'r StivaleStructReader<'a, T>) -> Self {
+        Self { reader }
+    }
+}
+
+fn memory_type_name(entry_type: u32) -> &'static str {
+    match entry_type {
+        1 => "usable",
+        2 => "reserved",
+        3 => "ACPI reclaimable",
+        4 => "ACPI NVS",
+        5 => "bad memory",
+        0x1000 => "bootloader reclaimable",
+        0x1001 => "kernel and modules",
+        0x1002 => "framebuffer",
+        _ => "unknown",
+    }
+}
+
+/// Writes the listed fields of the tag found in `tag`, as hexadecimal numbers.
+macro_rules! write_fields {
+    ($f:expr, $tag:expr, $name:ty { $($field:ident),* }) => {
+        match read::<$name>($tag.data, 0) {
+            Some(tag) => {
+                $(writeln!($f, "    {}: {:#x}", stringify!($field), tag.$field)?;)*
+            }
+            None => writeln!($f, "    (truncated)")?,
+        }
+    };
+}
+
+fn array<'a, U: StivaleArrayTag + ?Sized>(tag: &RawTag<'a>) -> Option<ArrayReader<'a, U::Entry>>
+where
+    U::Entry: super::reader::Plain,
+{
+    ArrayReader::new(tag.data, U::FIXED_SIZE)
+}
+
+impl<T: AddressTranslator> BootReport<'_, '_, T> {
+    fn write_tag(&self, f: &mut fmt::Formatter<'_>, tag: &RawTag<'_>) -> fmt::Result {
+        match tag.identifier {
+            StivaleCommandLineTag::IDENTIFIER => match self.reader.command_line() {
+                Some(command_line) => {
+                    writeln!(f, "    {:?}", LossyStr::from_c_bytes(command_line))?
+                }
+                None => writeln!(f, "    (string outside of the dump)")?,
+            },
+            StivaleMemoryMapTag::IDENTIFIER => {
+                let entries = ArrayReader::<RawMemoryMapEntry>::new(
+                    tag.data,
+                    <StivaleMemoryMapTag as StivaleArrayTag>::FIXED_SIZE,
+                );
+
+                for entry in entries.into_iter().flatten() {
+                    writeln!(
+                        f,
+                        "    {:#018x}-{:#018x} {}, {}",
+                        entry.base,
+                        entry.base.wrapping_add(entry.length),
+                        memory_type_name(entry.entry_type),
+                        ByteSize(entry.length)
+                    )?;
+                }
+            }
+            StivaleFramebufferTag::IDENTIFIER => match read::<StivaleFramebufferTag>(tag.data, 0) {
+                Some(fb) => writeln!(
+                    f,
+                    "    {}x{} at {:#x}, pitch {}, {} bpp, memory model {}",
+                    fb.framebuffer_width,
+                    fb.framebuffer_height,
+                    fb.framebuffer_addr,
+                    fb.framebuffer_pitch,
+                    fb.framebuffer_bpp,
+                    fb.memory_model
+                )?,
+                None => writeln!(f, "    (truncated)")?,
+            },
+            StivaleEdidInfoTag::IDENTIFIER => {
+                let len = array::<StivaleEdidInfoTag>(tag).map_or(0, |edid| edid.len());
+                writeln!(f, "    {} bytes", len)?;
+            }
+            StivaleModuleTag::IDENTIFIER => {
+                for module in array::<StivaleModuleTag>(tag).into_iter().flatten() {
+                    writeln!(
+                        f,
+                        "    {:#018x}-{:#018x} {:?}",
+                        module.start,
+                        module.end,
+                        module.as_str_lossy()
+                    )?;
+                }
+            }
+            StivaleSmpTag::IDENTIFIER => {
+                for cpu in array::<StivaleSmpTag>(tag).into_iter().flatten() {
+                    writeln!(
+                        f,
+                        "    CPU {}: {} {}",
+                        cpu.processor_id(),
+                        StivaleSmpInfo::HARDWARE_ID_NAME,
+                        cpu.hardware_id()
+                    )?;
+                }
+            }
+            StivalePmrsTag::IDENTIFIER => {
+                for pmr in array::<StivalePmrsTag>(tag).into_iter().flatten() {
+                    writeln!(
+                        f,
+                        "    {:#018x}-{:#018x} {:?}",
+                        pmr.base,
+                        pmr.base.wrapping_add(pmr.size),
+                        pmr.permissions
+                    )?;
+                }
+            }
+            StivalePxeInfoTag::IDENTIFIER => match read::<StivalePxeInfoTag>(tag.data, 0) {
+                Some(pxe) => writeln!(f, "    {}", pxe)?,
+                None => writeln!(f, "    (truncated)")?,
+            },
+            StivaleTerminalTag::IDENTIFIER => {
+                write_fields!(
+                    f,
+                    tag,
+                    StivaleTerminalTag {
+                        flags,
+                        cols,
+                        rows,
+                        term_write_addr
+                    }
+                )
+            }
+            StivaleRsdpTag::IDENTIFIER => write_fields!(f, tag, StivaleRsdpTag { rsdp }),
+            StivaleSmbiosTag::IDENTIFIER => write_fields!(
+                f,
+                tag,
+                StivaleSmbiosTag {
+                    flags,
+                    smbios_entry_32,
+                    smbios_entry_64
+                }
+            ),
+            StivaleEpochTag::IDENTIFIER => write_fields!(f, tag, StivaleEpochTag { epoch }),
+            StivaleEfiSystemTableTag::IDENTIFIER => {
+                write_fields!(f, tag, StivaleEfiSystemTableTag { system_table_addr })
+            }
+            StivaleKernelFileTag::IDENTIFIER => {
+                write_fields!(f, tag, StivaleKernelFileTag { kernel_file_addr })
+            }
+            StivaleKernelFileV2Tag::IDENTIFIER => {
+                write_fields!(
+                    f,
+                    tag,
+                    StivaleKernelFileV2Tag {
+                        kernel_start,
+                        kernel_size
+                    }
+                )
+            }
+            StivaleKernelSlideTag::IDENTIFIER => {
+                write_fields!(f, tag, StivaleKernelSlideTag { kernel_slide })
+            }
+            StivaleKernelBaseAddressTag::IDENTIFIER => write_fields!(
+                f,
+                tag,
+                StivaleKernelBaseAddressTag {
+                    physical_base_address,
+                    virtual_base_address
+                }
+            ),
+            StivaleUartTag::IDENTIFIER => write_fields!(f, tag, StivaleUartTag { address }),
+            StivaleDeviceTreeTag::IDENTIFIER => {
+                write_fields!(f, tag, StivaleDeviceTreeTag { address, size })
+            }
+            StivaleVMapTag::IDENTIFIER => write_fields!(f, tag, StivaleVMapTag { address }),
+            StivaleFirmwareTag::IDENTIFIER => match read::<StivaleFirmwareTag>(tag.data, 0) {
+                Some(firmware) => writeln!(f, "    flags: {:?}", firmware.flags)?,
+                None => writeln!(f, "    (truncated)")?,
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: AddressTranslator> fmt::Display for BootReport<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "bootloader: {} {}",
+            LossyStr::from_c_bytes(self.reader.bootloader_brand()),
+            LossyStr::from_c_bytes(self.reader.bootloader_version())
+        )?;
+
+        let mut count = 0;
+
+        for tag in self.reader.tags() {
+            match tag_name(tag.identifier) {
+                Some(name) => writeln!(f, "{} at {:#x}", name, tag.address)?,
+                None => writeln!(
+                    f,
+                    "unknown tag {:#018x} at {:#x}",
+                    tag.identifier, tag.address
+                )?,
+            }
+
+            self.write_tag(f, &tag)?;
+            count += 1;
+        }
+
+        writeln!(f, "{} tags", count)
+    }
+}
+
+/// Renders a report of the boot structure at `address` in `memory`, a dump of the memory
+/// starting at `base`. Returns `None` if the structure is not part of the dump.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{render_dump, StivaleUartTag, StivaleTag};
+///
+/// let mut dump = [0u8; 160];
+/// dump[..6].copy_from_slice(b"Limine");
+/// dump[128..136].copy_from_slice(&0x1088u64.to_ne_bytes());
+/// dump[136..144].copy_from_slice(&StivaleUartTag::IDENTIFIER.to_ne_bytes());
+/// dump[152..160].copy_from_slice(&0x3f8u64.to_ne_bytes());
+///
+/// let report = render_dump(&dump, 0x1000, 0x1000).unwrap();
+/// assert!(report.contains("StivaleUartTag at 0x1088\n    address: 0x3f8"));
+/// ```
+pub fn render_dump(memory: &[u8], base: u64, address: u64) -> Option<String> {
+    let reader = StivaleStructReader::new(memory, address, OffsetTranslator { base })?;
+    Some(BootReport::new(&reader).to_string())
+}
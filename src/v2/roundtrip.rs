@@ -0,0 +1,426 @@
+//! Round-trip checking of boot structures: a [BootResponse] is laid out, parsed back as a
+//! [StivaleStruct] and compared against the response, so that bootloaders can test that the
+//! kernel will see what they meant to pass.
+
+use core::fmt;
+use core::mem;
+
+use super::builder::{bytes_of, write_boot_struct, BootResponse, BuildError, StivaleArrayTag};
+use super::tag::*;
+use super::StivaleStruct;
+
+/// The part of a parsed boot structure which does not match its [BootResponse].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundTripMismatch {
+    /// The bootloader brand differs.
+    BootloaderBrand,
+    /// The bootloader version differs.
+    BootloaderVersion,
+    /// The tag with this identifier is missing, unexpected or has different contents.
+    Tag(u64),
+    /// The tag chain does not contain the expected amount of tags.
+    TagCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for RoundTripMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundTripMismatch::BootloaderBrand => f.write_str("bootloader brand differs"),
+            RoundTripMismatch::BootloaderVersion => f.write_str("bootloader version differs"),
+            RoundTripMismatch::Tag(identifier) => match tag_name(*identifier) {
+                Some(name) => write!(f, "{} differs", name),
+                None => write!(f, "tag {:#018x} differs", identifier),
+            },
+            RoundTripMismatch::TagCount { expected, found } => {
+                write!(f, "expected {} tags, found {}", expected, found)
+            }
+        }
+    }
+}
+
+/// Errors returned by [round_trip].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundTripError {
+    /// Laying out the boot structure failed.
+    Build(BuildError),
+    /// The parsed boot structure does not match the response.
+    Mismatch(RoundTripMismatch),
+}
+
+impl fmt::Display for RoundTripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundTripError::Build(error) => write!(f, "failed to build: {:?}", error),
+            RoundTripError::Mismatch(mismatch) => write!(f, "round trip mismatch: {}", mismatch),
+        }
+    }
+}
+
+impl From<BuildError> for RoundTripError {
+    fn from(error: BuildError) -> Self {
+        RoundTripError::Build(error)
+    }
+}
+
+impl From<RoundTripMismatch> for RoundTripError {
+    fn from(mismatch: RoundTripMismatch) -> Self {
+        RoundTripError::Mismatch(mismatch)
+    }
+}
+
+/// Returns the bytes of the tag with identifier `T::IDENTIFIER` following its header, `len` bytes
+/// long, or `None` if the tag is missing.
+fn tag_body<T: StivaleTag + ?Sized>(stivale_struct: &StivaleStruct, len: usize) -> Option<&[u8]> {
+    let addr = stivale_struct.get_tag(T::IDENTIFIER)?;
+    let ptr = super::utils::addr_to_ptr::<u8>(addr);
+
+    Some(unsafe { core::slice::from_raw_parts(ptr.add(mem::size_of::<StivaleTagHeader>()), len) })
+}
+
+/// Checks that the fixed size tag `T` is present with the contents of `expected`, or absent if
+/// `expected` is `None`.
+fn check_tag<T: StivaleTag>(
+    stivale_struct: &StivaleStruct,
+    expected: Option<&T>,
+) -> Result<usize, RoundTripMismatch> {
+    let body_len = mem::size_of::<T>() - mem::size_of::<StivaleTagHeader>();
+    let found = tag_body::<T>(stivale_struct, body_len);
+
+    match (expected, found) {
+        (None, None) => Ok(0),
+        (Some(expected), Some(found))
+            if &bytes_of(expected)[mem::size_of::<StivaleTagHeader>()..] == found =>
+        {
+            Ok(1)
+        }
+        _ => Err(RoundTripMismatch::Tag(T::IDENTIFIER)),
+    }
+}
+
+/// Checks that the variable length tag `T` is present with the fixed fields `fields` (without
+/// the header and the count) followed by `entries`, or absent if `entries` is `None`.
+fn check_array_tag<T: StivaleArrayTag + ?Sized>(
+    stivale_struct: &StivaleStruct,
+    fields: &[u8],
+    entries: Option<&[T::Entry]>,
+) -> Result<usize, RoundTripMismatch> {
+    let header_len = mem::size_of::<StivaleTagHeader>();
+    let mismatch = RoundTripMismatch::Tag(T::IDENTIFIER);
+
+    let entries = match entries {
+        Some(entries) => entries,
+        None if stivale_struct.get_tag(T::IDENTIFIER).is_none() => return Ok(0),
+        None => return Err(mismatch),
+    };
+
+    let body = tag_body::<T>(stivale_struct, T::FIXED_SIZE - header_len).ok_or(mismatch)?;
+    let (found_fields, count) = body.split_at(fields.len());
+
+    if found_fields != fields || count != (entries.len() as u64).to_ne_bytes() {
+        return Err(mismatch);
+    }
+
+    let entries = bytes_of(entries);
+    let found_entries = tag_body::<T>(stivale_struct, T::FIXED_SIZE - header_len + entries.len())
+        .map(|body| &body[T::FIXED_SIZE - header_len..])
+        .ok_or(mismatch)?;
+
+    if found_entries == entries {
+        Ok(1)
+    } else {
+        Err(mismatch)
+    }
+}
+
+/// Returns the amount of tags in the tag chain of `stivale_struct`, stopping at
+/// [Limits::max_tags](super::Limits).
+fn count_tags(stivale_struct: &StivaleStruct) -> usize {
+    let max_tags = super::limits().max_tags;
+    let mut current_tag = stivale_struct.tags;
+    let mut count = 0;
+
+    while current_tag != 0 && count <= max_tags {
+        let tag = super::utils::addr_to_ptr::<StivaleTagHeader>(super::to_hhdm(current_tag));
+
+        current_tag = unsafe { (*tag).next };
+        count += 1;
+    }
+
+    count
+}
+
+/// Checks that `stivale_struct` describes exactly what `response` contains: the same bootloader
+/// strings and the same tags, with the same contents, and no other tag.
+pub fn verify_boot_struct(
+    stivale_struct: &StivaleStruct,
+    response: &BootResponse,
+) -> Result<(), RoundTripMismatch> {
+    if stivale_struct.bootloader_brand() != response.bootloader_brand {
+        return Err(RoundTripMismatch::BootloaderBrand);
+    }
+
+    if stivale_struct.bootloader_version() != response.bootloader_version {
+        return Err(RoundTripMismatch::BootloaderVersion);
+    }
+
+    let mut expected = 0;
+
+    match (response.command_line, stivale_struct.command_line()) {
+        (None, None) => {}
+        (Some(command_line), Some(tag)) if tag.as_cstr_bytes() == command_line.as_bytes() => {
+            expected += 1
+        }
+        _ => return Err(RoundTripMismatch::Tag(StivaleCommandLineTag::IDENTIFIER)),
+    }
+
+    expected += check_array_tag::<StivaleMemoryMapTag>(stivale_struct, &[], response.memory_map)?;
+    expected += check_tag(stivale_struct, response.framebuffer)?;
+    expected += check_array_tag::<StivaleEdidInfoTag>(stivale_struct, &[], response.edid)?;
+    expected += check_tag(stivale_struct, response.terminal)?;
+    expected += check_array_tag::<StivaleModuleTag>(stivale_struct, &[], response.modules)?;
+    expected += check_tag(stivale_struct, response.rsdp)?;
+    expected += check_tag(stivale_struct, response.smbios)?;
+    expected += check_tag(stivale_struct, response.epoch)?;
+    expected += check_tag(stivale_struct, response.firmware)?;
+    expected += check_tag(stivale_struct, response.efi_system_table)?;
+    expected += check_tag(stivale_struct, response.kernel_file)?;
+    expected += check_tag(stivale_struct, response.kernel_file_v2)?;
+    expected += check_tag(stivale_struct, response.kernel_slide)?;
+    expected += check_tag(stivale_struct, response.kernel_base_addr)?;
+
+    let mut smp_fields = [0; 16];
+
+    if let Some(smp) = &response.smp {
+        smp_fields[..8].copy_from_slice(&smp.flags.bits().to_ne_bytes());
+        #[cfg(not(target_arch = "aarch64"))]
+        smp_fields[8..12].copy_from_slice(&smp.bsp_lapic_id.to_ne_bytes());
+        #[cfg(target_arch = "aarch64")]
+        smp_fields[8..16].copy_from_slice(&smp.bsp_mpidr.to_ne_bytes());
+    }
+
+    expected += check_array_tag::<StivaleSmpTag>(
+        stivale_struct,
+        &smp_fields,
+        response.smp.as_ref().map(|smp| smp.cpus),
+    )?;
+    expected += check_tag(stivale_struct, response.pxe_info)?;
+    expected += check_tag(stivale_struct, response.uart)?;
+    expected += check_tag(stivale_struct, response.dev_tree)?;
+    expected += check_tag(stivale_struct, response.vmap)?;
+    expected += check_array_tag::<StivalePmrsTag>(stivale_struct, &[], response.pmrs)?;
+
+    let found = count_tags(stivale_struct);
+
+    if found != expected {
+        return Err(RoundTripMismatch::TagCount { expected, found });
+    }
+
+    Ok(())
+}
+
+/// Lays out `response` in `buffer` with [write_boot_struct], parses the result back and checks
+/// it with [verify_boot_struct]. Returns the parsed [StivaleStruct] on success.
+///
+/// The buffer must be `'static`, as the tag accessors of [StivaleStruct] return `'static`
+/// references into it. Tests can leak a heap allocation with `Box::leak`.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootResponse, StivaleMemoryMapEntry, StivaleMemoryMapEntryType};
+///
+/// let memory_map = [StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Usable)];
+/// let response = BootResponse {
+///     bootloader_brand: "example",
+///     bootloader_version: "1.0",
+///     command_line: Some("console=ttyS0"),
+///     memory_map: Some(&memory_map),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 512]));
+/// let stivale_struct = round_trip(buffer, &response).unwrap();
+/// assert_eq!(stivale_struct.bootloader_brand(), "example");
+/// ```
+pub fn round_trip(
+    buffer: &'static mut [u8],
+    response: &BootResponse,
+) -> Result<&'static StivaleStruct, RoundTripError> {
+    let address = write_boot_struct(buffer, response)?;
+    let stivale_struct = unsafe { &*super::utils::addr_to_ptr::<StivaleStruct>(address) };
+
+    verify_boot_struct(stivale_struct, response)?;
+    Ok(stivale_struct)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::*;
+    use crate::v2::utils::{addr_to_mut_ptr, ptr_to_addr};
+    use crate::v2::{StivaleMemoryMapEntry, StivaleMemoryMapEntryType};
+
+    const MEMORY_MAP: [StivaleMemoryMapEntry; 2] = [
+        StivaleMemoryMapEntry::new(0x0, 0x9f000, StivaleMemoryMapEntryType::Usable),
+        StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Usable),
+    ];
+
+    static UART: StivaleUartTag = StivaleUartTag::new(0x3f8);
+
+    fn base_response() -> BootResponse<'static> {
+        BootResponse {
+            bootloader_brand: "Limine",
+            bootloader_version: "2.0",
+            command_line: Some("quiet"),
+            memory_map: Some(&MEMORY_MAP),
+            uart: Some(&UART),
+            ..Default::default()
+        }
+    }
+
+    fn boot_info() -> &'static StivaleStruct {
+        round_trip(Box::leak(Box::new([0u8; 1024])), &base_response()).unwrap()
+    }
+
+    #[test]
+    fn bootloader_strings_differ() {
+        let boot_info = boot_info();
+
+        let response = BootResponse {
+            bootloader_brand: "Other",
+            ..base_response()
+        };
+        assert_eq!(
+            verify_boot_struct(boot_info, &response),
+            Err(RoundTripMismatch::BootloaderBrand)
+        );
+
+        let response = BootResponse {
+            bootloader_version: "3.0",
+            ..base_response()
+        };
+        assert_eq!(
+            verify_boot_struct(boot_info, &response),
+            Err(RoundTripMismatch::BootloaderVersion)
+        );
+    }
+
+    #[test]
+    fn command_line_differs() {
+        let boot_info = boot_info();
+        let mismatch = Err(RoundTripMismatch::Tag(StivaleCommandLineTag::IDENTIFIER));
+
+        let response = BootResponse {
+            command_line: Some("nosmp"),
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+
+        let response = BootResponse {
+            command_line: None,
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+    }
+
+    #[test]
+    fn fixed_size_tag_differs() {
+        let boot_info = boot_info();
+        let mismatch = Err(RoundTripMismatch::Tag(StivaleUartTag::IDENTIFIER));
+
+        let uart = StivaleUartTag::new(0x2f8);
+        let response = BootResponse {
+            uart: Some(&uart),
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+
+        let response = BootResponse {
+            uart: None,
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+
+        let epoch = StivaleEpochTag::new(1_600_000_000);
+        let response = BootResponse {
+            epoch: Some(&epoch),
+            ..base_response()
+        };
+        assert_eq!(
+            verify_boot_struct(boot_info, &response),
+            Err(RoundTripMismatch::Tag(StivaleEpochTag::IDENTIFIER))
+        );
+    }
+
+    #[test]
+    fn array_tag_differs() {
+        let boot_info = boot_info();
+        let mismatch = Err(RoundTripMismatch::Tag(StivaleMemoryMapTag::IDENTIFIER));
+
+        let response = BootResponse {
+            memory_map: Some(&MEMORY_MAP[..1]),
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+
+        let memory_map = [
+            MEMORY_MAP[0],
+            StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Reserved),
+        ];
+        let response = BootResponse {
+            memory_map: Some(&memory_map),
+            ..base_response()
+        };
+        assert_eq!(verify_boot_struct(boot_info, &response), mismatch);
+    }
+
+    #[test]
+    fn unexpected_tag_in_chain() {
+        let boot_info = boot_info();
+        let extra: &'static mut StivaleTagHeader =
+            Box::leak(Box::new(StivaleTagHeader::new(0x1234)));
+
+        // Link an unknown tag at the end of the chain.
+        let mut tag = boot_info.tags;
+
+        loop {
+            let header = addr_to_mut_ptr::<StivaleTagHeader>(tag);
+            let next = unsafe { (*header).next };
+
+            if next == 0 {
+                unsafe { (*header).next = ptr_to_addr(extra as *const StivaleTagHeader) };
+                break;
+            }
+
+            tag = next;
+        }
+
+        assert_eq!(
+            verify_boot_struct(boot_info, &base_response()),
+            Err(RoundTripMismatch::TagCount {
+                expected: 3,
+                found: 4
+            })
+        );
+    }
+
+    #[test]
+    fn mismatch_is_displayed() {
+        let uart = RoundTripMismatch::Tag(StivaleUartTag::IDENTIFIER);
+        let unknown = RoundTripMismatch::Tag(0x1234);
+        let count = RoundTripError::Mismatch(RoundTripMismatch::TagCount {
+            expected: 3,
+            found: 4,
+        });
+
+        assert_eq!(std::format!("{}", uart), "StivaleUartTag differs");
+        assert_eq!(
+            std::format!("{}", unknown),
+            "tag 0x0000000000001234 differs"
+        );
+        assert_eq!(
+            std::format!("{}", count),
+            "round trip mismatch: expected 3 tags, found 4"
+        );
+    }
+}
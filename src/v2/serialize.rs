@@ -0,0 +1,249 @@
+//! `serde` serialization of the boot information, so that it can be dumped as JSON, postcard or
+//! any other format for offline analysis. Addresses are serialized as hexadecimal strings.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+use super::tag::*;
+use super::utils::LossyStr;
+use super::StivaleStruct;
+
+/// An address, serialized as a hexadecimal string.
+struct Hex(u64);
+
+impl Serialize for Hex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:#x}", self.0))
+    }
+}
+
+/// A value serialized as its `Debug` representation, used for enums.
+struct Debugged<T>(T);
+
+impl<T: fmt::Debug> Serialize for Debugged<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:?}", self.0))
+    }
+}
+
+impl Serialize for LossyStr<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+macro_rules! impl_serialize {
+    ($($name:ident |$this:ident| { $($field:ident: $value:expr),* $(,)? })*) => {
+        $(impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let $this = self;
+                let mut state = serializer
+                    .serialize_struct(stringify!($name), [$(stringify!($field)),*].len())?;
+
+                $(state.serialize_field(stringify!($field), &$value)?;)*
+                state.end()
+            }
+        })*
+    };
+}
+
+impl_serialize! {
+    StivaleCommandLineTag |tag| {
+        command_line: Hex(tag.command_line),
+        string: tag.as_str_lossy(),
+    }
+    StivaleMemoryMapEntry |entry| {
+        base: Hex(entry.base),
+        length: entry.length,
+        entry_type: Debugged(entry.entry_type),
+    }
+    StivaleMemoryMapTag |tag| {
+        entries: tag.as_slice(),
+    }
+    StivaleFramebufferTag |tag| {
+        framebuffer_addr: Hex(tag.framebuffer_addr),
+        framebuffer_width: tag.framebuffer_width,
+        framebuffer_height: tag.framebuffer_height,
+        framebuffer_pitch: tag.framebuffer_pitch,
+        framebuffer_bpp: tag.framebuffer_bpp,
+        memory_model: tag.memory_model,
+        red_mask_size: tag.red_mask_size,
+        red_mask_shift: tag.red_mask_shift,
+        green_mask_size: tag.green_mask_size,
+        green_mask_shift: tag.green_mask_shift,
+        blue_mask_size: tag.blue_mask_size,
+        blue_mask_shift: tag.blue_mask_shift,
+    }
+    StivaleEdidInfoTag |tag| {
+        info: tag.as_slice(),
+    }
+    StivaleTerminalTag |tag| {
+        flags: tag.flags,
+        cols: tag.cols,
+        rows: tag.rows,
+        term_write_addr: Hex(tag.term_write_addr),
+    }
+    StivaleModule |module| {
+        start: Hex(module.start),
+        end: Hex(module.end),
+        string: module.as_str_lossy(),
+    }
+    StivaleModuleTag |tag| {
+        modules: tag.as_slice(),
+    }
+    StivaleRsdpTag |tag| {
+        rsdp: Hex(tag.rsdp),
+    }
+    StivaleSmbiosTag |tag| {
+        flags: tag.flags,
+        smbios_entry_32: Hex(tag.smbios_entry_32),
+        smbios_entry_64: Hex(tag.smbios_entry_64),
+    }
+    StivaleEpochTag |tag| {
+        epoch: tag.epoch,
+    }
+    StivaleFirmwareTag |tag| {
+        flags: tag.flags.bits(),
+    }
+    StivaleEfiSystemTableTag |tag| {
+        system_table_addr: Hex(tag.system_table_addr),
+    }
+    StivaleKernelFileTag |tag| {
+        kernel_file_addr: Hex(tag.kernel_file_addr),
+    }
+    StivaleKernelFileV2Tag |tag| {
+        kernel_start: Hex(tag.kernel_start),
+        kernel_size: tag.kernel_size,
+    }
+    StivaleKernelSlideTag |tag| {
+        kernel_slide: Hex(tag.kernel_slide),
+    }
+    StivaleKernelBaseAddressTag |tag| {
+        physical_base_address: Hex(tag.physical_base_address),
+        virtual_base_address: Hex(tag.virtual_base_address),
+    }
+    StivalePxeInfoTag |tag| {
+        server_ip: Debugged(tag.server_addr()),
+    }
+    StivaleUartTag |tag| {
+        address: Hex(tag.address),
+    }
+    StivaleDeviceTreeTag |tag| {
+        address: Hex(tag.address),
+        size: tag.size,
+    }
+    StivaleVMapTag |tag| {
+        address: Hex(tag.address),
+    }
+    StivalePmr |pmr| {
+        base: Hex(pmr.base),
+        size: pmr.size,
+        permissions: pmr.permissions.bits(),
+    }
+    StivalePmrsTag |tag| {
+        pmrs: tag.as_slice(),
+    }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+impl_serialize! {
+    StivaleSmpInfo |info| {
+        acpi_processor_uid: info.acpi_processor_uid,
+        lapic_id: info.lapic_id,
+        target_stack: Hex(info.target_stack.load(Ordering::Relaxed)),
+        goto_address: Hex(info.goto_address.load(Ordering::Relaxed)),
+        extra: info.extra.load(Ordering::Relaxed),
+    }
+    StivaleSmpTag |tag| {
+        flags: tag.flags.bits(),
+        bsp_lapic_id: tag.bsp_lapic_id,
+        cpus: tag.as_slice(),
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl_serialize! {
+    StivaleSmpInfo |info| {
+        processor_id: info.processor_id,
+        gic_iface_no: info.gic_iface_no,
+        mpidr: Hex(info.mpidr),
+        target_stack: Hex(info.target_stack.load(Ordering::Relaxed)),
+        goto_address: Hex(info.goto_address.load(Ordering::Relaxed)),
+        extra: info.extra.load(Ordering::Relaxed),
+    }
+    StivaleSmpTag |tag| {
+        flags: tag.flags.bits(),
+        bsp_mpidr: Hex(tag.bsp_mpidr),
+        cpus: tag.as_slice(),
+    }
+}
+
+/// Serializes the bootloader strings followed by every tag the bootloader provided, keyed by
+/// the name of their accessor.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootResponse, StivaleEpochTag};
+///
+/// let epoch = StivaleEpochTag::new(1_600_000_000);
+/// let response = BootResponse {
+///     bootloader_brand: "example",
+///     bootloader_version: "1.0",
+///     epoch: Some(&epoch),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 512]));
+/// let boot_info = round_trip(buffer, &response).unwrap();
+///
+/// assert_eq!(
+///     serde_json::to_string(boot_info).unwrap(),
+///     r#"{"bootloader_brand":"example","bootloader_version":"1.0","epoch":{"epoch":1600000000}}"#
+/// );
+/// ```
+impl Serialize for StivaleStruct {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        macro_rules! serialize_tags {
+            ($($accessor:ident),*) => {{
+                let count = 2 $(+ self.$accessor().is_some() as usize)*;
+                let mut map = serializer.serialize_map(Some(count))?;
+
+                map.serialize_entry("bootloader_brand", &self.bootloader_brand_lossy())?;
+                map.serialize_entry("bootloader_version", &self.bootloader_version_lossy())?;
+
+                $(if let Some(tag) = self.$accessor() {
+                    map.serialize_entry(stringify!($accessor), tag)?;
+                })*
+
+                map.end()
+            }};
+        }
+
+        serialize_tags!(
+            command_line,
+            memory_map,
+            framebuffer,
+            edid_info,
+            terminal,
+            modules,
+            rsdp,
+            smbios,
+            epoch,
+            firmware,
+            efi_system_table,
+            kernel_file,
+            kernel_file_v2,
+            kernel_slide,
+            kernel_base_addr,
+            smp,
+            pxe_info,
+            uart,
+            dev_tree,
+            vmap,
+            pmrs
+        )
+    }
+}
@@ -0,0 +1,189 @@
+//! Sharing of the boot information between processors.
+//!
+//! The tags returned by [StivaleStruct] only hold plain data, and the fields written to start
+//! the APs are atomics, so `&'static` references to them are `Send` and `Sync` and can be
+//! stored in statics as is. This holds as long as the bootloader reclaimable memory they live
+//! in is not reused. The assertions below make sure this stays the case.
+//!
+//! The stivale2 terminal is the exception: its `term_write` function is not reentrant, so
+//! [LockedTerminal] serializes the calls made by different processors.
+
+use core::fmt;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use super::lock::RawLock;
+use super::tag::*;
+use super::StivaleStruct;
+
+const fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+
+#[allow(deprecated)]
+const _: () = {
+    assert_send_sync::<StivaleStruct>();
+    assert_send_sync::<StivaleCommandLineTag>();
+    assert_send_sync::<StivaleMemoryMapTag>();
+    assert_send_sync::<StivaleFramebufferTag>();
+    assert_send_sync::<StivaleEdidInfoTag>();
+    assert_send_sync::<StivaleMtrrTag>();
+    assert_send_sync::<StivaleTerminalTag>();
+    assert_send_sync::<StivaleModuleTag>();
+    assert_send_sync::<StivaleRsdpTag>();
+    assert_send_sync::<StivaleSmbiosTag>();
+    assert_send_sync::<StivaleEpochTag>();
+    assert_send_sync::<StivaleFirmwareTag>();
+    assert_send_sync::<StivaleEfiSystemTableTag>();
+    assert_send_sync::<StivaleKernelFileTag>();
+    assert_send_sync::<StivaleKernelSlideTag>();
+    assert_send_sync::<StivaleSmpTag>();
+    assert_send_sync::<StivalePxeInfoTag>();
+    assert_send_sync::<StivaleUartTag>();
+    assert_send_sync::<StivaleDeviceTreeTag>();
+    assert_send_sync::<StivaleVMapTag>();
+    assert_send_sync::<StivaleKernelFileV2Tag>();
+    assert_send_sync::<StivalePmrsTag>();
+    assert_send_sync::<StivaleKernelBaseAddressTag>();
+};
+
+/// The way a [LockedTerminal] calls `term_write`.
+pub trait TerminalCall {
+    /// Writes `bytes` to the terminal described by `tag`.
+    ///
+    /// # Safety
+    /// The lock of the [LockedTerminal] owning `self` must be held.
+    unsafe fn write(&self, tag: &StivaleTerminalTag, bytes: &[u8]);
+}
+
+/// Calls `term_write` on the stack of the caller, see [SharedTerminal].
+pub struct DirectCall;
+
+impl TerminalCall for DirectCall {
+    unsafe fn write(&self, tag: &StivaleTerminalTag, bytes: &[u8]) {
+        tag.write_bytes(bytes);
+    }
+}
+
+/// The stivale2 terminal, shareable between processors. Calls to `term_write` are serialized
+/// with a spin lock, held inside a critical section with the `critical-section` feature so that
+/// the terminal can be written to from interrupt handlers. `C` is the way `term_write` is
+/// called, see [SharedTerminal] and [StackedTerminal](super::StackedTerminal).
+pub struct LockedTerminal<C> {
+    tag: AtomicPtr<StivaleTerminalTag>,
+    lock: RawLock,
+    call: C,
+}
+
+/// The stivale2 terminal, shareable between processors, called on the stack of the caller. See
+/// [LockedTerminal].
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{SharedTerminal, StivaleStruct};
+///
+/// static TERMINAL: SharedTerminal = SharedTerminal::new();
+///
+/// fn kmain(boot_info: &'static StivaleStruct) {
+///     if let Some(terminal) = boot_info.terminal() {
+///         TERMINAL.init(terminal);
+///     }
+///
+///     // On any processor:
+///     TERMINAL.write_str("Hello from an AP!\n");
+/// }
+/// ```
+pub type SharedTerminal = LockedTerminal<DirectCall>;
+
+impl<C> LockedTerminal<C> {
+    /// Creates a new terminal calling `term_write` with `call`, which discards its output until
+    /// it is initialized.
+    pub(crate) const fn with_call(call: C) -> Self {
+        Self {
+            tag: AtomicPtr::new(core::ptr::null_mut()),
+            lock: RawLock::new(),
+            call,
+        }
+    }
+}
+
+impl SharedTerminal {
+    /// Creates a new shared terminal, which discards its output until it is initialized.
+    pub const fn new() -> Self {
+        Self::with_call(DirectCall)
+    }
+}
+
+impl<C: TerminalCall> LockedTerminal<C> {
+    /// Makes this terminal write to the terminal described by `tag`.
+    ///
+    /// The terminal must not be written to through `tag` directly from then on, as those
+    /// calls would not be serialized.
+    pub fn init(&self, tag: &'static StivaleTerminalTag) {
+        self.tag.store(
+            tag as *const StivaleTerminalTag as *mut StivaleTerminalTag,
+            Ordering::Release,
+        );
+    }
+
+    /// Returns whether this terminal was initialized.
+    pub fn is_initialized(&self) -> bool {
+        !self.tag.load(Ordering::Acquire).is_null()
+    }
+
+    /// Writes `bytes` to the terminal. The lock must be held.
+    fn write_locked(&self, bytes: &[u8]) {
+        let tag = self.tag.load(Ordering::Acquire);
+
+        if !tag.is_null() {
+            // SAFETY: The lock is held.
+            unsafe { self.call.write(&*tag, bytes) };
+        }
+    }
+
+    /// Writes `bytes` to the terminal, waiting for other processors to finish writing first.
+    /// They may contain null bytes and invalid UTF-8, see [StivaleTerminalTag::write_bytes].
+    ///
+    /// Without the `critical-section` feature, this must not be called while the same
+    /// processor is already writing, such as from an interrupt handler which interrupted a
+    /// write, as it would wait forever; use [LockedTerminal::try_write_bytes] there.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        let _guard = self.lock.lock();
+        self.write_locked(bytes);
+    }
+
+    /// Writes `bytes` to the terminal if nothing else is writing to it, returning whether they
+    /// were written. Meant for panic and interrupt handlers, which must not wait on a lock held by
+    /// the code they interrupted.
+    pub fn try_write_bytes(&self, bytes: &[u8]) -> bool {
+        match self.lock.try_lock() {
+            Some(_guard) => {
+                self.write_locked(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes `s` to the terminal, waiting for other processors to finish writing first. See
+    /// [LockedTerminal::write_bytes].
+    pub fn write_str(&self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Writes `s` to the terminal if nothing else is writing to it, returning whether it was
+    /// written. See [LockedTerminal::try_write_bytes].
+    pub fn try_write_str(&self, s: &str) -> bool {
+        self.try_write_bytes(s.as_bytes())
+    }
+}
+
+impl Default for SharedTerminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: TerminalCall> fmt::Write for &LockedTerminal<C> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        LockedTerminal::write_str(self, s);
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Translation between the link time addresses of a relocatable kernel and the addresses it
+//! runs at, for kernels embedding absolute addresses which the relocations do not cover.
+
+use core::mem;
+
+use super::tag::StivaleKernelSlideTag;
+use super::StivaleStruct;
+
+/// The offset the bootloader loaded the kernel at relative to its link address, as reported by
+/// the [StivaleKernelSlideTag].
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{KernelSlide, StivaleKernelSlideTag};
+///
+/// let slide = KernelSlide::from(&StivaleKernelSlideTag::new(0x20_0000));
+///
+/// assert_eq!(slide.adjust(0xffff_ffff_8000_1000), 0xffff_ffff_8020_1000);
+/// assert_eq!(slide.unadjust(0xffff_ffff_8020_1000), 0xffff_ffff_8000_1000);
+///
+/// // A table of addresses recorded at link time, with an empty slot.
+/// let mut handlers = [0xffff_ffff_8000_2000u64, 0, 0xffff_ffff_8000_3000];
+/// slide.adjust_table(&mut handlers);
+/// assert_eq!(handlers, [0xffff_ffff_8020_2000, 0, 0xffff_ffff_8020_3000]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct KernelSlide(u64);
+
+impl KernelSlide {
+    /// The slide of a kernel running at its link address.
+    pub const NONE: KernelSlide = KernelSlide(0);
+
+    /// Creates a slide of `slide` bytes.
+    pub const fn new(slide: u64) -> Self {
+        Self(slide)
+    }
+
+    /// Returns the slide of the kernel described by `boot_info`, which is [KernelSlide::NONE]
+    /// if the bootloader did not provide the kernel slide tag.
+    pub fn from_boot_info(boot_info: &StivaleStruct) -> Self {
+        boot_info.kernel_slide().map_or(Self::NONE, Self::from)
+    }
+
+    /// Returns the slide in bytes.
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the address the link time address `link_addr` is at at runtime.
+    pub const fn adjust(self, link_addr: u64) -> u64 {
+        link_addr.wrapping_add(self.0)
+    }
+
+    /// Returns the link time address of the runtime address `runtime_addr`, such as a return
+    /// address to be looked up in the symbol table of the kernel.
+    pub const fn unadjust(self, runtime_addr: u64) -> u64 {
+        runtime_addr.wrapping_sub(self.0)
+    }
+
+    /// Adjusts every link time address of `table` in place. Zero entries, such as empty slots
+    /// or terminators, are left untouched. The table must only be adjusted once.
+    pub fn adjust_table(self, table: &mut [u64]) {
+        for entry in table.iter_mut().filter(|entry| **entry != 0) {
+            *entry = self.adjust(*entry);
+        }
+    }
+
+    /// Adjusts every function pointer of `table` in place, such as the entries of a handler
+    /// table generated with link time addresses. Null entries of tables of optional function
+    /// pointers are left untouched.
+    ///
+    /// ## Safety
+    /// `F` must be a function pointer type or an `Option` of one, and every entry must hold a
+    /// link time address which was not adjusted yet.
+    ///
+    /// ## Panics
+    /// Panics if `F` is not pointer sized.
+    pub unsafe fn adjust_fn_table<F: Copy>(self, table: &mut [F]) {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<usize>());
+
+        let entries =
+            core::slice::from_raw_parts_mut(table.as_mut_ptr().cast::<usize>(), table.len());
+
+        for entry in entries.iter_mut().filter(|entry| **entry != 0) {
+            *entry = entry.wrapping_add(self.0 as usize);
+        }
+    }
+}
+
+impl From<&StivaleKernelSlideTag> for KernelSlide {
+    fn from(tag: &StivaleKernelSlideTag) -> Self {
+        Self(tag.kernel_slide)
+    }
+}
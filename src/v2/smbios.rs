@@ -0,0 +1,458 @@
+//! Parsing of the SMBIOS entry points referenced by the [StivaleSmbiosTag] and of the structure
+//! table they describe, which holds information such as the BIOS vendor, the product name and
+//! the installed memory devices.
+
+use super::tag::StivaleSmbiosTag;
+
+/// The anchor at the start of the 32-bit SMBIOS entry point.
+pub const SMBIOS_ENTRY_32_ANCHOR: [u8; 4] = *b"_SM_";
+/// The intermediate anchor of the 32-bit SMBIOS entry point.
+pub const SMBIOS_ENTRY_32_INTERMEDIATE_ANCHOR: [u8; 5] = *b"_DMI_";
+/// The anchor at the start of the 64-bit SMBIOS entry point.
+pub const SMBIOS_ENTRY_64_ANCHOR: [u8; 5] = *b"_SM3_";
+
+/// The type of the structure marking the end of the structure table.
+const SMBIOS_END_OF_TABLE: u8 = 127;
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let mut word = [0; 2];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(2)?)?);
+    Some(u16::from_le_bytes(word))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let mut word = [0; 4];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(4)?)?);
+    Some(u32::from_le_bytes(word))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes.get(offset..offset.checked_add(8)?)?);
+    Some(u64::from_le_bytes(word))
+}
+
+/// A parsed and validated SMBIOS entry point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosEntryPoint {
+    /// Major version of the SMBIOS specification implemented by the firmware.
+    pub major_version: u8,
+    /// Minor version of the SMBIOS specification implemented by the firmware.
+    pub minor_version: u8,
+    /// Physical address of the structure table.
+    pub table_address: u64,
+    /// Length of the structure table in bytes. For the 64-bit entry point, this is the
+    /// maximum size of the table.
+    pub table_length: u32,
+    /// Number of structures in the table. Only provided by the 32-bit entry point.
+    pub structure_count: Option<u16>,
+}
+
+impl SmbiosEntryPoint {
+    /// Parses the 32-bit entry point at the start of `bytes`, returning `None` if its anchors or
+    /// checksums are invalid.
+    pub fn parse_32(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(..4)? != SMBIOS_ENTRY_32_ANCHOR {
+            return None;
+        }
+
+        let length = *bytes.get(5)? as usize;
+        let entry = bytes.get(..length)?;
+
+        if length < 0x1f
+            || checksum(entry) != 0
+            || entry[0x10..0x15] != SMBIOS_ENTRY_32_INTERMEDIATE_ANCHOR
+            || checksum(&entry[0x10..0x1f]) != 0
+        {
+            return None;
+        }
+
+        Some(Self {
+            major_version: entry[6],
+            minor_version: entry[7],
+            table_address: read_u32(entry, 0x18)? as u64,
+            table_length: read_u16(entry, 0x16)? as u32,
+            structure_count: Some(read_u16(entry, 0x1c)?),
+        })
+    }
+
+    /// Parses the 64-bit entry point at the start of `bytes`, returning `None` if its anchor or
+    /// checksum is invalid.
+    pub fn parse_64(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(..5)? != SMBIOS_ENTRY_64_ANCHOR {
+            return None;
+        }
+
+        let length = *bytes.get(6)? as usize;
+        let entry = bytes.get(..length)?;
+
+        if length < 0x18 || checksum(entry) != 0 {
+            return None;
+        }
+
+        Some(Self {
+            major_version: entry[7],
+            minor_version: entry[8],
+            table_address: read_u64(entry, 0x10)?,
+            table_length: read_u32(entry, 0x0c)?,
+            structure_count: None,
+        })
+    }
+}
+
+/// A structure of the SMBIOS structure table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosStructure<'a> {
+    /// The type of the structure, for example 0 for the BIOS information and 17 for memory
+    /// devices.
+    pub kind: u8,
+    /// The handle of the structure, used by other structures to refer to it.
+    pub handle: u16,
+    /// The formatted area of the structure, including its 4 byte header.
+    pub data: &'a [u8],
+    /// The string set following the formatted area, without the final terminator.
+    strings: &'a [u8],
+}
+
+impl<'a> SmbiosStructure<'a> {
+    /// Returns the string with the provided index, as referenced by the formatted area. Indices
+    /// start at 1, 0 meaning that no string is provided.
+    pub fn string(&self, index: u8) -> Option<&'a str> {
+        if index == 0 {
+            return None;
+        }
+
+        let string = self
+            .strings
+            .split(|c| *c == 0)
+            .filter(|string| !string.is_empty())
+            .nth(index as usize - 1)?;
+
+        core::str::from_utf8(string).ok()
+    }
+
+    /// Returns the byte at `offset` in the formatted area.
+    pub fn byte(&self, offset: usize) -> Option<u8> {
+        self.data.get(offset).copied()
+    }
+
+    /// Returns the word at `offset` in the formatted area.
+    pub fn word(&self, offset: usize) -> Option<u16> {
+        read_u16(self.data, offset)
+    }
+
+    /// Returns the string referenced by the byte at `offset` in the formatted area.
+    pub fn string_at(&self, offset: usize) -> Option<&'a str> {
+        self.string(self.byte(offset)?)
+    }
+
+    /// Returns the double word at `offset` in the formatted area.
+    pub fn dword(&self, offset: usize) -> Option<u32> {
+        read_u32(self.data, offset)
+    }
+
+    /// Returns the BIOS information if this is a type 0 structure.
+    pub fn bios_info(&self) -> Option<SmbiosBiosInfo<'a>> {
+        if self.kind != SMBIOS_BIOS_INFO {
+            return None;
+        }
+
+        Some(SmbiosBiosInfo {
+            vendor: self.string_at(0x04),
+            version: self.string_at(0x05),
+            release_date: self.string_at(0x08),
+            release: self.byte(0x14).zip(self.byte(0x15)),
+        })
+    }
+
+    /// Returns the system information if this is a type 1 structure.
+    pub fn system_info(&self) -> Option<SmbiosSystemInfo<'a>> {
+        if self.kind != SMBIOS_SYSTEM_INFO {
+            return None;
+        }
+
+        Some(SmbiosSystemInfo {
+            manufacturer: self.string_at(0x04),
+            product_name: self.string_at(0x05),
+            version: self.string_at(0x06),
+            serial_number: self.string_at(0x07),
+            uuid: self.data.get(0x08..0x18).map(|uuid| {
+                let mut bytes = [0; 16];
+                bytes.copy_from_slice(uuid);
+                bytes
+            }),
+            family: self.string_at(0x1a),
+        })
+    }
+
+    /// Returns the processor information if this is a type 4 structure.
+    pub fn processor_info(&self) -> Option<SmbiosProcessorInfo<'a>> {
+        if self.kind != SMBIOS_PROCESSOR_INFO {
+            return None;
+        }
+
+        // Counts above 255 are stored in the words added by SMBIOS 3.0.
+        let count = |byte_offset: usize, word_offset: usize| match self.byte(byte_offset)? {
+            0 => None,
+            0xff => self.word(word_offset).or(Some(0xff)),
+            count => Some(count as u16),
+        };
+
+        Some(SmbiosProcessorInfo {
+            socket_designation: self.string_at(0x04),
+            manufacturer: self.string_at(0x07),
+            version: self.string_at(0x10),
+            max_speed_mhz: self.word(0x14).filter(|&speed| speed != 0),
+            current_speed_mhz: self.word(0x16).filter(|&speed| speed != 0),
+            populated: self.byte(0x18).is_some_and(|status| status & 0x40 != 0),
+            core_count: count(0x23, 0x2a),
+            thread_count: count(0x25, 0x2e),
+        })
+    }
+
+    /// Returns the memory device information if this is a type 17 structure.
+    pub fn memory_device(&self) -> Option<SmbiosMemoryDevice<'a>> {
+        if self.kind != SMBIOS_MEMORY_DEVICE {
+            return None;
+        }
+
+        let size = match self.word(0x0c)? {
+            0 => Some(0),
+            0xffff => None,
+            // The size does not fit, so it is stored in megabytes in the extended size.
+            0x7fff => self
+                .dword(0x1c)
+                .map(|size| (size as u64 & 0x7fff_ffff) << 20),
+            // The size is in kilobytes if the top bit is set, in megabytes otherwise.
+            size if size & 0x8000 != 0 => Some(((size & 0x7fff) as u64) << 10),
+            size => Some((size as u64) << 20),
+        };
+
+        Some(SmbiosMemoryDevice {
+            size,
+            locator: self.string_at(0x10),
+            bank_locator: self.string_at(0x11),
+            memory_type: self.byte(0x12),
+            speed_mts: self.word(0x15).filter(|&speed| speed != 0),
+            manufacturer: self.string_at(0x17),
+            part_number: self.string_at(0x1a),
+        })
+    }
+}
+
+/// The type of the BIOS information structure.
+pub const SMBIOS_BIOS_INFO: u8 = 0;
+/// The type of the system information structure.
+pub const SMBIOS_SYSTEM_INFO: u8 = 1;
+/// The type of the processor information structure.
+pub const SMBIOS_PROCESSOR_INFO: u8 = 4;
+/// The type of the memory device structure.
+pub const SMBIOS_MEMORY_DEVICE: u8 = 17;
+
+/// The common fields of the BIOS information structure (type 0). Fields the structure is too
+/// short to hold, or strings which are not provided, are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosBiosInfo<'a> {
+    pub vendor: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub release_date: Option<&'a str>,
+    /// The major and minor release of the firmware.
+    pub release: Option<(u8, u8)>,
+}
+
+/// The common fields of the system information structure (type 1). Fields the structure is too
+/// short to hold, or strings which are not provided, are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosSystemInfo<'a> {
+    pub manufacturer: Option<&'a str>,
+    pub product_name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub serial_number: Option<&'a str>,
+    /// The UUID of the system, in the byte order of the structure.
+    pub uuid: Option<[u8; 16]>,
+    pub family: Option<&'a str>,
+}
+
+/// The common fields of the processor information structure (type 4), which describes a
+/// processor socket. Fields the structure is too short to hold, unknown values, or strings
+/// which are not provided, are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosProcessorInfo<'a> {
+    pub socket_designation: Option<&'a str>,
+    pub manufacturer: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub max_speed_mhz: Option<u16>,
+    pub current_speed_mhz: Option<u16>,
+    /// Whether the socket holds a processor.
+    pub populated: bool,
+    pub core_count: Option<u16>,
+    pub thread_count: Option<u16>,
+}
+
+/// The common fields of the memory device structure (type 17), which describes a memory slot.
+/// Fields the structure is too short to hold, unknown values, or strings which are not provided,
+/// are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmbiosMemoryDevice<'a> {
+    /// The size of the installed module in bytes, zero if the slot is empty.
+    pub size: Option<u64>,
+    /// The slot the module is installed in, such as `DIMM 0`.
+    pub locator: Option<&'a str>,
+    pub bank_locator: Option<&'a str>,
+    /// The memory type as defined by the SMBIOS specification, such as `0x1a` for DDR4.
+    pub memory_type: Option<u8>,
+    /// The maximum speed of the module in megatransfers per second.
+    pub speed_mts: Option<u16>,
+    pub manufacturer: Option<&'a str>,
+    pub part_number: Option<&'a str>,
+}
+
+/// Iterator over the structures of an SMBIOS structure table.
+#[derive(Clone)]
+pub struct SmbiosStructureIter<'a> {
+    table: &'a [u8],
+    /// The amount of structures left, if known.
+    remaining: Option<u16>,
+}
+
+impl<'a> SmbiosStructureIter<'a> {
+    /// Creates a new iterator over the structure table in `table`, stopping after
+    /// `structure_count` structures if provided.
+    pub fn new(table: &'a [u8], structure_count: Option<u16>) -> Self {
+        Self {
+            table,
+            remaining: structure_count,
+        }
+    }
+
+    /// Returns the BIOS information, from the first type 0 structure.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::SmbiosStructureIter;
+    ///
+    /// let mut table = vec![0, 0x18, 0x00, 0x00, 1, 2, 0, 0, 3];
+    /// table.resize(0x14, 0);
+    /// table.extend_from_slice(&[1, 4, 0xff, 0xff]);
+    /// table.extend_from_slice(b"ACME\01.2\001/01/2024\0\0");
+    /// table.extend_from_slice(&[127, 4, 0x01, 0x00, 0, 0]);
+    ///
+    /// let structures = SmbiosStructureIter::new(&table, None);
+    /// let bios = structures.clone().bios_info().unwrap();
+    ///
+    /// assert_eq!(bios.vendor, Some("ACME"));
+    /// assert_eq!(bios.release_date, Some("01/01/2024"));
+    /// assert_eq!(bios.release, Some((1, 4)));
+    /// assert_eq!(structures.memory_devices().count(), 0);
+    /// ```
+    pub fn bios_info(mut self) -> Option<SmbiosBiosInfo<'a>> {
+        self.find_map(|structure| structure.bios_info())
+    }
+
+    /// Returns the system information, from the first type 1 structure.
+    pub fn system_info(mut self) -> Option<SmbiosSystemInfo<'a>> {
+        self.find_map(|structure| structure.system_info())
+    }
+
+    /// Returns an iterator over the processor sockets, described by the type 4 structures.
+    pub fn processors(self) -> impl Iterator<Item = SmbiosProcessorInfo<'a>> {
+        self.filter_map(|structure| structure.processor_info())
+    }
+
+    /// Returns an iterator over the memory slots, described by the type 17 structures.
+    pub fn memory_devices(self) -> impl Iterator<Item = SmbiosMemoryDevice<'a>> {
+        self.filter_map(|structure| structure.memory_device())
+    }
+}
+
+impl<'a> Iterator for SmbiosStructureIter<'a> {
+    type Item = SmbiosStructure<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        let kind = *self.table.first()?;
+        let length = *self.table.get(1)? as usize;
+
+        if length < 4 || kind == SMBIOS_END_OF_TABLE {
+            self.table = &[];
+            return None;
+        }
+
+        let data = self.table.get(..length)?;
+        let handle = read_u16(data, 2)?;
+
+        // The string set ends with two null bytes, which are present even if there are no
+        // strings.
+        let rest = &self.table[length..];
+        let end = rest.windows(2).position(|pair| pair == [0, 0])?;
+        let strings = &rest[..end];
+
+        self.table = &rest[end + 2..];
+        self.remaining = self.remaining.map(|remaining| remaining - 1);
+
+        Some(SmbiosStructure {
+            kind,
+            handle,
+            data,
+            strings,
+        })
+    }
+}
+
+impl StivaleSmbiosTag {
+    /// Parses the 32-bit SMBIOS entry point, returning `None` if it is unavailable or invalid.
+    pub fn entry_point_32(&self) -> Option<SmbiosEntryPoint> {
+        if self.smbios_entry_32 == 0 {
+            return None;
+        }
+
+        let ptr = super::utils::addr_to_ptr::<u8>(super::hhdm::to_hhdm(self.smbios_entry_32));
+
+        unsafe {
+            let length = ptr.add(5).read_volatile() as usize;
+            SmbiosEntryPoint::parse_32(core::slice::from_raw_parts(ptr, length.max(0x1f)))
+        }
+    }
+
+    /// Parses the 64-bit SMBIOS entry point, returning `None` if it is unavailable or invalid.
+    pub fn entry_point_64(&self) -> Option<SmbiosEntryPoint> {
+        if self.smbios_entry_64 == 0 {
+            return None;
+        }
+
+        let ptr = super::utils::addr_to_ptr::<u8>(super::hhdm::to_hhdm(self.smbios_entry_64));
+
+        unsafe {
+            let length = ptr.add(6).read_volatile() as usize;
+            SmbiosEntryPoint::parse_64(core::slice::from_raw_parts(ptr, length.max(0x18)))
+        }
+    }
+
+    /// Returns the 64-bit SMBIOS entry point if it is valid, falling back to the 32-bit one.
+    pub fn entry_point(&self) -> Option<SmbiosEntryPoint> {
+        self.entry_point_64().or_else(|| self.entry_point_32())
+    }
+
+    /// Returns an iterator over the SMBIOS structures.
+    ///
+    /// ## Safety
+    /// The structure table address is physical, so `physical_offset` must be the offset at
+    /// which physical memory is mapped, zero if it is identity mapped. The whole structure
+    /// table must be mapped at that offset.
+    pub unsafe fn structures(&self, physical_offset: u64) -> Option<SmbiosStructureIter<'static>> {
+        let entry = self.entry_point()?;
+        let table = core::slice::from_raw_parts(
+            super::utils::addr_to_ptr(entry.table_address + physical_offset),
+            entry.table_length as usize,
+        );
+
+        Some(SmbiosStructureIter::new(table, entry.structure_count))
+    }
+}
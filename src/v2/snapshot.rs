@@ -0,0 +1,298 @@
+//! Fixed-capacity copies of the memory map, which can be compared to find the ranges that were
+//! added, removed or retyped, such as across bootloader versions or after the kernel carved out
+//! its own regions.
+
+use core::fmt;
+
+use super::builder::BuildError;
+use super::error::Error;
+use super::tag::{StivaleMemoryMapEntry, StivaleMemoryMapEntryType, StivaleMemoryMapTag};
+
+/// A copy of up to `N` memory map entries, which stays valid once the bootloader reclaimable
+/// memory holding the memory map tag is reused.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{
+///     MemoryMapChange, MemoryMapSnapshot, StivaleMemoryMapEntry, StivaleMemoryMapEntryType,
+/// };
+///
+/// let before = MemoryMapSnapshot::<16>::from_entries(&[
+///     StivaleMemoryMapEntry::new(0x0, 0x9f000, StivaleMemoryMapEntryType::Usable),
+///     StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Usable),
+/// ])
+/// .unwrap();
+///
+/// // The kernel reserves the first page for itself.
+/// let after = MemoryMapSnapshot::<16>::from_entries(&[
+///     StivaleMemoryMapEntry::new(0x0, 0x1000, StivaleMemoryMapEntryType::Reserved),
+///     StivaleMemoryMapEntry::new(0x1000, 0x9e000, StivaleMemoryMapEntryType::Usable),
+///     StivaleMemoryMapEntry::new(0x100000, 0x100000, StivaleMemoryMapEntryType::Usable),
+/// ])
+/// .unwrap();
+///
+/// let mut changes = MemoryMapSnapshot::diff(&before, &after);
+/// assert_eq!(
+///     changes.next(),
+///     Some(MemoryMapChange::Retyped {
+///         base: 0x0,
+///         length: 0x1000,
+///         from: StivaleMemoryMapEntryType::Usable,
+///         to: StivaleMemoryMapEntryType::Reserved,
+///     })
+/// );
+/// assert_eq!(changes.next(), None);
+/// ```
+#[derive(Clone, Copy)]
+pub struct MemoryMapSnapshot<const N: usize> {
+    entries: [StivaleMemoryMapEntry; N],
+    len: usize,
+}
+
+impl<const N: usize> MemoryMapSnapshot<N> {
+    /// Creates an empty snapshot.
+    pub fn new() -> Self {
+        Self {
+            entries: [StivaleMemoryMapEntry::new(0, 0, StivaleMemoryMapEntryType::Reserved); N],
+            len: 0,
+        }
+    }
+
+    /// Copies the entries of `memory_map`, failing with
+    /// [BuildError::BufferTooSmall] if it has more than `N` entries.
+    pub fn from_tag(memory_map: &StivaleMemoryMapTag) -> Result<Self, Error> {
+        Self::from_entries(memory_map.as_slice())
+    }
+
+    /// Copies `entries`, failing with [BuildError::BufferTooSmall] if there are more than `N`
+    /// of them.
+    pub fn from_entries(entries: &[StivaleMemoryMapEntry]) -> Result<Self, Error> {
+        let mut snapshot = Self::new();
+
+        for &entry in entries {
+            snapshot.push(entry)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Appends `entry`, failing with [BuildError::BufferTooSmall] if the snapshot is full.
+    pub fn push(&mut self, entry: StivaleMemoryMapEntry) -> Result<(), Error> {
+        let slot = self
+            .entries
+            .get_mut(self.len)
+            .ok_or(Error::Build(BuildError::BufferTooSmall))?;
+
+        *slot = entry;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Returns the entries of the snapshot.
+    pub fn as_slice(&self) -> &[StivaleMemoryMapEntry] {
+        &self.entries[..self.len]
+    }
+
+    /// Returns the amount of entries in the snapshot.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the snapshot has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the differences between `before` and `after`, in ascending
+    /// address order. Adjacent ranges with the same change are merged, so splitting or merging
+    /// entries without changing their types is not reported.
+    pub fn diff<'a, const M: usize>(
+        before: &'a MemoryMapSnapshot<N>,
+        after: &'a MemoryMapSnapshot<M>,
+    ) -> MemoryMapDiff<'a> {
+        MemoryMapDiff {
+            before: before.as_slice(),
+            after: after.as_slice(),
+            position: Some(0),
+        }
+    }
+}
+
+impl<const N: usize> Default for MemoryMapSnapshot<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for MemoryMapSnapshot<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+/// A range whose type differs between two memory maps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryMapChange {
+    /// The range is only part of the second memory map.
+    Added {
+        base: u64,
+        length: u64,
+        entry_type: StivaleMemoryMapEntryType,
+    },
+    /// The range is only part of the first memory map.
+    Removed {
+        base: u64,
+        length: u64,
+        entry_type: StivaleMemoryMapEntryType,
+    },
+    /// The range is part of both memory maps, with different types.
+    Retyped {
+        base: u64,
+        length: u64,
+        from: StivaleMemoryMapEntryType,
+        to: StivaleMemoryMapEntryType,
+    },
+}
+
+impl MemoryMapChange {
+    /// Classifies a range which has the type `before` in the first memory map and `after` in
+    /// the second one, `None` meaning that it is not part of the memory map.
+    fn new(
+        base: u64,
+        length: u64,
+        before: Option<StivaleMemoryMapEntryType>,
+        after: Option<StivaleMemoryMapEntryType>,
+    ) -> Option<Self> {
+        match (before, after) {
+            (None, Some(entry_type)) => Some(MemoryMapChange::Added {
+                base,
+                length,
+                entry_type,
+            }),
+            (Some(entry_type), None) => Some(MemoryMapChange::Removed {
+                base,
+                length,
+                entry_type,
+            }),
+            (Some(from), Some(to)) if from != to => Some(MemoryMapChange::Retyped {
+                base,
+                length,
+                from,
+                to,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the changed range.
+    pub fn range(&self) -> core::ops::Range<u64> {
+        let (base, length) = match *self {
+            MemoryMapChange::Added { base, length, .. }
+            | MemoryMapChange::Removed { base, length, .. }
+            | MemoryMapChange::Retyped { base, length, .. } => (base, length),
+        };
+
+        base..base + length
+    }
+}
+
+impl fmt::Display for MemoryMapChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.range();
+
+        match self {
+            MemoryMapChange::Added { entry_type, .. } => write!(
+                f,
+                "added {:#018x}-{:#018x} {:?}",
+                range.start, range.end, entry_type
+            ),
+            MemoryMapChange::Removed { entry_type, .. } => write!(
+                f,
+                "removed {:#018x}-{:#018x} {:?}",
+                range.start, range.end, entry_type
+            ),
+            MemoryMapChange::Retyped { from, to, .. } => write!(
+                f,
+                "retyped {:#018x}-{:#018x} {:?} -> {:?}",
+                range.start, range.end, from, to
+            ),
+        }
+    }
+}
+
+/// An iterator over the differences between two memory maps, returned by
+/// [MemoryMapSnapshot::diff].
+pub struct MemoryMapDiff<'a> {
+    before: &'a [StivaleMemoryMapEntry],
+    after: &'a [StivaleMemoryMapEntry],
+    /// The address up to which the memory maps were compared, or `None` once done.
+    position: Option<u64>,
+}
+
+/// The types of a range in both memory maps, `None` meaning that it is not part of the memory
+/// map.
+type RangeTypes = (
+    Option<StivaleMemoryMapEntryType>,
+    Option<StivaleMemoryMapEntryType>,
+);
+
+impl MemoryMapDiff<'_> {
+    /// Returns the first entry boundary of either memory map above `addr`.
+    fn next_boundary(&self, addr: u64) -> Option<u64> {
+        self.before
+            .iter()
+            .chain(self.after)
+            .flat_map(|entry| [entry.base, entry.base.saturating_add(entry.length)])
+            .filter(|&boundary| boundary > addr)
+            .min()
+    }
+
+    /// Returns the end of the range starting at `start` and ending at the next boundary, which
+    /// lies within at most a single entry of each memory map, along with its types.
+    fn range_at(&self, start: u64) -> Option<(u64, RangeTypes)> {
+        let end = self.next_boundary(start)?;
+        let entry_type = |entries: &[StivaleMemoryMapEntry]| {
+            entries
+                .iter()
+                .find(|entry| {
+                    entry.base <= start && start < entry.base.saturating_add(entry.length)
+                })
+                .map(|entry| entry.entry_type())
+        };
+
+        Some((end, (entry_type(self.before), entry_type(self.after))))
+    }
+}
+
+impl Iterator for MemoryMapDiff<'_> {
+    type Item = MemoryMapChange;
+
+    fn next(&mut self) -> Option<MemoryMapChange> {
+        loop {
+            let start = self.position?;
+            let (mut end, types) = match self.range_at(start) {
+                Some(range) => range,
+                None => {
+                    self.position = None;
+                    return None;
+                }
+            };
+
+            // Merge the following ranges with the same types.
+            while let Some((next_end, next_types)) = self.range_at(end) {
+                if next_types != types {
+                    break;
+                }
+
+                end = next_end;
+            }
+
+            self.position = Some(end);
+
+            if let Some(change) = MemoryMapChange::new(start, end - start, types.0, types.1) {
+                return Some(change);
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Switching to a kernel provided stack before entering the kernel, as the stack provided by the
+//! bootloader is small and lies in bootloader reclaimable memory.
+
+use core::cell::UnsafeCell;
+
+use super::StivaleStruct;
+
+/// A statically allocated stack of `N` bytes, aligned to 16 bytes.
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{switch_stack, KernelStack, StivaleStruct};
+///
+/// static STACK: KernelStack<0x10000> = KernelStack::new();
+///
+/// extern "C" fn _start(boot_info: &'static StivaleStruct) -> ! {
+///     unsafe { switch_stack(STACK.top(), boot_info, kmain) }
+/// }
+///
+/// extern "C" fn kmain(boot_info: &'static StivaleStruct) -> ! {
+///     loop {}
+/// }
+/// ```
+#[repr(C, align(16))]
+pub struct KernelStack<const N: usize>(UnsafeCell<[u8; N]>);
+
+// SAFETY: The stack is only accessed through the raw pointer returned by `top`.
+unsafe impl<const N: usize> Sync for KernelStack<N> {}
+
+impl<const N: usize> KernelStack<N> {
+    /// Creates a zeroed stack.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new([0; N]))
+    }
+
+    /// Returns the top of the stack, which is where the stack pointer starts as stacks grow
+    /// downwards. The top is 16 bytes aligned if `N` is a multiple of 16.
+    pub const fn top(&self) -> *mut u8 {
+        self.0.get().cast::<u8>().wrapping_add(N)
+    }
+
+    /// Returns the size of the stack.
+    pub const fn size(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Default for KernelStack<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Switches the stack pointer to `stack_top` and calls `entry` with `boot_info` on the new stack.
+/// The frame pointer is cleared, so that backtraces end at `entry`.
+///
+/// ## Safety
+/// `stack_top` must be the 16 bytes aligned top of a mapped stack which nothing else uses, large
+/// enough for `entry`. The current stack is abandoned, so nothing on it may be referenced by
+/// `boot_info` or by anything else `entry` uses.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn switch_stack(
+    stack_top: *mut u8,
+    boot_info: &'static StivaleStruct,
+    entry: extern "C" fn(&'static StivaleStruct) -> !,
+) -> ! {
+    core::arch::asm!(
+        "mov rsp, {stack}",
+        "xor ebp, ebp",
+        "call {entry}",
+        "ud2",
+        stack = in(reg) stack_top,
+        entry = in(reg) entry,
+        in("rdi") boot_info,
+        options(noreturn)
+    )
+}
+
+/// Switches the stack pointer to `stack_top` and calls `entry` with `boot_info` on the new stack.
+/// The frame pointer is cleared, so that backtraces end at `entry`.
+///
+/// ## Safety
+/// `stack_top` must be the 16 bytes aligned top of a mapped stack which nothing else uses, large
+/// enough for `entry`. The current stack is abandoned, so nothing on it may be referenced by
+/// `boot_info` or by anything else `entry` uses.
+#[cfg(target_arch = "x86")]
+pub unsafe fn switch_stack(
+    stack_top: *mut u8,
+    boot_info: &'static StivaleStruct,
+    entry: extern "C" fn(&'static StivaleStruct) -> !,
+) -> ! {
+    core::arch::asm!(
+        "mov esp, {stack}",
+        "xor ebp, ebp",
+        // Keep the stack 16 bytes aligned at the call.
+        "sub esp, 12",
+        "push {boot_info}",
+        "call {entry}",
+        "ud2",
+        stack = in(reg) stack_top,
+        boot_info = in(reg) boot_info,
+        entry = in(reg) entry,
+        options(noreturn)
+    )
+}
+
+/// Switches the stack pointer to `stack_top` and calls `entry` with `boot_info` on the new stack.
+/// The frame pointer and the link register are cleared, so that backtraces end at `entry`.
+///
+/// ## Safety
+/// `stack_top` must be the 16 bytes aligned top of a mapped stack which nothing else uses, large
+/// enough for `entry`. The current stack is abandoned, so nothing on it may be referenced by
+/// `boot_info` or by anything else `entry` uses.
+#[cfg(target_arch = "aarch64")]
+pub unsafe fn switch_stack(
+    stack_top: *mut u8,
+    boot_info: &'static StivaleStruct,
+    entry: extern "C" fn(&'static StivaleStruct) -> !,
+) -> ! {
+    core::arch::asm!(
+        "mov sp, {stack}",
+        "mov x29, xzr",
+        "mov x30, xzr",
+        "br {entry}",
+        stack = in(reg) stack_top,
+        entry = in(reg) entry,
+        in("x0") boot_info,
+        options(noreturn)
+    )
+}
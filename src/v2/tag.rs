@@ -1,16 +1,173 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::marker::PhantomData;
+use core::net::Ipv4Addr;
+use core::sync::atomic::{AtomicU64, Ordering};
 
+use super::builder::BuildError;
 use super::header::StivaleSmpHeaderTagFlags;
+use super::utils::{addr_to_ptr, checked_slice, FixedCStr, LossyStr};
 
 #[repr(C)]
+#[derive(Debug)]
 pub struct StivaleTagHeader {
     pub identifier: u64,
     pub next: u64,
 }
 
+/// Headers are compared by identifier only, as the `next` pointer depends on the position of
+/// the tag in the chain rather than on its contents, so that tags read from the chain compare
+/// equal to the tags they were built from.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{round_trip, BootResponse, StivaleEpochTag, StivaleUartTag};
+///
+/// let epoch = StivaleEpochTag::new(1_600_000_000);
+/// let uart = StivaleUartTag::new(0x9000000);
+/// let response = BootResponse {
+///     epoch: Some(&epoch),
+///     uart: Some(&uart),
+///     ..Default::default()
+/// };
+///
+/// let buffer = Box::leak(Box::new([0u8; 4096]));
+/// let stivale_struct = round_trip(buffer, &response).unwrap();
+///
+/// assert_eq!(stivale_struct.epoch(), Some(&epoch));
+/// assert_eq!(stivale_struct.uart(), Some(&uart));
+/// ```
+impl PartialEq for StivaleTagHeader {
+    fn eq(&self, other: &Self) -> bool {
+        self.identifier == other.identifier
+    }
+}
+
+impl Eq for StivaleTagHeader {}
+
+impl Hash for StivaleTagHeader {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identifier.hash(state);
+    }
+}
+
+impl StivaleTagHeader {
+    /// Creates a new tag header with the provided identifier and the `next` pointer zeroed.
+    pub const fn new(identifier: u64) -> Self {
+        Self {
+            identifier,
+            next: 0x00,
+        }
+    }
+}
+
+/// Trait implemented by every stivale2 struct tag, associating the tag type with the
+/// identifier the bootloader uses for it in the tag chain.
+///
+/// # Safety
+/// The type must be `#[repr(C)]` or `#[repr(C, packed)]`, start with a [StivaleTagHeader], and
+/// be plain old data: it must not contain references, `bool`s, enums or other types with
+/// invalid bit patterns, as tags are reinterpreted from and copied into raw memory.
+///
+/// ```rust,compile_fail,E0200
+/// use stivale_boot::v2::StivaleTag;
+///
+/// struct Tiny(u8);
+///
+/// // Fails to compile, the trait is `unsafe`.
+/// impl StivaleTag for Tiny {
+///     const IDENTIFIER: u64 = 0;
+/// }
+/// ```
+pub unsafe trait StivaleTag {
+    /// The unique identifier of this tag.
+    const IDENTIFIER: u64;
+}
+
+macro_rules! impl_stivale_tag {
+    ($($name:ident: $id:expr;)*) => {
+        $(#[allow(deprecated)] unsafe impl StivaleTag for $name {
+            const IDENTIFIER: u64 = $id;
+        })*
+
+        /// Returns the name of the struct tag type with the provided identifier, if known.
+        pub fn tag_name(identifier: u64) -> Option<&'static str> {
+            $(if identifier == $id {
+                return Some(stringify!($name));
+            })*
+
+            None
+        }
+
+        /// The identifiers of every struct tag type known to this crate.
+        pub(crate) const KNOWN_TAGS: &[u64] = &[$($id),*];
+    };
+}
+
+impl_stivale_tag! {
+    StivaleCommandLineTag: 0xe5e76a1b4597a781;
+    StivaleMemoryMapTag: 0x2187f79e8612de07;
+    StivaleFramebufferTag: 0x506461d2950408fa;
+    StivaleEdidInfoTag: 0x968609d7af96b845;
+    StivaleMtrrTag: 0x6bc1a78ebe871172;
+    StivaleTerminalTag: 0xc2b3f4c3233b0974;
+    StivaleModuleTag: 0x4b6fe466aade04ce;
+    StivaleRsdpTag: 0x9e1786930a375e78;
+    StivaleSmbiosTag: 0x274bd246c62bf7d1;
+    StivaleEpochTag: 0x566a7bed888e1407;
+    StivaleFirmwareTag: 0x359d837855e3858c;
+    StivaleEfiSystemTableTag: 0x4bc5ec15845b558e;
+    StivaleKernelFileTag: 0xe599d90c2975584a;
+    StivaleKernelSlideTag: 0xee80847d01506c57;
+    StivaleSmpTag: 0x34d1d96339647025;
+    StivalePxeInfoTag: 0x29d1e96239247032;
+    StivaleUartTag: 0xb813f9b8dbc78797;
+    StivaleDeviceTreeTag: 0xabb29bd49a2833fa;
+    StivaleVMapTag: 0xb0ed257db18cb58f;
+    StivaleKernelFileV2Tag: 0x37c13018a02c6ea2;
+    StivalePmrsTag: 0x5df266a64047b6bd;
+    StivaleKernelBaseAddressTag: 0x060d78874a2a8af0;
+}
+
+/// Implements `Default` for fixed-size tags, creating them with the identifier set and every
+/// other field zeroed through their `new` constructor.
+macro_rules! impl_default_tag {
+    ($($name:ident($($arg:expr),*);)*) => {
+        $(#[allow(deprecated)] impl Default for $name {
+            fn default() -> Self {
+                Self::new($($arg),*)
+            }
+        })*
+    };
+}
+
+// The framebuffer tag keeps the RGB memory model, as it is the only one the specification
+// defines.
+impl_default_tag! {
+    StivaleFramebufferTag(0, 0, 0, 0, 0);
+    StivaleTerminalTag(0, 0, 0);
+    StivaleRsdpTag(0);
+    StivaleEpochTag(0);
+    StivaleFirmwareTag(StivaleFirmwareTagFlags::empty());
+    StivaleEfiSystemTableTag(0);
+    StivaleKernelFileTag(0);
+    StivaleKernelSlideTag(0);
+    StivaleCommandLineTag(0);
+    StivaleMtrrTag();
+    StivaleSmbiosTag(0, 0);
+    StivalePxeInfoTag(0);
+    StivaleUartTag(0);
+    StivaleDeviceTreeTag(0, 0);
+    StivaleVMapTag(0);
+    StivaleKernelFileV2Tag(0, 0);
+    StivaleKernelBaseAddressTag(0, 0);
+}
+
 /// If the framebuffer tag was requested through the framebuffer tag header and its supported by the stivale
 /// bootloader, this tag is returned to the kernel. This tag provides an interface to the framebuffer.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleFramebufferTag {
     pub header: StivaleTagHeader,
     /// The address of the framebuffer.
@@ -42,6 +199,33 @@ pub struct StivaleFramebufferTag {
 }
 
 impl StivaleFramebufferTag {
+    /// Creates a new framebuffer tag describing a RGB framebuffer at `framebuffer_addr`. The
+    /// colour mask sizes and shifts are zeroed and should be filled in by the caller.
+    pub const fn new(
+        framebuffer_addr: u64,
+        framebuffer_width: u16,
+        framebuffer_height: u16,
+        framebuffer_pitch: u16,
+        framebuffer_bpp: u16,
+    ) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            framebuffer_addr,
+            framebuffer_width,
+            framebuffer_height,
+            framebuffer_pitch,
+            framebuffer_bpp,
+            memory_model: 1,
+            red_mask_size: 0,
+            red_mask_shift: 0,
+            green_mask_size: 0,
+            green_mask_shift: 0,
+            blue_mask_size: 0,
+            blue_mask_shift: 0,
+            _padding: 0,
+        }
+    }
+
     /// Returns the size of the framebuffer.
     pub fn size(&self) -> usize {
         self.framebuffer_pitch as usize
@@ -66,6 +250,18 @@ pub struct StivaleTerminalTag {
 }
 
 impl StivaleTerminalTag {
+    /// Creates a new terminal tag with the provided dimensions and the address of the
+    /// `term_write` function.
+    pub const fn new(cols: u16, rows: u16, term_write_addr: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            flags: 0,
+            cols,
+            rows,
+            term_write_addr,
+        }
+    }
+
     /// Returns the terminal write function provided by the terminal stivale tag. This function
     /// returns the transmuted function for you to simplify the process of passing the string as a raw pointer
     /// and passing the string length.
@@ -86,7 +282,7 @@ impl StivaleTerminalTag {
     /// ## Safety
     /// This function is **not** thread safe.
     pub fn term_write(&self) -> impl Fn(&str) {
-        let __fn_ptr = self.term_write_addr as *const ();
+        let __fn_ptr = addr_to_ptr::<()>(self.term_write_addr);
         let __term_func =
             unsafe { core::mem::transmute::<*const (), extern "C" fn(*const i8, u64)>(__fn_ptr) };
 
@@ -94,16 +290,151 @@ impl StivaleTerminalTag {
             __term_func(txt.as_ptr() as *const i8, txt.len() as u64);
         }
     }
+
+    /// Writes `bytes` to the terminal. The terminal is passed their length rather than a
+    /// null-terminated string, so they may contain null bytes and invalid UTF-8, such as binary
+    /// data, which the terminal displays as is.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use core::sync::atomic::{AtomicU64, Ordering};
+    /// use stivale_boot::v2::StivaleTerminalTag;
+    ///
+    /// static WRITTEN: AtomicU64 = AtomicU64::new(0);
+    ///
+    /// extern "C" fn term_write(_: *const i8, length: u64) {
+    ///     WRITTEN.fetch_add(length, Ordering::Relaxed);
+    /// }
+    ///
+    /// let tag = StivaleTerminalTag::new(80, 25, term_write as usize as u64);
+    /// tag.write_bytes(&[0xde, 0x00, 0xad, 0xff]);
+    ///
+    /// assert_eq!(WRITTEN.load(Ordering::Relaxed), 4);
+    /// ```
+    ///
+    /// ## Safety
+    /// This function is **not** thread safe.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        let term_func = unsafe {
+            core::mem::transmute::<*const (), extern "C" fn(*const i8, u64)>(addr_to_ptr(
+                self.term_write_addr,
+            ))
+        };
+
+        term_func(bytes.as_ptr() as *const i8, bytes.len() as u64);
+    }
 }
 
+/// The signature at the start of every ACPI RSDP structure.
+pub const ACPI_RSDP_SIGNATURE: [u8; 8] = *b"RSD PTR ";
+
+/// Size of the ACPI 1.0 RSDP structure, which is covered by the first checksum.
+const ACPI_RSDP_V1_SIZE: usize = 20;
+/// Size of the ACPI 2.0 RSDP structure, which is covered by the extended checksum.
+const ACPI_RSDP_V2_SIZE: usize = 36;
+
 /// This tag is used to get the location of the ACPI RSDP structure in memory.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleRsdpTag {
     pub header: StivaleTagHeader,
     /// Pointer to the ACPI RSDP structure.
     pub rsdp: u64,
 }
 
+impl StivaleRsdpTag {
+    /// Creates a new RSDP tag pointing at the ACPI RSDP structure at `rsdp`.
+    pub const fn new(rsdp: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            rsdp,
+        }
+    }
+
+    /// Returns the bytes of the RSDP structure. Only the ACPI 1.0 part of the structure is
+    /// returned, unless the revision says that the structure has been extended.
+    fn rsdp_bytes(&self) -> &[u8] {
+        if self.rsdp == 0 {
+            return &[];
+        }
+
+        let ptr = addr_to_ptr::<u8>(super::hhdm::to_hhdm(self.rsdp));
+
+        unsafe {
+            let bytes = core::slice::from_raw_parts(ptr, ACPI_RSDP_V1_SIZE);
+
+            if bytes[15] < 2 {
+                return bytes;
+            }
+
+            let length = core::ptr::read_unaligned(ptr.add(20) as *const u32) as usize;
+            core::slice::from_raw_parts(ptr, length.max(ACPI_RSDP_V2_SIZE))
+        }
+    }
+
+    /// Returns the ACPI revision of the RSDP structure. The revision is 0 for ACPI 1.0, which
+    /// only provides the RSDT, and 2 for ACPI 2.0 and later, which also provide the XSDT.
+    pub fn revision(&self) -> u8 {
+        self.rsdp_bytes().get(15).copied().unwrap_or(0)
+    }
+
+    /// Returns whether the RSDP structure has the expected signature and whether its checksums
+    /// are correct. The extended checksum is only verified for ACPI 2.0 and later.
+    pub fn is_valid(&self) -> bool {
+        let bytes = self.rsdp_bytes();
+
+        if bytes.len() < ACPI_RSDP_V1_SIZE || bytes[..8] != ACPI_RSDP_SIGNATURE {
+            return false;
+        }
+
+        let checksum = |bytes: &[u8]| bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        checksum(&bytes[..ACPI_RSDP_V1_SIZE]) == 0
+            && (bytes.len() == ACPI_RSDP_V1_SIZE || checksum(bytes) == 0)
+    }
+
+    /// Returns the physical address of the RSDT, or `None` if the RSDP structure is invalid.
+    pub fn rsdt_address(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
+        }
+
+        let bytes = self.rsdp_bytes();
+        let mut address = [0; 4];
+        address.copy_from_slice(&bytes[16..20]);
+
+        Some(u32::from_le_bytes(address) as u64)
+    }
+
+    /// Returns the physical address of the XSDT, or `None` if the RSDP structure is invalid or
+    /// predates ACPI 2.0.
+    pub fn xsdt_address(&self) -> Option<u64> {
+        if !self.is_valid() || self.revision() < 2 {
+            return None;
+        }
+
+        let bytes = self.rsdp_bytes();
+        let mut address = [0; 8];
+        address.copy_from_slice(&bytes[24..32]);
+
+        Some(u64::from_le_bytes(address))
+    }
+
+    /// Parses the ACPI tables starting at the RSDP, mapping them with `handler`.
+    ///
+    /// ## Safety
+    /// The RSDP address must be the physical address of a valid RSDP, and `handler` must map
+    /// physical memory correctly. If higher half pointers were requested, the RSDP address is
+    /// a higher half address and `handler` has to accept it as such.
+    #[cfg(feature = "acpi")]
+    pub unsafe fn acpi_tables<H: acpi::AcpiHandler>(
+        &self,
+        handler: H,
+    ) -> acpi::AcpiResult<acpi::AcpiTables<H>> {
+        acpi::AcpiTables::from_rsdp(handler, self.rsdp as usize)
+    }
+}
+
 /// The type of a memory map entry. The entries are guaranteed to be sorted by base address,
 /// lowest to highest.
 ///
@@ -132,6 +463,48 @@ pub enum StivaleMemoryMapEntryType {
     Framebuffer = 0x1002,
 }
 
+impl TryFrom<u32> for StivaleMemoryMapEntryType {
+    type Error = super::error::Error;
+
+    /// Converts a raw entry type, failing with [Error::InvalidValue](super::Error::InvalidValue)
+    /// if it is not defined by the specification.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use core::convert::TryFrom;
+    /// use stivale_boot::v2::{Error, StivaleMemoryMapEntryType};
+    ///
+    /// assert_eq!(
+    ///     StivaleMemoryMapEntryType::try_from(0x1000u32),
+    ///     Ok(StivaleMemoryMapEntryType::BootloaderReclaimable)
+    /// );
+    /// assert_eq!(StivaleMemoryMapEntryType::try_from(6u32), Err(Error::InvalidValue(6)));
+    /// ```
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::Usable,
+            2 => Self::Reserved,
+            3 => Self::AcpiReclaimable,
+            4 => Self::AcpiNvs,
+            5 => Self::BadMemory,
+            0x1000 => Self::BootloaderReclaimable,
+            0x1001 => Self::Kernel,
+            0x1002 => Self::Framebuffer,
+            _ => return Err(super::error::Error::InvalidValue(value as u64)),
+        })
+    }
+}
+
+impl TryFrom<u64> for StivaleMemoryMapEntryType {
+    type Error = super::error::Error;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        u32::try_from(value)
+            .map_err(|_| super::error::Error::InvalidValue(value))
+            .and_then(Self::try_from)
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct StivaleMemoryMapEntry {
@@ -146,12 +519,32 @@ pub struct StivaleMemoryMapEntry {
 }
 
 impl StivaleMemoryMapEntry {
+    /// Creates a new memory map entry of type `entry_type` spanning `length` bytes from `base`.
+    pub const fn new(base: u64, length: u64, entry_type: StivaleMemoryMapEntryType) -> Self {
+        Self {
+            base,
+            length,
+            entry_type,
+            _padding: 0,
+        }
+    }
+
     /// Returns the end address of this memory region.
     #[inline]
     pub fn end_address(&self) -> u64 {
         self.base + self.length
     }
 
+    /// Returns the part of this memory region which lies in the address space, which is all of
+    /// it on 64-bit targets and the part below 4GiB on 32-bit targets. Returns `None` if no
+    /// part of it does.
+    pub fn addressable_range(&self) -> Option<core::ops::Range<usize>> {
+        let start = usize::try_from(self.base).ok()?;
+        let end = usize::try_from(self.end_address()).unwrap_or(usize::MAX);
+
+        Some(start..end).filter(|range| !range.is_empty())
+    }
+
     /// Returns the entry type of this memory region. External function is required
     /// as reference the entry_type packed field is not aligned.
     #[inline]
@@ -175,25 +568,99 @@ impl StivaleMemoryMapTag {
         unsafe { core::slice::from_raw_parts(self.entry_array.as_ptr(), self.entries_len as usize) }
     }
 
-    /// # Safety
-    /// `ptr` must be a pointer to a properly initialized [`StivaleMemoryMapTag`] struct with
-    /// `mem_entry_count` entries in the `entry_array`.
-    pub unsafe fn new_from_ptr_count(ptr: *mut (), mem_entry_count: u64) -> *mut Self {
-        // Construct a pointer to a slice that has the appropriate length metadata
-        let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, mem_entry_count as usize);
-        // Change the pointer to point to the proper struct, the length metadata is unchanged, so the DST
-        // field has the same length.
-        slice_ptr as *mut Self
+    raw_constructor! {
+        /// # Safety
+        /// `ptr` must be a pointer to a properly initialized [`StivaleMemoryMapTag`] struct with
+        /// `mem_entry_count` entries in the `entry_array`.
+        pub unsafe fn new_from_ptr_count(ptr: *mut (), mem_entry_count: u64) -> *mut Self {
+            // Construct a pointer to a slice that has the appropriate length metadata
+            let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, mem_entry_count as usize);
+            // Change the pointer to point to the proper struct, the length metadata is unchanged, so the DST
+            // field has the same length.
+            slice_ptr as *mut Self
+        }
     }
 
     /// Returns an iterator over all the memory regions.
-    pub fn iter(&self) -> StivaleMemoryMapIter {
+    pub fn iter(&self) -> StivaleMemoryMapIter<'_> {
         StivaleMemoryMapIter {
             sref: self,
             current: 0x00,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         }
     }
+
+    /// Returns the entry containing `addr`, or `None` if `addr` is not part of any entry. As the
+    /// entries are sorted by base address, the lookup is a binary search.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{
+    ///     round_trip, BootResponse, StivaleMemoryMapEntry, StivaleMemoryMapEntryType,
+    /// };
+    ///
+    /// let memory_map = [
+    ///     StivaleMemoryMapEntry::new(0x0, 0x9f000, StivaleMemoryMapEntryType::Usable),
+    ///     StivaleMemoryMapEntry::new(0x100000, 0x700000, StivaleMemoryMapEntryType::Usable),
+    ///     StivaleMemoryMapEntry::new(0x800000, 0x1000, StivaleMemoryMapEntryType::Reserved),
+    ///     StivaleMemoryMapEntry::new(0x1000000, 0x100000, StivaleMemoryMapEntryType::Usable),
+    /// ];
+    /// let response = BootResponse {
+    ///     memory_map: Some(&memory_map),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let buffer = Box::leak(Box::new([0u8; 512]));
+    /// let memory_map = round_trip(buffer, &response).unwrap().memory_map().unwrap();
+    ///
+    /// assert_eq!(memory_map.find_entry_containing(0x800fff).unwrap().base, 0x800000);
+    /// assert!(memory_map.find_entry_containing(0x801000).is_none());
+    /// assert_eq!(memory_map.entries_from(0x9f000)[0].base, 0x100000);
+    /// assert_eq!(memory_map.next_usable_frame(0x7ff800, 0x1000), Some(0x1000000));
+    /// ```
+    pub fn find_entry_containing(&self, addr: u64) -> Option<&StivaleMemoryMapEntry> {
+        let entries = self.as_slice();
+        let index = entries.partition_point(|entry| entry.base <= addr);
+
+        entries[..index]
+            .last()
+            .filter(|entry| addr < entry.end_address())
+    }
+
+    /// Returns the entries which end above `addr`, skipping the ones before it with a binary
+    /// search. The first returned entry contains `addr` if any entry does.
+    pub fn entries_from(&self, addr: u64) -> &[StivaleMemoryMapEntry] {
+        let entries = self.as_slice();
+        let index = entries.partition_point(|entry| entry.end_address() <= addr);
+
+        &entries[index..]
+    }
+
+    /// Returns the address of the first usable frame of `frame_size` bytes at or above `addr`,
+    /// aligned to `frame_size`, which must be a power of two. Frames which would straddle the
+    /// end of an entry are skipped.
+    pub fn next_usable_frame(&self, addr: u64, frame_size: u64) -> Option<u64> {
+        debug_assert!(frame_size.is_power_of_two());
+
+        let align = |addr: u64| {
+            addr.checked_add(frame_size - 1)
+                .map(|addr| addr & !(frame_size - 1))
+        };
+        let addr = align(addr)?;
+
+        self.entries_from(addr)
+            .iter()
+            .filter(|entry| entry.entry_type() == StivaleMemoryMapEntryType::Usable)
+            .find_map(|entry| {
+                let frame = align(entry.base.max(addr))?;
+
+                if frame.checked_add(frame_size)? <= entry.end_address() {
+                    Some(frame)
+                } else {
+                    None
+                }
+            })
+    }
 }
 
 /// Iterator over all the memory regions provided by the stivale bootloader.
@@ -223,12 +690,23 @@ impl<'a> Iterator for StivaleMemoryMapIter<'a> {
 
 /// This tag is used to get the current UNIX epoch, as per RTC.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleEpochTag {
     pub header: StivaleTagHeader,
     /// UNIX epoch at boot, which is read from system RTC.
     pub epoch: u64,
 }
 
+impl StivaleEpochTag {
+    /// Creates a new epoch tag with the provided UNIX epoch.
+    pub const fn new(epoch: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            epoch,
+        }
+    }
+}
+
 bitflags::bitflags! {
     /// Bitfield representing the firmware and boot flags passed by the bootloader.
     pub struct StivaleFirmwareTagFlags: u64 {
@@ -239,14 +717,91 @@ bitflags::bitflags! {
     }
 }
 
+impl_try_from_bits!(StivaleFirmwareTagFlags);
+
 /// This tag is used to get the info about the firmware.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleFirmwareTag {
     pub header: StivaleTagHeader,
     /// Flags telling about the firmware and boot flags passed by the bootloader.
     pub flags: StivaleFirmwareTagFlags,
 }
 
+impl StivaleFirmwareTag {
+    /// Creates a new firmware tag with the provided firmware flags.
+    pub const fn new(flags: StivaleFirmwareTagFlags) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            flags,
+        }
+    }
+
+    /// Returns the kind of firmware the kernel was booted from.
+    pub fn kind(&self) -> FirmwareKind {
+        if self.flags.contains(StivaleFirmwareTagFlags::BIOS) {
+            FirmwareKind::Bios
+        } else {
+            FirmwareKind::Uefi
+        }
+    }
+}
+
+/// The kind of firmware the kernel was booted from, as reported by the [StivaleFirmwareTag].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirmwareKind {
+    /// The kernel was booted in UEFI mode.
+    Uefi,
+    /// The kernel was booted in a legacy BIOS mode.
+    Bios,
+}
+
+/// The medium the kernel was booted from, as reported by
+/// [StivaleStruct::boot_medium](super::StivaleStruct::boot_medium).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootMedium {
+    /// The kernel was loaded over the network using PXE.
+    Pxe,
+    /// The kernel was loaded from a local disk.
+    Disk,
+}
+
+/// The state of the firmware services when the kernel is entered, as reported by
+/// [StivaleStruct::efi_boot_state](super::StivaleStruct::efi_boot_state).
+///
+/// When booted from UEFI, stivale2 bootloaders always call `ExitBootServices` before handing
+/// off to the kernel. The boot services table is thus unusable, and only the runtime services
+/// may be called through the EFI system table. The memory map tag reflects the memory map
+/// after boot services were exited, with the boot services memory reported as usable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EfiBootState {
+    /// The kernel was booted from a legacy BIOS, no EFI services are available.
+    Bios,
+    /// The kernel was booted from UEFI and boot services have been exited.
+    RuntimeOnly {
+        /// Address of the EFI system table, if the bootloader provided it.
+        system_table: Option<u64>,
+    },
+    /// The bootloader did not provide the firmware tag.
+    Unknown,
+}
+
+impl EfiBootState {
+    /// Returns whether boot services were exited, meaning that they must not be called.
+    /// This is also the case when booted from a legacy BIOS, as there are no boot services.
+    pub fn boot_services_exited(&self) -> bool {
+        !matches!(self, EfiBootState::Unknown)
+    }
+
+    /// Returns the address of the EFI system table, if runtime services may be used.
+    pub fn runtime_system_table(&self) -> Option<u64> {
+        match self {
+            EfiBootState::RuntimeOnly { system_table } => *system_table,
+            _ => None,
+        }
+    }
+}
+
 /// This tag is used to get a pointer to the EFI system table if available.
 #[repr(C)]
 pub struct StivaleEfiSystemTableTag {
@@ -255,6 +810,42 @@ pub struct StivaleEfiSystemTableTag {
     pub system_table_addr: u64,
 }
 
+impl StivaleEfiSystemTableTag {
+    /// Creates a new EFI system table tag pointing at the system table at `system_table_addr`.
+    pub const fn new(system_table_addr: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            system_table_addr,
+        }
+    }
+
+    /// Returns the EFI system table as a runtime system table of the `uefi` crate, or `None`
+    /// if the address is null.
+    ///
+    /// ## Safety
+    /// The system table address must point to a valid EFI system table, and boot services must
+    /// have been exited. Runtime services may only be called if the runtime services memory
+    /// regions are still mapped at the addresses the firmware expects, which for higher half
+    /// kernels requires `SetVirtualAddressMap` to have been called.
+    #[cfg(feature = "uefi")]
+    pub unsafe fn system_table(&self) -> Option<uefi::table::SystemTable<uefi::table::Runtime>> {
+        uefi::table::SystemTable::from_ptr(super::utils::addr_to_mut_ptr(self.system_table_addr))
+    }
+}
+
+/// Looks up the function containing the virtual address `addr` in the symbol table of the
+/// kernel ELF file `kernel_file`, returning its name along with the offset of `addr` in it.
+///
+/// The symbol table holds link-time addresses, so the kernel slide has to be subtracted from
+/// runtime addresses of relocated kernels first.
+#[cfg(feature = "elf")]
+pub fn symbolize(kernel_file: &[u8], addr: u64) -> Option<(&str, u64)> {
+    let elf = crate::elf::ElfFile::parse(kernel_file).ok()?;
+    let (name, offset) = elf.symbolize(addr).ok()??;
+
+    Some((core::str::from_utf8(name).ok()?, offset))
+}
+
 /// This tag is used to get the kernel with a pointer to a copy the raw executable
 /// file of the kernel that the bootloader loaded.
 #[repr(C)]
@@ -264,15 +855,72 @@ pub struct StivaleKernelFileTag {
     pub kernel_file_addr: u64,
 }
 
+impl StivaleKernelFileTag {
+    /// Creates a new kernel file tag pointing at the raw kernel file at `kernel_file_addr`.
+    pub const fn new(kernel_file_addr: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            kernel_file_addr,
+        }
+    }
+
+    /// Returns the raw kernel file as a byte slice. As this tag does not provide the size of the
+    /// file, it is derived from the ELF header, and an empty slice is returned if the file is
+    /// not a little endian ELF.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.kernel_file_addr == 0 {
+            return &[];
+        }
+
+        let ptr = addr_to_ptr::<u8>(super::hhdm::to_hhdm(self.kernel_file_addr));
+
+        unsafe {
+            match super::utils::elf_file_size(ptr) {
+                Some(size) => core::slice::from_raw_parts(ptr, size as usize),
+                None => &[],
+            }
+        }
+    }
+
+    /// Returns the name of the kernel function containing `addr` along with the offset of
+    /// `addr` in it, using the symbol table of the kernel file. See [symbolize].
+    #[cfg(feature = "elf")]
+    pub fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        symbolize(self.as_bytes(), addr)
+    }
+}
+
 /// This tag is used to get the slide that the bootloader applied over the kernel's load
 /// address as a positive offset.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleKernelSlideTag {
     pub header: StivaleTagHeader,
     /// The kernel slide. See structure-level documentation for more information.
     pub kernel_slide: u64,
 }
 
+impl StivaleKernelSlideTag {
+    /// Creates a new kernel slide tag with the provided slide.
+    pub const fn new(kernel_slide: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            kernel_slide,
+        }
+    }
+
+    /// Returns whether the bootloader did not randomize the kernel load address, either
+    /// because KASLR was disabled in its configuration or because the kernel is not
+    /// relocatable.
+    pub fn kaslr_disabled(&self) -> bool {
+        self.kernel_slide == 0
+    }
+}
+
+/// The default maximum amount of bytes scanned for the terminator of the command line. See
+/// [Limits::max_string_len](super::Limits::max_string_len).
+pub const MAX_COMMAND_LINE_LEN: usize = 4096;
+
 /// This tag is used to get the kernel the command line string that was passed to it by
 /// the bootloader.
 #[repr(C)]
@@ -282,6 +930,45 @@ pub struct StivaleCommandLineTag {
     pub command_line: u64,
 }
 
+impl StivaleCommandLineTag {
+    /// Creates a new command line tag pointing at the null-terminated string at `command_line`.
+    pub const fn new(command_line: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            command_line,
+        }
+    }
+
+    /// Returns the bytes of the null-terminated command line, without the terminator. At most
+    /// [Limits::max_string_len](super::Limits::max_string_len) bytes are scanned for the
+    /// terminator, longer command lines are truncated.
+    pub fn as_cstr_bytes(&self) -> &[u8] {
+        if self.command_line == 0 {
+            return &[];
+        }
+
+        let ptr = addr_to_ptr::<u8>(super::hhdm::to_hhdm(self.command_line));
+        let max_length = super::limits::limits().max_string_len;
+
+        unsafe {
+            let length = super::utils::c_string_length_raw(ptr, max_length);
+            core::slice::from_raw_parts(ptr, length)
+        }
+    }
+
+    /// Returns the command line passed to the kernel as a rust string, or an error if it is not
+    /// valid UTF-8.
+    pub fn as_str(&self) -> Result<&str, core::str::Utf8Error> {
+        core::str::from_utf8(self.as_cstr_bytes())
+    }
+
+    /// Returns the command line passed to the kernel, displayed with invalid UTF-8 replaced by
+    /// `U+FFFD`.
+    pub fn as_str_lossy(&self) -> LossyStr<'_> {
+        LossyStr::from_c_bytes(self.as_cstr_bytes())
+    }
+}
+
 /// This tag is used to get the EDID information as acquired by the firmware.
 #[repr(C)]
 pub struct StivaleEdidInfoTag {
@@ -298,15 +985,17 @@ impl StivaleEdidInfoTag {
         unsafe { core::slice::from_raw_parts(self.info_array.as_ptr(), self.edid_len as usize) }
     }
 
-    /// # Safety
-    /// `ptr` must be a pointer to a properly initialized [`StivaleEdidInfoTag`] struct with
-    /// `edid_count` entries in the `info_array`
-    pub unsafe fn new_from_ptr_count(ptr: *mut (), edid_count: u64) -> *mut Self {
-        // Construct a pointer to a slice that has the appropriate length metadata.
-        let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, edid_count as usize);
-        // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
-        // DST field has the same length
-        slice_ptr as *mut Self
+    raw_constructor! {
+        /// # Safety
+        /// `ptr` must be a pointer to a properly initialized [`StivaleEdidInfoTag`] struct with
+        /// `edid_count` entries in the `info_array`
+        pub unsafe fn new_from_ptr_count(ptr: *mut (), edid_count: u64) -> *mut Self {
+            // Construct a pointer to a slice that has the appropriate length metadata.
+            let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, edid_count as usize);
+            // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
+            // DST field has the same length
+            slice_ptr as *mut Self
+        }
     }
 }
 
@@ -323,6 +1012,16 @@ pub struct StivaleMtrrTag {
     pub header: StivaleTagHeader,
 }
 
+#[allow(deprecated)]
+impl StivaleMtrrTag {
+    /// Creates a new MTRR tag.
+    pub const fn new() -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+        }
+    }
+}
+
 /// Structure representing a module, containing the information of a module that
 /// the bootloader loaded alongside the kernel.
 #[repr(C)]
@@ -333,21 +1032,96 @@ pub struct StivaleModule {
     pub end: u64,
     /// ASCII 0-terminated string passed to the module as specified in
     /// the config file.
-    pub string: [u8; 128],
+    pub string: FixedCStr<128>,
 }
 
 impl StivaleModule {
+    /// Creates a new module spanning from `start` to `end` with the provided string. Strings
+    /// longer than 127 bytes are truncated on a character boundary so that the string stays
+    /// null-terminated.
+    pub fn new(string: &str, start: u64, end: u64) -> Self {
+        let mut module = Self {
+            start,
+            end,
+            string: FixedCStr::new(),
+        };
+
+        module.string.set_truncated(string);
+        module
+    }
+
     /// Returns the size of this module.
     #[inline]
     pub fn size(&self) -> u64 {
         self.end - self.start
     }
 
+    /// Creates a new module spanning from `start` to `end` with the provided string, returning
+    /// an error instead of truncating strings longer than 127 bytes.
+    pub fn try_new(string: &str, start: u64, end: u64) -> Result<Self, BuildError> {
+        let mut module = Self {
+            start,
+            end,
+            string: FixedCStr::new(),
+        };
+
+        module.set_string(string)?;
+        Ok(module)
+    }
+
     /// Returns the ASCII 0-terminated string passed to the module as specified in the config file
-    /// as a rust string.
+    /// as a rust string. If the string is not valid UTF-8, it is cut at the first invalid byte.
     #[inline]
     pub fn as_str(&self) -> &str {
-        super::utils::string_from_slice(&self.string)
+        self.string.as_str()
+    }
+
+    /// Returns the string of this module, displayed with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn as_str_lossy(&self) -> LossyStr<'_> {
+        self.string.as_str_lossy()
+    }
+
+    /// Returns whether the string is missing its null terminator, meaning that the bootloader
+    /// truncated it to fit the 128 byte field.
+    pub fn is_string_truncated(&self) -> bool {
+        self.string.is_truncated()
+    }
+
+    /// Replaces the string of this module. Returns an error if the string does not fit in 127
+    /// bytes, leaving room for the null terminator, or if it contains a null byte.
+    pub fn set_string(&mut self, string: &str) -> Result<(), BuildError> {
+        self.string.try_set(string)
+    }
+
+    /// Returns the size of this module in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start) as usize
+    }
+
+    /// Returns whether this module is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the contents of this module as a byte slice.
+    ///
+    /// The module addresses are physical addresses in an identity mapped context, and higher
+    /// half addresses in the higher half direct map if higher half pointers were requested in
+    /// the header. Physical addresses are translated through the direct map registered with
+    /// [register_hhdm_offset](super::register_hhdm_offset), if any, and used as is otherwise, so
+    /// the slice is valid as long as the matching mappings are in place. The module memory is
+    /// marked as kernel and modules in the memory map, so it is never reclaimed by the kernel
+    /// unless it explicitly frees it. The slice is empty if the module lies outside of the
+    /// address space of a 32-bit kernel.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.start == 0 {
+            return &[];
+        }
+
+        let start = super::hhdm::to_hhdm(self.start);
+        unsafe { checked_slice(start, self.end.saturating_sub(self.start)) }.unwrap_or_default()
     }
 }
 
@@ -365,14 +1139,28 @@ impl<'a> Iterator for StivaleModuleIter<'a> {
     type Item = &'a StivaleModule;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current < self.sref.module_len {
-            let entry = &self.sref.as_slice()[self.current as usize];
-            self.current += 1;
+        let entry = self.sref.as_slice().get(self.current as usize)?;
+        self.current += 1;
 
-            Some(entry)
-        } else {
-            None
-        }
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.sref.module_len - self.current) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for StivaleModuleIter<'a> {}
+
+impl<'a> core::iter::FusedIterator for StivaleModuleIter<'a> {}
+
+impl<'a> IntoIterator for &'a StivaleModuleTag {
+    type Item = &'a StivaleModule;
+    type IntoIter = StivaleModuleIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
@@ -388,14 +1176,30 @@ pub struct StivaleModuleTag {
 
 impl StivaleModuleTag {
     /// Returns an iterator over all the modules that were loaded.
-    pub fn iter(&self) -> StivaleModuleIter {
+    pub fn iter(&self) -> StivaleModuleIter<'_> {
         StivaleModuleIter {
             sref: self,
             current: 0,
-            phantom: PhantomData::default(),
+            phantom: PhantomData,
         }
     }
 
+    /// Returns the amount of modules that were loaded.
+    pub fn count(&self) -> usize {
+        self.module_len as usize
+    }
+
+    /// Returns the first module whose string is exactly `name`.
+    pub fn find(&self, name: &str) -> Option<&StivaleModule> {
+        self.iter().find(|module| module.as_str() == name)
+    }
+
+    /// Returns the first module whose string starts with `prefix`.
+    pub fn find_prefix(&self, prefix: &str) -> Option<&StivaleModule> {
+        self.iter()
+            .find(|module| module.as_str().starts_with(prefix))
+    }
+
     /// Return's the modules array pointer as a rust slice.
     pub fn as_slice(&self) -> &[StivaleModule] {
         unsafe {
@@ -403,15 +1207,17 @@ impl StivaleModuleTag {
         }
     }
 
-    /// # Safety
-    /// `ptr` must be a pointer to a properly initialized [`StivaleModuleTag`] struct with
-    /// `module_count` entries in the `modules_array`
-    pub unsafe fn new_from_ptr_count(ptr: *mut (), module_count: u64) -> *mut Self {
-        // Construct a pointer to a slice that has the appropriate length metadata.
-        let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, module_count as usize);
-        // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
-        // DST field has the same length
-        slice_ptr as *mut Self
+    raw_constructor! {
+        /// # Safety
+        /// `ptr` must be a pointer to a properly initialized [`StivaleModuleTag`] struct with
+        /// `module_count` entries in the `modules_array`
+        pub unsafe fn new_from_ptr_count(ptr: *mut (), module_count: u64) -> *mut Self {
+            // Construct a pointer to a slice that has the appropriate length metadata.
+            let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, module_count as usize);
+            // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
+            // DST field has the same length
+            slice_ptr as *mut Self
+        }
     }
 }
 
@@ -428,17 +1234,53 @@ pub struct StivaleSmbiosTag {
     pub smbios_entry_64: u64,
 }
 
+impl StivaleSmbiosTag {
+    /// Creates a new SMBIOS tag with the provided entry points. The flags are zeroed as
+    /// required by the specification.
+    pub const fn new(smbios_entry_32: u64, smbios_entry_64: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            flags: 0,
+            smbios_entry_32,
+            smbios_entry_64,
+        }
+    }
+}
+
 /// SMP imformation structure.
+///
+/// The fields written by the kernel to start an AP are atomics, so that they can be written
+/// through the shared references returned by [StivaleStruct::smp](super::StivaleStruct::smp).
+/// See [StivaleSmpInfo::start].
+///
+/// The identifiers of the processor depend on the architecture: x86 processors are identified
+/// by their LAPIC ID and aarch64 processors by their MPIDR. [StivaleSmpInfo::processor_id] and
+/// [StivaleSmpInfo::hardware_id] return them regardless of the architecture.
 #[repr(C)]
 pub struct StivaleSmpInfo {
     /// ACPI Processor UID as specified by MADT.
+    #[cfg(not(target_arch = "aarch64"))]
     pub acpi_processor_uid: u32,
     /// LAPIC ID as specified by MADT.
+    #[cfg(not(target_arch = "aarch64"))]
     pub lapic_id: u32,
+    /// ACPI Processor UID as specified by MADT.
+    #[cfg(target_arch = "aarch64")]
+    pub processor_id: u32,
+    /// GIC CPU interface number of the processor, as specified by MADT. Only valid if the GIC
+    /// is used.
+    #[cfg(target_arch = "aarch64")]
+    pub gic_iface_no: u32,
+    /// Physical ID of the processor, as specified by MADT and in the MPIDR_EL1 register.
+    #[cfg(target_arch = "aarch64")]
+    pub mpidr: u64,
+    /// Stivale specification says that this field is reserved for future use.
+    #[cfg(target_arch = "aarch64")]
+    pub reserved: u64,
     /// The stack that will be loaded in ESP/RSP once the goto_address field is loaded.
     /// This MUST point to a valid stack of at least 256 bytes in size, and 16-byte aligned.
     /// target_stack is an unused field for the struct describing the BSP.
-    pub target_stack: u64,
+    pub target_stack: AtomicU64,
     /// This field is polled by the started APs until the kernel on another CPU performs an
     /// atomic write to this field. When that happens, bootloader code will load up ESP/RSP with the stack
     /// value as specified in target_stack. It will then proceed to load a pointer to this very structure into
@@ -447,7 +1289,7 @@ pub struct StivaleSmpInfo {
     /// The CPU state will be the same as describedin "kernel entry machine state", with the exception
     /// of ESP/RSP and RDI/stack arg being set up as above. goto_address is an unused field for the
     /// struct describing the BSP.
-    pub goto_address: u64,
+    pub goto_address: AtomicU64,
     /// This field is polled by the started APs until the kernel on another CPU performs an
     /// atomic write to this field. When that happens, bootloader code will
     /// load up ESP/RSP with the stack value as specified in target_stack.
@@ -458,7 +1300,98 @@ pub struct StivaleSmpInfo {
     /// The CPU state will be the same as described in "kernel entry machine state", with the exception
     /// of ESP/RSP and RDI/stack arg being set up as above. goto_address is an unused field for the
     /// struct describing the BSP.
-    pub extra: u64,
+    pub extra: AtomicU64,
+}
+
+impl StivaleSmpInfo {
+    /// Creates a new SMP information structure for the processor with the provided ACPI UID
+    /// and LAPIC ID, which has not been started yet.
+    #[cfg(not(target_arch = "aarch64"))]
+    pub const fn new(acpi_processor_uid: u32, lapic_id: u32) -> Self {
+        Self {
+            acpi_processor_uid,
+            lapic_id,
+            target_stack: AtomicU64::new(0),
+            goto_address: AtomicU64::new(0),
+            extra: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a new SMP information structure for the processor with the provided ACPI UID,
+    /// GIC CPU interface number and MPIDR, which has not been started yet.
+    #[cfg(target_arch = "aarch64")]
+    pub const fn new(processor_id: u32, gic_iface_no: u32, mpidr: u64) -> Self {
+        Self {
+            processor_id,
+            gic_iface_no,
+            mpidr,
+            reserved: 0,
+            target_stack: AtomicU64::new(0),
+            goto_address: AtomicU64::new(0),
+            extra: AtomicU64::new(0),
+        }
+    }
+
+    /// The name of the physical ID of a processor on the target architecture, see
+    /// [StivaleSmpInfo::hardware_id].
+    #[cfg(not(target_arch = "aarch64"))]
+    pub const HARDWARE_ID_NAME: &'static str = "LAPIC ID";
+    /// The name of the physical ID of a processor on the target architecture, see
+    /// [StivaleSmpInfo::hardware_id].
+    #[cfg(target_arch = "aarch64")]
+    pub const HARDWARE_ID_NAME: &'static str = "MPIDR";
+
+    /// Returns the ACPI Processor UID of the processor.
+    pub fn processor_id(&self) -> u32 {
+        #[cfg(not(target_arch = "aarch64"))]
+        return self.acpi_processor_uid;
+        #[cfg(target_arch = "aarch64")]
+        return self.processor_id;
+    }
+
+    /// Returns the physical ID of the processor: its LAPIC ID on x86 and its MPIDR on aarch64.
+    pub fn hardware_id(&self) -> u64 {
+        #[cfg(not(target_arch = "aarch64"))]
+        return self.lapic_id as u64;
+        #[cfg(target_arch = "aarch64")]
+        return self.mpidr;
+    }
+
+    /// Returns the stack that is loaded once the AP is started.
+    pub fn target_stack(&self) -> u64 {
+        self.target_stack.load(Ordering::Acquire)
+    }
+
+    /// Returns the address the AP jumps to, zero if it has not been started.
+    pub fn goto_address(&self) -> u64 {
+        self.goto_address.load(Ordering::Acquire)
+    }
+
+    /// Returns the extra argument passed to the kernel.
+    pub fn extra(&self) -> u64 {
+        self.extra.load(Ordering::Acquire)
+    }
+
+    /// Starts the AP described by this structure, making it jump to `entry` with its stack
+    /// pointer set to `stack_top`. `extra` can be used to pass an argument to the AP.
+    ///
+    /// ## Safety
+    /// - The memory area of the stack must be exclusively used by the AP, at least 256 bytes in
+    ///   size and 16-byte aligned. Stack overflows can trigger UB, so consider using a guard
+    ///   page.
+    /// - This must only be called once per AP, and never for the BSP.
+    pub unsafe fn start(
+        &self,
+        entry: extern "C" fn(&'static StivaleSmpInfo) -> !,
+        stack_top: u64,
+        extra: u64,
+    ) {
+        self.target_stack.store(stack_top, Ordering::Relaxed);
+        self.extra.store(extra, Ordering::Relaxed);
+        // The release store publishes the stack and the extra argument to the polling AP.
+        self.goto_address
+            .store(entry as usize as u64, Ordering::Release);
+    }
 }
 
 #[repr(C)]
@@ -466,9 +1399,14 @@ pub struct StivaleSmpTag {
     header: StivaleTagHeader,
     pub flags: StivaleSmpHeaderTagFlags,
     /// LAPIC ID of the BSP (bootstrap processor).
+    #[cfg(not(target_arch = "aarch64"))]
     pub bsp_lapic_id: u32,
     /// Stivale specification says that this field is reserved for future use.
+    #[cfg(not(target_arch = "aarch64"))]
     pub unused: u32,
+    /// MPIDR of the BSP (bootstrap processor).
+    #[cfg(target_arch = "aarch64")]
+    pub bsp_mpidr: u64,
     /// The total number of logical CPUs (including BSP).
     cpu_count: u64,
     /// The variable length SMP info array (including BSP).
@@ -481,6 +1419,14 @@ impl StivaleSmpTag {
         &self.header
     }
 
+    /// Returns the physical ID of the BSP: its LAPIC ID on x86 and its MPIDR on aarch64.
+    pub fn bsp_hardware_id(&self) -> u64 {
+        #[cfg(not(target_arch = "aarch64"))]
+        return self.bsp_lapic_id as u64;
+        #[cfg(target_arch = "aarch64")]
+        return self.bsp_mpidr;
+    }
+
     /// Return's the total number of logical CPUs (including BSP).
     pub fn cpu_count(&self) -> u64 {
         self.cpu_count
@@ -501,48 +1447,108 @@ impl StivaleSmpTag {
     /// rules in order to not trigger UB:
     ///
     /// - Writing to [`StivaleSmpInfo::goto_address`] will cause it to start executing at the
-    /// provided address as such a proper stack must have been set at
-    /// [`StivaleSmpInfo::target_stack`] already if a stack is needed.
+    ///   provided address as such a proper stack must have been set at
+    ///   [`StivaleSmpInfo::target_stack`] already if a stack is needed.
     /// - The stack pointer written to [`StivaleSmpInfo::target_stack`] must not alias already
-    /// mapped memory, this means that the memory area dedicated to the stack must be exclusively
-    /// used for the AP stack and stack overflows can trigger UB (consider using a guard page).
+    ///   mapped memory, this means that the memory area dedicated to the stack must be exclusively
+    ///   used for the AP stack and stack overflows can trigger UB (consider using a guard page).
     /// - The address pointed by [`StivaleSmpInfo::goto_address`] must be that of a
-    /// `extern "C" fn(&'static StivaleSmpInfo) -> !`, this also means that once written this
-    /// struct must not be mutated any further.
+    ///   `extern "C" fn(&'static StivaleSmpInfo) -> !`, this also means that once written this
+    ///   struct must not be mutated any further.
     pub unsafe fn as_slice_mut(&mut self) -> &mut [StivaleSmpInfo] {
         core::slice::from_raw_parts_mut(self.smp_info_array.as_mut_ptr(), self.cpu_count as usize)
     }
 
-    /// # Safety
-    /// `ptr` must be a pointer to a *properly* initialized [`StivaleSmpTag`] struct with `cpu_count`
-    /// entries in the `smp_info_array`.
-    pub unsafe fn new_from_ptr_count(ptr: *mut (), cpu_count: u64) -> *mut Self {
-        // Construct a pointer to a slice that has the appropriate length metadata.
-        let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, cpu_count as usize);
-        // Change the pointer to point to the proper struct, the length metadata is unchanged,
-        // so the DST field has the same length.
-        slice_ptr as *mut Self
+    raw_constructor! {
+        /// # Safety
+        /// `ptr` must be a pointer to a *properly* initialized [`StivaleSmpTag`] struct with `cpu_count`
+        /// entries in the `smp_info_array`.
+        pub unsafe fn new_from_ptr_count(ptr: *mut (), cpu_count: u64) -> *mut Self {
+            // Construct a pointer to a slice that has the appropriate length metadata.
+            let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, cpu_count as usize);
+            // Change the pointer to point to the proper struct, the length metadata is unchanged,
+            // so the DST field has the same length.
+            slice_ptr as *mut Self
+        }
     }
 }
 
 /// This tag reports that the kernel has been booted via PXE, and reports the server ip that
 /// it was booted from.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivalePxeInfoTag {
     pub header: StivaleTagHeader,
     /// Server IP in network byte order.
     pub server_ip: u32,
+    _padding: u32,
+}
+
+impl StivalePxeInfoTag {
+    /// Creates a new PXE info tag with the provided server IP in network byte order.
+    pub const fn new(server_ip: u32) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            server_ip,
+            _padding: 0,
+        }
+    }
+
+    /// Returns the IP address of the server the kernel was booted from.
+    pub fn server_addr(&self) -> Ipv4Addr {
+        // The address is stored in network byte order, so its bytes are already in order.
+        Ipv4Addr::from(self.server_ip.to_ne_bytes())
+    }
+}
+
+impl fmt::Display for StivalePxeInfoTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PXE boot from {}", self.server_addr())
+    }
 }
 
 /// This tag reports that there is a memory mapped UART port and its address.
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleUartTag {
     pub header: StivaleTagHeader,
     /// The address of the UART port.
     pub address: u64,
 }
 
+impl StivaleUartTag {
+    /// Creates a new UART tag with the provided UART port address.
+    pub const fn new(address: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            address,
+        }
+    }
+}
+
+/// The magic at the start of every device tree blob, stored big endian.
+pub const FDT_MAGIC: u32 = 0xd00dfeed;
+
+/// Size of the device tree header since version 17 of the format.
+const FDT_HEADER_SIZE: usize = 40;
+
 /// This tag describes a device tree blob for the platform.
+///
+/// Like the other fixed-size tags, it implements `Default` with every field but the identifier
+/// zeroed, so that it can be built with struct-update syntax.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{StivaleDeviceTreeTag, StivaleTag};
+///
+/// let device_tree = StivaleDeviceTreeTag {
+///     address: 0x4000_0000,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(device_tree.header.identifier, StivaleDeviceTreeTag::IDENTIFIER);
+/// assert_eq!(device_tree.size, 0);
+/// ```
 #[repr(C)]
 pub struct StivaleDeviceTreeTag {
     pub header: StivaleTagHeader,
@@ -552,6 +1558,84 @@ pub struct StivaleDeviceTreeTag {
     pub size: u64,
 }
 
+impl StivaleDeviceTreeTag {
+    /// Creates a new device tree tag describing the blob at `address` with length `size`.
+    pub const fn new(address: u64, size: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            address,
+            size,
+        }
+    }
+
+    /// Returns the device tree blob as a byte slice, empty if the address is null or if the blob
+    /// lies outside of the address space of a 32-bit kernel.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.address == 0 {
+            return &[];
+        }
+
+        let address = super::hhdm::to_hhdm(self.address);
+        unsafe { checked_slice(address, self.size) }.unwrap_or_default()
+    }
+
+    /// Returns whether the blob starts with a device tree header with the expected magic, and
+    /// whether the total size in the header fits in the size of the tag.
+    pub fn is_valid(&self) -> bool {
+        let blob = self.as_bytes();
+
+        if blob.len() < FDT_HEADER_SIZE {
+            return false;
+        }
+
+        let read_be = |offset: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&blob[offset..offset + 4]);
+            u32::from_be_bytes(word)
+        };
+
+        let total_size = read_be(4) as usize;
+
+        read_be(0) == FDT_MAGIC && total_size >= FDT_HEADER_SIZE && total_size <= blob.len()
+    }
+
+    /// Parses the device tree blob with the `fdt` crate.
+    #[cfg(feature = "fdt")]
+    pub fn parse(&self) -> Result<fdt::Fdt<'_>, fdt::FdtError> {
+        fdt::Fdt::new(self.as_bytes())
+    }
+
+    /// Returns the UART the device tree designates as the console through the `stdout-path`
+    /// property of `/chosen`, along with the way its registers are accessed. aarch64
+    /// bootloaders often only describe the UART in the device tree, without a UART tag.
+    #[cfg(feature = "fdt")]
+    pub fn stdout_uart(&self) -> Option<(StivaleUartTag, super::uart::UartAccess)> {
+        let fdt = self.parse().ok()?;
+        let path = fdt.find_node("/chosen")?.property("stdout-path")?.value;
+        let path = core::str::from_utf8(path).ok()?.trim_end_matches('\0');
+        // The path can be followed by the options of the console, such as `:115200n8`.
+        let node = fdt.find_node(path.split(':').next()?)?;
+
+        let address = node.reg()?.next()?.starting_address as u64;
+        let compatible = node.compatible()?;
+
+        let access = if compatible.all().any(|name| name == "arm,pl011") {
+            super::uart::UartAccess::Pl011
+        } else if compatible.all().any(|name| name.starts_with("ns16550")) {
+            let shift = node
+                .property("reg-shift")
+                .and_then(|shift| shift.as_usize())
+                .unwrap_or(0);
+
+            super::uart::UartAccess::Mmio { stride: 1 << shift }
+        } else {
+            return None;
+        };
+
+        Some((StivaleUartTag::new(address), access))
+    }
+}
+
 /// This tag describes the high physical memory location.
 #[repr(C)]
 pub struct StivaleVMapTag {
@@ -560,6 +1644,16 @@ pub struct StivaleVMapTag {
     pub address: u64,
 }
 
+impl StivaleVMapTag {
+    /// Creates a new VMAP tag with the provided higher half base address.
+    pub const fn new(address: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            address,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct StivaleKernelFileV2Tag {
     pub header: StivaleTagHeader,
@@ -569,25 +1663,105 @@ pub struct StivaleKernelFileV2Tag {
     pub kernel_size: u64,
 }
 
+impl StivaleKernelFileV2Tag {
+    /// Creates a new kernel file v2 tag describing the raw kernel file at `kernel_start`.
+    pub const fn new(kernel_start: u64, kernel_size: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            kernel_start,
+            kernel_size,
+        }
+    }
+
+    /// Returns the raw kernel file as a byte slice, empty if the file lies outside of the address
+    /// space of a 32-bit kernel.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.kernel_start == 0 {
+            return &[];
+        }
+
+        let start = super::hhdm::to_hhdm(self.kernel_start);
+        unsafe { checked_slice(start, self.kernel_size) }.unwrap_or_default()
+    }
+
+    /// Returns the name of the kernel function containing `addr` along with the offset of
+    /// `addr` in it, using the symbol table of the kernel file. See [symbolize].
+    #[cfg(feature = "elf")]
+    pub fn symbolize(&self, addr: u64) -> Option<(&str, u64)> {
+        symbolize(self.as_bytes(), addr)
+    }
+}
+
 bitflags::bitflags! {
-    pub struct StivalePmrPermissionFlags: u64 {
-        const EXECUTABLE = 1 << 0;
-        const WRITABLE   = 1 << 1;
-        const READABLE   = 1 << 2;
+    /// The permissions of a protected memory range.
+    pub struct PmrPermissions: u64 {
+        /// The range is executable.
+        const EXEC  = 1 << 0;
+        /// The range is writable.
+        const WRITE = 1 << 1;
+        /// The range is readable.
+        const READ  = 1 << 2;
     }
 }
 
+impl_try_from_bits!(PmrPermissions);
+
+#[allow(deprecated)]
+impl PmrPermissions {
+    #[deprecated(note = "use `PmrPermissions::EXEC` instead")]
+    pub const EXECUTABLE: Self = Self::EXEC;
+    #[deprecated(note = "use `PmrPermissions::WRITE` instead")]
+    pub const WRITABLE: Self = Self::WRITE;
+    #[deprecated(note = "use `PmrPermissions::READ` instead")]
+    pub const READABLE: Self = Self::READ;
+}
+
+#[deprecated(note = "use `PmrPermissions` instead")]
+pub type StivalePmrPermissionFlags = PmrPermissions;
+
+/// A protected memory range, describing the permissions the kernel expects for a range of its
+/// virtual address space.
 #[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct StivalePmr {
     pub base: u64,
     pub size: u64,
-    /// The permissions field contains flgas to determine the range's permissions.
-    pub permissions: u64,
+    /// The permissions field contains flags to determine the range's permissions.
+    pub permissions: PmrPermissions,
 }
 
 impl StivalePmr {
-    pub fn permissions(&self) -> StivalePmrPermissionFlags {
-        StivalePmrPermissionFlags::from_bits_truncate(self.permissions)
+    /// Creates a new protected memory range spanning `size` bytes from `base`.
+    pub const fn new(base: u64, size: u64, permissions: PmrPermissions) -> Self {
+        Self {
+            base,
+            size,
+            permissions,
+        }
+    }
+
+    pub fn permissions(&self) -> PmrPermissions {
+        self.permissions
+    }
+
+    /// Returns whether the range is executable.
+    pub fn is_executable(&self) -> bool {
+        self.permissions.contains(PmrPermissions::EXEC)
+    }
+
+    /// Returns whether the range is writable.
+    pub fn is_writable(&self) -> bool {
+        self.permissions.contains(PmrPermissions::WRITE)
+    }
+
+    /// Returns whether the range is readable.
+    pub fn is_readable(&self) -> bool {
+        self.permissions.contains(PmrPermissions::READ)
+    }
+
+    /// Returns whether `virt_addr` lies in this range.
+    pub fn contains(&self, virt_addr: u64) -> bool {
+        virt_addr >= self.base && virt_addr - self.base < self.size
     }
 }
 
@@ -601,26 +1775,253 @@ pub struct StivalePmrsTag {
 }
 
 impl StivalePmrsTag {
+    /// Returns an iterator over all the protected memory ranges.
+    pub fn iter(&self) -> core::slice::Iter<'_, StivalePmr> {
+        self.as_slice().iter()
+    }
+
+    /// Returns the protected memory range containing the virtual address `virt_addr`.
+    pub fn find_containing(&self, virt_addr: u64) -> Option<&StivalePmr> {
+        self.iter().find(|pmr| pmr.contains(virt_addr))
+    }
+
     /// Return's the PMRs array pointer as a rust slice.
     pub fn as_slice(&self) -> &[StivalePmr] {
         unsafe { core::slice::from_raw_parts(self.pmrs.as_ptr(), self.pmr_count as usize) }
     }
 
-    /// # Safety
-    /// `ptr` must be a pointer to a properly initialized [`StivalePmrsTag`] struct with `pmr_count`
-    /// entries in the `prms` field.
-    pub unsafe fn new_from_ptr_count(ptr: *mut (), pmr_count: u64) -> *mut Self {
-        // Construct a pointer to a slice that has the appropriate length metadata.
-        let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, pmr_count as usize);
-        // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
-        // DST field has the same length.
-        slice_ptr as *mut Self
+    raw_constructor! {
+        /// # Safety
+        /// `ptr` must be a pointer to a properly initialized [`StivalePmrsTag`] struct with `pmr_count`
+        /// entries in the `prms` field.
+        pub unsafe fn new_from_ptr_count(ptr: *mut (), pmr_count: u64) -> *mut Self {
+            // Construct a pointer to a slice that has the appropriate length metadata.
+            let slice_ptr = core::ptr::slice_from_raw_parts_mut(ptr, pmr_count as usize);
+            // Change the pointer to point to the proper struct, the length metadata is unchanged, so the
+            // DST field has the same length.
+            slice_ptr as *mut Self
+        }
     }
 }
 
 #[repr(C)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StivaleKernelBaseAddressTag {
     pub header: StivaleTagHeader,
     pub physical_base_address: u64,
     pub virtual_base_address: u64,
 }
+
+impl StivaleKernelBaseAddressTag {
+    /// Creates a new kernel base address tag with the provided physical and virtual base
+    /// addresses.
+    pub const fn new(physical_base_address: u64, virtual_base_address: u64) -> Self {
+        Self {
+            header: StivaleTagHeader::new(Self::IDENTIFIER),
+            physical_base_address,
+            virtual_base_address,
+        }
+    }
+
+    /// Creates a new kernel base address tag, returning an error if either base address is not
+    /// page aligned.
+    pub fn try_new(
+        physical_base_address: u64,
+        virtual_base_address: u64,
+    ) -> Result<Self, BuildError> {
+        if (physical_base_address | virtual_base_address) & 0xfff != 0 {
+            return Err(BuildError::UnalignedRange);
+        }
+
+        Ok(Self::new(physical_base_address, virtual_base_address))
+    }
+
+    /// Checks that the base addresses are consistent with the protected memory ranges of the
+    /// kernel: both base addresses must be page aligned, and the virtual base address must be
+    /// the base of the lowest range, so that every range can be translated to the physical
+    /// memory backing it.
+    pub fn verify_against(&self, pmrs: &StivalePmrsTag) -> Result<(), BuildError> {
+        if (self.physical_base_address | self.virtual_base_address) & 0xfff != 0 {
+            return Err(BuildError::UnalignedRange);
+        }
+
+        match pmrs.iter().map(|pmr| pmr.base).min() {
+            Some(lowest) if lowest != self.virtual_base_address => Err(BuildError::InvalidRange),
+            _ => Ok(()),
+        }
+    }
+
+    /// Translates the virtual address `virt_addr` of the kernel image into a physical address.
+    /// Returns `None` if the address lies below the virtual base address of the kernel.
+    pub fn virt_to_phys(&self, virt_addr: u64) -> Option<u64> {
+        let offset = virt_addr.checked_sub(self.virtual_base_address)?;
+        self.physical_base_address.checked_add(offset)
+    }
+
+    /// Translates the physical address `phys_addr` of the kernel image into a virtual address.
+    /// Returns `None` if the address lies below the physical base address of the kernel.
+    pub fn phys_to_virt(&self, phys_addr: u64) -> Option<u64> {
+        let offset = phys_addr.checked_sub(self.physical_base_address)?;
+        self.virtual_base_address.checked_add(offset)
+    }
+
+    /// Translates the link-time address `link_addr` of the kernel image into a physical address,
+    /// applying the slide of `slide` first.
+    pub fn link_to_phys(&self, link_addr: u64, slide: &StivaleKernelSlideTag) -> Option<u64> {
+        self.virt_to_phys(link_addr.checked_add(slide.kernel_slide)?)
+    }
+}
+
+// Layouts of the tags as defined by the stivale2 specification. The accessors of
+// [StivaleStruct](super::StivaleStruct) rely on the offsets of the counts of the variable length
+// tags, such as the `cpu_count` of the SMP tag at offset 32.
+assert_layout!(StivaleTagHeader: 16 { identifier: 0, next: 8 });
+assert_layout!(StivaleFramebufferTag: 40 {
+    framebuffer_addr: 16,
+    framebuffer_width: 24,
+    framebuffer_height: 26,
+    framebuffer_pitch: 28,
+    framebuffer_bpp: 30,
+    memory_model: 32,
+    red_mask_size: 33,
+    red_mask_shift: 34,
+    green_mask_size: 35,
+    green_mask_shift: 36,
+    blue_mask_size: 37,
+    blue_mask_shift: 38,
+});
+assert_layout!(StivaleTerminalTag: 32 { flags: 16, cols: 20, rows: 22, term_write_addr: 24 });
+assert_layout!(StivaleRsdpTag: 24 { rsdp: 16 });
+assert_layout!(StivaleMemoryMapEntry: 24 { base: 0, length: 8, entry_type: 16 });
+assert_layout!(StivaleMemoryMapTag { entries_len: 16 });
+assert_layout!(StivaleEpochTag: 24 { epoch: 16 });
+assert_layout!(StivaleFirmwareTag: 24 { flags: 16 });
+assert_layout!(StivaleEfiSystemTableTag: 24 { system_table_addr: 16 });
+assert_layout!(StivaleKernelFileTag: 24 { kernel_file_addr: 16 });
+assert_layout!(StivaleKernelSlideTag: 24 { kernel_slide: 16 });
+assert_layout!(StivaleCommandLineTag: 24 { command_line: 16 });
+assert_layout!(StivaleEdidInfoTag { edid_len: 16 });
+assert_layout!(StivaleMtrrTag: 16 { header: 0 });
+assert_layout!(StivaleModule: 144 { start: 0, end: 8, string: 16 });
+assert_layout!(StivaleModuleTag { module_len: 16 });
+assert_layout!(StivaleSmbiosTag: 40 { flags: 16, smbios_entry_32: 24, smbios_entry_64: 32 });
+#[cfg(not(target_arch = "aarch64"))]
+assert_layout!(StivaleSmpInfo: 32 {
+    acpi_processor_uid: 0,
+    lapic_id: 4,
+    target_stack: 8,
+    goto_address: 16,
+    extra: 24,
+});
+#[cfg(target_arch = "aarch64")]
+assert_layout!(StivaleSmpInfo: 48 {
+    processor_id: 0,
+    gic_iface_no: 4,
+    mpidr: 8,
+    reserved: 16,
+    target_stack: 24,
+    goto_address: 32,
+    extra: 40,
+});
+#[cfg(not(target_arch = "aarch64"))]
+assert_layout!(StivaleSmpTag {
+    flags: 16,
+    bsp_lapic_id: 24,
+    unused: 28,
+    cpu_count: 32
+});
+#[cfg(target_arch = "aarch64")]
+assert_layout!(StivaleSmpTag {
+    flags: 16,
+    bsp_mpidr: 24,
+    cpu_count: 32
+});
+assert_layout!(StivalePxeInfoTag: 24 { server_ip: 16 });
+assert_layout!(StivaleUartTag: 24 { address: 16 });
+assert_layout!(StivaleDeviceTreeTag: 32 { address: 16, size: 24 });
+assert_layout!(StivaleVMapTag: 24 { address: 16 });
+assert_layout!(StivaleKernelFileV2Tag: 32 { kernel_start: 16, kernel_size: 24 });
+assert_layout!(StivalePmr: 24 { base: 0, size: 8, permissions: 16 });
+assert_layout!(StivalePmrsTag { pmr_count: 16 });
+assert_layout!(StivaleKernelBaseAddressTag: 32 {
+    physical_base_address: 16,
+    virtual_base_address: 24,
+});
+
+/// Returns the size of the tag at `tag`, including its variable length array. Fails with
+/// [BuildError::UnknownTag] if the tag is unknown to this crate, and with
+/// [BuildError::TooManyEntries] if its entry count is above the matching
+/// [Limits](super::Limits) field.
+pub(crate) unsafe fn tag_size(tag: *const StivaleTagHeader) -> Result<usize, BuildError> {
+    let identifier = (*tag).identifier;
+    let limits = super::limits::limits();
+
+    // The entry count is the last field of the fixed part of the tag.
+    let array_size = |fixed_size: usize, entry_size: usize, limit: usize| {
+        let count = (tag as *const u8).add(fixed_size - 8).cast::<u64>().read();
+
+        usize::try_from(count)
+            .ok()
+            .filter(|&count| count <= limit)
+            .and_then(|count| count.checked_mul(entry_size))
+            .and_then(|size| size.checked_add(fixed_size))
+            .ok_or(BuildError::TooManyEntries(identifier))
+    };
+
+    macro_rules! fixed_size {
+        ($($name:ty),*) => {
+            $(if identifier == <$name>::IDENTIFIER {
+                return Ok(core::mem::size_of::<$name>());
+            })*
+        };
+    }
+
+    match identifier {
+        StivaleMemoryMapTag::IDENTIFIER => array_size(
+            24,
+            core::mem::size_of::<StivaleMemoryMapEntry>(),
+            limits.max_memory_map_entries,
+        ),
+        StivaleEdidInfoTag::IDENTIFIER => array_size(24, 1, limits.max_array_entries),
+        StivaleModuleTag::IDENTIFIER => array_size(
+            24,
+            core::mem::size_of::<StivaleModule>(),
+            limits.max_modules,
+        ),
+        StivaleSmpTag::IDENTIFIER => {
+            array_size(40, core::mem::size_of::<StivaleSmpInfo>(), limits.max_cpus)
+        }
+        StivalePmrsTag::IDENTIFIER => array_size(
+            24,
+            core::mem::size_of::<StivalePmr>(),
+            limits.max_array_entries,
+        ),
+        _ => {
+            fixed_size!(
+                StivaleCommandLineTag,
+                StivaleFramebufferTag,
+                StivaleTerminalTag,
+                StivaleRsdpTag,
+                StivaleSmbiosTag,
+                StivaleEpochTag,
+                StivaleFirmwareTag,
+                StivaleEfiSystemTableTag,
+                StivaleKernelFileTag,
+                StivaleKernelFileV2Tag,
+                StivaleKernelSlideTag,
+                StivaleKernelBaseAddressTag,
+                StivalePxeInfoTag,
+                StivaleUartTag,
+                StivaleDeviceTreeTag,
+                StivaleVMapTag
+            );
+
+            #[allow(deprecated)]
+            if identifier == StivaleMtrrTag::IDENTIFIER {
+                return Ok(core::mem::size_of::<StivaleMtrrTag>());
+            }
+
+            Err(BuildError::UnknownTag(identifier))
+        }
+    }
+}
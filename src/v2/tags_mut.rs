@@ -0,0 +1,87 @@
+//! Mutable traversal of the tag chain, for bootloaders and loaders fixing up a boot structure in
+//! place before handing it to the kernel.
+
+use core::marker::PhantomData;
+use core::ptr;
+
+use super::hhdm::to_hhdm;
+use super::limits::limits;
+use super::tag::{StivaleTag, StivaleTagHeader};
+use super::utils::addr_to_mut_ptr;
+use super::StivaleStruct;
+
+/// A cursor over the tags of a boot structure, returned by
+/// [StivaleStruct::iter_tags_mut](super::StivaleStruct::iter_tags_mut).
+///
+/// Unlike an [Iterator], every tag returned borrows the cursor, so only one tag is accessible
+/// at a time. This keeps the mutable references unique even if the chain loops or a `next`
+/// pointer is rewritten during the traversal, which only takes effect on the following call to
+/// [TagsMut::next_tag]. The traversal stops after [Limits::max_tags](super::Limits::max_tags)
+/// tags.
+pub struct TagsMut<'a> {
+    /// The field pointing to the current tag: the `tags` field of the structure or the `next`
+    /// field of the previous tag.
+    /// It is a raw pointer as the chain may loop back to the current tag.
+    link: *mut u64,
+    /// The tag last returned by [TagsMut::next_tag], or null before the first one and once it
+    /// was removed.
+    current: *mut StivaleTagHeader,
+    walked: usize,
+    _marker: PhantomData<&'a mut StivaleStruct>,
+}
+
+impl<'a> TagsMut<'a> {
+    pub(crate) fn new(tags: &'a mut u64) -> Self {
+        Self {
+            link: tags,
+            current: ptr::null_mut(),
+            walked: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Advances to the next tag and returns its header.
+    pub fn next_tag(&mut self) -> Option<&mut StivaleTagHeader> {
+        if !self.current.is_null() {
+            // SAFETY: The current tag is part of the chain, which lives as long as the
+            // structure is borrowed, and is no longer borrowed through the cursor.
+            self.link = unsafe { ptr::addr_of_mut!((*self.current).next) };
+            self.current = ptr::null_mut();
+        }
+
+        let addr = unsafe { self.link.read_volatile() };
+
+        if addr == 0 || self.walked == limits().max_tags {
+            return None;
+        }
+
+        self.walked += 1;
+        self.current = addr_to_mut_ptr(to_hhdm(addr));
+
+        Some(unsafe { &mut *self.current })
+    }
+
+    /// Returns the tag last returned by [TagsMut::next_tag] as a `T`, or `None` if it has
+    /// another identifier or was removed.
+    pub fn current_as<T: StivaleTag>(&mut self) -> Option<&mut T> {
+        if self.current.is_null() || unsafe { (*self.current).identifier } != T::IDENTIFIER {
+            return None;
+        }
+
+        // SAFETY: The tag has the identifier of `T`, and `StivaleTag` requires `T` to be plain
+        // old data starting with the tag header.
+        Some(unsafe { &mut *(self.current as *mut T) })
+    }
+
+    /// Unlinks the tag last returned by [TagsMut::next_tag] from the chain, so that the kernel
+    /// does not see it. The next call to [TagsMut::next_tag] returns the tag which followed it.
+    /// Does nothing if the tag was already removed.
+    pub fn remove(&mut self) {
+        if self.current.is_null() {
+            return;
+        }
+
+        unsafe { self.link.write_volatile((*self.current).next) };
+        self.current = ptr::null_mut();
+    }
+}
@@ -0,0 +1,143 @@
+//! Calling the stivale2 terminal on a dedicated stack.
+//!
+//! `term_write` runs bootloader code on the stack of its caller, which has to be large enough
+//! for it. Kernels calling it from small stacks, such as interrupt or processor stacks, can use
+//! [StackedTerminal] to switch to a static stack reserved for the terminal around every call.
+
+use super::shared::{LockedTerminal, TerminalCall};
+use super::stack::KernelStack;
+use super::tag::StivaleTerminalTag;
+
+/// Calls `term_write` on a dedicated static stack of `N` bytes, see [StackedTerminal].
+pub struct TerminalStack<const N: usize>(KernelStack<N>);
+
+impl<const N: usize> TerminalCall for TerminalStack<N> {
+    unsafe fn write(&self, tag: &StivaleTerminalTag, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            // SAFETY: The lock is held, so nothing else uses the stack.
+            call_on_stack(
+                self.0.top(),
+                tag.term_write_addr as usize,
+                bytes.as_ptr(),
+                bytes.len() as u64,
+            );
+        }
+    }
+}
+
+/// The stivale2 terminal, called on a dedicated static stack of `N` bytes. As the stack is only
+/// used by one call at a time, calls are serialized by the lock of the [LockedTerminal], which
+/// also makes the terminal shareable between processors.
+///
+/// `N` must be a multiple of 16, so that the stack stays aligned:
+///
+/// ```rust,compile_fail,E0080
+/// use stivale_boot::v2::StackedTerminal;
+///
+/// static TERMINAL: StackedTerminal<0x1008> = StackedTerminal::new();
+/// ```
+///
+/// ## Example
+/// ```rust,no_run
+/// use stivale_boot::v2::{StackedTerminal, StivaleStruct};
+///
+/// static TERMINAL: StackedTerminal = StackedTerminal::new();
+///
+/// fn kmain(boot_info: &'static StivaleStruct) {
+///     if let Some(terminal) = boot_info.terminal() {
+///         TERMINAL.init(terminal);
+///     }
+///
+///     // Even from an interrupt handler with a small stack:
+///     TERMINAL.write_str("Hello, Stivale!\n");
+/// }
+/// ```
+pub type StackedTerminal<const N: usize = 0x4000> = LockedTerminal<TerminalStack<N>>;
+
+impl<const N: usize> StackedTerminal<N> {
+    /// Creates a new stacked terminal, which discards its output until it is initialized.
+    pub const fn new() -> Self {
+        const { assert!(N.is_multiple_of(16), "`N` must be a multiple of 16") };
+
+        Self::with_call(TerminalStack(KernelStack::new()))
+    }
+}
+
+impl<const N: usize> Default for StackedTerminal<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calls the `term_write` function at `func` with `ptr` and `len` on the stack whose top is
+/// `stack_top`, switching back to the current stack once it returns.
+///
+/// # Safety
+/// `stack_top` must be the 16 bytes aligned top of a stack which nothing else uses, and `func`
+/// must be the address of a `term_write` function.
+#[cfg(target_arch = "x86_64")]
+unsafe fn call_on_stack(stack_top: *mut u8, func: usize, ptr: *const u8, len: u64) {
+    // r12 is preserved by the callee, and holds the stack pointer to return to.
+    core::arch::asm!(
+        "mov r12, rsp",
+        "mov rsp, {stack}",
+        "call {func}",
+        "mov rsp, r12",
+        stack = in(reg) stack_top,
+        func = in(reg) func,
+        in("rdi") ptr,
+        in("rsi") len,
+        out("r12") _,
+        clobber_abi("C"),
+    )
+}
+
+/// Calls the `term_write` function at `func` with `ptr` and `len` on the stack whose top is
+/// `stack_top`, switching back to the current stack once it returns.
+///
+/// # Safety
+/// `stack_top` must be the 16 bytes aligned top of a stack which nothing else uses, and `func`
+/// must be the address of a `term_write` function.
+#[cfg(target_arch = "x86")]
+unsafe fn call_on_stack(stack_top: *mut u8, func: usize, ptr: *const u8, len: u64) {
+    // The arguments are laid out on the new stack, which keeps it 16 bytes aligned at the call.
+    let frame = stack_top.sub(16) as *mut u32;
+    frame.write(ptr as u32);
+    frame.add(1).write(len as u32);
+    frame.add(2).write((len >> 32) as u32);
+
+    // edi is preserved by the callee, and holds the stack pointer to return to.
+    core::arch::asm!(
+        "mov edi, esp",
+        "mov esp, {frame}",
+        "call {func}",
+        "mov esp, edi",
+        frame = in(reg) frame,
+        func = in(reg) func,
+        out("edi") _,
+        clobber_abi("C"),
+    )
+}
+
+/// Calls the `term_write` function at `func` with `ptr` and `len` on the stack whose top is
+/// `stack_top`, switching back to the current stack once it returns.
+///
+/// # Safety
+/// `stack_top` must be the 16 bytes aligned top of a stack which nothing else uses, and `func`
+/// must be the address of a `term_write` function.
+#[cfg(target_arch = "aarch64")]
+unsafe fn call_on_stack(stack_top: *mut u8, func: usize, ptr: *const u8, len: u64) {
+    // x20 is preserved by the callee, and holds the stack pointer to return to.
+    core::arch::asm!(
+        "mov x20, sp",
+        "mov sp, {stack}",
+        "blr {func}",
+        "mov sp, x20",
+        stack = in(reg) stack_top,
+        func = in(reg) func,
+        in("x0") ptr,
+        in("x1") len,
+        out("x20") _,
+        clobber_abi("C"),
+    )
+}
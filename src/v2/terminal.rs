@@ -0,0 +1,100 @@
+//! Buffering of the output written to the stivale2 terminal.
+
+use core::fmt;
+
+use super::tag::StivaleTerminalTag;
+
+/// Writer batching the output written to the stivale2 terminal, as every `term_write` call
+/// round-trips into bootloader code, while formatting produces many small fragments. The
+/// buffered output is written on newlines, when the `N` byte buffer is full, on [flush] and
+/// when the writer is dropped.
+///
+/// [flush]: BufferedTerminal::flush
+///
+/// ## Example
+/// ```rust
+/// use core::fmt::Write;
+/// use core::sync::atomic::{AtomicUsize, Ordering};
+/// use stivale_boot::v2::{BufferedTerminal, StivaleTerminalTag};
+///
+/// static CALLS: AtomicUsize = AtomicUsize::new(0);
+///
+/// extern "C" fn term_write(_: *const i8, _: u64) {
+///     CALLS.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// let tag = StivaleTerminalTag::new(80, 25, term_write as usize as u64);
+/// let mut terminal = BufferedTerminal::<64>::new(&tag);
+///
+/// write!(terminal, "{} + {} = {}", 1, 2, 3).unwrap();
+/// assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+///
+/// writeln!(terminal).unwrap();
+/// assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+/// ```
+pub struct BufferedTerminal<'a, const N: usize = 128> {
+    tag: &'a StivaleTerminalTag,
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<'a, const N: usize> BufferedTerminal<'a, N> {
+    /// Creates a new buffered writer to the terminal described by `tag`.
+    pub fn new(tag: &'a StivaleTerminalTag) -> Self {
+        Self {
+            tag,
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the buffered output, which is not written to the terminal yet.
+    pub fn buffered(&self) -> &str {
+        // Only whole strings are ever copied into the buffer.
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or_default()
+    }
+
+    /// Writes `bytes` to the terminal after the buffered output. As the buffer only holds
+    /// strings, the bytes are written directly, and may contain null bytes and invalid UTF-8.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.flush();
+        self.tag.write_bytes(bytes);
+    }
+
+    /// Writes the buffered output to the terminal.
+    pub fn flush(&mut self) {
+        if self.len != 0 {
+            self.tag.term_write()(self.buffered());
+            self.len = 0;
+        }
+    }
+}
+
+impl<const N: usize> fmt::Write for BufferedTerminal<'_, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.len() > N - self.len {
+            self.flush();
+        }
+
+        if s.len() >= N {
+            // Too large to be buffered, so there is nothing to gain from copying it.
+            self.tag.term_write()(s);
+            return Ok(());
+        }
+
+        self.buffer[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+
+        if s.contains('\n') {
+            self.flush();
+        }
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> Drop for BufferedTerminal<'_, N> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
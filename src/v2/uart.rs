@@ -0,0 +1,262 @@
+//! Output to the 16550 compatible or PL011 UART described by the [StivaleUartTag], so that a
+//! kernel can print to the serial port without pulling in a separate UART crate.
+//!
+//! The registers of a 16550 UART are accessed either through I/O ports, as is usual for the COM
+//! ports of x86 machines, or as a memory mapped register block, as is common on ARM and RISC-V.
+//! The PL011 is the UART of most aarch64 machines, including the QEMU `virt` machine.
+
+use core::fmt;
+
+use super::tag::StivaleUartTag;
+use super::utils::{addr_to_mut_ptr, addr_to_ptr};
+
+/// Offset of the data register, or of the low byte of the divisor latch when `DLAB` is set.
+const DATA: u16 = 0;
+/// Offset of the interrupt enable register, or of the high byte of the divisor latch when
+/// `DLAB` is set.
+const INTERRUPT_ENABLE: u16 = 1;
+/// Offset of the FIFO control register.
+const FIFO_CONTROL: u16 = 2;
+/// Offset of the line control register.
+const LINE_CONTROL: u16 = 3;
+/// Offset of the modem control register.
+const MODEM_CONTROL: u16 = 4;
+/// Offset of the line status register.
+const LINE_STATUS: u16 = 5;
+
+/// Divisor latch access bit of the line control register.
+const LINE_CONTROL_DLAB: u8 = 1 << 7;
+/// 8 data bits, no parity and one stop bit.
+const LINE_CONTROL_8N1: u8 = 0x03;
+/// The highest I/O port at which the registers of a UART fit in the 16-bit I/O port space.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const MAX_PORT_BASE: u64 = (u16::MAX - LINE_STATUS) as u64;
+
+/// Bit of the line status register set when the transmit holding register is empty.
+const LINE_STATUS_THR_EMPTY: u8 = 1 << 5;
+
+/// The divisor of the 115200 Hz UART clock, resulting in 115200 baud.
+const BAUD_DIVISOR: u16 = 1;
+
+/// Offset of the PL011 data register.
+const PL011_DATA: u64 = 0x00;
+/// Offset of the PL011 flag register.
+const PL011_FLAG: u64 = 0x18;
+/// Bit of the PL011 flag register set when the transmit FIFO is full.
+const PL011_FLAG_TX_FULL: u32 = 1 << 5;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+unsafe fn port_write(port: u16, value: u8) {
+    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+unsafe fn port_read(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// The way the registers of a UART are accessed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UartAccess {
+    /// The registers are I/O ports, starting at the UART address.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Port,
+    /// The registers are memory mapped starting at the UART address, `stride` bytes apart.
+    Mmio {
+        /// The distance between two consecutive registers in bytes, usually 1 or 4.
+        stride: usize,
+    },
+    /// The registers are those of an ARM PL011 UART, memory mapped starting at the UART
+    /// address. The UART is expected to have been programmed by the firmware, as its baud rate
+    /// divisor depends on a reference clock which is not known to the kernel.
+    Pl011,
+}
+
+impl UartAccess {
+    /// Guesses the access method of the UART at `address`. On x86, addresses at which the
+    /// registers fit in the 16-bit I/O port space are treated as I/O ports. On aarch64, UARTs are assumed to be
+    /// PL011s. Everything else is treated as a memory mapped register block with a stride of
+    /// 1 byte.
+    pub fn detect(address: u64) -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if address <= MAX_PORT_BASE {
+            return UartAccess::Port;
+        }
+
+        let _ = address;
+
+        if cfg!(target_arch = "aarch64") {
+            UartAccess::Pl011
+        } else {
+            UartAccess::Mmio { stride: 1 }
+        }
+    }
+}
+
+/// Writer to a 16550 compatible UART, implementing [fmt::Write].
+///
+/// ## Example
+/// ```rust,no_run
+/// use core::fmt::Write;
+/// use stivale_boot::v2::{StivaleStruct, UartWriter};
+///
+/// fn hello(boot_info: &StivaleStruct) {
+///     if let Some(uart) = boot_info.uart() {
+///         // SAFETY: The UART tag was provided by the bootloader.
+///         let mut writer = unsafe { UartWriter::new(uart) };
+///         writeln!(writer, "Hello from the kernel!").unwrap();
+///     }
+/// }
+/// ```
+pub struct UartWriter {
+    /// The I/O port or the virtual address of the data register.
+    base: u64,
+    access: UartAccess,
+}
+
+impl UartWriter {
+    /// Creates a new writer to the UART at the address in `tag`, programming it for 115200
+    /// baud with 8 data bits, no parity and one stop bit. The access method is guessed with
+    /// [UartAccess::detect].
+    ///
+    /// ## Safety
+    /// `tag` must describe a UART of the machine, such as the tag provided by the bootloader, as
+    /// its registers are written to. See [UartWriter::with_access].
+    pub unsafe fn new(tag: &StivaleUartTag) -> Self {
+        Self::with_access(tag, UartAccess::detect(tag.address))
+    }
+
+    /// Creates a new writer to the UART at the address in `tag`, accessing its registers with
+    /// `access`. Memory mapped registers are translated through the registered direct map, see
+    /// [register_hhdm_offset](super::register_hhdm_offset).
+    ///
+    /// ## Safety
+    /// `tag` must describe a UART of the machine accessed with `access`, such as the tag
+    /// provided by the bootloader, as its registers are written to. Memory mapped registers
+    /// must be mapped at their translated address.
+    ///
+    /// ## Panics
+    /// Panics if `access` is [UartAccess::Port] and the registers of the UART do not fit in the
+    /// 16-bit I/O port space.
+    pub unsafe fn with_access(tag: &StivaleUartTag, access: UartAccess) -> Self {
+        let base = match access {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            UartAccess::Port => {
+                assert!(
+                    tag.address <= MAX_PORT_BASE,
+                    "the UART registers do not fit in the I/O port space"
+                );
+
+                tag.address
+            }
+            UartAccess::Mmio { .. } | UartAccess::Pl011 => super::hhdm::to_hhdm(tag.address),
+        };
+
+        let writer = Self { base, access };
+
+        writer.init();
+        writer
+    }
+
+    /// Returns the way the registers of the UART are accessed.
+    pub fn access(&self) -> UartAccess {
+        self.access
+    }
+
+    fn write_reg(&self, register: u16, value: u8) {
+        match self.access {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            UartAccess::Port => unsafe { port_write(self.base as u16 + register, value) },
+            UartAccess::Mmio { stride } => unsafe {
+                let ptr = addr_to_mut_ptr::<u8>(self.base + register as u64 * stride as u64);
+                ptr.write_volatile(value)
+            },
+            // The PL011 registers are accessed by `write_pl011`.
+            UartAccess::Pl011 => unreachable!(),
+        }
+    }
+
+    fn read_reg(&self, register: u16) -> u8 {
+        match self.access {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            UartAccess::Port => unsafe { port_read(self.base as u16 + register) },
+            UartAccess::Mmio { stride } => unsafe {
+                let ptr = addr_to_ptr::<u8>(self.base + register as u64 * stride as u64);
+                ptr.read_volatile()
+            },
+            UartAccess::Pl011 => unreachable!(),
+        }
+    }
+
+    fn write_pl011(&self, byte: u8) {
+        unsafe {
+            let flag = addr_to_ptr::<u32>(self.base + PL011_FLAG);
+
+            while flag.read_volatile() & PL011_FLAG_TX_FULL != 0 {
+                core::hint::spin_loop();
+            }
+
+            addr_to_mut_ptr::<u32>(self.base + PL011_DATA).write_volatile(byte as u32);
+        }
+    }
+
+    fn init(&self) {
+        if self.access == UartAccess::Pl011 {
+            return;
+        }
+
+        // Disable interrupts, as the UART is polled.
+        self.write_reg(INTERRUPT_ENABLE, 0x00);
+
+        let [low, high] = BAUD_DIVISOR.to_le_bytes();
+
+        self.write_reg(LINE_CONTROL, LINE_CONTROL_DLAB);
+        self.write_reg(DATA, low);
+        self.write_reg(INTERRUPT_ENABLE, high);
+        self.write_reg(LINE_CONTROL, LINE_CONTROL_8N1);
+
+        // Enable and clear the FIFOs, with a 14 byte threshold.
+        self.write_reg(FIFO_CONTROL, 0xc7);
+        // Assert DTR and RTS.
+        self.write_reg(MODEM_CONTROL, 0x03);
+    }
+
+    /// Writes `byte`, waiting for the transmit holding register to be empty, or for the
+    /// transmit FIFO to have room on a PL011, first.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.access == UartAccess::Pl011 {
+            return self.write_pl011(byte);
+        }
+
+        while self.read_reg(LINE_STATUS) & LINE_STATUS_THR_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+
+        self.write_reg(DATA, byte);
+    }
+
+    /// Writes `bytes` as is, without the CRLF translation done when writing strings, so that
+    /// binary data reaches the other end unchanged.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_byte(byte);
+        }
+    }
+}
+
+impl fmt::Write for UartWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // Terminals expect CRLF line endings.
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+
+            self.write_byte(byte);
+        }
+
+        Ok(())
+    }
+}
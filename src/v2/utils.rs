@@ -1,10 +1,362 @@
-/// Helper function to validate and create a string from a slice.
-pub(crate) fn string_from_slice(slice: &[u8]) -> &str {
+use core::convert::TryFrom;
+use core::fmt::{self, Write};
+use core::mem;
+
+use super::builder::BuildError;
+
+/// Converts an address provided by the bootloader into a pointer. Such addresses do not come
+/// from a Rust allocation, so they are given the exposed provenance, which is how integers
+/// received from firmware are meant to become pointers under the strict provenance model.
+pub(crate) fn addr_to_ptr<T>(addr: u64) -> *const T {
+    core::ptr::with_exposed_provenance(addr as usize)
+}
+
+/// Mutable version of [addr_to_ptr].
+pub(crate) fn addr_to_mut_ptr<T>(addr: u64) -> *mut T {
+    core::ptr::with_exposed_provenance_mut(addr as usize)
+}
+
+/// Converts `ptr` into an address stored in a boot structure, exposing its provenance so that
+/// it can be turned back into a pointer with [addr_to_ptr].
+pub(crate) fn ptr_to_addr<T>(ptr: *const T) -> u64 {
+    ptr.expose_provenance() as u64
+}
+
+/// Returns the `length` bytes at `addr` as a slice, or `None` if they do not fit in the address
+/// space, which happens on 32-bit targets for memory above 4GiB.
+///
+/// # Safety
+/// The `length` bytes at `addr` must be readable for the lifetime `'a`.
+pub(crate) unsafe fn checked_slice<'a>(addr: u64, length: u64) -> Option<&'a [u8]> {
+    let start = usize::try_from(addr).ok()?;
+    let length = usize::try_from(length).ok()?;
+
+    start.checked_add(length)?;
+    Some(core::slice::from_raw_parts(addr_to_ptr(addr), length))
+}
+
+/// Returns whether any byte of `word` is zero.
+#[inline]
+const fn has_zero_byte(word: u64) -> bool {
+    const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+    word.wrapping_sub(LOW_BITS) & !word & HIGH_BITS != 0
+}
+
+/// Returns the offset of the first null byte among the `max_length` bytes at `ptr`, or
+/// `max_length` if there is none. The bytes are read with volatile loads, as they are written by
+/// the bootloader, a word at a time once `ptr` is aligned. No byte past `max_length` is read.
+///
+/// # Safety
+/// The `max_length` bytes at `ptr` must be readable.
+pub(crate) unsafe fn c_string_length_raw(ptr: *const u8, max_length: usize) -> usize {
     let mut length = 0;
+    let byte_at = |offset: usize| ptr.add(offset).read_volatile();
+
+    // Scan the unaligned head byte by byte.
+    while length < max_length && ptr.add(length).align_offset(mem::align_of::<u64>()) != 0 {
+        if byte_at(length) == 0 {
+            return length;
+        }
+
+        length += 1;
+    }
+
+    // Scan whole words, as long as they are within bounds.
+    while max_length - length >= mem::size_of::<u64>() {
+        let word = (ptr.add(length) as *const u64).read_volatile();
+
+        if has_zero_byte(word) {
+            break;
+        }
+
+        length += mem::size_of::<u64>();
+    }
+
+    // Locate the null byte in the last word, or scan the tail.
+    while length < max_length {
+        if byte_at(length) == 0 {
+            return length;
+        }
 
-    while length < slice.len() && slice[length] != 0 {
         length += 1;
     }
 
-    unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(slice.as_ptr(), length)) }
+    max_length
+}
+
+/// Returns the length of the null-terminated string in `slice`, or the length of `slice` if it
+/// is not terminated.
+pub(crate) fn c_string_length(slice: &[u8]) -> usize {
+    unsafe { c_string_length_raw(slice.as_ptr(), slice.len()) }
+}
+
+/// A byte string provided by the bootloader, displayed as UTF-8 with invalid sequences replaced
+/// by `U+FFFD`, so that printing it cannot fail or panic.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LossyStr<'a>(&'a [u8]);
+
+impl<'a> LossyStr<'a> {
+    /// Wraps the null-terminated string in `slice`, or all of `slice` if it is not terminated.
+    pub fn from_c_bytes(slice: &'a [u8]) -> Self {
+        Self(&slice[..c_string_length(slice)])
+    }
+
+    /// Returns the raw bytes of the string, without the null terminator.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Returns the string if it is valid UTF-8.
+    pub fn to_str(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.0).ok()
+    }
+}
+
+impl fmt::Display for LossyStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_lossy(f, [self.0, &[]])
+    }
+}
+
+impl fmt::Debug for LossyStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        write_lossy(f, [self.0, &[]])?;
+        f.write_char('"')
+    }
+}
+
+/// A null-terminated string stored inline in a field of `N` bytes, such as the bootloader brand
+/// and version or the string of a module.
+///
+/// The setters always leave room for the null terminator. Strings written by the bootloader may
+/// still fill the whole field, in which case [FixedCStr::is_truncated] returns `true`.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::{BuildError, FixedCStr};
+///
+/// let mut string = FixedCStr::<8>::new();
+/// string.try_set("initrd").unwrap();
+/// assert_eq!(string.as_str(), "initrd");
+///
+/// // One byte is kept for the null terminator.
+/// assert_eq!(string.try_set("12345678"), Err(BuildError::StringTooLong));
+/// assert_eq!(string.as_str(), "initrd");
+///
+/// string.set_truncated("12345678");
+/// assert_eq!(string.as_str(), "1234567");
+/// ```
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixedCStr<const N: usize>([u8; N]);
+
+impl<const N: usize> FixedCStr<N> {
+    /// Creates an empty string.
+    pub const fn new() -> Self {
+        Self([0; N])
+    }
+
+    /// Replaces the string with `string`. Returns an error, leaving the string unchanged, if
+    /// `string` does not fit in `N - 1` bytes or contains a null byte.
+    pub fn try_set(&mut self, string: &str) -> Result<(), BuildError> {
+        if string.len() >= N {
+            return Err(BuildError::StringTooLong);
+        }
+
+        if string.contains('\0') {
+            return Err(BuildError::EmbeddedNul);
+        }
+
+        self.copy_from(string.as_bytes());
+        Ok(())
+    }
+
+    /// Replaces the string with `string`, truncated on a character boundary to `N - 1` bytes
+    /// and at its first null byte.
+    pub fn set_truncated(&mut self, string: &str) {
+        let string = string.split('\0').next().unwrap_or_default();
+        let mut length = string.len().min(N.saturating_sub(1));
+
+        while !string.is_char_boundary(length) {
+            length -= 1;
+        }
+
+        self.copy_from(&string.as_bytes()[..length]);
+    }
+
+    fn copy_from(&mut self, bytes: &[u8]) {
+        self.0 = [0; N];
+        self.0[..bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Returns the string. If it is not valid UTF-8, it is cut at the first invalid byte; see
+    /// [FixedCStr::as_str_lossy] to display all of it.
+    pub fn as_str(&self) -> &str {
+        lossy_string_from_slice(&self.0)
+    }
+
+    /// Returns the string, displayed with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn as_str_lossy(&self) -> LossyStr<'_> {
+        LossyStr::from_c_bytes(&self.0)
+    }
+
+    /// Returns the bytes of the string, without the null terminator.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..c_string_length(&self.0)]
+    }
+
+    /// Returns the whole field, including the null terminator and the bytes following it.
+    pub fn as_raw(&self) -> &[u8; N] {
+        &self.0
+    }
+
+    /// Returns whether the null terminator is missing, meaning that the string was truncated to
+    /// fit the field.
+    pub fn is_truncated(&self) -> bool {
+        !self.0.contains(&0)
+    }
+}
+
+impl<const N: usize> Default for FixedCStr<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedCStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_str_lossy(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for FixedCStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_str_lossy(), f)
+    }
+}
+
+/// Creates a string from the null-terminated string in `slice`, stopping at the first byte
+/// that is not valid UTF-8.
+pub(crate) fn lossy_string_from_slice(slice: &[u8]) -> &str {
+    let bytes = &slice[..c_string_length(slice)];
+
+    core::str::from_utf8(bytes).unwrap_or_else(|error| {
+        // SAFETY: The bytes up to `valid_up_to` were validated.
+        unsafe { core::str::from_utf8_unchecked(&bytes[..error.valid_up_to()]) }
+    })
+}
+
+/// Computes the size of the ELF file whose header is at `ptr` from the end of its header tables
+/// and of the file data of its segments, or returns `None` if it is not a little endian ELF or if
+/// the size overflows.
+///
+/// # Safety
+/// `ptr` must point to a readable ELF header, followed by readable program headers.
+pub(crate) unsafe fn elf_file_size(ptr: *const u8) -> Option<u64> {
+    let at = |offset: u64| Some(ptr.add(usize::try_from(offset).ok()?));
+    let read_u16 = |offset: u64| Some(core::ptr::read_unaligned(at(offset)? as *const u16));
+    let read_u32 = |offset: u64| Some(core::ptr::read_unaligned(at(offset)? as *const u32));
+    let read_u64 = |offset: u64| Some(core::ptr::read_unaligned(at(offset)? as *const u64));
+
+    if core::slice::from_raw_parts(ptr, 4) != b"\x7fELF" || *ptr.add(5) != 1 {
+        return None;
+    }
+
+    // The program and section header table offsets, followed by the offset of the entry size
+    // and count fields of both tables.
+    let (elf64, phoff, shoff, fields) = match *ptr.add(4) {
+        1 => (false, read_u32(0x1c)? as u64, read_u32(0x20)? as u64, 0x2a),
+        2 => (true, read_u64(0x20)?, read_u64(0x28)?, 0x36),
+        _ => return None,
+    };
+
+    let phentsize = read_u16(fields)? as u64;
+    let phnum = read_u16(fields + 2)? as u64;
+    let shentsize = read_u16(fields + 4)? as u64;
+    let shnum = read_u16(fields + 6)? as u64;
+
+    let table_end = |offset: u64, entry_size: u64, count: u64| {
+        entry_size.checked_mul(count)?.checked_add(offset)
+    };
+
+    let mut size = table_end(phoff, phentsize, phnum)?.max(table_end(shoff, shentsize, shnum)?);
+
+    for index in 0..phnum {
+        let header = index.checked_mul(phentsize)?.checked_add(phoff)?;
+        let end = if elf64 {
+            read_u64(header.checked_add(0x08)?)?
+                .checked_add(read_u64(header.checked_add(0x20)?)?)?
+        } else {
+            read_u32(header.checked_add(0x04)?)? as u64
+                + read_u32(header.checked_add(0x10)?)? as u64
+        };
+
+        size = size.max(end);
+    }
+
+    Some(size)
+}
+
+/// Writes the concatenation of `slices` to `out` as UTF-8, replacing invalid sequences with
+/// `U+FFFD`.
+pub(crate) fn write_lossy(out: &mut impl fmt::Write, slices: [&[u8]; 2]) -> fmt::Result {
+    // A character split across the two slices.
+    let mut pending = [0u8; 4];
+    let mut pending_len = 0;
+
+    for slice in slices {
+        let mut rest = slice;
+
+        while pending_len != 0 && !rest.is_empty() {
+            pending[pending_len] = rest[0];
+            pending_len += 1;
+            rest = &rest[1..];
+
+            match core::str::from_utf8(&pending[..pending_len]) {
+                Ok(s) => {
+                    out.write_str(s)?;
+                    pending_len = 0;
+                }
+                Err(error) if error.error_len().is_some() => {
+                    out.write_char(char::REPLACEMENT_CHARACTER)?;
+                    pending_len = 0;
+                }
+                Err(_) => {}
+            }
+        }
+
+        loop {
+            match core::str::from_utf8(rest) {
+                Ok(s) => {
+                    out.write_str(s)?;
+                    break;
+                }
+                Err(error) => {
+                    let (valid, invalid) = rest.split_at(error.valid_up_to());
+                    out.write_str(unsafe { core::str::from_utf8_unchecked(valid) })?;
+
+                    match error.error_len() {
+                        Some(len) => {
+                            out.write_char(char::REPLACEMENT_CHARACTER)?;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            pending[..invalid.len()].copy_from_slice(invalid);
+                            pending_len = invalid.len();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if pending_len != 0 {
+        out.write_char(char::REPLACEMENT_CHARACTER)?;
+    }
+
+    Ok(())
 }
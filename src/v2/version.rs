@@ -0,0 +1,104 @@
+//! Lenient parsing of the bootloader version string, so that kernels can work around the bugs of
+//! specific bootloader releases.
+
+use core::cmp::Ordering;
+
+/// A bootloader version, parsed from a string such as `2.0.1`, `v3.4` or `2.78-rc1`.
+///
+/// Versions are compared by their numeric components; the extra part is ignored, so that
+/// `2.0.1-rc1` is considered equal to `2.0.1`.
+///
+/// ## Example
+/// ```rust
+/// use stivale_boot::v2::BootloaderVersion;
+///
+/// let version = BootloaderVersion::parse("v2.78-rc1").unwrap();
+///
+/// assert_eq!((version.major, version.minor, version.patch), (2, 78, 0));
+/// assert_eq!(version.extra, "rc1");
+/// assert!(version >= BootloaderVersion::new(2, 0, 0));
+/// assert!(version < BootloaderVersion::new(3, 0, 0));
+/// assert!(BootloaderVersion::parse("unknown").is_none());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BootloaderVersion<'a> {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// What follows the numeric components, without its leading separator, such as a
+    /// pre-release tag or a build identifier. Empty if there is none.
+    pub extra: &'a str,
+}
+
+impl<'a> BootloaderVersion<'a> {
+    /// Creates a new version without an extra part, meant to be compared against.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            extra: "",
+        }
+    }
+
+    /// Parses `version`, skipping any prefix before the first digit. The minor and patch
+    /// components default to 0 if missing. Returns `None` if `version` contains no number or
+    /// if a component does not fit in a `u32`.
+    pub fn parse(version: &'a str) -> Option<Self> {
+        let mut rest = version.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let mut components = [0u32; 3];
+
+        for (index, component) in components.iter_mut().enumerate() {
+            if index != 0 {
+                match rest.strip_prefix('.') {
+                    Some(next) if next.starts_with(|c: char| c.is_ascii_digit()) => rest = next,
+                    _ => break,
+                }
+            }
+
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+
+            if end == 0 {
+                return None;
+            }
+
+            *component = rest[..end].parse().ok()?;
+            rest = &rest[end..];
+        }
+
+        let [major, minor, patch] = components;
+
+        Some(Self {
+            major,
+            minor,
+            patch,
+            extra: rest.trim_start_matches(['-', '+', '.', '_', ' ']),
+        })
+    }
+
+    fn numbers(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl PartialEq for BootloaderVersion<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.numbers() == other.numbers()
+    }
+}
+
+impl Eq for BootloaderVersion<'_> {}
+
+impl PartialOrd for BootloaderVersion<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BootloaderVersion<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers().cmp(&other.numbers())
+    }
+}
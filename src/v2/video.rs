@@ -0,0 +1,164 @@
+//! Bootloader side negotiation of the video mode, implementing the fallback rules between the
+//! framebuffer, any video and terminal header tags.
+
+use super::builder::BootResponse;
+use super::header::{StivaleAnyVideoTag, StivaleFramebufferHeaderTag, StivaleTerminalHeaderTag};
+use super::tag::{StivaleFramebufferTag, StivaleTerminalTag};
+
+/// Errors returned while negotiating the video mode, in which case the kernel must not be
+/// booted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoError {
+    /// The kernel requested a terminal without the framebuffer header tag, which the
+    /// specification requires.
+    TerminalWithoutFramebuffer,
+    /// The kernel requires a video mode the platform does not support, such as CGA text mode on
+    /// UEFI systems.
+    Unsupported,
+}
+
+/// The video modes the platform supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoCapabilities {
+    /// Whether a linear framebuffer can be set up, such as through VBE or GOP.
+    pub linear_framebuffer: bool,
+    /// Whether CGA text mode is available, which is usually only the case on BIOS systems.
+    pub text_mode: bool,
+}
+
+/// The video mode the bootloader has to set up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoMode {
+    /// A linear framebuffer, with the resolution and depth requested by the kernel, zero
+    /// meaning that the bootloader picks the best value.
+    LinearFramebuffer { width: u16, height: u16, bpp: u16 },
+    /// CGA text mode.
+    TextMode,
+    /// No video mode, which the kernel accepts through the any video header tag.
+    None,
+}
+
+/// The outcome of the video mode negotiation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoDecision {
+    /// The video mode to set up.
+    pub mode: VideoMode,
+    /// Whether the kernel requested a terminal, which has to be provided on top of the mode.
+    pub terminal: bool,
+}
+
+impl VideoDecision {
+    /// Decides which video mode to set up for a kernel with the provided header tags, following
+    /// the fallback rules of the specification:
+    ///
+    /// - With the framebuffer header tag, a linear framebuffer is preferred, falling back to CGA
+    ///   text mode.
+    /// - With the any video header tag alone, its preference decides between a linear
+    ///   framebuffer and CGA text mode, and no video mode at all is accepted.
+    /// - Without either, CGA text mode is required.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use stivale_boot::v2::{
+    ///     StivaleAnyVideoTag, StivaleFramebufferHeaderTag, VideoCapabilities, VideoDecision,
+    ///     VideoError, VideoMode,
+    /// };
+    ///
+    /// let uefi = VideoCapabilities {
+    ///     linear_framebuffer: true,
+    ///     text_mode: false,
+    /// };
+    ///
+    /// let framebuffer = StivaleFramebufferHeaderTag::new().framebuffer_bpp(32);
+    /// let decision = VideoDecision::negotiate(Some(&framebuffer), None, None, uefi).unwrap();
+    /// assert_eq!(
+    ///     decision.mode,
+    ///     VideoMode::LinearFramebuffer { width: 0, height: 0, bpp: 32 }
+    /// );
+    ///
+    /// // Prefers text mode, but accepts no video mode at all.
+    /// let any_video = StivaleAnyVideoTag::new().preference(1);
+    /// let no_video = VideoCapabilities {
+    ///     linear_framebuffer: false,
+    ///     text_mode: false,
+    /// };
+    /// let decision = VideoDecision::negotiate(None, Some(&any_video), None, no_video).unwrap();
+    /// assert_eq!(decision.mode, VideoMode::None);
+    ///
+    /// // CGA text mode is required without any video header tag.
+    /// assert_eq!(
+    ///     VideoDecision::negotiate(None, None, None, uefi),
+    ///     Err(VideoError::Unsupported)
+    /// );
+    /// ```
+    pub fn negotiate(
+        framebuffer: Option<&StivaleFramebufferHeaderTag>,
+        any_video: Option<&StivaleAnyVideoTag>,
+        terminal: Option<&StivaleTerminalHeaderTag>,
+        capabilities: VideoCapabilities,
+    ) -> Result<Self, VideoError> {
+        if terminal.is_some() && framebuffer.is_none() {
+            return Err(VideoError::TerminalWithoutFramebuffer);
+        }
+
+        let linear_framebuffer = match framebuffer {
+            Some(tag) => VideoMode::LinearFramebuffer {
+                width: tag.framebuffer_width,
+                height: tag.framebuffer_height,
+                bpp: tag.framebuffer_bpp,
+            },
+            None => VideoMode::LinearFramebuffer {
+                width: 0,
+                height: 0,
+                bpp: 0,
+            },
+        };
+
+        let prefers_text_mode =
+            framebuffer.is_none() && any_video.is_some_and(|tag| tag.preference == 1);
+
+        // The modes to try in order of preference.
+        let candidates = [VideoMode::TextMode, linear_framebuffer, VideoMode::TextMode];
+        let candidates = match (
+            framebuffer.is_some() || any_video.is_some(),
+            prefers_text_mode,
+        ) {
+            (_, true) => &candidates[..2],
+            (true, false) => &candidates[1..],
+            (false, false) => &candidates[..1],
+        };
+
+        let mode = candidates
+            .iter()
+            .copied()
+            .find(|mode| match mode {
+                VideoMode::LinearFramebuffer { .. } => capabilities.linear_framebuffer,
+                VideoMode::TextMode => capabilities.text_mode,
+                VideoMode::None => true,
+            })
+            .or_else(|| any_video.map(|_| VideoMode::None))
+            .ok_or(VideoError::Unsupported)?;
+
+        Ok(Self {
+            mode,
+            terminal: terminal.is_some(),
+        })
+    }
+
+    /// Records the decision into `response`: the framebuffer tag is only provided if a linear
+    /// framebuffer was chosen, and the terminal tag only if the kernel requested a terminal.
+    /// `framebuffer` and `terminal` describe what the bootloader actually set up.
+    pub fn record<'a>(
+        &self,
+        response: &mut BootResponse<'a>,
+        framebuffer: Option<&'a StivaleFramebufferTag>,
+        terminal: Option<&'a StivaleTerminalTag>,
+    ) {
+        response.framebuffer = match self.mode {
+            VideoMode::LinearFramebuffer { .. } => framebuffer,
+            VideoMode::TextMode | VideoMode::None => None,
+        };
+
+        response.terminal = if self.terminal { terminal } else { None };
+    }
+}
@@ -0,0 +1,31 @@
+//! A kernel printing a summary of its boot information to the first serial port, booted by the
+//! QEMU test in `tests/qemu.rs`.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use stivale_boot::v2::{StivaleStruct, StivaleUartTag, UartWriter};
+
+#[stivale_boot::v2::main]
+fn kmain(boot_info: &'static StivaleStruct) -> ! {
+    // SAFETY: COM1 is present on the QEMU `pc` and `q35` machines.
+    let mut serial = unsafe { UartWriter::new(&StivaleUartTag::new(0x3f8)) };
+
+    let _ = writeln!(serial, "bootloader: {}", boot_info.bootloader_brand());
+
+    if let Some(command_line) = boot_info.command_line() {
+        let _ = writeln!(serial, "command line: {}", command_line.as_str_lossy());
+    }
+
+    if let Some(memory_map) = boot_info.memory_map() {
+        let _ = writeln!(serial, "memory map entries: {}", memory_map.entries_len);
+    }
+
+    let _ = writeln!(serial, "TEST DONE");
+
+    loop {
+        unsafe { core::arch::asm!("hlt") };
+    }
+}
@@ -0,0 +1,59 @@
+//! Boots the kernel in `tests/kernel` with Limine under QEMU and checks its serial output.
+//!
+//! The test is ignored by default, as it needs `qemu-system-x86_64`, `xorriso` and a Limine 3.x
+//! binary release on the host. Run it with:
+//!
+//! ```sh
+//! git clone https://github.com/limine-bootloader/limine --branch=v3.0-branch-binary --depth=1
+//! make -C limine
+//! LIMINE_DIR=$PWD/limine cargo test --features qemu --test qemu -- --ignored
+//! ```
+
+#![cfg(feature = "qemu")]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use stivale_boot::qemu::{build_iso, QemuHarness};
+
+const COMMAND_LINE: &str = "console=ttyS0 quiet";
+
+/// Builds the test kernel and returns the path of its ELF file.
+fn build_kernel() -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let kernel_dir = manifest_dir.join("tests/kernel");
+
+    // The kernel is built with its own target and flags, see `tests/kernel/.cargo/config.toml`.
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--release"])
+        .current_dir(&kernel_dir)
+        .env_remove("RUSTFLAGS")
+        .env_remove("CARGO_TARGET_DIR")
+        .status()
+        .expect("failed to run cargo");
+
+    assert!(status.success(), "failed to build the test kernel");
+
+    kernel_dir.join("target/x86_64-unknown-none/release/stivale-test-kernel")
+}
+
+#[test]
+#[ignore = "needs QEMU, xorriso and Limine, see the documentation of this file"]
+fn boot_with_limine() {
+    let limine = env::var_os("LIMINE_DIR").expect("LIMINE_DIR must point to a Limine 3.x release");
+    let kernel = build_kernel();
+    let iso = kernel.with_extension("iso");
+
+    build_iso(&kernel, Path::new(&limine), COMMAND_LINE, &iso).unwrap();
+
+    let output = QemuHarness::new(&iso)
+        .timeout(Duration::from_secs(30))
+        .run_until("TEST DONE")
+        .unwrap();
+
+    output.assert_contains("bootloader: Limine");
+    output.assert_contains(&format!("command line: {}", COMMAND_LINE));
+    output.assert_contains("memory map entries:");
+}